@@ -22,6 +22,14 @@ pub async fn list_accounts() -> Result<Vec<Account>, String> {
     modules::list_accounts()
 }
 
+/// 按导入来源列出账号
+#[tauri::command]
+pub async fn list_accounts_by_source(
+    source: crate::models::AccountSource,
+) -> Result<Vec<Account>, String> {
+    modules::list_accounts_by_source(source)
+}
+
 /// 添加账号
 #[tauri::command]
 pub async fn add_account(
@@ -91,6 +99,26 @@ pub async fn delete_accounts(
     Ok(())
 }
 
+/// 清除全部账号及凭据 (不可恢复，需显式确认)
+#[tauri::command]
+pub async fn purge_all_accounts(
+    app: tauri::AppHandle,
+    proxy_state: tauri::State<'_, crate::commands::proxy::ProxyServiceState>,
+    confirm: bool,
+) -> Result<crate::models::PurgeReport, String> {
+    let report = modules::account::purge_all(confirm).map_err(|e| {
+        modules::logger::log_error(&format!("清除全部账号失败: {}", e));
+        e
+    })?;
+
+    crate::modules::tray::update_tray_menus(&app);
+
+    // 清空内存中的账号池
+    let _ = crate::commands::proxy::reload_proxy_accounts(proxy_state).await;
+
+    Ok(report)
+}
+
 /// 重新排序账号列表
 /// 根据传入的账号ID数组顺序更新账号排列
 #[tauri::command]
@@ -118,12 +146,15 @@ pub async fn switch_account(
     app: tauri::AppHandle,
     proxy_state: tauri::State<'_, crate::commands::proxy::ProxyServiceState>,
     account_id: String,
+    force: Option<bool>,
 ) -> Result<(), String> {
     let service = modules::account_service::AccountService::new(
         crate::modules::integration::SystemManager::Desktop(app.clone()),
     );
 
-    service.switch_account(&account_id).await?;
+    service
+        .switch_account(&account_id, force.unwrap_or(false))
+        .await?;
 
     // 同步托盘
     crate::modules::tray::update_tray_menus(&app);
@@ -160,6 +191,12 @@ pub async fn export_accounts(account_ids: Vec<String>) -> Result<AccountExportRe
     modules::account::export_accounts_by_ids(&account_ids)
 }
 
+/// 导出账号清单摘要（CSV / JSONL，不含 token）
+#[tauri::command]
+pub async fn export_account_summary(format: crate::models::ExportFormat) -> Result<String, String> {
+    modules::account::export_summary(format)
+}
+
 /// 内部辅助功能：在添加或导入账号后自动刷新一次额度
 async fn internal_refresh_account_quota(
     app: &tauri::AppHandle,
@@ -344,6 +381,9 @@ pub async fn save_config(
     // 通知托盘配置已更新
     let _ = app.emit("config://updated", ());
 
+    // 更新全局配额保护配置 (反代未运行时也需同步，供下次启动后的账号预留使用)
+    crate::proxy::update_quota_protection_config(config.quota_protection.clone());
+
     // 热更新正在运行的服务
     let instance_lock = proxy_state.instance.read().await;
     if let Some(instance) = instance_lock.as_ref() {
@@ -376,6 +416,10 @@ pub async fn save_config(
         crate::proxy::update_global_system_prompt_config(config.proxy.global_system_prompt.clone());
         // [NEW] 更新全局图像思维模式配置
         crate::proxy::update_image_thinking_mode(config.proxy.image_thinking_mode.clone());
+        // 更新全局上游限流配置
+        crate::proxy::update_upstream_rate_limit(&config.proxy.upstream_rate_limit);
+        // 更新账号分组
+        crate::proxy::update_account_groups(config.proxy.account_groups.clone());
         // 更新代理池配置
         instance
             .axum_server
@@ -480,18 +524,100 @@ pub async fn set_active_oauth_client(client_key: String) -> Result<(), String> {
 pub async fn import_v1_accounts(
     app: tauri::AppHandle,
     proxy_state: tauri::State<'_, crate::commands::proxy::ProxyServiceState>,
-) -> Result<Vec<Account>, String> {
-    let accounts = modules::migration::import_from_v1().await?;
+    filter: Option<modules::migration::EmailFilter>,
+    max_accounts: Option<usize>,
+) -> Result<modules::migration::V1ImportReport, String> {
+    let report = modules::migration::import_from_v1_with_report(filter, max_accounts).await?;
 
     // 对导入的账号尝试刷新一波
-    for mut account in accounts.clone() {
+    for mut account in report.imported.clone() {
         let _ = internal_refresh_account_quota(&app, &mut account).await;
     }
 
     // Reload token pool
     let _ = crate::commands::proxy::reload_proxy_accounts(proxy_state).await;
 
-    Ok(accounts)
+    // 返回导入结果及被跳过的条目 (不匹配过滤条件 / 超出数量上限)
+    Ok(report)
+}
+
+/// 导入前检查 V1 索引文件 (dry run)，返回每个条目缺失 / 类型错误的字段
+#[tauri::command]
+pub async fn validate_v1_index() -> Result<Vec<modules::migration::V1IndexValidation>, String> {
+    modules::migration::validate_v1_data()
+}
+
+/// 导入验证的取消状态：每次验证使用根令牌的子令牌，取消时作废根令牌并换新，
+/// 因此一次取消会停止所有进行中的验证，而不影响之后发起的验证
+pub struct ImportVerifyState {
+    cancel: std::sync::Mutex<tokio_util::sync::CancellationToken>,
+}
+
+impl ImportVerifyState {
+    pub fn new() -> Self {
+        Self {
+            cancel: std::sync::Mutex::new(tokio_util::sync::CancellationToken::new()),
+        }
+    }
+
+    fn child_token(&self) -> tokio_util::sync::CancellationToken {
+        self.cancel.lock().unwrap_or_else(|e| e.into_inner()).child_token()
+    }
+
+    fn cancel_all(&self) {
+        let mut cancel = self.cancel.lock().unwrap_or_else(|e| e.into_inner());
+        cancel.cancel();
+        *cancel = tokio_util::sync::CancellationToken::new();
+    }
+}
+
+impl Default for ImportVerifyState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 导入后验证账号可用 (刷新 token + 获取用户信息)，返回 "X of Y" 验证报告
+///
+/// 可通过 `cancel_import_verification` 取消，尚未验证的账号在报告中记为未验证
+#[tauri::command]
+pub async fn verify_imported_accounts(
+    verify_state: tauri::State<'_, ImportVerifyState>,
+    account_ids: Vec<String>,
+    concurrency: Option<usize>,
+) -> Result<modules::migration::VerifyReport, String> {
+    let accounts = account_ids
+        .iter()
+        .map(|id| modules::account::load_account(id))
+        .collect::<Result<Vec<_>, String>>()?;
+    Ok(modules::migration::verify_import(
+        &accounts,
+        concurrency.unwrap_or(4),
+        verify_state.child_token(),
+    )
+    .await)
+}
+
+/// 取消进行中的导入验证
+#[tauri::command]
+pub async fn cancel_import_verification(
+    verify_state: tauri::State<'_, ImportVerifyState>,
+) -> Result<(), String> {
+    verify_state.cancel_all();
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn import_current_v1_account(
+    app: tauri::AppHandle,
+    proxy_state: tauri::State<'_, crate::commands::proxy::ProxyServiceState>,
+) -> Result<Account, String> {
+    let mut account = modules::migration::import_current_v1_account().await?;
+
+    let _ = internal_refresh_account_quota(&app, &mut account).await;
+    let _ = crate::commands::proxy::reload_proxy_accounts(proxy_state).await;
+
+    Ok(account)
 }
 
 #[tauri::command]
@@ -518,6 +644,41 @@ pub async fn import_from_db(
     Ok(account)
 }
 
+/// 通过运行中 IDE 的本地 API 导入当前账号，端点不可用时回退到读取数据库
+#[tauri::command]
+pub async fn import_from_local_ide_api(
+    app: tauri::AppHandle,
+    proxy_state: tauri::State<'_, crate::commands::proxy::ProxyServiceState>,
+    port: Option<u16>,
+) -> Result<Account, String> {
+    let mut account = modules::migration::import_from_local_ide_api(port).await?;
+
+    // 与 import_from_db 相同：导入的是 IDE 当前账号，设为 Manager 的当前账号
+    let account_id = account.id.clone();
+    modules::account::set_current_account_id(&account_id)?;
+
+    let _ = internal_refresh_account_quota(&app, &mut account).await;
+
+    crate::modules::tray::update_tray_menus(&app);
+
+    // Reload token pool
+    let _ = crate::commands::proxy::reload_proxy_accounts(proxy_state).await;
+
+    Ok(account)
+}
+
+/// 列出检测到的 IDE 安装 (Stable/Insiders)，前端可选择其 db_path 调用 import_custom_db
+#[tauri::command]
+pub async fn list_ide_installations() -> Result<Vec<modules::db::Installation>, String> {
+    Ok(modules::db::list_installations())
+}
+
+/// 检测 DB / 备份文件的格式 (不提取 token)，用于排查导入失败
+#[tauri::command]
+pub async fn detect_import_format(path: String) -> Result<modules::migration::DbFormat, String> {
+    modules::migration::detect_format(&std::path::PathBuf::from(path))
+}
+
 #[tauri::command]
 #[allow(dead_code)]
 pub async fn import_custom_db(
@@ -814,8 +975,8 @@ pub async fn toggle_proxy_status(
         return Err(format!("账号文件不存在: {}", account_id));
     }
 
-    let content =
-        std::fs::read_to_string(&account_path).map_err(|e| format!("读取账号文件失败: {}", e))?;
+    let content = modules::account_store::read_account_file(&account_path)
+        .map_err(|e| format!("读取账号文件失败: {}", e))?;
 
     let mut account_json: serde_json::Value =
         serde_json::from_str(&content).map_err(|e| format!("解析账号文件失败: {}", e))?;
@@ -838,7 +999,8 @@ pub async fn toggle_proxy_status(
     // 3. 保存到磁盘
     let json_str = serde_json::to_string_pretty(&account_json)
         .map_err(|e| format!("序列化账号数据失败: {}", e))?;
-    std::fs::write(&account_path, json_str).map_err(|e| format!("写入账号文件失败: {}", e))?;
+    modules::account_store::write_account_file(&account_path, &json_str)
+        .map_err(|e| format!("写入账号文件失败: {}", e))?;
 
     modules::logger::log_info(&format!(
         "账号反代状态已更新: {} ({})",
@@ -879,6 +1041,34 @@ pub async fn toggle_proxy_status(
     Ok(())
 }
 
+/// 手动指定账号的订阅等级 (None 表示恢复使用上游返回的等级)
+#[tauri::command]
+pub async fn set_account_tier_override(
+    proxy_state: tauri::State<'_, crate::commands::proxy::ProxyServiceState>,
+    account_id: String,
+    tier: Option<String>,
+) -> Result<Account, String> {
+    let account = modules::account::set_tier_override(&account_id, tier.as_deref())?;
+
+    // 反代运行中时同步到内存池，立即影响排序
+    let instance_lock = proxy_state.instance.read().await;
+    if let Some(instance) = instance_lock.as_ref() {
+        instance
+            .token_manager
+            .reload_account(&account_id)
+            .await
+            .map_err(|e| format!("同步账号失败: {}", e))?;
+    }
+
+    Ok(account)
+}
+
+/// 启用 / 停用账号 (软禁用，保留数据；反代运行中时下次选择前生效)
+#[tauri::command]
+pub async fn set_account_enabled(email: String, enabled: bool) -> Result<Account, String> {
+    modules::account::set_enabled(&email, enabled)
+}
+
 /// 预热所有可用账号
 #[tauri::command]
 pub async fn warm_up_all_accounts() -> Result<String, String> {
@@ -915,8 +1105,8 @@ pub async fn update_account_label(account_id: String, label: String) -> Result<(
         return Err(format!("账号文件不存在: {}", account_id));
     }
 
-    let content =
-        std::fs::read_to_string(&account_path).map_err(|e| format!("读取账号文件失败: {}", e))?;
+    let content = modules::account_store::read_account_file(&account_path)
+        .map_err(|e| format!("读取账号文件失败: {}", e))?;
 
     let mut account_json: serde_json::Value =
         serde_json::from_str(&content).map_err(|e| format!("解析账号文件失败: {}", e))?;
@@ -931,7 +1121,8 @@ pub async fn update_account_label(account_id: String, label: String) -> Result<(
     // 3. 保存到磁盘
     let json_str = serde_json::to_string_pretty(&account_json)
         .map_err(|e| format!("序列化账号数据失败: {}", e))?;
-    std::fs::write(&account_path, json_str).map_err(|e| format!("写入账号文件失败: {}", e))?;
+    modules::account_store::write_account_file(&account_path, &json_str)
+        .map_err(|e| format!("写入账号文件失败: {}", e))?;
 
     modules::logger::log_info(&format!(
         "账号标签已更新: {} ({})",
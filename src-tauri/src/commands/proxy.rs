@@ -7,6 +7,11 @@ use tauri::State;
 use tokio::sync::RwLock;
 use tokio::time::Duration;
 
+/// 启动预热时同时处理的账号数
+const WARMUP_CONCURRENCY: usize = 4;
+/// 检查模型能力缓存是否过期的间隔
+const CAPABILITY_REFRESH_INTERVAL: Duration = Duration::from_secs(30 * 60);
+
 /// 反代服务状态
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProxyStatus {
@@ -180,6 +185,25 @@ pub async fn internal_start_proxy_service(
         }
     }
 
+    // 4. 后台预热：刷新即将过期的 Token 并拉取配额，不阻塞启动；停机时随 TokenManager 一同取消。
+    //    预热完成后继续在后台刷新过期的模型能力缓存
+    if active_accounts > 0 {
+        let warmup_manager = token_manager.clone();
+        let cancel = token_manager.child_cancel_token();
+        tokio::spawn(async move {
+            warmup_manager.warmup(WARMUP_CONCURRENCY, cancel.clone()).await;
+            if cancel.is_cancelled() {
+                return;
+            }
+            // 预热后定期刷新超过 TTL 的模型能力缓存 (注册到维护调度器)
+            warmup_manager.start_capability_refresh(
+                crate::proxy::capability_cache::DEFAULT_CAPABILITY_TTL_SECS,
+                CAPABILITY_REFRESH_INTERVAL,
+                WARMUP_CONCURRENCY,
+            );
+        });
+    }
+
     let mut instance_lock = state.instance.write().await;
     let admin_lock = state.admin_server.read().await;
     let axum_server = admin_lock.as_ref().unwrap().axum_server.clone();
@@ -274,6 +298,16 @@ pub async fn ensure_admin_server(
     crate::proxy::update_global_system_prompt_config(config.global_system_prompt.clone());
     // [NEW] 初始化全局图像思维模式配置
     crate::proxy::update_image_thinking_mode(config.image_thinking_mode.clone());
+    // 初始化全局上游限流配置
+    crate::proxy::update_upstream_rate_limit(&config.upstream_rate_limit);
+    // 初始化全局配额保护配置 (位于应用配置而非反代配置)
+    match crate::modules::config::load_app_config() {
+        Ok(app_config) => crate::proxy::update_quota_protection_config(app_config.quota_protection),
+        Err(e) => tracing::warn!("Failed to load quota protection config: {}", e),
+    }
+    // 恢复持久化的账号选择配置，再同步账号分组
+    crate::proxy::selection::restore_selection_config();
+    crate::proxy::update_account_groups(config.account_groups.clone());
 
     Ok(())
 }
@@ -728,6 +762,103 @@ pub async fn get_preferred_account(
     }
 }
 
+/// 获取账号池中各账号的运行时状态 (不含令牌)
+#[tauri::command]
+pub async fn get_proxy_token_views(
+    state: State<'_, ProxyServiceState>,
+) -> Result<Vec<crate::proxy::token_manager::ProxyTokenView>, String> {
+    let instance_lock = state.instance.read().await;
+    if let Some(instance) = instance_lock.as_ref() {
+        Ok(instance.token_manager.token_views())
+    } else {
+        Err("服务未运行".to_string())
+    }
+}
+
+/// 获取账号池对指定模型的总可用容量
+#[tauri::command]
+pub async fn get_proxy_model_capacity(
+    state: State<'_, ProxyServiceState>,
+    model: String,
+) -> Result<crate::proxy::ModelCapacity, String> {
+    let instance_lock = state.instance.read().await;
+    if let Some(instance) = instance_lock.as_ref() {
+        Ok(instance.token_manager.model_capacity(&model))
+    } else {
+        Err("服务未运行".to_string())
+    }
+}
+
+/// 为模型固定账号 (该账号可服务时优先选中，持久化到选择配置)
+#[tauri::command]
+pub async fn pin_proxy_account(model: String, email: String) -> Result<(), String> {
+    crate::proxy::pin_account(&model, &email)
+}
+
+/// 取消模型的固定账号，返回之前是否存在固定
+#[tauri::command]
+pub async fn unpin_proxy_account(model: String) -> Result<bool, String> {
+    crate::proxy::unpin_account(&model)
+}
+
+/// 获取所有模型固定账号 (标准化模型 ID -> 账号邮箱)
+#[tauri::command]
+pub async fn get_proxy_pinned_accounts() -> Result<std::collections::BTreeMap<String, String>, String> {
+    Ok(crate::proxy::pinned_accounts())
+}
+
+/// 获取账号池当前的配额快照
+#[tauri::command]
+pub async fn get_proxy_quota_snapshot(
+    state: State<'_, ProxyServiceState>,
+) -> Result<crate::proxy::QuotaSnapshot, String> {
+    let instance_lock = state.instance.read().await;
+    if let Some(instance) = instance_lock.as_ref() {
+        Ok(instance.token_manager.snapshot_quotas())
+    } else {
+        Err("服务未运行".to_string())
+    }
+}
+
+/// 比较两次配额快照，得到各账号各模型的消耗 (配额刷新不计为负消耗)
+#[tauri::command]
+pub async fn diff_proxy_quota_snapshots(
+    old: crate::proxy::QuotaSnapshot,
+    new: crate::proxy::QuotaSnapshot,
+) -> Result<crate::proxy::QuotaDiff, String> {
+    Ok(crate::proxy::diff_snapshots(&old, &new))
+}
+
+/// 目标模型的选择诊断 (各账号排序位置、针对该模型的健康状态与跳过原因)
+#[tauri::command]
+pub async fn get_proxy_selection_trace(
+    state: State<'_, ProxyServiceState>,
+    model: String,
+) -> Result<crate::proxy::SelectionTrace, String> {
+    let instance_lock = state.instance.read().await;
+    if let Some(instance) = instance_lock.as_ref() {
+        Ok(instance.token_manager.selection_trace(&model))
+    } else {
+        Err("服务未运行".to_string())
+    }
+}
+
+/// 使用指定账号向模型发送一次最小请求，验证该账号上的模型是否真实可用
+#[tauri::command]
+pub async fn probe_proxy_model(
+    state: State<'_, ProxyServiceState>,
+    email: String,
+    model: String,
+) -> Result<crate::proxy::ProbeResult, String> {
+    // 探测需要等待上游响应，先取出句柄再释放实例锁
+    let (token_manager, upstream) = {
+        let instance_lock = state.instance.read().await;
+        let instance = instance_lock.as_ref().ok_or("服务未运行")?;
+        (instance.token_manager.clone(), instance.axum_server.upstream_client())
+    };
+    crate::proxy::probe_model(&token_manager, &upstream, &email, &model).await
+}
+
 /// 清除指定账号的限流记录
 #[tauri::command]
 pub async fn clear_proxy_rate_limit(
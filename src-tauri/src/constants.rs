@@ -15,15 +15,69 @@ const KNOWN_STABLE_VERSION: &str = "4.1.32";
 const KNOWN_STABLE_ELECTRON: &str = "39.2.3";
 const KNOWN_STABLE_CHROME: &str = "132.0.6834.160";
 
-/// Pre-compiled regex for version parsing (X.Y.Z pattern)
+/// Env var selecting how version strings are parsed: "strict" (default, X.Y.Z only)
+/// or "lenient" (also accepts X.Y, normalized to X.Y.0)
+const VERSION_PARSE_MODE_ENV: &str = "ABV_VERSION_PARSE_MODE";
+
+/// Env var: when "1"/"true", the User-Agent version keeps the prerelease/build suffix
+const UA_INCLUDE_PRERELEASE_ENV: &str = "ABV_UA_INCLUDE_PRERELEASE";
+
+/// Pre-compiled regex for version parsing: X.Y[.Z] plus optional `-prerelease` / `+build` suffix
 static VERSION_REGEX: LazyLock<Regex> = LazyLock::new(|| {
-    Regex::new(r"\d+\.\d+\.\d+").expect("Invalid version regex")
+    Regex::new(r"(\d+)\.(\d+)(?:\.(\d+))?((?:-[0-9A-Za-z][0-9A-Za-z.-]*)?(?:\+[0-9A-Za-z][0-9A-Za-z.-]*)?)")
+        .expect("Invalid version regex")
 });
 
-/// Parse version from response text using pre-compiled regex
+/// How strictly `parse_version` matches version numbers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum VersionParseMode {
+    /// Only X.Y.Z
+    #[default]
+    Strict,
+    /// X.Y.Z or X.Y (normalized to X.Y.0)
+    Lenient,
+}
+
+impl VersionParseMode {
+    fn from_env() -> Self {
+        match std::env::var(VERSION_PARSE_MODE_ENV) {
+            Ok(mode) if mode.trim().eq_ignore_ascii_case("lenient") => Self::Lenient,
+            _ => Self::Strict,
+        }
+    }
+}
+
+/// Version found in a response: numeric X.Y.Z core plus the raw suffix (e.g. "-beta.2")
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ParsedVersion {
+    core: String,
+    suffix: Option<String>,
+}
+
+/// Parse the X.Y.Z core of the first version in `text` (mode from `ABV_VERSION_PARSE_MODE`)
 /// Matches semver pattern: X.Y.Z (e.g., "1.15.8")
 fn parse_version(text: &str) -> Option<String> {
-    VERSION_REGEX.find(text).map(|m| m.as_str().to_string())
+    parse_version_full(text).map(|v| v.core)
+}
+
+fn parse_version_full(text: &str) -> Option<ParsedVersion> {
+    parse_version_with(text, VersionParseMode::from_env())
+}
+
+/// Find the first version in `text` accepted by `mode`; X.Y is normalized to X.Y.0
+fn parse_version_with(text: &str, mode: VersionParseMode) -> Option<ParsedVersion> {
+    VERSION_REGEX.captures_iter(text).find_map(|caps| {
+        let patch = match (caps.get(3), mode) {
+            (Some(patch), _) => patch.as_str(),
+            (None, VersionParseMode::Lenient) => "0",
+            (None, VersionParseMode::Strict) => return None,
+        };
+        let suffix = caps.get(4).map(|m| m.as_str()).filter(|s| !s.is_empty());
+        Some(ParsedVersion {
+            core: format!("{}.{}.{}", &caps[1], &caps[2], patch),
+            suffix: suffix.map(|s| s.to_string()),
+        })
+    })
 }
 
 /// Compare two X.Y.Z semantic version strings.
@@ -60,21 +114,37 @@ enum VersionSource {
 /// Helper struct for version info
 struct VersionConfig {
     version: String,
+    /// Prerelease/build suffix of the chosen version (e.g. "-beta.2")
+    suffix: Option<String>,
     electron: String,
     chrome: String,
 }
 
+impl VersionConfig {
+    /// Version reported in the User-Agent; the suffix is only kept when
+    /// `ABV_UA_INCLUDE_PRERELEASE` is enabled
+    fn user_agent_version(&self) -> String {
+        let include_suffix = std::env::var(UA_INCLUDE_PRERELEASE_ENV)
+            .map(|v| matches!(v.trim().to_lowercase().as_str(), "1" | "true"))
+            .unwrap_or(false);
+        match &self.suffix {
+            Some(suffix) if include_suffix => format!("{}{}", self.version, suffix),
+            _ => self.version.clone(),
+        }
+    }
+}
+
 /// Try to fetch the latest Antigravity version from the remote update server.
 /// Runs in a dedicated OS thread to avoid blocking Tokio's async runtime.
 /// Returns None on any network/parse failure — always non-fatal, 5s timeout.
-fn try_fetch_remote_version() -> Option<String> {
+fn try_fetch_remote_version() -> Option<ParsedVersion> {
     // Spawn a dedicated OS thread so that `reqwest::blocking` never touches
     // the Tokio thread-pool and cannot trigger the "Cannot block the current
     // thread from within an asynchronous execution context" panic.
-    let (tx, rx) = std::sync::mpsc::channel::<Option<String>>();
+    let (tx, rx) = std::sync::mpsc::channel::<Option<ParsedVersion>>();
 
     std::thread::spawn(move || {
-        let result = (|| -> Option<String> {
+        let result = (|| -> Option<ParsedVersion> {
             let client = reqwest::blocking::Client::builder()
                 .timeout(std::time::Duration::from_secs(5))
                 .build()
@@ -83,8 +153,8 @@ fn try_fetch_remote_version() -> Option<String> {
             // 1. Try primary update URL
             if let Ok(resp) = client.get(VERSION_URL).send() {
                 if let Ok(text) = resp.text() {
-                    if let Some(ver) = parse_version(&text) {
-                        tracing::debug!(remote_version = %ver, "Fetched remote version from VERSION_URL");
+                    if let Some(ver) = parse_version_full(&text) {
+                        tracing::debug!(remote_version = %ver.core, "Fetched remote version from VERSION_URL");
                         return Some(ver);
                     }
                 }
@@ -93,8 +163,8 @@ fn try_fetch_remote_version() -> Option<String> {
             // 2. Try changelog page as secondary fallback
             if let Ok(resp) = client.get(CHANGELOG_URL).send() {
                 if let Ok(text) = resp.text() {
-                    if let Some(ver) = parse_version(&text) {
-                        tracing::debug!(remote_version = %ver, "Fetched remote version from CHANGELOG_URL");
+                    if let Some(ver) = parse_version_full(&text) {
+                        tracing::debug!(remote_version = %ver.core, "Fetched remote version from CHANGELOG_URL");
                         return Some(ver);
                     }
                 }
@@ -122,14 +192,15 @@ fn try_fetch_remote_version() -> Option<String> {
 fn resolve_version_config() -> (VersionConfig, VersionSource) {
     // Floor: static known-stable value (updated with each release of this project)
     let mut best_version = KNOWN_STABLE_VERSION.to_string();
+    let mut best_suffix = None;
     let mut source = VersionSource::KnownStableFallback;
 
     // 1. Try Local Installation
     if let Ok(local_ver) = crate::modules::version::get_antigravity_version() {
-        let local_parsed = parse_version(&local_ver.short_version)
-            .or_else(|| parse_version(&local_ver.bundle_version));
+        let local_parsed = parse_version_full(&local_ver.short_version)
+            .or_else(|| parse_version_full(&local_ver.bundle_version));
 
-        if let Some(local_v) = local_parsed {
+        if let Some(ParsedVersion { core: local_v, suffix }) = local_parsed {
             if compare_semver(&local_v, &best_version) > std::cmp::Ordering::Equal {
                 // Local is newer than the floor — use it
                 tracing::debug!(
@@ -137,6 +208,7 @@ fn resolve_version_config() -> (VersionConfig, VersionSource) {
                     "Local installation version is newer than known-stable floor; using local"
                 );
                 best_version = local_v;
+                best_suffix = suffix;
                 source = VersionSource::LocalInstallation;
             } else {
                 // Local is older than or equal to the floor (e.g. user hasn't updated yet)
@@ -152,7 +224,7 @@ fn resolve_version_config() -> (VersionConfig, VersionSource) {
     }
 
     // 2. Try Remote Version (best-effort; failure is silently ignored)
-    if let Some(remote_v) = try_fetch_remote_version() {
+    if let Some(ParsedVersion { core: remote_v, suffix }) = try_fetch_remote_version() {
         if compare_semver(&remote_v, &best_version) > std::cmp::Ordering::Equal {
             tracing::info!(
                 remote_version = %remote_v,
@@ -160,6 +232,7 @@ fn resolve_version_config() -> (VersionConfig, VersionSource) {
                 "Remote version is newer than current best; upgrading fingerprint version"
             );
             best_version = remote_v;
+            best_suffix = suffix;
             source = VersionSource::RemoteAPI;
         }
     }
@@ -167,6 +240,7 @@ fn resolve_version_config() -> (VersionConfig, VersionSource) {
     (
         VersionConfig {
             version: best_version,
+            suffix: best_suffix,
             electron: KNOWN_STABLE_ELECTRON.to_string(),
             chrome: KNOWN_STABLE_CHROME.to_string(),
         },
@@ -223,7 +297,7 @@ pub static USER_AGENT: LazyLock<String> = LazyLock::new(|| {
 
     format!(
         "Antigravity/{} ({}) Chrome/{} Electron/{}",
-        config.version,
+        config.user_agent_version(),
         platform_info,
         config.chrome,
         config.electron
@@ -256,11 +330,52 @@ mod tests {
 
     #[test]
     fn test_parse_version_with_suffix() {
-        // Regex only matches X.Y.Z, suffix is naturally excluded
+        // parse_version returns only the X.Y.Z core, suffix is excluded
         let text = "antigravity/1.15.8 windows/amd64";
         assert_eq!(parse_version(text), Some("1.15.8".to_string()));
     }
 
+    #[test]
+    fn test_parse_version_lenient_mode_and_suffix() {
+        let strict = VersionParseMode::Strict;
+        let lenient = VersionParseMode::Lenient;
+
+        // X.Y only in lenient mode, normalized to X.Y.0
+        assert_eq!(parse_version_with("1.16", strict), None);
+        assert_eq!(
+            parse_version_with("1.16", lenient),
+            Some(ParsedVersion { core: "1.16.0".to_string(), suffix: None })
+        );
+        assert_eq!(parse_version_with("v2.0", lenient).unwrap().core, "2.0.0");
+
+        // Prerelease / build suffix captured separately in both modes
+        for mode in [strict, lenient] {
+            assert_eq!(
+                parse_version_with("1.16.0-beta.2", mode),
+                Some(ParsedVersion {
+                    core: "1.16.0".to_string(),
+                    suffix: Some("-beta.2".to_string()),
+                })
+            );
+        }
+        assert_eq!(
+            parse_version_with("Version 2.1+build.7", lenient).unwrap().suffix.as_deref(),
+            Some("+build.7")
+        );
+
+        // Strict mode skips an earlier X.Y and keeps looking for X.Y.Z
+        assert_eq!(parse_version_with("vscode 1.2 / 1.15.8", strict).unwrap().core, "1.15.8");
+        assert_eq!(parse_version_with("vscode 1.2 / 1.15.8", lenient).unwrap().core, "1.2.0");
+
+        let config = VersionConfig {
+            version: "1.16.0".to_string(),
+            suffix: Some("-beta.2".to_string()),
+            electron: KNOWN_STABLE_ELECTRON.to_string(),
+            chrome: KNOWN_STABLE_CHROME.to_string(),
+        };
+        assert_eq!(config.user_agent_version(), "1.16.0");
+    }
+
     #[test]
     fn test_compare_semver() {
         assert_eq!(compare_semver("4.1.32", "4.1.22"), std::cmp::Ordering::Greater);
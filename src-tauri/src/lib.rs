@@ -115,12 +115,17 @@ pub fn run() {
     #[cfg(target_os = "macos")]
     increase_nofile_limit();
 
-    // Initialize logger
-    logger::init_logger();
+    // Initialize logger (destination / rotation configurable via ABV_LOG_* env vars)
+    logger::configure(logger::LoggerConfig::from_env());
 
     #[cfg(target_os = "linux")]
     configure_linux_gdk_backend();
 
+    // 读取任何账号文件之前先加载配置：确定账号文件加密开关，并在此一次性迁移已有文件
+    if let Err(e) = modules::config::load_app_config() {
+        error!("Failed to load config before account store init: {}", e);
+    }
+
     // Initialize token stats database
     if let Err(e) = modules::token_stats::init_db() {
         error!("Failed to initialize token stats database: {}", e);
@@ -312,6 +317,7 @@ pub fn run() {
         }))
         .manage(commands::proxy::ProxyServiceState::new())
         .manage(commands::cloudflared::CloudflaredState::new())
+        .manage(commands::ImportVerifyState::new())
         .manage(AppRuntimeFlags { tray_enabled })
         .setup(|app| {
             info!("Setup starting...");
@@ -360,6 +366,11 @@ pub fn run() {
                     let cf_state = handle.state::<commands::cloudflared::CloudflaredState>();
                     let integration = crate::modules::integration::SystemManager::Desktop(handle.clone());
 
+                    // 0. 账号体检 (空 Refresh Token 等)，按配置尝试从 IDE 数据库自动修复
+                    tauri::async_runtime::spawn(modules::account::run_startup_audit(
+                        config.auto_repair_accounts,
+                    ));
+
                     // 1. 确保管理后台开启
                     if let Err(e) = commands::proxy::ensure_admin_server(
                         config.proxy.clone(),
@@ -424,12 +435,15 @@ pub fn run() {
             greet,
             // Account management commands
             commands::list_accounts,
+            commands::list_accounts_by_source,
             commands::add_account,
             commands::delete_account,
             commands::delete_accounts,
+            commands::purge_all_accounts,
             commands::reorder_accounts,
             commands::switch_account,
             commands::export_accounts,
+            commands::export_account_summary,
             // Device fingerprint
             commands::get_device_profiles,
             commands::bind_device_profile,
@@ -458,8 +472,15 @@ pub fn run() {
             commands::get_active_oauth_client,
             commands::set_active_oauth_client,
             commands::import_v1_accounts,
+            commands::validate_v1_index,
+            commands::verify_imported_accounts,
+            commands::cancel_import_verification,
+            commands::import_current_v1_account,
             commands::import_from_db,
+            commands::import_from_local_ide_api,
             commands::import_custom_db,
+            commands::detect_import_format,
+            commands::list_ide_installations,
             commands::sync_account_from_db,
             commands::save_text_file,
             commands::read_text_file,
@@ -506,6 +527,15 @@ pub fn run() {
             commands::proxy::clear_proxy_session_bindings,
             commands::proxy::set_preferred_account,
             commands::proxy::get_preferred_account,
+            commands::proxy::get_proxy_token_views,
+            commands::proxy::get_proxy_model_capacity,
+            commands::proxy::pin_proxy_account,
+            commands::proxy::unpin_proxy_account,
+            commands::proxy::get_proxy_pinned_accounts,
+            commands::proxy::get_proxy_quota_snapshot,
+            commands::proxy::diff_proxy_quota_snapshots,
+            commands::proxy::get_proxy_selection_trace,
+            commands::proxy::probe_proxy_model,
             commands::proxy::clear_proxy_rate_limit,
             commands::proxy::clear_all_proxy_rate_limits,
             commands::proxy::check_proxy_health,
@@ -521,6 +551,8 @@ pub fn run() {
             commands::warm_up_all_accounts,
             commands::warm_up_account,
             commands::update_account_label,
+            commands::set_account_tier_override,
+            commands::set_account_enabled,
             // HTTP API settings commands
             commands::get_http_api_settings,
             commands::save_http_api_settings,
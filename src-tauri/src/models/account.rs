@@ -1,12 +1,21 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use sha2::Digest;
+use std::time::Duration;
 use super::{token::TokenData, quota::QuotaData};
 
+/// 默认身份提供方，用于派生稳定账号 ID
+pub const DEFAULT_IDENTITY_PROVIDER: &str = "google";
+
 /// 账号数据结构
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Account {
     pub id: String,
+    /// 显示用邮箱；可能变更，身份以 `subject` / `id` 为准
     pub email: String,
+    /// 身份提供方的稳定主体标识 (Google userinfo `id` / OIDC `sub`)，旧数据为 None
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
     pub name: Option<String>,
     pub token: TokenData,
     /// 可选的设备指纹，用于切换账号时固定机器信息
@@ -25,6 +34,10 @@ pub struct Account {
     /// Unix timestamp when the account was disabled.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub disabled_at: Option<i64>,
+    /// 用户主动停用账号 (软禁用，保留全部数据)；停用后不参与反代选择。
+    /// 与自动/临时的封禁 (validation_blocked、403) 不同，只由用户切换。
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
     /// User manually disabled proxy feature (does not affect app usage).
     #[serde(default)]
     pub proxy_disabled: bool,
@@ -60,6 +73,38 @@ pub struct Account {
     /// 用户自定义标签
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub custom_label: Option<String>,
+    /// 手动指定的订阅等级，优先于上游返回的 subscription_tier (用于纠正误报的等级)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tier_override: Option<String>,
+    /// 为该账号构建上游请求时附加的请求头 (覆盖默认的客户端特征头)，用于透传请求身份
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub extra_headers: HashMap<String, String>,
+    /// 最近一次 Token 刷新或配额查询成功的时间戳 (None = 未知，兼容旧数据)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_refreshed: Option<i64>,
+    /// 首次导入时的来源 (None = 未知，如旧数据或 OAuth 登录添加)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<AccountSource>,
+}
+
+/// 账号导入来源，用于按来源审计与清理
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountSource {
+    /// V1 版本数据迁移
+    V1Migration,
+    /// 当前 IDE 的数据库 / 本地 API
+    LiveDb,
+    /// 手动粘贴的 refresh_token
+    ManualToken,
+    /// JSONL 批量导入
+    Jsonl,
+    /// 环境变量注入
+    Env,
+}
+
+fn default_enabled() -> bool {
+    true
 }
 
 impl Account {
@@ -68,6 +113,7 @@ impl Account {
         Self {
             id,
             email,
+            subject: None,
             name: None,
             token,
             device_profile: None,
@@ -76,6 +122,7 @@ impl Account {
             disabled: false,
             disabled_reason: None,
             disabled_at: None,
+            enabled: true,
             proxy_disabled: false,
             proxy_disabled_reason: None,
             proxy_disabled_at: None,
@@ -89,15 +136,39 @@ impl Account {
             proxy_id: None,
             proxy_bound_at: None,
             custom_label: None,
+            tier_override: None,
+            extra_headers: HashMap::new(),
+            last_refreshed: None,
+            source: None,
         }
     }
 
+    /// 由 (provider, subject) 派生稳定的账号 ID (UUID 格式)，与可变的 email 无关
+    pub fn derive_id(provider: &str, subject: &str) -> String {
+        let digest = sha2::Sha256::digest(format!("{}:{}", provider, subject).as_bytes());
+        uuid::Uuid::from_slice(&digest[..16])
+            .expect("SHA-256 digest is longer than 16 bytes")
+            .to_string()
+    }
+
     pub fn update_last_used(&mut self) {
         self.last_used = chrono::Utc::now().timestamp();
     }
 
     pub fn update_quota(&mut self, quota: QuotaData) {
         self.quota = Some(quota);
+        self.mark_refreshed(crate::utils::clock::now());
+    }
+
+    /// 记录一次成功的 Token 刷新 / 配额查询
+    pub fn mark_refreshed(&mut self, now: i64) {
+        self.last_refreshed = Some(now);
+    }
+
+    /// 距上次成功刷新经过的时间，未知时返回 None；时钟回拨时视为 0
+    pub fn staleness(&self, now: i64) -> Option<Duration> {
+        self.last_refreshed
+            .map(|ts| Duration::from_secs(now.saturating_sub(ts).max(0) as u64))
     }
 }
 
@@ -114,6 +185,8 @@ pub struct AccountIndex {
 pub struct AccountSummary {
     pub id: String,
     pub email: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
     pub name: Option<String>,
     #[serde(default)]
     pub disabled: bool,
@@ -174,3 +247,56 @@ pub struct AccountExportItem {
 pub struct AccountExportResponse {
     pub accounts: Vec<AccountExportItem>,
 }
+
+/// 账号清单摘要导出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    Jsonl,
+}
+
+/// 账号清单摘要行（用于审计，不含任何 token/密钥）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountSummaryRow {
+    pub email: String,
+    pub tier: Option<String>,
+    /// 各模型中最大的剩余配额百分比
+    pub remaining_quota: Option<i32>,
+    /// ok / disabled / proxy_disabled / forbidden / validation_blocked
+    pub health: String,
+    pub blocked_until: Option<i64>,
+    pub last_refreshed: Option<i64>,
+}
+
+/// 账号体检发现的问题类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountIssueKind {
+    /// Refresh Token 为空或仅含空白，每次刷新都会失败
+    EmptyRefreshToken,
+    /// 邮箱为空
+    EmptyEmail,
+    /// 仍是导入时的占位 Access Token 且已过期
+    PlaceholderAccessToken,
+}
+
+/// 账号体检结果（不含任何 token/密钥）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountIssue {
+    pub account_id: String,
+    pub email: String,
+    pub kind: AccountIssueKind,
+}
+
+
+/// 清除全部账号数据的结果
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PurgeReport {
+    /// 被移除的账号数 (索引 + 账号目录中的文件，按 ID 去重)
+    pub accounts_removed: usize,
+    /// 被删除的文件数
+    pub files_removed: usize,
+    /// 删除前被覆写的字节数
+    pub bytes_overwritten: u64,
+}
@@ -30,6 +30,59 @@ pub struct AppConfig {
     pub hidden_menu_items: Vec<String>, // Hidden menu item path list
     #[serde(default)]
     pub cloudflared: CloudflaredConfig, // [NEW] Cloudflared configuration
+    #[serde(default)]
+    pub encrypt_account_store: bool, // Encrypt accounts/*.json at rest
+    #[serde(default)]
+    pub encryption_key_source: EncryptionKeySource, // Where the at-rest encryption key comes from
+    #[serde(default)]
+    pub token_field_paths: TokenFieldPathsConfig, // Override protobuf field paths used to extract IDE tokens
+    #[serde(default)]
+    pub auto_repair_accounts: bool, // On startup, try to restore empty refresh tokens from the IDE database
+}
+
+/// Source of the key used to encrypt secrets at rest
+///
+/// Persisted in the app config and applied at startup before any account file is read,
+/// so secrets written under a non-default source stay readable after a restart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EncryptionKeySource {
+    /// Derived from the machine id (per-install secret when the machine id is unavailable)
+    #[default]
+    MachineId,
+    /// Random key stored in `encryption.key` under the data directory
+    KeyFile,
+}
+
+/// Protobuf field paths of the OAuth token info inside one IDE storage format
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OAuthFieldPaths {
+    /// Path from the format's container message to the OAuthInfo message (empty = the container itself)
+    #[serde(default)]
+    pub oauth_info: Vec<u32>,
+    /// Path from OAuthInfo to the refresh token string
+    pub refresh_token: Vec<u32>,
+    /// Path from OAuthInfo to the access token string (empty = not extracted)
+    #[serde(default)]
+    pub access_token: Vec<u32>,
+    /// Path from OAuthInfo to the expiry seconds; the last element is a varint field (empty = not extracted)
+    #[serde(default)]
+    pub expiry_seconds: Vec<u32>,
+}
+
+/// Per-format overrides of the built-in field paths, so a shifted field number
+/// in a new IDE version can be corrected via config without waiting for a patch
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenFieldPathsConfig {
+    /// `antigravityUnifiedStateSync.oauthToken` (>= 1.16.5); container = decoded unified state payload
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub new_unified: Option<OAuthFieldPaths>,
+    /// `jetskiStateSync.agentManagerInitState` (< 1.16.5); container = decoded agent state
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub legacy_jetski: Option<OAuthFieldPaths>,
+    /// Maximum protobuf nesting depth followed by a field path (None = built-in default of 32)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_nesting_depth: Option<usize>,
 }
 
 /// Scheduled warmup configuration
@@ -147,17 +200,27 @@ pub struct CircuitBreakerConfig {
     /// Default: [60, 300, 1800, 7200]
     #[serde(default = "default_backoff_steps")]
     pub backoff_steps: Vec<u64>,
+
+    /// Escalating validation block durations (seconds), indexed by consecutive blocks
+    /// Default: [600, 1800, 7200]; reset after a successful request
+    #[serde(default = "default_validation_block_steps")]
+    pub validation_block_steps: Vec<u64>,
 }
 
 fn default_backoff_steps() -> Vec<u64> {
     vec![60, 300, 1800, 7200]
 }
 
+fn default_validation_block_steps() -> Vec<u64> {
+    vec![600, 1800, 7200]
+}
+
 impl CircuitBreakerConfig {
     pub fn new() -> Self {
         Self {
             enabled: true,
             backoff_steps: default_backoff_steps(),
+            validation_block_steps: default_validation_block_steps(),
         }
     }
 }
@@ -188,6 +251,10 @@ impl AppConfig {
             circuit_breaker: CircuitBreakerConfig::default(),
             hidden_menu_items: Vec::new(),
             cloudflared: CloudflaredConfig::default(),
+            encrypt_account_store: false,
+            encryption_key_source: EncryptionKeySource::default(),
+            token_field_paths: TokenFieldPathsConfig::default(),
+            auto_repair_accounts: false,
         }
     }
 }
@@ -6,6 +6,9 @@ pub struct ModelQuota {
     pub name: String,
     pub percentage: i32,  // 剩余百分比 0-100
     pub reset_time: String,
+    /// `percentage` 对应的满额值；上游未返回剩余比例时为 None (满额未知)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quota_max: Option<i32>,
     
     // -- 动态参数解析与持久化 --
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -22,6 +22,12 @@ pub struct TokenData {
     pub session_id: Option<String>,  // 新增：Antigravity sessionId
     #[serde(default = "default_is_gcp_tos")]
     pub is_gcp_tos: bool,
+    /// OAuth 授权时实际授予的 scope 列表 (旧数据为 None)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scopes: Option<Vec<String>>,
+    /// `email` 仅来自访问令牌的 JWT 声明 (userinfo 获取失败时的回退)，未经服务端确认
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub email_unverified: bool,
 }
 
 impl TokenData {
@@ -34,7 +40,7 @@ impl TokenData {
         session_id: Option<String>,
         is_gcp_tos: bool,
     ) -> Self {
-        let expiry_timestamp = chrono::Utc::now().timestamp() + expires_in;
+        let expiry_timestamp = crate::utils::clock::now() + expires_in;
         Self {
             access_token,
             refresh_token,
@@ -46,6 +52,8 @@ impl TokenData {
             oauth_client_key: None,
             session_id,
             is_gcp_tos,
+            scopes: None,
+            email_unverified: false,
         }
     }
 
@@ -53,4 +61,21 @@ impl TokenData {
         self.oauth_client_key = oauth_client_key;
         self
     }
+
+    pub fn with_scopes(mut self, scopes: Option<Vec<String>>) -> Self {
+        self.scopes = scopes;
+        self
+    }
+
+    pub fn with_email_unverified(mut self, unverified: bool) -> Self {
+        self.email_unverified = unverified;
+        self
+    }
+
+    /// 是否授予了指定 scope (未知 scope 时返回 false)
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes
+            .as_ref()
+            .is_some_and(|scopes| scopes.iter().any(|s| s == scope))
+    }
 }
@@ -6,10 +6,12 @@ use std::path::PathBuf;
 use uuid::Uuid;
 
 use crate::models::{
-    Account, AccountIndex, AccountSummary, DeviceProfile, DeviceProfileVersion, QuotaData,
-    TokenData,
+    Account, AccountIndex, AccountSource, AccountSummary, DeviceProfile, DeviceProfileVersion,
+    PurgeReport, QuotaData, TokenData,
 };
 use crate::modules;
+use crate::modules::account_store::AccountStore;
+use crate::utils::{emails_match, normalize_email};
 use once_cell::sync::Lazy;
 use std::sync::Mutex;
 
@@ -208,7 +210,7 @@ mod tests {
         let account_files: Vec<_> = fs::read_dir(&accounts_dir)
             .unwrap()
             .filter_map(|e| e.ok())
-            .filter(|e| e.path().extension().map_or(false, |ext| ext == "json"))
+            .filter(|e| e.path().extension().is_some_and(|ext| ext == "json"))
             .collect();
         assert_eq!(account_files.len(), 2, "Account files should still exist on disk");
         
@@ -228,6 +230,7 @@ mod tests {
                 AccountSummary {
                     id: "acc-1".to_string(),
                     email: "user1@example.com".to_string(),
+                    subject: None,
                     name: Some("User One".to_string()),
                     disabled: false,
                     proxy_disabled: false,
@@ -238,6 +241,7 @@ mod tests {
                 AccountSummary {
                     id: "acc-2".to_string(),
                     email: "user2@example.com".to_string(),
+                    subject: None,
                     name: None,
                     disabled: true,
                     proxy_disabled: true,
@@ -306,7 +310,7 @@ mod tests {
             .filter(|e| {
                 e.file_name()
                     .to_str()
-                    .map_or(false, |name| name.starts_with("accounts.json.corrupt-"))
+                    .is_some_and(|name| name.starts_with("accounts.json.corrupt-"))
             })
             .collect();
         
@@ -318,11 +322,486 @@ mod tests {
 
         println!("Backup creation on parse failure: successfully created backup");
     }
+    #[test]
+    fn test_account_without_last_refreshed_deserializes_as_unknown() {
+        let account = Account::new(
+            "old-acc".to_string(),
+            "old@example.com".to_string(),
+            TokenData::new("at".to_string(), "rt".to_string(), 3600, None, None, None, true),
+        );
+        let mut json = serde_json::to_value(&account).unwrap();
+        json.as_object_mut().unwrap().remove("last_refreshed");
+
+        let loaded: Account = serde_json::from_value(json).expect("old data should deserialize");
+        assert_eq!(loaded.last_refreshed, None);
+        assert_eq!(loaded.staleness(chrono::Utc::now().timestamp()), None);
+    }
+
+    #[test]
+    fn test_last_refreshed_updates_on_quota_and_token_refresh() {
+        let mut account = Account::new(
+            "acc".to_string(),
+            "acc@example.com".to_string(),
+            TokenData::new("at".to_string(), "rt".to_string(), 3600, None, None, None, true),
+        );
+        assert!(account.last_refreshed.is_none());
+
+        account.update_quota(QuotaData::new());
+        let refreshed_at = account.last_refreshed.expect("quota fetch should mark refreshed");
+
+        account.mark_refreshed(1_000);
+        assert_eq!(
+            account.staleness(1_000 + 2 * 3600),
+            Some(std::time::Duration::from_secs(2 * 3600))
+        );
+        // 时钟回拨不产生负值
+        assert_eq!(account.staleness(500), Some(std::time::Duration::ZERO));
+
+        let json = serde_json::to_value(&account).unwrap();
+        assert_eq!(json["last_refreshed"], 1_000);
+        assert!(refreshed_at > 0);
+    }
+
+    fn summary_with(id: &str, email: &str, subject: Option<&str>) -> AccountSummary {
+        AccountSummary {
+            id: id.to_string(),
+            email: email.to_string(),
+            subject: subject.map(|s| s.to_string()),
+            name: None,
+            disabled: false,
+            proxy_disabled: false,
+            protected_models: HashSet::new(),
+            created_at: 0,
+            last_used: 0,
+        }
+    }
+
+    #[test]
+    fn test_derived_account_id_is_stable() {
+        let id = Account::derive_id("google", "1234567890");
+        assert_eq!(id, Account::derive_id("google", "1234567890"));
+        assert!(Uuid::parse_str(&id).is_ok());
+        assert_ne!(id, Account::derive_id("google", "1234567891"));
+        assert_ne!(id, Account::derive_id("other-provider", "1234567890"));
+    }
+
+    #[test]
+    fn test_email_change_preserves_account_id() {
+        let stable_id = Account::derive_id("google", "sub-1");
+        let index = AccountIndex {
+            version: "2.0".to_string(),
+            accounts: vec![
+                summary_with(&stable_id, "old@example.com", Some("sub-1")),
+                summary_with("legacy-uuid", "legacy@example.com", None),
+                summary_with("other", "shared@example.com", Some("sub-2")),
+            ],
+            current_account_id: None,
+        };
+
+        // 同一 subject 换了邮箱：仍解析到原账号
+        assert_eq!(
+            find_account_id_in_index(&index, "new@example.com", Some("sub-1")),
+            Ok(Some(stable_id.clone()))
+        );
+        // 旧数据无 subject：按邮箱匹配
+        assert_eq!(
+            find_account_id_in_index(&index, "legacy@example.com", Some("sub-3")),
+            Ok(Some("legacy-uuid".to_string()))
+        );
+        // 邮箱相同但 subject 不同：明确拒绝，而不是再建一条同邮箱记录
+        assert!(find_account_id_in_index(&index, "shared@example.com", Some("sub-4")).is_err());
+        // 未知邮箱
+        assert_eq!(find_account_id_in_index(&index, "none@example.com", Some("sub-5")), Ok(None));
+
+        let mut account = Account::new(
+            stable_id.clone(),
+            "old@example.com".to_string(),
+            TokenData::new("at".to_string(), "rt".to_string(), 3600, None, None, None, true),
+        );
+        assert!(sync_account_identity(&mut account, "new@example.com", Some("sub-1")));
+        assert_eq!(account.id, stable_id);
+        assert_eq!(account.email, "new@example.com");
+        assert_eq!(account.subject.as_deref(), Some("sub-1"));
+        assert!(!sync_account_identity(&mut account, "new@example.com", Some("sub-1")));
+    }
+
+    fn summary_fixture() -> Vec<crate::models::AccountSummaryRow> {
+        let now = chrono::Utc::now().timestamp();
+        let mut healthy = Account::new(
+            "acc1".to_string(),
+            "a@example.com".to_string(),
+            TokenData::new(
+                "secret-access-1".to_string(),
+                "secret-refresh-1".to_string(),
+                3600,
+                None,
+                Some("proj-1".to_string()),
+                None,
+                true,
+            ),
+        );
+        let quota: QuotaData = serde_json::from_value(serde_json::json!({
+            "models": [
+                { "name": "gemini-3-flash", "percentage": 40, "reset_time": "" },
+                { "name": "claude-sonnet-4-6", "percentage": 75, "reset_time": "" }
+            ],
+            "last_updated": now,
+            "subscription_tier": "PRO"
+        }))
+        .unwrap();
+        healthy.update_quota(quota);
+
+        let mut blocked = Account::new(
+            "acc2".to_string(),
+            "b,c@example.com".to_string(),
+            TokenData::new(
+                "secret-access-2".to_string(),
+                "secret-refresh-2".to_string(),
+                3600,
+                None,
+                None,
+                None,
+                true,
+            ),
+        );
+        blocked.validation_blocked = true;
+        blocked.validation_blocked_until = Some(now + 600);
+
+        vec![summarize_account(&healthy, now), summarize_account(&blocked, now)]
+    }
+
+    #[test]
+    fn test_export_summary_csv_has_header_and_rows_without_secrets() {
+        let rows = summary_fixture();
+        let csv = render_summary(&rows, crate::models::ExportFormat::Csv).unwrap();
+
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            lines[0],
+            "email,tier,remaining_quota,health,blocked_until,last_refreshed"
+        );
+        assert!(lines[1].starts_with("a@example.com,PRO,75,ok,,"));
+        // 含逗号的字段需加引号
+        assert!(lines[2].starts_with("\"b,c@example.com\",,,validation_blocked,"));
+        assert!(!csv.contains("secret"));
+        assert!(!csv.contains("proj-1"));
+    }
+
+    #[test]
+    fn test_export_summary_jsonl_one_line_per_account_without_secrets() {
+        let rows = summary_fixture();
+        let jsonl = render_summary(&rows, crate::models::ExportFormat::Jsonl).unwrap();
+
+        let lines: Vec<&str> = jsonl.lines().collect();
+        assert_eq!(lines.len(), 2);
+        let first: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(first["tier"], "PRO");
+        assert_eq!(first["remaining_quota"], 75);
+        assert!(first["last_refreshed"].as_i64().is_some());
+        let second: serde_json::Value = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!(second["health"], "validation_blocked");
+        assert!(second["blocked_until"].as_i64().is_some());
+        assert!(!jsonl.contains("secret"));
+        assert!(!jsonl.contains("token"));
+    }
+
+    #[test]
+    fn test_audit_flags_empty_refresh_token() {
+        use crate::models::AccountIssueKind;
+
+        let now = chrono::Utc::now().timestamp();
+        let healthy = Account::new("ok".to_string(), "ok@example.com".to_string(), token("at", "rt"));
+        let empty_rt = Account::new(
+            "empty-rt".to_string(),
+            "empty@example.com".to_string(),
+            token("at", "  "),
+        );
+        let mut placeholder = Account::new(
+            "placeholder".to_string(),
+            String::new(),
+            token(modules::migration::IMPORTED_ACCESS_TOKEN_PLACEHOLDER, "rt"),
+        );
+        placeholder.token.expiry_timestamp = now - 1;
+
+        let issues = audit_account_list(&[healthy, empty_rt, placeholder], now);
+        let found: Vec<(&str, AccountIssueKind)> = issues
+            .iter()
+            .map(|issue| (issue.account_id.as_str(), issue.kind))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("empty-rt", AccountIssueKind::EmptyRefreshToken),
+                ("placeholder", AccountIssueKind::EmptyEmail),
+                ("placeholder", AccountIssueKind::PlaceholderAccessToken),
+            ]
+        );
+        assert_eq!(issues[0].email, "empty@example.com");
+    }
+
+    fn token(access: &str, refresh: &str) -> TokenData {
+        TokenData::new(access.to_string(), refresh.to_string(), 3600, None, None, None, true)
+    }
+
+    #[test]
+    fn test_upsert_against_memory_store() {
+        use crate::modules::account_store::MemoryAccountStore;
+
+        let store = MemoryAccountStore::new();
+
+        // 新建：有 subject 时使用稳定 ID
+        let created = upsert_account_in(
+            &store,
+            "old@example.com".to_string(),
+            Some("User".to_string()),
+            token("at-1", "rt-1"),
+            Some("sub-1".to_string()),
+            None,
+        )
+        .unwrap()
+        .account;
+        assert_eq!(created.id, Account::derive_id("google", "sub-1"));
+        assert_eq!(store.list().unwrap().len(), 1);
+
+        // 同一 subject 换邮箱：更新原账号而非新建
+        let mut disabled = store.get(&created.id).unwrap().unwrap();
+        disabled.disabled = true;
+        disabled.disabled_reason = Some("invalid_grant".to_string());
+        store.upsert(&disabled).unwrap();
+
+        let updated = upsert_account_in(
+            &store,
+            "new@example.com".to_string(),
+            None,
+            token("at-2", "rt-2"),
+            Some("sub-1".to_string()),
+            None,
+        )
+        .unwrap()
+        .account;
+        assert_eq!(updated.id, created.id);
+        assert_eq!(updated.email, "new@example.com");
+        assert_eq!(updated.token.refresh_token, "rt-2");
+        // 更换凭证后自动解除禁用
+        assert!(!updated.disabled);
+        assert!(updated.disabled_reason.is_none());
+        assert!(updated.last_refreshed.is_some());
+        assert_eq!(store.list().unwrap().len(), 1);
+
+        // 无 subject 的旧调用方按邮箱匹配
+        let legacy = upsert_account_in(
+            &store,
+            "legacy@example.com".to_string(),
+            None,
+            token("at-3", "rt-3"),
+            None,
+            None,
+        )
+        .unwrap()
+        .account;
+        assert_ne!(legacy.id, created.id);
+        // 邮箱大小写与首尾空白不影响匹配
+        let same_legacy = upsert_account_in(
+            &store,
+            " Legacy@Example.com ".to_string(),
+            None,
+            token("at-4", "rt-4"),
+            None,
+            None,
+        )
+        .unwrap()
+        .account;
+        assert_eq!(same_legacy.id, legacy.id);
+        assert_eq!(same_legacy.email, "legacy@example.com");
+        let ids: Vec<String> = store.list().unwrap().into_iter().map(|a| a.id).collect();
+        assert_eq!(ids, vec![created.id.clone(), legacy.id.clone()]);
+
+        // 同邮箱、不同 subject：拒绝且不新增记录
+        let err = upsert_account_in(
+            &store,
+            "new@example.com".to_string(),
+            None,
+            token("at-5", "rt-5"),
+            Some("sub-2".to_string()),
+            None,
+        )
+        .unwrap_err();
+        assert!(err.contains("different Google account"));
+        assert_eq!(store.list().unwrap().len(), 2);
+        assert_eq!(store.get(&created.id).unwrap().unwrap().token.refresh_token, "rt-2");
+
+        // 旧记录 (无 subject) 首次带 subject 更新时合并并记录 subject
+        let adopted = upsert_account_in(
+            &store,
+            "legacy@example.com".to_string(),
+            None,
+            token("at-6", "rt-6"),
+            Some("sub-3".to_string()),
+            None,
+        )
+        .unwrap()
+        .account;
+        assert_eq!(adopted.id, legacy.id);
+        assert_eq!(adopted.subject.as_deref(), Some("sub-3"));
+        assert_eq!(store.list().unwrap().len(), 2);
+
+        store.remove(&legacy.id).unwrap();
+        assert!(store.get(&legacy.id).unwrap().is_none());
+        assert!(store.remove(&legacy.id).is_err());
+    }
+
+    #[test]
+    fn test_purge_all_removes_accounts_and_store_file() {
+        let _guard = TEST_MUTEX.lock().unwrap();
+        let dir = TestDataDir::new();
+
+        create_account_file(dir.path(), "purge-1", "user1@example.com");
+        create_account_file(dir.path(), "purge-2", "user2@example.com");
+        let index = load_account_index_in_dir(dir.path()).unwrap();
+        save_account_index_in_dir(dir.path(), &index).unwrap();
+        fs::write(dir.path().join("accounts").join("purge-1.json.tmp"), "partial").unwrap();
+        let key_file = dir.path().join(crate::utils::key_provider::KEY_FILE_NAME);
+        let install_secret = dir.path().join(crate::utils::crypto::INSTALL_SECRET_FILE);
+        fs::write(&key_file, "00".repeat(32)).unwrap();
+        fs::write(&install_secret, "11".repeat(32)).unwrap();
+        let index_path = dir.path().join("accounts.json");
+        assert!(index_path.exists());
+
+        // 未确认时拒绝执行
+        assert!(purge_all(false).is_err());
+
+        let (report, mut ids) = purge_all_in_dir(dir.path()).unwrap();
+        ids.sort();
+        assert_eq!(ids, vec!["purge-1".to_string(), "purge-2".to_string()]);
+        assert_eq!(report.accounts_removed, 2);
+        assert_eq!(report.files_removed, 6);
+        assert!(report.bytes_overwritten > 0);
+
+        assert!(!index_path.exists());
+        assert!(!key_file.exists());
+        assert!(!install_secret.exists());
+        assert!(!dir.path().join("accounts").exists());
+
+        // 再次清除为空操作
+        let (report, _) = purge_all_in_dir(dir.path()).unwrap();
+        assert_eq!(report, PurgeReport::default());
+        assert!(load_account_index_in_dir(dir.path()).unwrap().accounts.is_empty());
+    }
+
+    #[test]
+    fn test_noop_upsert_does_not_rewrite_account_files() {
+        let _guard = TEST_MUTEX.lock().unwrap();
+        let dir = TestDataDir::new();
+
+        create_account_file(dir.path(), "noop-1", "noop@example.com");
+        let index = load_account_index_in_dir(dir.path()).unwrap();
+        save_account_index_in_dir(dir.path(), &index).unwrap();
+        let account_path = dir.path().join("accounts").join("noop-1.json");
+        let index_path = dir.path().join("accounts.json");
+        let snapshot = |path: &PathBuf| {
+            (
+                fs::read(path).unwrap(),
+                fs::metadata(path).unwrap().modified().unwrap(),
+            )
+        };
+        let before = (snapshot(&account_path), snapshot(&index_path));
+
+        // 与已存储的凭据完全一致 (expiry 按导入时刻重新计算也视为未变化)
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        let same_token = || {
+            TokenData::new(
+                "test_access_token".to_string(),
+                "test_refresh_token".to_string(),
+                3600,
+                Some("noop@example.com".to_string()),
+                None,
+                None,
+                true,
+            )
+        };
+        let store = FileAccountStore::in_dir(dir.path().clone());
+        let upsert = |token, source| {
+            upsert_account_in(&store, "noop@example.com".to_string(), None, token, None, source).unwrap()
+        };
+        let outcome = upsert(same_token(), None);
+        assert!(!outcome.written);
+        assert_eq!(outcome.account.id, "noop-1");
+        assert_eq!((snapshot(&account_path), snapshot(&index_path)), before);
+
+        // 令牌变化时正常写入
+        let mut rotated = same_token();
+        rotated.refresh_token = "rotated_refresh_token".to_string();
+        let outcome = upsert(rotated, None);
+        assert!(outcome.written);
+        assert_ne!(fs::read(&account_path).unwrap(), before.0 .0);
+        assert_eq!(
+            load_account_in_dir(dir.path(), "noop-1").unwrap().token.refresh_token,
+            "rotated_refresh_token"
+        );
+
+        // 首次记录导入来源会写入；之后从其他来源重复导入保留原来源
+        let rotated_token = || {
+            let mut token = same_token();
+            token.refresh_token = "rotated_refresh_token".to_string();
+            token
+        };
+        let tag = |source| upsert(rotated_token(), Some(source));
+        let tagged = tag(AccountSource::LiveDb);
+        assert!(tagged.written);
+        assert_eq!(tagged.account.source, Some(AccountSource::LiveDb));
+        let retagged = tag(AccountSource::ManualToken);
+        assert!(!retagged.written);
+        assert_eq!(retagged.account.source, Some(AccountSource::LiveDb));
+
+        // 未匹配任何账号时新增
+        let added =
+            upsert_account_in(&store, "other@example.com".to_string(), None, same_token(), None, None)
+                .unwrap();
+        assert!(added.written);
+        assert_ne!(added.account.id, "noop-1");
+        assert_eq!(store.list().unwrap().len(), 2);
+    }
 
+    #[test]
+    fn test_set_enabled_keeps_account_data() {
+        use crate::modules::account_store::MemoryAccountStore;
+
+        // 旧数据没有 enabled 字段时默认启用
+        let mut json = serde_json::to_value(Account::new(
+            "old".to_string(),
+            "old@example.com".to_string(),
+            token("at", "rt"),
+        ))
+        .unwrap();
+        json.as_object_mut().unwrap().remove("enabled");
+        assert!(serde_json::from_value::<Account>(json).unwrap().enabled);
+
+        let store = MemoryAccountStore::new();
+        let mut account = Account::new("acc".to_string(), "user@example.com".to_string(), token("at-1", "rt-1"));
+        account.custom_label = Some("main".to_string());
+        account.tier_override = Some("ULTRA".to_string());
+        store.upsert(&account).unwrap();
+
+        let disabled = set_enabled_in(&store, "User@Example.com", false).unwrap();
+        assert!(!disabled.enabled);
+        let stored = store.get("acc").unwrap().unwrap();
+        assert!(!stored.enabled);
+        assert!(!stored.disabled && !stored.proxy_disabled);
+        assert_eq!(stored.token.refresh_token, "rt-1");
+        assert_eq!(stored.custom_label.as_deref(), Some("main"));
+        assert_eq!(stored.tier_override.as_deref(), Some("ULTRA"));
+
+        assert!(set_enabled_in(&store, "user@example.com", true).unwrap().enabled);
+        assert!(store.get("acc").unwrap().unwrap().enabled);
+        assert!(set_enabled_in(&store, "missing@example.com", false).is_err());
+    }
 }
 
 /// Global account write lock to prevent corruption during concurrent operations
 static ACCOUNT_INDEX_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+/// Serializes identity matching + write in `upsert_account_in`
+static UPSERT_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
 
 // ... existing constants ...
 const DATA_DIR: &str = ".antigravity_tools";
@@ -462,13 +941,14 @@ fn rebuild_index_from_accounts_in_dir(data_dir: &PathBuf) -> Result<AccountIndex
         if let Ok(entries) = fs::read_dir(&accounts_dir) {
             for entry in entries.filter_map(|e| e.ok()) {
                 let path = entry.path();
-                if path.extension().map_or(false, |ext| ext == "json") {
+                if path.extension().is_some_and(|ext| ext == "json") {
                     if let Some(account_id) = path.file_stem().and_then(|s| s.to_str()) {
                         match load_account_at_path(&path) {
                             Ok(account) => {
                                     summaries.push(AccountSummary {
                                         id: account.id,
                                         email: account.email,
+                                        subject: account.subject,
                                         name: account.name,
                                         disabled: account.disabled,
                                         proxy_disabled: account.proxy_disabled,
@@ -513,7 +993,7 @@ fn rebuild_index_from_accounts_in_dir(data_dir: &PathBuf) -> Result<AccountIndex
 
 /// Load account from a specific path (internal helper)
 fn load_account_at_path(account_path: &PathBuf) -> Result<Account, String> {
-    let content = fs::read_to_string(account_path)
+    let content = super::account_store::read_account_file(account_path)
         .map_err(|e| format!("failed_to_read_account_data: {}", e))?;
     serde_json::from_str(&content).map_err(|e| format!("failed_to_parse_account_data: {}", e))
 }
@@ -646,14 +1126,31 @@ fn atomic_replace_file(src: &PathBuf, dst: &PathBuf) -> Result<(), String> {
 
 /// Load account data
 pub fn load_account(account_id: &str) -> Result<Account, String> {
-    let accounts_dir = get_accounts_dir()?;
-    let account_path = accounts_dir.join(format!("{}.json", account_id));
+    let data_dir = get_data_dir()?;
+    load_account_in_dir(&data_dir, account_id)
+}
+
+/// Load account data from a specific data directory (internal helper)
+fn load_account_in_dir(data_dir: &PathBuf, account_id: &str) -> Result<Account, String> {
+    let account_path = data_dir
+        .join(ACCOUNTS_DIR)
+        .join(format!("{}.json", account_id));
     load_account_at_path(&account_path)
 }
 
 /// Save account data
 pub fn save_account(account: &Account) -> Result<(), String> {
-    let accounts_dir = get_accounts_dir()?;
+    let data_dir = get_data_dir()?;
+    save_account_in_dir(&data_dir, account)
+}
+
+/// Save account data into a specific data directory (internal helper)
+fn save_account_in_dir(data_dir: &PathBuf, account: &Account) -> Result<(), String> {
+    let accounts_dir = data_dir.join(ACCOUNTS_DIR);
+    if !accounts_dir.exists() {
+        fs::create_dir_all(&accounts_dir)
+            .map_err(|e| format!("failed_to_create_accounts_dir: {}", e))?;
+    }
     let account_path = accounts_dir.join(format!("{}.json", account.id));
 
     let temp_filename = format!("{}.tmp.{}", account.id, Uuid::new_v4());
@@ -662,7 +1159,7 @@ pub fn save_account(account: &Account) -> Result<(), String> {
     let content = serde_json::to_string_pretty(account)
         .map_err(|e| format!("failed_to_serialize_account_data: {}", e))?;
 
-    if let Err(e) = std::fs::write(&temp_path, content) {
+    if let Err(e) = super::account_store::write_account_file(&temp_path, &content) {
         let _ = std::fs::remove_file(&temp_path);
         return Err(format!("failed_to_write_temp_account_file: {}", e));
     }
@@ -678,25 +1175,15 @@ pub fn save_account(account: &Account) -> Result<(), String> {
 /// List all accounts
 pub fn list_accounts() -> Result<Vec<Account>, String> {
     crate::modules::logger::log_info("Listing accounts...");
-    let index = load_account_index()?;
-    let mut accounts = Vec::new();
-
-    for summary in &index.accounts {
-        match load_account(&summary.id) {
-            Ok(account) => accounts.push(account),
-            Err(e) => {
-                crate::modules::logger::log_error(&format!(
-                    "Failed to load account {}: {}",
-                    summary.id, e
-                ));
-                // [FIX #929] Removed auto-repair logic.
-                // We no longer silently delete account IDs from the index if the file is missing.
-                // This prevents account loss during version upgrades or temporary FS issues.
-            }
-        }
-    }
+    FileAccountStore::new().list()
+}
 
-    Ok(accounts)
+/// List accounts imported from `source` (accounts without a recorded source are excluded)
+pub fn list_accounts_by_source(source: AccountSource) -> Result<Vec<Account>, String> {
+    Ok(list_accounts()?
+        .into_iter()
+        .filter(|account| account.source == Some(source))
+        .collect())
 }
 
 /// Add account
@@ -705,20 +1192,40 @@ pub fn add_account(
     name: Option<String>,
     token: TokenData,
 ) -> Result<Account, String> {
+    add_account_with_subject(email, name, token, None)
+}
+
+/// Add account; when the provider subject is known the account id is derived from it
+pub fn add_account_with_subject(
+    email: String,
+    name: Option<String>,
+    token: TokenData,
+    subject: Option<String>,
+) -> Result<Account, String> {
+    add_account_tagged(email, name, token, subject, None)
+}
+
+fn add_account_tagged(
+    email: String,
+    name: Option<String>,
+    token: TokenData,
+    subject: Option<String>,
+    source: Option<AccountSource>,
+) -> Result<Account, String> {
+    let email = normalize_email(&email);
     let _lock = ACCOUNT_INDEX_LOCK
         .lock()
         .map_err(|e| format!("failed_to_acquire_lock: {}", e))?;
     let mut index = load_account_index()?;
 
     // Check if account already exists
-    if index.accounts.iter().any(|s| s.email == email) {
+    if find_account_id_in_index(&index, &email, subject.as_deref())?.is_some() {
         return Err(format!("Account already exists: {}", email));
     }
 
-    // Create new account
-    let account_id = Uuid::new_v4().to_string();
-    let mut account = Account::new(account_id.clone(), email.clone(), token);
-    account.name = name.clone();
+    let mut account = new_account_for_identity(email, name, token, subject);
+    account.source = source;
+    let account_id = account.id.clone();
 
     // Save account data
     save_account(&account)?;
@@ -727,6 +1234,7 @@ pub fn add_account(
     index.accounts.push(AccountSummary {
         id: account.id.clone(),
         email: account.email.clone(),
+        subject: account.subject.clone(),
         name: account.name.clone(),
         disabled: account.disabled,
         proxy_disabled: account.proxy_disabled,
@@ -745,118 +1253,410 @@ pub fn add_account(
     Ok(account)
 }
 
-/// Add or update account
-pub fn upsert_account(
+/// Find an existing account for this identity.
+/// The provider subject is the identity: a known subject wins (derived id or stored subject),
+/// so an email change keeps the same account. Email only matches entries without a subject
+/// (legacy records adopt the subject on update); the same email under a different subject
+/// is rejected instead of creating a second record for that email.
+fn find_account_id_in_index(
+    index: &AccountIndex,
+    email: &str,
+    subject: Option<&str>,
+) -> Result<Option<String>, String> {
+    let entries = index
+        .accounts
+        .iter()
+        .map(|s| (s.id.as_str(), s.email.as_str(), s.subject.as_deref()));
+    find_identity_match(entries, email, subject).map(|id| id.map(|id| id.to_string()))
+}
+
+/// Identity matching over (id, email, subject) entries, shared by the index and `AccountStore`s
+fn find_identity_match<'a, I>(
+    mut entries: I,
+    email: &str,
+    subject: Option<&str>,
+) -> Result<Option<&'a str>, String>
+where
+    I: Iterator<Item = (&'a str, &'a str, Option<&'a str>)> + Clone,
+{
+    if let Some(sub) = subject {
+        let stable_id = Account::derive_id(crate::models::account::DEFAULT_IDENTITY_PROVIDER, sub);
+        if let Some((id, _, _)) = entries
+            .clone()
+            .find(|(id, _, s)| *id == stable_id || *s == Some(sub))
+        {
+            return Ok(Some(id));
+        }
+    }
+
+    match entries.find(|(_, e, _)| emails_match(e, email)) {
+        Some((id, _, stored)) if subject.is_none() || stored.is_none() => Ok(Some(id)),
+        Some((id, e, _)) => Err(format!(
+            "Account {} ({}) belongs to a different Google account; remove it before adding this one",
+            e, id
+        )),
+        None => Ok(None),
+    }
+}
+
+/// Create a new account for this identity (stable id from subject, random id for legacy callers)
+fn new_account_for_identity(
     email: String,
     name: Option<String>,
     token: TokenData,
-) -> Result<Account, String> {
-    let _lock = ACCOUNT_INDEX_LOCK
+    subject: Option<String>,
+) -> Account {
+    let account_id = match subject.as_deref() {
+        Some(sub) => Account::derive_id(crate::models::account::DEFAULT_IDENTITY_PROVIDER, sub),
+        None => Uuid::new_v4().to_string(),
+    };
+    let mut account = Account::new(account_id, email, token);
+    account.name = name;
+    account.subject = subject;
+    account
+}
+
+/// Apply new credentials / identity to an existing account
+fn apply_account_update(
+    account: &mut Account,
+    email: &str,
+    name: Option<String>,
+    token: TokenData,
+    subject: Option<&str>,
+) {
+    let old_access_token = account.token.access_token.clone();
+    let old_refresh_token = account.token.refresh_token.clone();
+    account.token = token;
+    account.name = name;
+    sync_account_identity(account, email, subject);
+    // If an account was previously disabled (e.g. invalid_grant), any explicit token upsert
+    // should re-enable it (user manually updated credentials in the UI).
+    if account.disabled
+        && (account.token.refresh_token != old_refresh_token
+            || account.token.access_token != old_access_token)
+    {
+        account.disabled = false;
+        account.disabled_reason = None;
+        account.disabled_at = None;
+    }
+    if account.token.access_token != old_access_token {
+        account.mark_refreshed(crate::utils::clock::now());
+    }
+    account.update_last_used();
+}
+
+/// Add or update an account in any `AccountStore` (same matching rules as `upsert_account_with_subject`).
+/// The source is recorded on first import; re-importing from elsewhere keeps the original.
+pub fn upsert_account_in(
+    store: &dyn AccountStore,
+    email: String,
+    name: Option<String>,
+    token: TokenData,
+    subject: Option<String>,
+    source: Option<AccountSource>,
+) -> Result<UpsertOutcome, String> {
+    let email = normalize_email(&email);
+    // Matching and writing must not interleave with another upsert of the same identity
+    let _lock = UPSERT_LOCK
         .lock()
         .map_err(|e| format!("failed_to_acquire_lock: {}", e))?;
-    let mut index = load_account_index()?;
-
-    // Find account ID if exists
-    let existing_account_id = index
-        .accounts
+    let identities = store.identities()?;
+    let entries = identities
         .iter()
-        .find(|s| s.email == email)
-        .map(|s| s.id.clone());
-
-    if let Some(account_id) = existing_account_id {
-        // Update existing account
-        match load_account(&account_id) {
-            Ok(mut account) => {
-                let old_access_token = account.token.access_token.clone();
-                let old_refresh_token = account.token.refresh_token.clone();
-                account.token = token;
-                account.name = name.clone();
-                // If an account was previously disabled (e.g. invalid_grant), any explicit token upsert
-                // should re-enable it (user manually updated credentials in the UI).
-                if account.disabled
-                    && (account.token.refresh_token != old_refresh_token
-                        || account.token.access_token != old_access_token)
-                {
-                    account.disabled = false;
-                    account.disabled_reason = None;
-                    account.disabled_at = None;
+        .map(|(id, e, s)| (id.as_str(), e.as_str(), s.as_deref()));
+    let existing_id = find_identity_match(entries, &email, subject.as_deref())?.map(str::to_string);
+
+    let account = match existing_id {
+        Some(account_id) => match store.get(&account_id)? {
+            Some(stored) => {
+                let mut account = stored.clone();
+                apply_account_update(&mut account, &email, name, token, subject.as_deref());
+                if account.source.is_none() {
+                    account.source = source;
                 }
-                account.update_last_used();
-                save_account(&account)?;
-
-                // Sync name in index
-                if let Some(idx_summary) = index.accounts.iter_mut().find(|s| s.id == account_id) {
-                    idx_summary.name = name;
-                    save_account_index(&index)?;
+                if is_unchanged_update(&stored, &account) {
+                    return Ok(UpsertOutcome {
+                        account: stored,
+                        written: false,
+                    });
                 }
-
-                return Ok(account);
+                account
             }
-            Err(e) => {
+            None => {
+                // Index entry exists but the account data is missing, recreate with the same id
                 crate::modules::logger::log_warn(&format!(
-                    "Account {} file missing ({}), recreating...",
-                    account_id, e
+                    "Account {} file missing, recreating...",
+                    account_id
                 ));
-                // Index exists but file is missing, recreating
-                let mut account = Account::new(account_id.clone(), email.clone(), token);
-                account.name = name.clone();
-                save_account(&account)?;
-
-                // Sync name in index
-                if let Some(idx_summary) = index.accounts.iter_mut().find(|s| s.id == account_id) {
-                    idx_summary.name = name;
-                    save_account_index(&index)?;
-                }
-
-                return Ok(account);
+                let mut account = Account::new(account_id, email, token);
+                account.name = name;
+                account.subject = subject;
+                account.source = source;
+                account
             }
+        },
+        None => {
+            let mut account = new_account_for_identity(email, name, token, subject);
+            account.source = source;
+            account
         }
+    };
+    store.upsert(&account)?;
+    Ok(UpsertOutcome {
+        account,
+        written: true,
+    })
+}
+
+/// Apply the latest identity to a stored account; returns true if the email changed
+fn sync_account_identity(account: &mut Account, email: &str, subject: Option<&str>) -> bool {
+    if let Some(sub) = subject {
+        account.subject = Some(sub.to_string());
+    }
+    if account.email == email {
+        return false;
+    }
+    // 仅大小写/空白不同时静默归一化
+    if !emails_match(&account.email, email) {
+        crate::modules::logger::log_info(&format!(
+            "Account {} email changed: {} -> {}",
+            account.id, account.email, email
+        ));
     }
+    account.email = email.to_string();
+    true
+}
 
-    // Add if not exists
-    // Note: add_account will attempt to acquire lock, which would deadlock here.
-    // Use an internal version or release lock.
+/// Add or update account
+pub fn upsert_account(
+    email: String,
+    name: Option<String>,
+    token: TokenData,
+) -> Result<Account, String> {
+    upsert_account_with_subject(email, name, token, None)
+}
 
-    // Release lock, let add_account handle it
-    drop(_lock);
-    add_account(email, name, token)
+/// Add or update account, matching by provider subject first so email changes keep the same id
+pub fn upsert_account_with_subject(
+    email: String,
+    name: Option<String>,
+    token: TokenData,
+    subject: Option<String>,
+) -> Result<Account, String> {
+    upsert_account_with_outcome(email, name, token, subject).map(|outcome| outcome.account)
 }
 
-/// Delete account
-pub fn delete_account(account_id: &str) -> Result<(), String> {
-    let _lock = ACCOUNT_INDEX_LOCK
-        .lock()
-        .map_err(|e| format!("failed_to_acquire_lock: {}", e))?;
-    let mut index = load_account_index()?;
+/// Add or update an account imported from `source`.
+/// The source is recorded on first import; re-importing from elsewhere keeps the original.
+pub fn upsert_account_from_source(
+    email: String,
+    name: Option<String>,
+    token: TokenData,
+    subject: Option<String>,
+    source: AccountSource,
+) -> Result<Account, String> {
+    upsert_account_tagged(email, name, token, subject, Some(source)).map(|outcome| outcome.account)
+}
+
+/// Result of an upsert: the stored account and whether anything was written to disk
+#[derive(Debug, Clone)]
+pub struct UpsertOutcome {
+    pub account: Account,
+    /// false when the incoming data matched the stored account and the write was skipped
+    pub written: bool,
+}
 
-    // Remove from index
-    let original_len = index.accounts.len();
-    index.accounts.retain(|s| s.id != account_id);
+/// Same as `upsert_account_with_subject`, reporting whether a write occurred.
+/// Re-importing identical credentials leaves the account files untouched.
+pub fn upsert_account_with_outcome(
+    email: String,
+    name: Option<String>,
+    token: TokenData,
+    subject: Option<String>,
+) -> Result<UpsertOutcome, String> {
+    upsert_account_tagged(email, name, token, subject, None)
+}
 
-    if index.accounts.len() == original_len {
-        return Err(format!("Account ID not found: {}", account_id));
+fn upsert_account_tagged(
+    email: String,
+    name: Option<String>,
+    token: TokenData,
+    subject: Option<String>,
+    source: Option<AccountSource>,
+) -> Result<UpsertOutcome, String> {
+    upsert_account_in(&FileAccountStore::new(), email, name, token, subject, source)
+}
+
+/// The update changes nothing but bookkeeping: `last_used`, and the expiry recomputed
+/// from `expires_in` at import time (same access token means the same expiry)
+fn is_unchanged_update(stored: &Account, updated: &Account) -> bool {
+    if stored.token.access_token != updated.token.access_token {
+        return false;
+    }
+    let comparable = |account: &Account| {
+        let mut account = account.clone();
+        account.last_used = 0;
+        account.token.expires_in = 0;
+        account.token.expiry_timestamp = 0;
+        serde_json::to_value(account).ok()
+    };
+    match (comparable(stored), comparable(updated)) {
+        (Some(a), Some(b)) => a == b,
+        _ => false,
     }
+}
 
-    // Clear current account if it's being deleted
-    if index.current_account_id.as_deref() == Some(account_id) {
-        index.current_account_id = index.accounts.first().map(|s| s.id.clone());
+/// The on-disk store (`accounts.json` index + `accounts/<id>.json`)
+#[derive(Debug, Clone, Default)]
+pub struct FileAccountStore {
+    /// None uses the app data dir (`get_data_dir`)
+    data_dir: Option<PathBuf>,
+}
+
+impl FileAccountStore {
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    save_account_index(&index)?;
+    /// Store rooted at a specific data directory
+    pub fn in_dir(data_dir: PathBuf) -> Self {
+        Self {
+            data_dir: Some(data_dir),
+        }
+    }
 
-    // Delete account file
-    let accounts_dir = get_accounts_dir()?;
-    let account_path = accounts_dir.join(format!("{}.json", account_id));
+    fn data_dir(&self) -> Result<PathBuf, String> {
+        match &self.data_dir {
+            Some(dir) => Ok(dir.clone()),
+            None => get_data_dir(),
+        }
+    }
+}
+
+impl AccountStore for FileAccountStore {
+    fn list(&self) -> Result<Vec<Account>, String> {
+        let data_dir = self.data_dir()?;
+        let index = load_account_index_in_dir(&data_dir)?;
+        let mut accounts = Vec::new();
+
+        for summary in &index.accounts {
+            match load_account_in_dir(&data_dir, &summary.id) {
+                Ok(account) => accounts.push(account),
+                Err(e) => {
+                    crate::modules::logger::log_error(&format!(
+                        "Failed to load account {}: {}",
+                        summary.id, e
+                    ));
+                    // [FIX #929] Removed auto-repair logic.
+                    // We no longer silently delete account IDs from the index if the file is missing.
+                    // This prevents account loss during version upgrades or temporary FS issues.
+                }
+            }
+        }
 
-    if account_path.exists() {
-        fs::remove_file(&account_path)
-            .map_err(|e| format!("failed_to_delete_account_file: {}", e))?;
+        Ok(accounts)
     }
 
-    // [FIX #1477] Trigger TokenManager cache cleanup signal
-    crate::proxy::server::trigger_account_delete(account_id);
+    fn get(&self, account_id: &str) -> Result<Option<Account>, String> {
+        let account_path = self
+            .data_dir()?
+            .join(ACCOUNTS_DIR)
+            .join(format!("{}.json", account_id));
+        if !account_path.exists() {
+            return Ok(None);
+        }
+        load_account_at_path(&account_path).map(Some)
+    }
 
-    Ok(())
+    fn upsert(&self, account: &Account) -> Result<(), String> {
+        let data_dir = self.data_dir()?;
+        let _lock = ACCOUNT_INDEX_LOCK
+            .lock()
+            .map_err(|e| format!("failed_to_acquire_lock: {}", e))?;
+        let mut index = load_account_index_in_dir(&data_dir)?;
+
+        save_account_in_dir(&data_dir, account)?;
+
+        match index.accounts.iter_mut().find(|s| s.id == account.id) {
+            Some(summary) => {
+                summary.email = account.email.clone();
+                summary.subject = account.subject.clone();
+                summary.name = account.name.clone();
+                summary.disabled = account.disabled;
+                summary.proxy_disabled = account.proxy_disabled;
+                summary.protected_models = account.protected_models.clone();
+                summary.last_used = account.last_used;
+            }
+            None => index.accounts.push(AccountSummary {
+                id: account.id.clone(),
+                email: account.email.clone(),
+                subject: account.subject.clone(),
+                name: account.name.clone(),
+                disabled: account.disabled,
+                proxy_disabled: account.proxy_disabled,
+                protected_models: account.protected_models.clone(),
+                created_at: account.created_at,
+                last_used: account.last_used,
+            }),
+        }
+        if index.current_account_id.is_none() {
+            index.current_account_id = Some(account.id.clone());
+        }
+
+        save_account_index_in_dir(&data_dir, &index)
+    }
+
+    fn remove(&self, account_id: &str) -> Result<(), String> {
+        let data_dir = self.data_dir()?;
+        let _lock = ACCOUNT_INDEX_LOCK
+            .lock()
+            .map_err(|e| format!("failed_to_acquire_lock: {}", e))?;
+        let mut index = load_account_index_in_dir(&data_dir)?;
+
+        // Remove from index
+        let original_len = index.accounts.len();
+        index.accounts.retain(|s| s.id != account_id);
+
+        if index.accounts.len() == original_len {
+            return Err(format!("Account ID not found: {}", account_id));
+        }
+
+        // Clear current account if it's being deleted
+        if index.current_account_id.as_deref() == Some(account_id) {
+            index.current_account_id = index.accounts.first().map(|s| s.id.clone());
+        }
+
+        save_account_index_in_dir(&data_dir, &index)?;
+
+        // Delete account file
+        let account_path = data_dir
+            .join(ACCOUNTS_DIR)
+            .join(format!("{}.json", account_id));
+
+        if account_path.exists() {
+            fs::remove_file(&account_path)
+                .map_err(|e| format!("failed_to_delete_account_file: {}", e))?;
+        }
+
+        // [FIX #1477] Trigger TokenManager cache cleanup signal
+        crate::proxy::server::trigger_account_delete(account_id);
+
+        Ok(())
+    }
+
+    /// Matches against the index, so entries whose account file is missing are still found
+    fn identities(&self) -> Result<Vec<(String, String, Option<String>)>, String> {
+        Ok(load_account_index_in_dir(&self.data_dir()?)?
+            .accounts
+            .into_iter()
+            .map(|s| (s.id, s.email, s.subject))
+            .collect())
+    }
+}
+
+/// Delete account
+pub fn delete_account(account_id: &str) -> Result<(), String> {
+    FileAccountStore::new().remove(account_id)
 }
 
 /// Batch delete accounts (atomic index operation)
@@ -895,6 +1695,113 @@ pub fn delete_accounts(account_ids: &[String]) -> Result<(), String> {
     save_account_index(&index)
 }
 
+/// 清除全部账号及其凭据 (不可恢复)
+/// 删除账号索引、账号文件与模型能力缓存，删除前先用零覆写文件内容；
+/// 反代运行中时同时通知 TokenManager 移除内存中的账号。`confirm` 必须为 true
+pub fn purge_all(confirm: bool) -> Result<PurgeReport, String> {
+    if !confirm {
+        return Err("purge_not_confirmed".to_string());
+    }
+    let _lock = ACCOUNT_INDEX_LOCK
+        .lock()
+        .map_err(|e| format!("failed_to_acquire_lock: {}", e))?;
+    let data_dir = get_data_dir()?;
+    let (report, account_ids) = purge_all_in_dir(&data_dir)?;
+    crate::modules::account_store::forget_key_source();
+
+    for account_id in &account_ids {
+        crate::proxy::server::trigger_account_delete(account_id);
+    }
+    crate::modules::logger::log_warn(&format!(
+        "Purged all accounts: {} accounts, {} files removed",
+        report.accounts_removed, report.files_removed
+    ));
+    Ok(report)
+}
+
+/// 同 `purge_all`，作用于指定数据目录；返回报告与被移除的账号 ID
+fn purge_all_in_dir(data_dir: &PathBuf) -> Result<(PurgeReport, Vec<String>), String> {
+    let mut report = PurgeReport::default();
+    let mut account_ids: Vec<String> = Vec::new();
+
+    let index_path = data_dir.join(ACCOUNTS_INDEX);
+    if index_path.exists() {
+        // 索引损坏时仍然继续清除，账号 ID 以账号目录中的文件为准
+        if let Ok(content) = fs::read_to_string(&index_path) {
+            if let Ok(index) = serde_json::from_str::<AccountIndex>(&content) {
+                account_ids.extend(index.accounts.into_iter().map(|s| s.id));
+            }
+        }
+    }
+
+    let accounts_dir = data_dir.join(ACCOUNTS_DIR);
+    if accounts_dir.exists() {
+        let entries = fs::read_dir(&accounts_dir)
+            .map_err(|e| format!("failed_to_read_accounts_dir: {}", e))?;
+        for entry in entries.filter_map(|e| e.ok()) {
+            let path = entry.path();
+            if !path.is_file() {
+                continue;
+            }
+            if path.extension().is_some_and(|ext| ext == "json") {
+                if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+                    if !account_ids.iter().any(|id| id == stem) {
+                        account_ids.push(stem.to_string());
+                    }
+                }
+            }
+            // 包括写入中断遗留的临时文件
+            shred_file(&path, &mut report)?;
+        }
+        fs::remove_dir_all(&accounts_dir)
+            .map_err(|e| format!("failed_to_remove_accounts_dir: {}", e))?;
+    }
+
+    let capability_cache = data_dir.join(crate::proxy::capability_cache::CAPABILITY_CACHE_FILE);
+    // 密钥文件与安装级密钥一并清除，否则残留的密钥仍可解密已泄露的账号备份
+    let key_file = data_dir.join(crate::utils::key_provider::KEY_FILE_NAME);
+    let install_secret = data_dir.join(crate::utils::crypto::INSTALL_SECRET_FILE);
+    for path in [index_path, capability_cache, key_file, install_secret] {
+        if path.exists() {
+            shred_file(&path, &mut report)?;
+        }
+    }
+
+    report.accounts_removed = account_ids.len();
+    Ok((report, account_ids))
+}
+
+/// 用零覆写文件内容并落盘后删除 (覆写失败时仍删除文件)
+fn shred_file(path: &std::path::Path, report: &mut PurgeReport) -> Result<(), String> {
+    use std::io::Write;
+
+    let len = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    let overwritten = fs::OpenOptions::new()
+        .write(true)
+        .open(path)
+        .and_then(|mut file| {
+            let zeros = vec![0u8; 8192];
+            let mut remaining = len;
+            while remaining > 0 {
+                let chunk = remaining.min(zeros.len() as u64) as usize;
+                file.write_all(&zeros[..chunk])?;
+                remaining -= chunk as u64;
+            }
+            file.sync_all()
+        });
+    match overwritten {
+        Ok(()) => report.bytes_overwritten += len,
+        Err(e) => crate::modules::logger::log_warn(&format!(
+            "Failed to overwrite {:?} before deletion: {}",
+            path, e
+        )),
+    }
+
+    fs::remove_file(path).map_err(|e| format!("failed_to_delete_file: {}", e))?;
+    report.files_removed += 1;
+    Ok(())
+}
+
 /// Reorder account list
 /// Update account order in index file based on provided IDs
 pub fn reorder_accounts(account_ids: &[String]) -> Result<(), String> {
@@ -936,9 +1843,12 @@ pub fn reorder_accounts(account_ids: &[String]) -> Result<(), String> {
 }
 
 /// Switch current account (Core Logic)
+///
+/// `force` skips the "IDE still running" guard when writing the IDE database.
 pub async fn switch_account(
     account_id: &str,
     integration: &(impl modules::integration::SystemIntegration + ?Sized),
+    force: bool,
 ) -> Result<(), String> {
     use crate::modules::oauth;
 
@@ -995,7 +1905,7 @@ pub async fn switch_account(
     }
 
     // 3. Execute platform-specific system integration (Close proc, Inject DB, Start proc, etc.)
-    integration.on_account_switch(&account).await?;
+    integration.on_account_switch(&account, force).await?;
 
     // 4. Update tool internal state
     {
@@ -1439,7 +2349,7 @@ pub fn update_account_quota(account_id: &str, quota: QuotaData) -> Result<(), St
                     && account
                         .proxy_disabled_reason
                         .as_ref()
-                        .map_or(false, |r| r == "quota_protection")
+                        .is_some_and(|r| r == "quota_protection")
                 {
                     crate::modules::logger::log_info(&format!(
                         "[Quota] Migrating account {} from account-level to model-level protection",
@@ -1513,10 +2423,64 @@ pub fn toggle_proxy_status(
     Ok(())
 }
 
+/// 启用 / 停用账号 (软禁用，保留账号数据)，停用后不参与反代选择
+pub fn set_enabled(email: &str, enabled: bool) -> Result<Account, String> {
+    let account = set_enabled_in(&FileAccountStore::new(), email, enabled)?;
+    crate::proxy::server::trigger_account_reload(&account.id);
+    Ok(account)
+}
+
+/// 同 `set_enabled`，作用于指定存储
+pub fn set_enabled_in(
+    store: &dyn AccountStore,
+    email: &str,
+    enabled: bool,
+) -> Result<Account, String> {
+    let mut account = store
+        .list()?
+        .into_iter()
+        .find(|a| emails_match(&a.email, email))
+        .ok_or_else(|| format!("Account not found: {}", email))?;
+    if account.enabled != enabled {
+        account.enabled = enabled;
+        store.upsert(&account)?;
+        crate::modules::logger::log_info(&format!(
+            "Account {} {}",
+            account.email,
+            if enabled { "enabled" } else { "disabled by user" }
+        ));
+    }
+    Ok(account)
+}
+
+/// Pin (or clear with None) the tier used for account ranking
+pub fn set_tier_override(account_id: &str, tier: Option<&str>) -> Result<Account, String> {
+    let _lock = ACCOUNT_INDEX_LOCK
+        .lock()
+        .map_err(|e| format!("failed_to_acquire_lock: {}", e))?;
+
+    let mut account = load_account(account_id)?;
+    account.tier_override = tier
+        .map(|t| t.trim().to_uppercase())
+        .filter(|t| !t.is_empty());
+    save_account(&account)?;
+
+    crate::modules::logger::log_info(&format!(
+        "Tier override for {} set to {:?}",
+        account.email, account.tier_override
+    ));
+    Ok(account)
+}
+
+/// Find account ID by provider subject, falling back to email (from index)
+pub fn find_account_id_by_identity(email: &str, subject: Option<&str>) -> Option<String> {
+    find_account_id_in_index(&load_account_index().ok()?, email, subject).ok().flatten()
+}
+
 /// Find account ID by email (from index)
 pub fn find_account_id_by_email(email: &str) -> Option<String> {
     load_account_index().ok()?.accounts.into_iter()
-        .find(|a| a.email == email)
+        .find(|a| emails_match(&a.email, email))
         .map(|a| a.id)
 }
 
@@ -1595,6 +2559,167 @@ pub fn export_accounts() -> Result<Vec<(String, String)>, String> {
     Ok(exports)
 }
 
+/// Export a secret-free summary of all accounts as CSV or JSONL (for auditing)
+pub fn export_summary(format: crate::models::ExportFormat) -> Result<String, String> {
+    let accounts = list_accounts()?;
+    let now = chrono::Utc::now().timestamp();
+    let rows: Vec<_> = accounts.iter().map(|acc| summarize_account(acc, now)).collect();
+    render_summary(&rows, format)
+}
+
+fn summarize_account(account: &Account, now: i64) -> crate::models::AccountSummaryRow {
+    let quota = account.quota.as_ref();
+    let blocked_until = account
+        .validation_blocked_until
+        .filter(|until| account.validation_blocked && *until > now);
+
+    let health = if quota.is_some_and(|q| q.is_forbidden) {
+        "forbidden"
+    } else if account.disabled {
+        "disabled"
+    } else if account.proxy_disabled {
+        "proxy_disabled"
+    } else if blocked_until.is_some() {
+        "validation_blocked"
+    } else {
+        "ok"
+    };
+
+    crate::models::AccountSummaryRow {
+        email: account.email.clone(),
+        tier: quota.and_then(|q| q.subscription_tier.clone()),
+        remaining_quota: quota.and_then(|q| q.models.iter().map(|m| m.percentage).max()),
+        health: health.to_string(),
+        blocked_until,
+        last_refreshed: account.last_refreshed,
+    }
+}
+
+fn render_summary(
+    rows: &[crate::models::AccountSummaryRow],
+    format: crate::models::ExportFormat,
+) -> Result<String, String> {
+    use crate::models::ExportFormat;
+
+    let mut out = String::new();
+    match format {
+        ExportFormat::Csv => {
+            out.push_str("email,tier,remaining_quota,health,blocked_until,last_refreshed\n");
+            for row in rows {
+                let fields = [
+                    csv_field(&row.email),
+                    csv_field(row.tier.as_deref().unwrap_or("")),
+                    row.remaining_quota.map(|v| v.to_string()).unwrap_or_default(),
+                    csv_field(&row.health),
+                    row.blocked_until.map(|v| v.to_string()).unwrap_or_default(),
+                    row.last_refreshed.map(|v| v.to_string()).unwrap_or_default(),
+                ];
+                out.push_str(&fields.join(","));
+                out.push('\n');
+            }
+        }
+        ExportFormat::Jsonl => {
+            for row in rows {
+                let line = serde_json::to_string(row)
+                    .map_err(|e| format!("failed_to_serialize_summary: {}", e))?;
+                out.push_str(&line);
+                out.push('\n');
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Quote a CSV field when it contains a delimiter, quote or newline
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Scan all accounts for stored data that will make every refresh fail
+pub fn audit_accounts() -> Vec<crate::models::AccountIssue> {
+    match list_accounts() {
+        Ok(accounts) => audit_account_list(&accounts, chrono::Utc::now().timestamp()),
+        Err(e) => {
+            modules::logger::log_warn(&format!("Account audit skipped: {}", e));
+            Vec::new()
+        }
+    }
+}
+
+fn audit_account_list(accounts: &[Account], now: i64) -> Vec<crate::models::AccountIssue> {
+    use crate::models::{AccountIssue, AccountIssueKind};
+
+    let mut issues = Vec::new();
+    for account in accounts {
+        let mut flag = |kind| {
+            issues.push(AccountIssue {
+                account_id: account.id.clone(),
+                email: account.email.clone(),
+                kind,
+            })
+        };
+        if account.token.refresh_token.trim().is_empty() {
+            flag(AccountIssueKind::EmptyRefreshToken);
+        }
+        if account.email.trim().is_empty() {
+            flag(AccountIssueKind::EmptyEmail);
+        }
+        if account.token.access_token == modules::migration::IMPORTED_ACCESS_TOKEN_PLACEHOLDER
+            && account.token.expiry_timestamp <= now
+        {
+            flag(AccountIssueKind::PlaceholderAccessToken);
+        }
+    }
+    issues
+}
+
+/// Try to restore empty refresh tokens from the live IDE database.
+/// Only the account currently logged in to the IDE can be repaired; returns the repaired account ID.
+pub async fn repair_empty_refresh_tokens(
+    issues: &[crate::models::AccountIssue],
+) -> Option<String> {
+    let account_ids: Vec<String> = issues
+        .iter()
+        .filter(|issue| issue.kind == crate::models::AccountIssueKind::EmptyRefreshToken)
+        .map(|issue| issue.account_id.clone())
+        .collect();
+    if account_ids.is_empty() {
+        return None;
+    }
+
+    match modules::migration::repair_from_live_db(&account_ids).await {
+        Ok(account) => {
+            modules::logger::log_info(&format!(
+                "Repaired empty refresh token for {} from the IDE database",
+                account.email
+            ));
+            Some(account.id)
+        }
+        Err(e) => {
+            modules::logger::log_warn(&format!("Refresh token auto-repair failed: {}", e));
+            None
+        }
+    }
+}
+
+/// Startup account audit: log every issue, optionally attempt auto-repair
+pub async fn run_startup_audit(auto_repair: bool) {
+    let issues = audit_accounts();
+    for issue in &issues {
+        modules::logger::log_warn(&format!(
+            "Account audit: {} ({}) has issue {:?}",
+            issue.email, issue.account_id, issue.kind
+        ));
+    }
+    if auto_repair {
+        repair_empty_refresh_tokens(&issues).await;
+    }
+}
+
 /// Quota query with retry (moved from commands to modules for reuse)
 pub async fn fetch_quota_with_retry(account: &mut Account) -> crate::error::AppResult<QuotaData> {
     use crate::error::AppError;
@@ -1625,7 +2750,7 @@ pub async fn fetch_quota_with_retry(account: &mut Account) -> crate::error::AppR
 
         // Get display name (incidental to Token refresh)
         let name = if account.name.is_none()
-            || account.name.as_ref().map_or(false, |n| n.trim().is_empty())
+            || account.name.as_ref().is_some_and(|n| n.trim().is_empty())
         {
             match oauth::get_user_info(&token.access_token, Some(&account.id)).await {
                 Ok(user_info) => user_info.get_display_name(),
@@ -1640,7 +2765,7 @@ pub async fn fetch_quota_with_retry(account: &mut Account) -> crate::error::AppR
     }
 
     // 0. Supplement display name (if missing or upper step failed)
-    if account.name.is_none() || account.name.as_ref().map_or(false, |n| n.trim().is_empty()) {
+    if account.name.is_none() || account.name.as_ref().is_some_and(|n| n.trim().is_empty()) {
         modules::logger::log_info(&format!(
             "Account {} missing display name, attempting to fetch...",
             account.email
@@ -1702,6 +2827,7 @@ pub async fn fetch_quota_with_retry(account: &mut Account) -> crate::error::AppR
                 let token_res = match oauth::refresh_access_token_with_client(
                     &account.token.refresh_token,
                     Some(&account.id),
+                    Some(&account.email),
                     account.token.oauth_client_key.as_deref(),
                 ).await {
                     Ok(t) => t,
@@ -1735,11 +2861,12 @@ pub async fn fetch_quota_with_retry(account: &mut Account) -> crate::error::AppR
                         .oauth_client_key
                         .clone()
                         .or_else(|| account.token.oauth_client_key.clone()),
-                );
+                )
+                .with_scopes(token_res.scopes().or_else(|| account.token.scopes.clone()));
 
                 // Re-fetch display name
                 let name = if account.name.is_none()
-                    || account.name.as_ref().map_or(false, |n| n.trim().is_empty())
+                    || account.name.as_ref().is_some_and(|n| n.trim().is_empty())
                 {
                     match oauth::get_user_info(&token_res.access_token, Some(&account.id)).await {
                         Ok(user_info) => user_info.get_display_name(),
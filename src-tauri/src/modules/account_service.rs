@@ -37,11 +37,17 @@ impl AccountService {
             None,
             true,
         )
-        .with_oauth_client_key(token_res.oauth_client_key.clone());
+        .with_oauth_client_key(token_res.oauth_client_key.clone())
+        .with_scopes(token_res.scopes());
 
         // 5. 持久化
-        let mut account =
-            modules::upsert_account(user_info.email.clone(), user_info.get_display_name(), token)?;
+        let mut account = modules::upsert_account_from_source(
+            user_info.email.clone(),
+            user_info.get_display_name(),
+            token,
+            user_info.id.clone(),
+            crate::models::AccountSource::ManualToken,
+        )?;
 
         // 6. [NEW] 自动获取配额信息（用于刷新时间排序）
         let email_for_log = account.email.clone();
@@ -88,8 +94,8 @@ impl AccountService {
     }
 
     /// 切换账号逻辑
-    pub async fn switch_account(&self, account_id: &str) -> Result<(), String> {
-        modules::account::switch_account(account_id, &self.integration).await
+    pub async fn switch_account(&self, account_id: &str, force: bool) -> Result<(), String> {
+        modules::account::switch_account(account_id, &self.integration, force).await
     }
 
     /// 列表获取
@@ -167,12 +173,14 @@ impl AccountService {
             None,
             true,
         )
-        .with_oauth_client_key(token_res.oauth_client_key.clone());
+        .with_oauth_client_key(token_res.oauth_client_key.clone())
+        .with_scopes(token_res.scopes());
 
-        let account = modules::upsert_account(
+        let account = modules::upsert_account_with_subject(
             user_info.email.clone(),
             user_info.get_display_name(),
             token_data,
+            user_info.id.clone(),
         )?;
 
         // 发送 UI 更新通知 (通过 integration)
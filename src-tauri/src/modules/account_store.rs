@@ -0,0 +1,442 @@
+// 账号存储：
+// - `AccountStore` 抽象账号持久化后端 (默认实现为 `account::FileAccountStore`，测试使用内存实现)
+// - 账号文件静态加密：开启后 accounts/*.json 整体以 AES-GCM 加密落盘，
+//   读取时按内容自动识别明文/密文，因此开关切换前后的文件都能正常加载。
+//   读取从不改写文件；已有文件的格式迁移只在启动加载配置 / 切换开关时整目录进行
+
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::models::{Account, EncryptionKeySource};
+use crate::utils::crypto;
+use crate::utils::key_provider::FileKeyProvider;
+
+/// 账号持久化后端
+///
+/// 实现需可被多个线程同时使用 (选号、刷新、导入可能并发)：
+/// `list` / `get` 之间互不阻塞，`upsert` / `remove` 相互串行，且读不会观察到写了一半的账号。
+pub trait AccountStore: Send + Sync {
+    /// 按存储顺序列出所有账号
+    fn list(&self) -> Result<Vec<Account>, String>;
+    /// 按 ID 读取账号，不存在时返回 None
+    fn get(&self, account_id: &str) -> Result<Option<Account>, String>;
+    /// 新增或覆盖账号 (按 ID)
+    fn upsert(&self, account: &Account) -> Result<(), String>;
+    /// 删除账号，不存在时返回错误
+    fn remove(&self, account_id: &str) -> Result<(), String>;
+    /// 用于身份匹配的 (id, email, subject) 列表；默认取自 `list`
+    fn identities(&self) -> Result<Vec<(String, String, Option<String>)>, String> {
+        Ok(self
+            .list()?
+            .into_iter()
+            .map(|a| (a.id, a.email, a.subject))
+            .collect())
+    }
+}
+
+/// 内存账号存储 (测试或无需落盘的场景)
+#[derive(Debug, Default)]
+pub struct MemoryAccountStore {
+    accounts: RwLock<Vec<Account>>,
+}
+
+impl MemoryAccountStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, Vec<Account>> {
+        self.accounts.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Vec<Account>> {
+        self.accounts.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl AccountStore for MemoryAccountStore {
+    fn list(&self) -> Result<Vec<Account>, String> {
+        Ok(self.read().clone())
+    }
+
+    fn get(&self, account_id: &str) -> Result<Option<Account>, String> {
+        Ok(self.read().iter().find(|a| a.id == account_id).cloned())
+    }
+
+    fn upsert(&self, account: &Account) -> Result<(), String> {
+        let mut accounts = self.write();
+        match accounts.iter_mut().find(|a| a.id == account.id) {
+            Some(existing) => *existing = account.clone(),
+            None => accounts.push(account.clone()),
+        }
+        Ok(())
+    }
+
+    fn remove(&self, account_id: &str) -> Result<(), String> {
+        let mut accounts = self.write();
+        let before = accounts.len();
+        accounts.retain(|a| a.id != account_id);
+        if accounts.len() == before {
+            return Err(format!("Account ID not found: {}", account_id));
+        }
+        Ok(())
+    }
+}
+
+/// 账号文件密文绑定的关联数据，防止与其他用途的密文混用
+const STORE_AAD: &[u8] = b"antigravity-account-store";
+
+/// 当前是否以加密形式写入账号文件
+static ENCRYPT_AT_REST: AtomicBool = AtomicBool::new(false);
+
+pub fn is_encryption_enabled() -> bool {
+    ENCRYPT_AT_REST.load(Ordering::Relaxed)
+}
+
+/// 应用配置中的加密开关；状态发生变化时立即迁移已有账号文件
+pub fn apply_encryption_setting(enabled: bool) {
+    if ENCRYPT_AT_REST.swap(enabled, Ordering::Relaxed) == enabled {
+        return;
+    }
+
+    let result = crate::modules::account::get_accounts_dir()
+        .and_then(|dir| migrate_accounts_dir(&dir, enabled));
+    match result {
+        Ok(count) => crate::modules::logger::log_info(&format!(
+            "Account store encryption {}: {} file(s) migrated",
+            if enabled { "enabled" } else { "disabled" },
+            count
+        )),
+        Err(e) => crate::modules::logger::log_error(&format!(
+            "Failed to migrate account store: {}",
+            e
+        )),
+    }
+}
+
+/// 已应用到密钥环的密钥来源 (None = 本进程尚未加载配置)
+static KEY_SOURCE: Mutex<Option<EncryptionKeySource>> = Mutex::new(None);
+
+/// 应用配置中的密钥来源
+///
+/// 密钥环只存在于内存中，因此必须在配置落盘之后调用 (启动加载配置 / 保存配置之后)：
+/// 重启时按持久化的来源重建密钥环，再以新密钥重写仍由旧密钥加密的账号文件
+pub fn apply_key_source(source: EncryptionKeySource, encrypt: bool) {
+    let result = FileKeyProvider::in_data_dir().and_then(|key_file| {
+        let accounts_dir = crate::modules::account::get_accounts_dir()?;
+        apply_key_source_in(key_file.path(), &accounts_dir, source, encrypt)
+    });
+    match result {
+        Ok(Some(count)) => crate::modules::logger::log_info(&format!(
+            "Encryption key source set to {:?}: {} account file(s) re-encrypted",
+            source, count
+        )),
+        Ok(None) => {}
+        Err(e) => crate::modules::logger::log_error(&format!(
+            "Failed to apply encryption key source {:?}: {}",
+            source, e
+        )),
+    }
+}
+
+/// 重建密钥环：所选来源为当前密钥，其他已存在的来源登记为旧密钥，
+/// 来源变化 (含进程启动) 时迁移账号目录；来源未变化时返回 None
+fn apply_key_source_in(
+    key_file: &Path,
+    accounts_dir: &Path,
+    source: EncryptionKeySource,
+    encrypt: bool,
+) -> Result<Option<usize>, String> {
+    let mut current = KEY_SOURCE.lock().unwrap_or_else(|e| e.into_inner());
+    if *current == Some(source) {
+        return Ok(None);
+    }
+
+    let file_provider = FileKeyProvider::new(key_file).with_repair_permissions(true);
+    match source {
+        EncryptionKeySource::MachineId => {
+            // 不为此生成密钥文件：文件不存在说明从未使用过该来源
+            if key_file.exists() {
+                crypto::retire_key_provider(&file_provider)?;
+            }
+            crypto::set_active_key_provider(&crypto::MachineKeyProvider)?;
+        }
+        EncryptionKeySource::KeyFile => {
+            crypto::retire_key_provider(&crypto::MachineKeyProvider)?;
+            crypto::set_active_key_provider(&file_provider)?;
+        }
+    }
+    *current = Some(source);
+
+    if !encrypt {
+        return Ok(Some(0));
+    }
+    migrate_accounts_dir(accounts_dir, true).map(Some)
+}
+
+/// 丢弃内存中的密钥材料与已应用的来源 (清除全部账号后调用)，下次应用配置时重建
+pub fn forget_key_source() {
+    let mut current = KEY_SOURCE.lock().unwrap_or_else(|e| e.into_inner());
+    crypto::forget_keys();
+    *current = None;
+}
+
+/// 清空密钥环与已应用的来源，模拟进程重启
+#[cfg(test)]
+fn reset_key_source_for_test() {
+    crypto::reset_key_ring_for_test();
+    *KEY_SOURCE.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+pub fn is_encrypted_content(raw: &str) -> bool {
+    raw.trim_start().starts_with(crypto::ENCRYPTED_PREFIX)
+}
+
+/// 密文由切换密钥来源之前的旧密钥加密 (需要以当前密钥重写)
+fn has_stale_key(raw: &str) -> bool {
+    crypto::needs_reencryption(raw.trim(), STORE_AAD)
+}
+
+/// 将磁盘内容还原为 JSON 文本 (明文原样返回)
+pub fn decode_account_content(raw: String) -> Result<String, String> {
+    if !is_encrypted_content(&raw) {
+        return Ok(raw);
+    }
+    crypto::decrypt_string_with_aad(raw.trim(), STORE_AAD)
+        .map_err(|e| format!("failed_to_decrypt_account_file: {}", e))
+}
+
+/// 按需加密 JSON 文本，得到写入磁盘的内容
+fn encode_account_content(json: &str, encrypt: bool) -> Result<String, String> {
+    if encrypt {
+        crypto::encrypt_string_with_aad(json, STORE_AAD)
+    } else {
+        Ok(json.to_string())
+    }
+}
+
+/// 读取账号文件并解密 (只读，不改写文件)
+pub fn read_account_file(path: &Path) -> Result<String, String> {
+    let raw = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    decode_account_content(raw)
+}
+
+/// 写入账号文件 (根据全局开关决定是否加密)
+pub fn write_account_file(path: &Path, json: &str) -> Result<(), String> {
+    write_account_file_with(path, json, is_encryption_enabled())
+}
+
+/// 先写临时文件再替换，避免中途失败留下半个账号文件
+fn write_account_file_with(path: &Path, json: &str, encrypt: bool) -> Result<(), String> {
+    let content = encode_account_content(json, encrypt)?;
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, content).map_err(|e| e.to_string())?;
+    fs::rename(&tmp_path, path).map_err(|e| e.to_string())
+}
+
+/// 将目录下所有账号文件统一为目标格式 (含旧密钥密文的重新加密)，返回被改写的文件数
+pub fn migrate_accounts_dir(accounts_dir: &Path, encrypt: bool) -> Result<usize, String> {
+    if !accounts_dir.exists() {
+        return Ok(0);
+    }
+
+    let entries = fs::read_dir(accounts_dir)
+        .map_err(|e| format!("failed_to_read_accounts_dir: {}", e))?;
+
+    let mut migrated = 0;
+    for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+        if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+            continue;
+        }
+        let raw = match fs::read_to_string(&path) {
+            Ok(raw) => raw,
+            Err(e) => {
+                tracing::warn!("Skipping unreadable account file {:?}: {}", path, e);
+                continue;
+            }
+        };
+        if is_encrypted_content(&raw) == encrypt && !(encrypt && has_stale_key(&raw)) {
+            continue;
+        }
+
+        let json = decode_account_content(raw)?;
+        write_account_file_with(&path, &json, encrypt)?;
+        migrated += 1;
+    }
+
+    Ok(migrated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_accounts_dir() -> std::path::PathBuf {
+        let dir = std::env::temp_dir()
+            .join(format!("ag_account_store_{}", uuid::Uuid::new_v4()))
+            .join("accounts");
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn sample_account_json(id: &str) -> String {
+        let account = crate::models::Account::new(
+            id.to_string(),
+            format!("{}@test.com", id),
+            crate::models::TokenData::new(
+                "atk-secret".to_string(),
+                "rtk-secret".to_string(),
+                3600,
+                None,
+                None,
+                None,
+                true,
+            ),
+        );
+        serde_json::to_string_pretty(&account).unwrap()
+    }
+
+    #[test]
+    fn test_plaintext_store_migrates_to_encrypted() {
+        let _key = crate::utils::crypto::override_key_for_test("account-store-test");
+        let dir = temp_accounts_dir();
+        let first = dir.join("acc1.json");
+        let second = dir.join("acc2.json");
+        let (first_json, second_json) = (sample_account_json("acc1"), sample_account_json("acc2"));
+        fs::write(&first, &first_json).unwrap();
+        fs::write(&second, &second_json).unwrap();
+
+        // 读取不改写文件
+        assert_eq!(read_account_file(&first).unwrap(), first_json);
+        assert_eq!(fs::read_to_string(&first).unwrap(), first_json);
+
+        // 整目录迁移改写所有明文文件，不留下临时文件
+        assert_eq!(migrate_accounts_dir(&dir, true).unwrap(), 2);
+        let on_disk = fs::read_to_string(&first).unwrap();
+        assert!(is_encrypted_content(&on_disk));
+        assert!(!on_disk.contains("rtk-secret"));
+        assert!(!fs::read_to_string(&second).unwrap().contains("rtk-secret"));
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+        assert_eq!(migrate_accounts_dir(&dir, true).unwrap(), 0);
+        assert_eq!(read_account_file(&first).unwrap(), first_json);
+
+        // 关闭加密后可还原为明文
+        assert_eq!(migrate_accounts_dir(&dir, false).unwrap(), 2);
+        assert_eq!(fs::read_to_string(&second).unwrap(), second_json);
+
+        let _ = fs::remove_dir_all(dir.parent().unwrap());
+    }
+
+    #[test]
+    fn test_switching_key_provider_rewrites_stale_files() {
+        let _ring = crypto::lock_key_ring_for_test();
+        crypto::reset_key_ring_for_test();
+
+        let dir = temp_accounts_dir();
+        let keys = dir.parent().unwrap();
+        let (first, second) = (dir.join("acc1.json"), dir.join("acc2.json"));
+        let json = sample_account_json("acc1");
+
+        crypto::set_active_key_provider(&FileKeyProvider::new(keys.join("machine.key"))).unwrap();
+        write_account_file_with(&first, &json, true).unwrap();
+        write_account_file_with(&second, &json, true).unwrap();
+        let old_content = fs::read_to_string(&first).unwrap();
+
+        crypto::set_active_key_provider(&FileKeyProvider::new(keys.join("keystore.key"))).unwrap();
+        assert!(has_stale_key(&old_content));
+
+        // 读取不改写旧密钥文件，由整目录迁移统一以新密钥重写
+        assert_eq!(read_account_file(&first).unwrap(), json);
+        assert_eq!(fs::read_to_string(&first).unwrap(), old_content);
+        assert_eq!(migrate_accounts_dir(&dir, true).unwrap(), 2);
+        let rewritten = fs::read_to_string(&first).unwrap();
+        assert_ne!(rewritten, old_content);
+        assert!(!has_stale_key(&rewritten));
+        assert_eq!(migrate_accounts_dir(&dir, true).unwrap(), 0);
+        assert_eq!(read_account_file(&second).unwrap(), json);
+
+        crypto::reset_key_ring_for_test();
+        let _ = fs::remove_dir_all(keys);
+    }
+
+    #[test]
+    fn test_key_source_survives_restart() {
+        let _ring = crypto::lock_key_ring_for_test();
+        let dir = temp_accounts_dir();
+        let key_file = dir.parent().unwrap().join(crate::utils::key_provider::KEY_FILE_NAME);
+        let path = dir.join("acc1.json");
+        let json = sample_account_json("acc1");
+
+        // 首次启动：默认来源，账号以 machine_uid 密钥加密
+        reset_key_source_for_test();
+        apply_key_source_in(&key_file, &dir, EncryptionKeySource::MachineId, true).unwrap();
+        write_account_file_with(&path, &json, true).unwrap();
+        let machine_content = fs::read_to_string(&path).unwrap();
+        assert!(!key_file.exists());
+
+        // 切换到密钥文件 (配置已落盘)：已有文件立即以新密钥重写，重复应用不再迁移
+        assert_eq!(
+            apply_key_source_in(&key_file, &dir, EncryptionKeySource::KeyFile, true).unwrap(),
+            Some(1)
+        );
+        assert_eq!(
+            apply_key_source_in(&key_file, &dir, EncryptionKeySource::KeyFile, true).unwrap(),
+            None
+        );
+        let file_content = fs::read_to_string(&path).unwrap();
+        assert_ne!(file_content, machine_content);
+
+        // 重启：仅凭默认密钥无法解密，按持久化的来源重建密钥环后可正常读取
+        reset_key_source_for_test();
+        assert!(read_account_file(&path).is_err());
+        assert_eq!(
+            apply_key_source_in(&key_file, &dir, EncryptionKeySource::KeyFile, true).unwrap(),
+            Some(0)
+        );
+        assert_eq!(read_account_file(&path).unwrap(), json);
+        assert_eq!(fs::read_to_string(&path).unwrap(), file_content);
+
+        // 中途未完成迁移的旧密钥文件在启动时补齐
+        let stale = dir.join("acc2.json");
+        fs::write(&stale, &machine_content).unwrap();
+        reset_key_source_for_test();
+        assert_eq!(
+            apply_key_source_in(&key_file, &dir, EncryptionKeySource::KeyFile, true).unwrap(),
+            Some(1)
+        );
+        assert!(!has_stale_key(&fs::read_to_string(&stale).unwrap()));
+
+        // 切回默认来源并重启：密钥文件仍存在，其密文可解密并被迁回
+        reset_key_source_for_test();
+        assert_eq!(
+            apply_key_source_in(&key_file, &dir, EncryptionKeySource::MachineId, true).unwrap(),
+            Some(2)
+        );
+        reset_key_source_for_test();
+        assert_eq!(read_account_file(&path).unwrap(), json);
+        assert_eq!(read_account_file(&stale).unwrap(), json);
+
+        let _ = fs::remove_dir_all(dir.parent().unwrap());
+    }
+
+    #[test]
+    fn test_encrypted_round_trip_loads_account() {
+        let _key = crate::utils::crypto::override_key_for_test("account-store-test");
+        let dir = temp_accounts_dir();
+        let path = dir.join("acc1.json");
+        let json = sample_account_json("acc1");
+
+        write_account_file_with(&path, &json, true).unwrap();
+        assert!(!fs::read_to_string(&path).unwrap().contains("atk-secret"));
+
+        // 无论开关状态如何，密文都能被识别并解密
+        let loaded = read_account_file(&path).unwrap();
+        let account: crate::models::Account = serde_json::from_str(&loaded).unwrap();
+        assert_eq!(account.id, "acc1");
+        assert_eq!(account.token.refresh_token, "rtk-secret");
+
+        let _ = fs::remove_dir_all(dir.parent().unwrap());
+    }
+}
@@ -83,6 +83,11 @@ pub fn load_app_config() -> Result<AppConfig, String> {
         let _ = save_app_config(&config);
     }
 
+    // The key source must be applied before the encryption switch so files are migrated with the right key
+    super::account_store::apply_key_source(config.encryption_key_source, config.encrypt_account_store);
+    super::account_store::apply_encryption_setting(config.encrypt_account_store);
+    super::migration::apply_field_path_overrides(&config.token_field_paths);
+
     Ok(config)
 }
 
@@ -94,6 +99,16 @@ pub fn save_app_config(config: &AppConfig) -> Result<(), String> {
     let content = serde_json::to_string_pretty(config)
         .map_err(|e| format!("failed_to_serialize_config: {}", e))?;
     
-    fs::write(&config_path, content)
-        .map_err(|e| format!("failed_to_save_config: {}", e))
+    // Write-then-rename: a key source switch re-encrypts account files, so it must only take
+    // effect once the new choice is fully on disk
+    let tmp_path = config_path.with_extension("json.tmp");
+    fs::write(&tmp_path, content)
+        .map_err(|e| format!("failed_to_save_config: {}", e))?;
+    fs::rename(&tmp_path, &config_path)
+        .map_err(|e| format!("failed_to_save_config: {}", e))?;
+
+    super::account_store::apply_key_source(config.encryption_key_source, config.encrypt_account_store);
+    super::account_store::apply_encryption_setting(config.encrypt_account_store);
+    super::migration::apply_field_path_overrides(&config.token_field_paths);
+    Ok(())
 }
@@ -1,6 +1,47 @@
 use crate::utils::protobuf;
 use rusqlite::Connection;
-use std::path::PathBuf;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// Lock files created by the IDE (Electron/VS Code) inside its user-data directory
+const IDE_LOCK_FILES: &[&str] = &["code.lock", "SingletonLock"];
+
+/// A lock without a PID is only trusted if it was modified within this window (seconds);
+/// older ones are leftovers from a crash
+const PIDLESS_LOCK_FRESH_SECS: u64 = 30;
+
+/// Errors returned by operations that modify the IDE database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DbWriteError {
+    /// The IDE is running; writing now could be overwritten or corrupt its state.
+    /// Pass `force = true` to bypass this check.
+    Busy,
+    Failed(String),
+}
+
+impl std::fmt::Display for DbWriteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DbWriteError::Busy => write!(
+                f,
+                "Busy: Antigravity is running, close it before modifying its database"
+            ),
+            DbWriteError::Failed(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<String> for DbWriteError {
+    fn from(e: String) -> Self {
+        DbWriteError::Failed(e)
+    }
+}
+
+impl From<DbWriteError> for String {
+    fn from(e: DbWriteError) -> Self {
+        e.to_string()
+    }
+}
 
 fn get_antigravity_path() -> Option<PathBuf> {
     if let Ok(config) = crate::modules::config::load_app_config() {
@@ -61,7 +102,228 @@ pub fn get_db_path() -> Result<PathBuf, String> {
     }
 }
 
+/// Release channel of a detected IDE installation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum InstallationChannel {
+    Stable,
+    Insiders,
+    Unknown,
+}
+
+/// A detected IDE data directory and its state database
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Installation {
+    pub label: InstallationChannel,
+    pub user_data_dir: PathBuf,
+    pub db_path: PathBuf,
+}
+
+/// `<user-data>` -> `<user-data>/User/globalStorage/state.vscdb`
+fn db_path_in_user_data_dir(user_data_dir: &Path) -> PathBuf {
+    user_data_dir.join("User").join("globalStorage").join("state.vscdb")
+}
+
+/// Classify a data directory name such as "Antigravity" or "Antigravity - Insiders"
+fn classify_installation_dir(dir_name: &str) -> Option<InstallationChannel> {
+    let lower = dir_name.to_lowercase();
+    let suffix = lower
+        .strip_prefix("antigravity")?
+        .trim_matches(|c: char| c == ' ' || c == '-' || c == '_');
+    Some(match suffix {
+        "" => InstallationChannel::Stable,
+        "insiders" | "insider" | "preview" | "next" => InstallationChannel::Insiders,
+        _ => InstallationChannel::Unknown,
+    })
+}
+
+/// Platform directory that holds per-app data dirs (e.g. `~/.config` on Linux)
+fn app_config_base_dir() -> Option<PathBuf> {
+    #[cfg(target_os = "macos")]
+    {
+        dirs::home_dir().map(|h| h.join("Library/Application Support"))
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        std::env::var("APPDATA").ok().map(PathBuf::from)
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        dirs::home_dir().map(|h| h.join(".config"))
+    }
+}
+
+/// Enumerate IDE installations (Stable/Insiders/...) under a config base directory
+fn list_installations_in(base_dir: &Path) -> Vec<Installation> {
+    let Ok(entries) = std::fs::read_dir(base_dir) else {
+        return Vec::new();
+    };
+
+    let mut installations: Vec<Installation> = entries
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_dir())
+        .filter_map(|e| {
+            let label = classify_installation_dir(&e.file_name().to_string_lossy())?;
+            let user_data_dir = e.path();
+            let db_path = db_path_in_user_data_dir(&user_data_dir);
+            db_path.exists().then_some(Installation {
+                label,
+                user_data_dir,
+                db_path,
+            })
+        })
+        .collect();
+
+    installations.sort_by(|a, b| a.label.cmp(&b.label).then_with(|| a.db_path.cmp(&b.db_path)));
+    installations
+}
+
+/// List all detected IDE installations with an existing state database.
+///
+/// The database `get_db_path` would use (running process / portable mode) comes first,
+/// followed by the standard data directories, so import can target a specific build.
+pub fn list_installations() -> Vec<Installation> {
+    let mut installations = Vec::new();
+
+    if let Ok(db_path) = get_db_path() {
+        if db_path.exists() {
+            if let Some(user_data_dir) = user_data_dir_from_db_path(&db_path) {
+                let label = user_data_dir
+                    .file_name()
+                    .and_then(|n| classify_installation_dir(&n.to_string_lossy()))
+                    .unwrap_or(InstallationChannel::Unknown);
+                installations.push(Installation {
+                    label,
+                    user_data_dir,
+                    db_path,
+                });
+            }
+        }
+    }
+
+    if let Some(base_dir) = app_config_base_dir() {
+        for installation in list_installations_in(&base_dir) {
+            if !installations.iter().any(|i| i.db_path == installation.db_path) {
+                installations.push(installation);
+            }
+        }
+    }
+
+    installations
+}
+
+/// Check whether the Antigravity IDE is currently running.
+///
+/// Looks for the IDE process by name/path first, then falls back to lock files in the
+/// IDE user-data directory (covers cases where the process can't be enumerated).
+pub fn is_ide_running() -> bool {
+    if crate::modules::process::is_antigravity_running() {
+        return true;
+    }
+
+    get_db_path()
+        .ok()
+        .and_then(|db_path| user_data_dir_from_db_path(&db_path))
+        .map(|dir| is_ide_lock_held(&dir))
+        .unwrap_or(false)
+}
+
+/// `<user-data>/User/globalStorage/state.vscdb` -> `<user-data>`
+fn user_data_dir_from_db_path(db_path: &Path) -> Option<PathBuf> {
+    db_path
+        .parent()
+        .and_then(|p| p.parent())
+        .and_then(|p| p.parent())
+        .map(|p| p.to_path_buf())
+}
+
+/// Check the IDE lock files inside a user-data directory.
+///
+/// A lock that records a PID (plain content or a `host-pid` symlink target) is only
+/// considered held while that process is alive, so stale locks left by a crash are ignored.
+/// A lock without a recognizable PID is only considered held while it is fresh
+/// (modified within `PIDLESS_LOCK_FRESH_SECS`, e.g. the IDE is still writing it).
+pub fn is_ide_lock_held(user_data_dir: &Path) -> bool {
+    IDE_LOCK_FILES.iter().any(|name| {
+        let lock_path = user_data_dir.join(name);
+        let content = match std::fs::read_link(&lock_path) {
+            Ok(target) => target.to_string_lossy().to_string(),
+            Err(_) => match std::fs::read_to_string(&lock_path) {
+                Ok(c) => c,
+                Err(_) => return false,
+            },
+        };
+
+        match parse_lock_pid(&content) {
+            Some(pid) => is_pid_alive(pid),
+            None => is_lock_fresh(&lock_path),
+        }
+    })
+}
+
+fn is_lock_fresh(lock_path: &Path) -> bool {
+    std::fs::symlink_metadata(lock_path)
+        .and_then(|meta| meta.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_some_and(|age| age.as_secs() < PIDLESS_LOCK_FRESH_SECS)
+}
+
+fn parse_lock_pid(content: &str) -> Option<u32> {
+    let trimmed = content.trim();
+    trimmed
+        .parse::<u32>()
+        .ok()
+        .or_else(|| trimmed.rsplit('-').next().and_then(|s| s.parse::<u32>().ok()))
+}
+
+fn is_pid_alive(pid: u32) -> bool {
+    use sysinfo::{Pid, ProcessesToUpdate, System};
+
+    let pid = Pid::from_u32(pid);
+    let mut system = System::new();
+    system.refresh_processes(ProcessesToUpdate::Some(&[pid]));
+    system.process(pid).is_some()
+}
+
+/// Wait until the IDE lock files next to `db_path` are released (or `timeout` elapses).
+///
+/// The IDE process can disappear a moment before its lock files are cleaned up, so callers
+/// that just closed it should wait here instead of failing straight away with `Busy`.
+/// Returns `true` once no lock is held.
+pub async fn wait_for_ide_lock_release(db_path: &Path, timeout: std::time::Duration) -> bool {
+    let Some(user_data_dir) = user_data_dir_from_db_path(db_path) else {
+        return true;
+    };
+
+    let deadline = std::time::Instant::now() + timeout;
+    loop {
+        if !is_ide_lock_held(&user_data_dir) {
+            return true;
+        }
+        if std::time::Instant::now() >= deadline {
+            return false;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    }
+}
+
+fn ensure_ide_not_running(force: bool) -> Result<(), DbWriteError> {
+    if force {
+        return Ok(());
+    }
+    if is_ide_running() {
+        crate::modules::logger::log_warn("Refusing to modify database while Antigravity is running");
+        return Err(DbWriteError::Busy);
+    }
+    Ok(())
+}
+
 /// Inject Token and Email into database
+///
+/// Refuses with `DbWriteError::Busy` while the IDE is running unless `force` is set.
+#[allow(clippy::too_many_arguments)]
 pub fn inject_token(
     db_path: &PathBuf,
     access_token: &str,
@@ -70,7 +332,9 @@ pub fn inject_token(
     email: &str,
     is_gcp_tos: bool,
     project_id: Option<&str>,
-) -> Result<String, String> {
+    force: bool,
+) -> Result<String, DbWriteError> {
+    ensure_ide_not_running(force)?;
     crate::modules::logger::log_info("Starting Token injection...");
     
     // 1. Detect Antigravity version
@@ -89,7 +353,7 @@ pub fn inject_token(
                 crate::modules::logger::log_info(
                     "Using new format injection (antigravityUnifiedStateSync.oauthToken)",
                 );
-                inject_new_format(
+                Ok(inject_new_format(
                     db_path,
                     access_token,
                     refresh_token,
@@ -97,13 +361,13 @@ pub fn inject_token(
                     email,
                     is_gcp_tos,
                     project_id,
-                )
+                )?)
             } else {
                 // < 1.16.5: Use old format only
                 crate::modules::logger::log_info(
                     "Using old format injection (jetskiStateSync.agentManagerInitState)",
                 );
-                inject_old_format(db_path, access_token, refresh_token, expiry, email)
+                Ok(inject_old_format(db_path, access_token, refresh_token, expiry, email)?)
             }
         }
         Err(e) => {
@@ -131,11 +395,11 @@ pub fn inject_token(
             if new_result.is_ok() || old_result.is_ok() {
                 Ok("Token injection successful (dual format fallback)".to_string())
             } else {
-                Err(format!(
+                Err(DbWriteError::Failed(format!(
                     "Both formats failed - New: {:?}, Old: {:?}",
                     new_result.err(),
                     old_result.err()
-                ))
+                )))
             }
         }
     }
@@ -286,7 +550,13 @@ fn inject_old_format(
 }
 
 /// 注入 Service Machine ID 到数据库，解决 VS Code 缓存指纹不匹配导致 Token 失效的问题
-pub fn write_service_machine_id(db_path: &std::path::Path, service_machine_id: &str) -> Result<(), String> {
+/// IDE 运行中时返回 `DbWriteError::Busy`，除非指定 `force`
+pub fn write_service_machine_id(
+    db_path: &std::path::Path,
+    service_machine_id: &str,
+    force: bool,
+) -> Result<(), DbWriteError> {
+    ensure_ide_not_running(force)?;
     let conn = Connection::open(db_path).map_err(|e| format!("Failed to open database: {}", e))?;
     
     conn.execute(
@@ -302,3 +572,121 @@ pub fn write_service_machine_id(db_path: &std::path::Path, service_machine_id: &
     
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_user_data_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ag_ide_lock_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_ide_lock_absent() {
+        let dir = temp_user_data_dir();
+        assert!(!is_ide_lock_held(&dir));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_ide_lock_with_live_pid_is_held() {
+        let dir = temp_user_data_dir();
+        std::fs::write(dir.join("code.lock"), std::process::id().to_string()).unwrap();
+        assert!(is_ide_lock_held(&dir));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_ide_lock_with_dead_pid_is_stale() {
+        let dir = temp_user_data_dir();
+        std::fs::write(dir.join("code.lock"), "4194000").unwrap();
+        assert!(!is_ide_lock_held(&dir));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_ide_lock_without_pid_is_held_only_while_fresh() {
+        let dir = temp_user_data_dir();
+        let lock_path = dir.join("code.lock");
+        std::fs::write(&lock_path, "").unwrap();
+        assert!(is_ide_lock_held(&dir));
+
+        // An old lock left behind by a crash is no longer considered held
+        let stale = std::time::SystemTime::now()
+            - std::time::Duration::from_secs(PIDLESS_LOCK_FRESH_SECS + 60);
+        std::fs::File::options()
+            .write(true)
+            .open(&lock_path)
+            .unwrap()
+            .set_modified(stale)
+            .unwrap();
+        assert!(!is_ide_lock_held(&dir));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_singleton_lock_symlink() {
+        let dir = temp_user_data_dir();
+        std::os::unix::fs::symlink(
+            format!("my-host-{}", std::process::id()),
+            dir.join("SingletonLock"),
+        )
+        .unwrap();
+        assert!(is_ide_lock_held(&dir));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    fn create_installation(base: &Path, dir_name: &str) -> PathBuf {
+        let db_path = db_path_in_user_data_dir(&base.join(dir_name));
+        std::fs::create_dir_all(db_path.parent().unwrap()).unwrap();
+        std::fs::write(&db_path, b"").unwrap();
+        db_path
+    }
+
+    #[test]
+    fn test_list_installations_stable_and_insiders() {
+        let base = temp_user_data_dir();
+        let stable_db = create_installation(&base, "Antigravity");
+        let insiders_db = create_installation(&base, "Antigravity - Insiders");
+        // Data dir without a database and unrelated apps are ignored
+        std::fs::create_dir_all(base.join("Antigravity Preview")).unwrap();
+        create_installation(&base, "Code");
+
+        let installations = list_installations_in(&base);
+        assert_eq!(installations.len(), 2);
+        assert_eq!(installations[0].label, InstallationChannel::Stable);
+        assert_eq!(installations[0].db_path, stable_db);
+        assert_eq!(installations[0].user_data_dir, base.join("Antigravity"));
+        assert_eq!(installations[1].label, InstallationChannel::Insiders);
+        assert_eq!(installations[1].db_path, insiders_db);
+
+        let _ = std::fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn test_classify_installation_dir() {
+        assert_eq!(classify_installation_dir("Antigravity"), Some(InstallationChannel::Stable));
+        assert_eq!(
+            classify_installation_dir("antigravity-insiders"),
+            Some(InstallationChannel::Insiders)
+        );
+        assert_eq!(
+            classify_installation_dir("Antigravity Dev"),
+            Some(InstallationChannel::Unknown)
+        );
+        assert_eq!(classify_installation_dir("Cursor"), None);
+        assert!(list_installations_in(Path::new("/nonexistent/ag-base")).is_empty());
+    }
+
+    #[test]
+    fn test_user_data_dir_from_db_path() {
+        let db_path = PathBuf::from("/data/user-data/User/globalStorage/state.vscdb");
+        assert_eq!(
+            user_data_dir_from_db_path(&db_path),
+            Some(PathBuf::from("/data/user-data"))
+        );
+    }
+}
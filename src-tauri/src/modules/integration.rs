@@ -4,7 +4,8 @@ use std::fs;
 
 pub trait SystemIntegration: Send + Sync {
     /// 当切换账号时执行的系统层操作（如杀进程、写入文件、注入数据库）
-    async fn on_account_switch(&self, account: &crate::models::Account) -> Result<(), String>;
+    /// `force` 为 true 时跳过 IDE 运行检测，强制写入数据库
+    async fn on_account_switch(&self, account: &crate::models::Account, force: bool) -> Result<(), String>;
     
     /// 更新系统托盘（如果适用）
     fn update_tray(&self);
@@ -19,7 +20,7 @@ pub struct DesktopIntegration {
 }
 
 impl SystemIntegration for DesktopIntegration {
-    async fn on_account_switch(&self, account: &crate::models::Account) -> Result<(), String> {
+    async fn on_account_switch(&self, account: &crate::models::Account, force: bool) -> Result<(), String> {
         crate::modules::logger::log_info(&format!("[Desktop] Executing system switch for: {}", account.email));
         
         // 1. 获取存储路径
@@ -37,6 +38,12 @@ impl SystemIntegration for DesktopIntegration {
 
         // 4. 数据库处理与 Token 注入
         let db_path = db::get_db_path()?;
+
+        // 进程退出后锁文件可能稍晚才释放，短暂等待而不是直接报 Busy
+        if !force && !db::wait_for_ide_lock_release(&db_path, std::time::Duration::from_secs(5)).await {
+            crate::modules::logger::log_warn("[Desktop] IDE lock still held after closing Antigravity");
+        }
+
         if db_path.exists() {
             let backup_path = db_path.with_extension("vscdb.backup");
             let _ = fs::copy(&db_path, &backup_path);
@@ -50,11 +57,12 @@ impl SystemIntegration for DesktopIntegration {
             &account.email,
             account.token.is_gcp_tos,
             account.token.project_id.as_deref(),
+            force,
         )?;
         
         // 4.1 同步 Service Machine ID 到数据库 (关键修复点)
         if let Some(ref profile) = account.device_profile {
-            let _ = db::write_service_machine_id(&db_path, &profile.mac_machine_id);
+            let _ = db::write_service_machine_id(&db_path, &profile.mac_machine_id, force);
         }
 
         // 5. 重启外部进程
@@ -80,7 +88,7 @@ impl SystemIntegration for DesktopIntegration {
 pub struct HeadlessIntegration;
 
 impl SystemIntegration for HeadlessIntegration {
-    async fn on_account_switch(&self, account: &crate::models::Account) -> Result<(), String> {
+    async fn on_account_switch(&self, account: &crate::models::Account, _force: bool) -> Result<(), String> {
         crate::modules::logger::log_info(&format!("[Headless] Account switched in memory: {}", account.email));
         // Docker 模式下通常不直接控制宿主机的 VS Code 进程
         // 如果需要同步配置到某个 volume，可以在此处添加逻辑
@@ -103,15 +111,15 @@ pub enum SystemManager {
 }
 
 impl SystemManager {
-    pub async fn on_account_switch(&self, account: &Account) -> Result<(), String> {
+    pub async fn on_account_switch(&self, account: &Account, force: bool) -> Result<(), String> {
         match self {
             SystemManager::Desktop(handle) => {
                 let integration = DesktopIntegration { app_handle: handle.clone() };
-                integration.on_account_switch(account).await
+                integration.on_account_switch(account, force).await
             },
             SystemManager::Headless => {
                 let integration = HeadlessIntegration;
-                integration.on_account_switch(account).await
+                integration.on_account_switch(account, force).await
             }
         }
     }
@@ -138,15 +146,15 @@ impl SystemManager {
 }
 
 impl SystemIntegration for SystemManager {
-    async fn on_account_switch(&self, account: &crate::models::Account) -> Result<(), String> {
+    async fn on_account_switch(&self, account: &crate::models::Account, force: bool) -> Result<(), String> {
         match self {
             SystemManager::Desktop(handle) => {
                 let integration = DesktopIntegration { app_handle: handle.clone() };
-                integration.on_account_switch(account).await
+                integration.on_account_switch(account, force).await
             },
             SystemManager::Headless => {
                 let integration = HeadlessIntegration;
-                integration.on_account_switch(account).await
+                integration.on_account_switch(account, force).await
             }
         }
     }
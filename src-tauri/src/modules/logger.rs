@@ -25,47 +25,215 @@ pub fn get_log_dir() -> Result<PathBuf, String> {
     Ok(log_dir)
 }
 
-/// Initialize the log system
-pub fn init_logger() {
+/// Where log output is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogDestination {
+    /// Size-rotated log file only (headless servers with external log collection)
+    File,
+    /// Console only
+    Stdout,
+    /// Console + log file
+    #[default]
+    Both,
+}
+
+impl std::str::FromStr for LogDestination {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "file" => Ok(Self::File),
+            "stdout" | "console" => Ok(Self::Stdout),
+            "both" => Ok(Self::Both),
+            other => Err(format!("Unknown log destination: {}", other)),
+        }
+    }
+}
+
+/// Default size (bytes) at which the log file is rotated
+pub const DEFAULT_LOG_MAX_SIZE: u64 = 10 * 1024 * 1024;
+/// Default number of rotated files kept (app.log.1 .. app.log.N)
+pub const DEFAULT_LOG_ROTATE: usize = 5;
+
+/// Logger settings applied once at startup by `configure`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoggerConfig {
+    pub dest: LogDestination,
+    /// Log file path; None = `<data_dir>/logs/app.log`
+    pub path: Option<PathBuf>,
+    /// Rotate once the file would grow past this many bytes (0 = never rotate)
+    pub max_size: u64,
+    /// Number of rotated files to keep (0 = truncate in place)
+    pub rotate: usize,
+}
+
+impl Default for LoggerConfig {
+    fn default() -> Self {
+        Self {
+            dest: LogDestination::default(),
+            path: None,
+            max_size: DEFAULT_LOG_MAX_SIZE,
+            rotate: DEFAULT_LOG_ROTATE,
+        }
+    }
+}
+
+impl LoggerConfig {
+    /// Defaults overridden by `ABV_LOG_DEST` (file | stdout | both), `ABV_LOG_PATH`,
+    /// `ABV_LOG_MAX_SIZE` (bytes) and `ABV_LOG_ROTATE`; invalid values are reported and ignored
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+        if let Ok(dest) = std::env::var("ABV_LOG_DEST") {
+            match dest.parse() {
+                Ok(dest) => config.dest = dest,
+                Err(e) => eprintln!("Ignoring ABV_LOG_DEST: {}", e),
+            }
+        }
+        if let Ok(path) = std::env::var("ABV_LOG_PATH") {
+            if !path.trim().is_empty() {
+                config.path = Some(PathBuf::from(path.trim()));
+            }
+        }
+        if let Ok(size) = std::env::var("ABV_LOG_MAX_SIZE") {
+            match size.trim().parse() {
+                Ok(size) => config.max_size = size,
+                Err(e) => eprintln!("Ignoring ABV_LOG_MAX_SIZE: {}", e),
+            }
+        }
+        if let Ok(rotate) = std::env::var("ABV_LOG_ROTATE") {
+            match rotate.trim().parse() {
+                Ok(rotate) => config.rotate = rotate,
+                Err(e) => eprintln!("Ignoring ABV_LOG_ROTATE: {}", e),
+            }
+        }
+        config
+    }
+}
+
+/// Log file writer with size-based rotation: app.log -> app.log.1 -> ... -> app.log.N
+pub struct SizeRotatingWriter {
+    path: PathBuf,
+    max_size: u64,
+    rotate: usize,
+    file: fs::File,
+    size: u64,
+}
+
+impl SizeRotatingWriter {
+    pub fn open(path: PathBuf, max_size: u64, rotate: usize) -> std::io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = fs::OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path,
+            max_size,
+            rotate,
+            file,
+            size,
+        })
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{}", index));
+        PathBuf::from(name)
+    }
+
+    fn rotate_files(&mut self) -> std::io::Result<()> {
+        if self.rotate == 0 {
+            self.file.set_len(0)?;
+        } else {
+            let _ = fs::remove_file(self.rotated_path(self.rotate));
+            for index in (1..self.rotate).rev() {
+                let from = self.rotated_path(index);
+                if from.exists() {
+                    fs::rename(&from, self.rotated_path(index + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.rotated_path(1))?;
+            self.file = fs::OpenOptions::new().create(true).append(true).open(&self.path)?;
+        }
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl std::io::Write for SizeRotatingWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.max_size > 0 && self.size > 0 && self.size + buf.len() as u64 > self.max_size {
+            if let Err(e) = self.rotate_files() {
+                eprintln!("Failed to rotate log file {:?}: {}", self.path, e);
+            }
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Initialize the log system (call once at startup)
+pub fn configure(config: LoggerConfig) {
     // Capture log macro logs
     let _ = tracing_log::LogTracer::init();
-    
-    let log_dir = match get_log_dir() {
-        Ok(dir) => dir,
-        Err(e) => {
-            eprintln!("Failed to initialize log directory: {}", e);
-            return;
+
+    let write_file = config.dest != LogDestination::Stdout;
+    let write_console = config.dest != LogDestination::File;
+
+    // 1. Size-rotated file writer (behind a non-blocking worker)
+    let mut file_writer = None;
+    if write_file {
+        let path = match config.path.clone() {
+            Some(path) => Ok(path),
+            None => get_log_dir().map(|dir| dir.join("app.log")),
+        };
+        match path.and_then(|path| {
+            SizeRotatingWriter::open(path.clone(), config.max_size, config.rotate)
+                .map_err(|e| format!("Failed to open log file {:?}: {}", path, e))
+        }) {
+            Ok(writer) => {
+                let (non_blocking, guard) = tracing_appender::non_blocking(writer);
+                // Leak the guard so buffered logs are flushed for the lifetime of the program
+                std::mem::forget(guard);
+                file_writer = Some(non_blocking);
+            }
+            Err(e) => eprintln!("Failed to initialize log file, file logging disabled: {}", e),
         }
-    };
-    
-    // 1. Set up file Appender (using tracing-appender for rolling logs)
-    // Using a daily rolling strategy here
-    let file_appender = tracing_appender::rolling::daily(log_dir, "app.log");
-    let (non_blocking, _guard) = tracing_appender::non_blocking(file_appender);
-    
+    }
+    let file_enabled = file_writer.is_some();
+
     // 2. Console output layer (using local timezone)
-    let console_layer = fmt::Layer::new()
-        .with_target(false)
-        .with_thread_ids(false)
-        .with_level(true)
-        .with_timer(LocalTimer);
-        
+    let console_layer = write_console.then(|| {
+        fmt::Layer::new()
+            .with_target(false)
+            .with_thread_ids(false)
+            .with_level(true)
+            .with_timer(LocalTimer)
+    });
+
     // 3. File output layer (disable ANSI formatting, use local timezone)
-    let file_layer = fmt::Layer::new()
-        .with_writer(non_blocking)
-        .with_ansi(false)
-        .with_target(true)
-        .with_level(true)
-        .with_timer(LocalTimer);
+    let file_layer = file_writer.map(|writer| {
+        fmt::Layer::new()
+            .with_writer(writer)
+            .with_ansi(false)
+            .with_target(true)
+            .with_level(true)
+            .with_timer(LocalTimer)
+    });
 
     // 4. Set filtering layer (default to INFO level to reduce log size)
     let filter_layer = EnvFilter::try_from_default_env()
         .unwrap_or_else(|_| EnvFilter::new("info"));
 
-    // 6. Log bridge layer
+    // 5. Log bridge layer
     let bridge_layer = crate::modules::log_bridge::TauriLogBridgeLayer::new();
 
-    // 5. Initialize global subscriber (use try_init to avoid crash on repeated initialization)
+    // 6. Initialize global subscriber (use try_init to avoid crash on repeated initialization)
     let _ = tracing_subscriber::registry()
         .with(filter_layer)
         .with(console_layer)
@@ -73,15 +241,19 @@ pub fn init_logger() {
         .with(bridge_layer)
         .try_init();
 
-    // Leak _guard to ensure its lifetime lasts until program exit
-    // Recommended practice when using tracing_appender::non_blocking (if manual flushing is not needed)
-    std::mem::forget(_guard);
-    
-    info!("Log system initialized (Console + File persistence)");
-    
-    // Auto-cleanup logs older than 7 days
-    if let Err(e) = cleanup_old_logs(7) {
-        warn!("Failed to cleanup old logs: {}", e);
+    info!(
+        "Log system initialized (dest: {:?}, file: {}, max_size: {} bytes, rotate: {})",
+        config.dest,
+        if file_enabled { "on" } else { "off" },
+        config.max_size,
+        config.rotate
+    );
+
+    // Auto-cleanup logs older than 7 days (only in the app-managed log directory)
+    if file_enabled && config.path.is_none() {
+        if let Err(e) = cleanup_old_logs(7) {
+            warn!("Failed to cleanup old logs: {}", e);
+        }
     }
 }
 
@@ -222,3 +394,52 @@ pub fn log_warn(message: &str) {
 pub fn log_error(message: &str) {
     error!("{}", message);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_size_rotation_triggers_past_max_size() {
+        let dir = std::env::temp_dir().join(format!("ag_logger_{}", uuid::Uuid::new_v4()));
+        let path = dir.join("app.log");
+        let rotated = |index: usize| dir.join(format!("app.log.{}", index));
+
+        let mut writer = SizeRotatingWriter::open(path.clone(), 100, 2).unwrap();
+        writer.write_all(&[b'a'; 60]).unwrap();
+        assert!(!rotated(1).exists());
+
+        // 超过 max_size：旧内容移入 app.log.1，新内容写入新文件
+        writer.write_all(&[b'b'; 60]).unwrap();
+        writer.flush().unwrap();
+        assert_eq!(fs::read(rotated(1)).unwrap(), vec![b'a'; 60]);
+        assert_eq!(fs::read(&path).unwrap(), vec![b'b'; 60]);
+
+        // 最多保留 rotate 个历史文件
+        writer.write_all(&[b'c'; 60]).unwrap();
+        writer.write_all(&[b'd'; 60]).unwrap();
+        writer.flush().unwrap();
+        assert_eq!(fs::read(rotated(1)).unwrap(), vec![b'c'; 60]);
+        assert_eq!(fs::read(rotated(2)).unwrap(), vec![b'b'; 60]);
+        assert!(!rotated(3).exists());
+        assert_eq!(fs::read(&path).unwrap(), vec![b'd'; 60]);
+
+        // 重新打开时沿用已有文件大小
+        drop(writer);
+        let mut writer = SizeRotatingWriter::open(path.clone(), 100, 2).unwrap();
+        writer.write_all(&[b'e'; 60]).unwrap();
+        writer.flush().unwrap();
+        assert_eq!(fs::read(rotated(1)).unwrap(), vec![b'd'; 60]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_log_destination_parses_env_values() {
+        assert_eq!("file".parse::<LogDestination>(), Ok(LogDestination::File));
+        assert_eq!(" STDOUT ".parse::<LogDestination>(), Ok(LogDestination::Stdout));
+        assert_eq!("both".parse::<LogDestination>(), Ok(LogDestination::Both));
+        assert!("syslog".parse::<LogDestination>().is_err());
+    }
+}
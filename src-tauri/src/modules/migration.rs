@@ -1,38 +1,838 @@
-use crate::models::{Account, TokenData};
+use crate::models::{Account, AccountSource, OAuthFieldPaths, TokenData, TokenFieldPathsConfig};
 use crate::modules::{account, db};
 use crate::utils::protobuf;
 use base64::{engine::general_purpose, Engine as _};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::fs;
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex, OnceLock, RwLock};
+
+/// Access token stored for V1 accounts whose refresh token could not be exchanged
+pub(crate) const IMPORTED_ACCESS_TOKEN_PLACEHOLDER: &str = "imported_access_token";
+
+/// ItemTable key holding the OAuth token in the unified format (>= 1.16.5)
+const UNIFIED_OAUTH_TOKEN_KEY: &str = "antigravityUnifiedStateSync.oauthToken";
+/// ItemTable key (or V1 backup JSON key) holding the legacy agent state (< 1.16.5)
+const LEGACY_AGENT_STATE_KEY: &str = "jetskiStateSync.agentManagerInitState";
+
+/// V1 index file names, in lookup order
+const V1_INDEX_FILES: &[&str] = &["antigravity_accounts.json", "accounts.json"];
+
+/// SQLite files start with this 16-byte header
+const SQLITE_HEADER: &[u8] = b"SQLite format 3\0";
+
+/// Storage format of an IDE database or V1 backup file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DbFormat {
+    /// IDE database using `antigravityUnifiedStateSync.oauthToken` (>= 1.16.5)
+    NewUnified,
+    /// IDE database using `jetskiStateSync.agentManagerInitState` (< 1.16.5)
+    LegacyJetski,
+    /// V1 / script-generated JSON backup carrying a refresh token or legacy state
+    V1BackupJson,
+    Unknown,
+}
+
+/// User overrides of the built-in OAuthInfo field paths (from `AppConfig.token_field_paths`)
+static FIELD_PATH_OVERRIDES: OnceLock<RwLock<TokenFieldPathsConfig>> = OnceLock::new();
+
+fn field_path_overrides() -> &'static RwLock<TokenFieldPathsConfig> {
+    FIELD_PATH_OVERRIDES.get_or_init(|| RwLock::new(TokenFieldPathsConfig::default()))
+}
+
+/// Apply field path overrides from config (called when the app config is loaded or saved)
+pub fn apply_field_path_overrides(config: &TokenFieldPathsConfig) {
+    protobuf::set_max_nesting_depth(
+        config
+            .max_nesting_depth
+            .unwrap_or(protobuf::DEFAULT_MAX_NESTING_DEPTH),
+    );
+    if let Ok(mut overrides) = field_path_overrides().write() {
+        if *overrides != *config {
+            crate::modules::logger::log_info(&format!(
+                "Token field path overrides updated: {:?}",
+                config
+            ));
+            *overrides = config.clone();
+        }
+    }
+}
+
+/// Built-in OAuthInfo field paths for each storage format
+///
+/// - NewUnified: decoded unified state payload is the OAuthInfo itself
+/// - LegacyJetski / V1BackupJson: agent state -> F6 OAuthInfo
+/// - OAuthInfo: F1 access_token, F3 refresh_token, F4.F1 expiry seconds
+pub fn builtin_field_paths(format: DbFormat) -> Option<OAuthFieldPaths> {
+    let oauth_info = match format {
+        DbFormat::NewUnified => Vec::new(),
+        DbFormat::LegacyJetski | DbFormat::V1BackupJson => vec![6],
+        DbFormat::Unknown => return None,
+    };
+    Some(OAuthFieldPaths {
+        oauth_info,
+        refresh_token: vec![3],
+        access_token: vec![1],
+        expiry_seconds: vec![4, 1],
+    })
+}
+
+fn field_paths_with(overrides: &TokenFieldPathsConfig, format: DbFormat) -> Option<OAuthFieldPaths> {
+    let overridden = match format {
+        DbFormat::NewUnified => overrides.new_unified.as_ref(),
+        // V1 备份内嵌的就是旧版 agent state，沿用同一套路径
+        DbFormat::LegacyJetski | DbFormat::V1BackupJson => overrides.legacy_jetski.as_ref(),
+        DbFormat::Unknown => None,
+    };
+    overridden.cloned().or_else(|| builtin_field_paths(format))
+}
+
+/// Effective field paths for a format (user override, else built-in)
+fn field_paths_for(format: DbFormat) -> Option<OAuthFieldPaths> {
+    match field_path_overrides().read() {
+        Ok(overrides) => field_paths_with(&overrides, format),
+        Err(_) => builtin_field_paths(format),
+    }
+}
 
 #[derive(Debug, Clone)]
 struct ImportedOAuthState {
-    refresh_token: String,
+    oauth: OAuthInfo,
     is_gcp_tos: bool,
     project_id: Option<String>,
+    /// Extracted from the legacy (< 1.16.5) format; the token may be stale if the
+    /// IDE has since migrated to the unified format
+    from_legacy_format: bool,
+    /// Recorded on the saved account
+    source: AccountSource,
+}
+
+/// OAuth credentials stored in the IDE database (`OAuthTokenInfo` message)
+#[derive(Debug, Clone, PartialEq)]
+pub struct OAuthInfo {
+    /// Field 1, absent or empty in some older databases
+    pub access_token: Option<String>,
+    /// Field 3
+    pub refresh_token: String,
+    /// Field 4 (`Timestamp.seconds`), unix seconds
+    pub expiry: Option<i64>,
+}
+
+impl OAuthInfo {
+    fn from_refresh_token(refresh_token: String) -> Self {
+        Self {
+            access_token: None,
+            refresh_token,
+            expiry: None,
+        }
+    }
+
+    /// Access token and its remaining lifetime, when it is not close to expiry
+    fn reusable_access_token(&self, now: i64) -> Option<(String, i64)> {
+        let access_token = self.access_token.clone()?;
+        let expiry = self.expiry?;
+        (expiry > now + crate::modules::oauth::TOKEN_REFRESH_SKEW_SECONDS)
+            .then(|| (access_token, expiry - now))
+    }
+}
+
+/// Email filter applied when importing from a (possibly shared) V1 dump
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+pub enum EmailFilter {
+    /// Exact domain match, e.g. "example.com" (case-insensitive)
+    Domain(String),
+    /// Case-insensitive substring match
+    Contains(String),
+    /// Regular expression match
+    Regex(String),
+}
+
+impl EmailFilter {
+    /// Reject filters that can never match (e.g. invalid regex) before importing anything
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            EmailFilter::Regex(pattern) => compiled_email_regex(pattern).map(|_| ()),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn matches(&self, email: &str) -> bool {
+        let normalized = crate::utils::normalize_email(email);
+        match self {
+            EmailFilter::Domain(domain) => normalized
+                .rsplit_once('@')
+                .is_some_and(|(_, d)| {
+                    d.eq_ignore_ascii_case(domain.trim().trim_start_matches('@'))
+                }),
+            EmailFilter::Contains(needle) => normalized.contains(&needle.trim().to_lowercase()),
+            EmailFilter::Regex(pattern) => {
+                compiled_email_regex(pattern).is_ok_and(|re| re.is_match(email.trim()))
+            }
+        }
+    }
+}
+
+/// Compiled `EmailFilter::Regex` patterns, so an import compiles each pattern once
+/// instead of once per V1 entry
+static EMAIL_FILTER_REGEXES: LazyLock<Mutex<HashMap<String, regex::Regex>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn compiled_email_regex(pattern: &str) -> Result<regex::Regex, String> {
+    let mut cache = EMAIL_FILTER_REGEXES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    if let Some(re) = cache.get(pattern) {
+        return Ok(re.clone());
+    }
+    let re = regex::Regex::new(pattern)
+        .map_err(|e| format!("Invalid email filter regex: {}", e))?;
+    cache.insert(pattern.to_string(), re.clone());
+    Ok(re)
+}
+
+/// Why an account from the V1 index was not imported
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum ImportSkipReason {
+    /// Email did not match the requested `EmailFilter`
+    FilteredOut,
+    /// `max_accounts` was already reached
+    CapReached,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SkippedAccount {
+    pub email: String,
+    pub reason: ImportSkipReason,
+}
+
+/// Result of a V1 import: imported accounts plus the ones deliberately skipped
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct V1ImportReport {
+    pub imported: Vec<Account>,
+    pub skipped: Vec<SkippedAccount>,
+}
+
+impl V1ImportReport {
+    /// Order both lists by email so the result does not depend on V1 index key order
+    fn sorted(mut self) -> Self {
+        self.imported.sort_by(|a, b| a.email.cmp(&b.email));
+        self.skipped.sort_by(|a, b| a.email.cmp(&b.email));
+        self
+    }
+}
+
+/// Non-account keys that may appear next to the accounts in a V1 index
+const V1_INDEX_META_KEYS: &[&str] = &["current_account_id"];
+
+/// What is wrong with a V1 index (entry)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum IndexProblem {
+    /// Expected a JSON object
+    NotAnObject,
+    /// Required field is absent
+    MissingField,
+    /// Field is present with the wrong JSON type
+    WrongType { expected: String },
+    /// String field is present but blank
+    Empty,
+}
+
+/// One structured diagnostic from `validate_v1_index`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct IndexIssue {
+    /// Index key of the offending entry; None for problems with the index itself
+    pub account_id: Option<String>,
+    /// Offending field; None when the whole entry / index is malformed
+    pub field: Option<String>,
+    pub problem: IndexProblem,
+}
+
+impl std::fmt::Display for IndexIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let location = match (&self.account_id, &self.field) {
+            (Some(id), Some(field)) => format!("account {} field '{}'", id, field),
+            (Some(id), None) => format!("account {}", id),
+            (None, Some(field)) => format!("index field '{}'", field),
+            (None, None) => "index".to_string(),
+        };
+        match &self.problem {
+            IndexProblem::NotAnObject => write!(f, "{}: not a JSON object", location),
+            IndexProblem::MissingField => write!(f, "{}: missing", location),
+            IndexProblem::WrongType { expected } => write!(f, "{}: expected {}", location, expected),
+            IndexProblem::Empty => write!(f, "{}: empty", location),
+        }
+    }
+}
+
+/// Issues found in one V1 index file (dry-run validation)
+#[derive(Debug, Clone, Serialize)]
+pub struct V1IndexValidation {
+    pub index_file: String,
+    pub issues: Vec<IndexIssue>,
+}
+
+/// Check a parsed V1 index against the expected layout without importing anything.
+///
+/// Accepts both the direct map and the `{"accounts": {...}}` layouts. Every account entry
+/// needs a non-empty string `email` and a `backup_file` (or legacy `data_file`) path;
+/// entries `import_from_v1` would silently skip are reported here instead.
+pub fn validate_v1_index(value: &Value) -> Vec<IndexIssue> {
+    let issue = |account_id: Option<&str>, field: Option<&str>, problem| IndexIssue {
+        account_id: account_id.map(str::to_string),
+        field: field.map(str::to_string),
+        problem,
+    };
+    let wrong_type = |expected: &str| IndexProblem::WrongType {
+        expected: expected.to_string(),
+    };
+
+    let Some(root) = value.as_object() else {
+        return vec![issue(None, None, IndexProblem::NotAnObject)];
+    };
+    let mut issues = Vec::new();
+
+    let accounts = match root.get("accounts") {
+        Some(Value::Object(accounts)) => accounts,
+        Some(_) => return vec![issue(None, Some("accounts"), wrong_type("object"))],
+        None => root,
+    };
+    if let Some(current) = root.get("current_account_id") {
+        if !current.is_string() {
+            issues.push(issue(None, Some("current_account_id"), wrong_type("string")));
+        }
+    }
+
+    for (id, entry) in accounts {
+        if V1_INDEX_META_KEYS.contains(&id.as_str()) {
+            continue;
+        }
+        let Some(entry) = entry.as_object() else {
+            issues.push(issue(Some(id), None, IndexProblem::NotAnObject));
+            continue;
+        };
+
+        match entry.get("email") {
+            None => issues.push(issue(Some(id), Some("email"), IndexProblem::MissingField)),
+            Some(Value::String(email)) if email.trim().is_empty() => {
+                issues.push(issue(Some(id), Some("email"), IndexProblem::Empty))
+            }
+            Some(Value::String(_)) => {}
+            Some(_) => issues.push(issue(Some(id), Some("email"), wrong_type("string"))),
+        }
+
+        // backup_file 优先，data_file 为旧版字段名；两者都缺失时按 backup_file 报告
+        let path_field = ["backup_file", "data_file"]
+            .into_iter()
+            .find_map(|field| entry.get(field).map(|value| (field, value)));
+        match path_field {
+            None => issues.push(issue(Some(id), Some("backup_file"), IndexProblem::MissingField)),
+            Some((field, Value::String(path))) if path.trim().is_empty() => {
+                issues.push(issue(Some(id), Some(field), IndexProblem::Empty))
+            }
+            Some((_, Value::String(_))) => {}
+            Some((field, _)) => issues.push(issue(Some(id), Some(field), wrong_type("string"))),
+        }
+    }
+
+    issues
+}
+
+/// Validate the V1 index files in the V1 data directory (dry run, nothing is imported)
+pub fn validate_v1_data() -> Result<Vec<V1IndexValidation>, String> {
+    validate_v1_index_files(&v1_data_dir()?)
+}
+
+fn validate_v1_index_files(v1_dir: &Path) -> Result<Vec<V1IndexValidation>, String> {
+    let mut reports = Vec::new();
+    for index_filename in V1_INDEX_FILES {
+        let path = v1_dir.join(index_filename);
+        if !path.exists() {
+            continue;
+        }
+        let content = read_v1_file(&path)?;
+        let index = parse_v1_json(&content)
+            .map_err(|e| format!("Failed to parse {:?}: {}", path, e))?;
+        reports.push(V1IndexValidation {
+            index_file: index_filename.to_string(),
+            issues: validate_v1_index(&index),
+        });
+    }
+
+    if reports.is_empty() {
+        return Err("V1 account data file not found".to_string());
+    }
+    Ok(reports)
+}
+
+/// Split V1 index entries by the email filter.
+/// Entries without a known email are kept and re-checked once the real email is resolved.
+fn filter_v1_index_entries<'a>(
+    accounts_map: &'a serde_json::Map<String, Value>,
+    filter: Option<&EmailFilter>,
+) -> (Vec<(&'a String, &'a Value)>, Vec<SkippedAccount>) {
+    let mut kept = Vec::new();
+    let mut skipped = Vec::new();
+
+    for (id, acc_info) in accounts_map {
+        let email = acc_info.get("email").and_then(|v| v.as_str());
+        match (filter, email) {
+            (Some(filter), Some(email)) if acc_info.is_object() && !filter.matches(email) => {
+                skipped.push(SkippedAccount {
+                    email: email.to_string(),
+                    reason: ImportSkipReason::FilteredOut,
+                });
+            }
+            _ => kept.push((id, acc_info)),
+        }
+    }
+
+    (kept, skipped)
+}
+
+/// A resolved V1 account waiting to be saved
+struct PendingImport {
+    email: String,
+    /// Display name from get_user_info (if it succeeded)
+    name: Option<String>,
+    /// Stable Google account id from get_user_info (if it succeeded)
+    subject: Option<String>,
+    token_data: TokenData,
+    /// Refresh token was successfully exchanged for an access token
+    refreshed: bool,
+}
+
+/// Exchange a V1 refresh token and resolve the account's email / display name.
+///
+/// Falls back to the index email (and no name) when user info is unavailable, and to a
+/// placeholder access token when the refresh token can no longer be exchanged.
+async fn resolve_v1_account_with<R, RFut, U, UFut>(
+    refresh_token: String,
+    email_placeholder: &str,
+    refresh: R,
+    user_info: U,
+) -> PendingImport
+where
+    R: FnOnce(String) -> RFut,
+    RFut: std::future::Future<Output = Result<crate::modules::oauth::TokenResponse, String>>,
+    U: FnOnce(String) -> UFut,
+    UFut: std::future::Future<Output = Result<crate::modules::oauth::UserInfo, String>>,
+{
+    let (token_resp, user_info) = match refresh(refresh_token.clone()).await {
+        Ok(token_resp) => {
+            let user_info = user_info(token_resp.access_token.clone()).await.ok();
+            (Some(token_resp), user_info)
+        }
+        Err(e) => {
+            crate::modules::logger::log_warn(&format!(
+                "Token refresh failed (likely expired): {}",
+                e
+            ));
+            (None, None)
+        }
+    };
+
+    // userinfo 失败但访问令牌是带 email 声明的 JWT 时，使用该 email (标记为未验证)
+    let jwt_email = match (&user_info, &token_resp) {
+        (None, Some(resp)) => crate::utils::jwt::unverified_email(&resp.access_token),
+        _ => None,
+    };
+    let email_unverified = jwt_email.is_some();
+    let (email, name, subject) = match (user_info, jwt_email) {
+        (Some(info), _) => (info.email, info.name, info.id),
+        (None, Some(email)) => {
+            crate::modules::logger::log_warn(&format!(
+                "User info unavailable, using unverified email from token claims: {}",
+                email
+            ));
+            (email, None, None)
+        }
+        (None, None) => (email_placeholder.to_string(), None, None),
+    };
+    let refreshed = token_resp.is_some();
+    let (access_token, expires_in, oauth_client_key, scopes) = match token_resp {
+        Some(resp) => {
+            let scopes = resp.scopes();
+            (resp.access_token, resp.expires_in, resp.oauth_client_key, scopes)
+        }
+        None => (IMPORTED_ACCESS_TOKEN_PLACEHOLDER.to_string(), 0, None, None),
+    };
+
+    let token_data = TokenData::new(
+        access_token,
+        refresh_token,
+        expires_in,
+        Some(email.clone()),
+        None, // project_id will be fetched on demand
+        None, // session_id
+        true, // V1 tokens are Antigravity Google OAuth tokens
+    )
+    .with_oauth_client_key(oauth_client_key)
+    .with_scopes(scopes)
+    .with_email_unverified(email_unverified);
+
+    PendingImport {
+        email,
+        name,
+        subject,
+        token_data,
+        refreshed,
+    }
+}
+
+/// Keep at most `max` items, preferring valid ones (stable within each group).
+/// Returns `(kept, over_cap)`.
+fn split_at_cap<T>(
+    items: Vec<T>,
+    max: Option<usize>,
+    is_valid: impl Fn(&T) -> bool,
+) -> (Vec<T>, Vec<T>) {
+    let Some(max) = max else {
+        return (items, Vec::new());
+    };
+    let (mut kept, invalid): (Vec<T>, Vec<T>) = items.into_iter().partition(|item| is_valid(item));
+    kept.extend(invalid);
+    let over_cap = kept.split_off(max.min(kept.len()));
+    (kept, over_cap)
 }
 
 /// Scan and import V1 data
-pub async fn import_from_v1() -> Result<Vec<Account>, String> {
+pub async fn import_from_v1(
+    filter: Option<EmailFilter>,
+    max_accounts: Option<usize>,
+) -> Result<Vec<Account>, String> {
+    import_from_v1_with_report(filter, max_accounts)
+        .await
+        .map(|report| report.imported)
+}
+
+/// Scan and import V1 data, importing only accounts matching `filter` (if any).
+/// With `max_accounts`, at most that many accounts are added, preferring ones whose
+/// refresh token is still valid; the rest are reported as `CapReached`.
+#[tracing::instrument(
+    name = "import_from_v1",
+    skip_all,
+    fields(
+        filtered = filter.is_some(),
+        max_accounts = ?max_accounts,
+        imported = tracing::field::Empty,
+        skipped = tracing::field::Empty,
+        elapsed_ms = tracing::field::Empty,
+    )
+)]
+pub async fn import_from_v1_with_report(
+    filter: Option<EmailFilter>,
+    max_accounts: Option<usize>,
+) -> Result<V1ImportReport, String> {
+    import_v1_scoped(V1ImportScope {
+        filter,
+        max_accounts,
+        only_account_id: None,
+    })
+    .await
+}
+
+/// Import only the account recorded as `current_account_id` in the V1 index
+pub async fn import_current_v1_account() -> Result<Account, String> {
+    let v1_dir = v1_data_dir()?;
+    let current_id = read_v1_current_account_id(&v1_dir)?;
+    let report = import_v1_scoped(V1ImportScope {
+        only_account_id: Some(current_id.clone()),
+        ..Default::default()
+    })
+    .await?;
+    report
+        .imported
+        .into_iter()
+        .next()
+        .ok_or_else(|| format!("Current V1 account {} could not be imported", current_id))
+}
+
+/// Post-import verification result for one account
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AccountVerification {
+    pub account_id: String,
+    pub email: String,
+    pub verified: bool,
+    /// Why the account could not be verified (None when verified)
+    pub reason: Option<String>,
+}
+
+/// Result of `verify_import`, in the same order as the accounts passed in
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct VerifyReport {
+    pub total: usize,
+    pub verified: usize,
+    pub accounts: Vec<AccountVerification>,
+    /// Verification was cancelled before every account was checked
+    pub cancelled: bool,
+}
+
+impl VerifyReport {
+    /// e.g. "2 of 3 accounts verified working"
+    pub fn summary(&self) -> String {
+        format!("{} of {} accounts verified working", self.verified, self.total)
+    }
+}
+
+/// Check that freshly imported accounts actually work: refresh the access token and
+/// fetch user info for each, with at most `concurrency` accounts in flight.
+///
+/// Both requests go through the global upstream rate limiter. Once `cancel` fires,
+/// accounts not yet checked are reported as unverified.
+pub async fn verify_import(
+    accounts: &[Account],
+    concurrency: usize,
+    cancel: tokio_util::sync::CancellationToken,
+) -> VerifyReport {
+    verify_import_with(
+        accounts,
+        concurrency,
+        cancel,
+        |refresh_token, account_id| async move {
+            crate::modules::oauth::refresh_access_token(&refresh_token, Some(&account_id)).await
+        },
+        |access_token, account_id| async move {
+            crate::modules::oauth::get_user_info(&access_token, Some(&account_id)).await
+        },
+    )
+    .await
+}
+
+async fn verify_import_with<R, RFut, U, UFut>(
+    accounts: &[Account],
+    concurrency: usize,
+    cancel: tokio_util::sync::CancellationToken,
+    refresh: R,
+    user_info: U,
+) -> VerifyReport
+where
+    R: Fn(String, String) -> RFut,
+    RFut: std::future::Future<Output = Result<crate::modules::oauth::TokenResponse, String>>,
+    U: Fn(String, String) -> UFut,
+    UFut: std::future::Future<Output = Result<crate::modules::oauth::UserInfo, String>>,
+{
+    use futures::stream::{self, StreamExt};
+
+    let (refresh, user_info) = (&refresh, &user_info);
+    let mut results = std::pin::pin!(stream::iter(accounts.iter().enumerate())
+        .map(|(idx, account)| async move {
+            let result = async {
+                let token = refresh(account.token.refresh_token.clone(), account.id.clone())
+                    .await
+                    .map_err(|e| format!("Token refresh failed: {}", e))?;
+                user_info(token.access_token, account.id.clone())
+                    .await
+                    .map_err(|e| format!("User info request failed: {}", e))?;
+                Ok::<(), String>(())
+            }
+            .await;
+            (idx, result)
+        })
+        .buffer_unordered(concurrency.max(1))
+        .take_until(cancel.cancelled()));
+
+    let mut outcomes: Vec<Option<Result<(), String>>> = vec![None; accounts.len()];
+    while let Some((idx, result)) = results.next().await {
+        outcomes[idx] = Some(result);
+    }
+
+    let mut report = VerifyReport {
+        total: accounts.len(),
+        ..Default::default()
+    };
+    for (account, outcome) in accounts.iter().zip(outcomes) {
+        let reason = match outcome {
+            Some(Ok(())) => None,
+            Some(Err(e)) => Some(e),
+            None => {
+                report.cancelled = true;
+                Some("Verification cancelled".to_string())
+            }
+        };
+        if let Some(reason) = &reason {
+            crate::modules::logger::log_warn(&format!(
+                "Imported account {} not verified: {}",
+                account.email, reason
+            ));
+        } else {
+            report.verified += 1;
+        }
+        report.accounts.push(AccountVerification {
+            account_id: account.id.clone(),
+            email: account.email.clone(),
+            verified: reason.is_none(),
+            reason,
+        });
+    }
+
+    crate::modules::logger::log_info(&format!(
+        "Import verification: {}{}",
+        report.summary(),
+        if report.cancelled { " (cancelled)" } else { "" }
+    ));
+    report
+}
+
+/// Environment variable overriding the V1 data directory (like `ABV_DATA_DIR` for V2)
+const V1_DATA_DIR_ENV: &str = "ABV_V1_DATA_DIR";
+
+/// Home directory variables consulted when the system home directory is unavailable
+/// (some sandboxed / service contexts)
+const HOME_ENV_VARS: &[&str] = &["HOME", "USERPROFILE"];
+
+/// V1 data directory (confirmed cross-platform consistency from utils.py)
+fn v1_data_dir() -> Result<PathBuf, String> {
+    v1_data_dir_with(|key| std::env::var(key).ok(), dirs::home_dir)
+}
+
+/// Resolve the V1 data directory: explicit override, then the system home directory,
+/// then `HOME` / `USERPROFILE`; the error lists everything that was tried
+fn v1_data_dir_with<E, H>(env: E, home_dir: H) -> Result<PathBuf, String>
+where
+    E: Fn(&str) -> Option<String>,
+    H: FnOnce() -> Option<PathBuf>,
+{
+    let non_empty = |key: &str| env(key).filter(|value| !value.trim().is_empty());
+
+    if let Some(dir) = non_empty(V1_DATA_DIR_ENV) {
+        return Ok(PathBuf::from(dir));
+    }
+    if let Some(home) = home_dir() {
+        return Ok(home.join(".antigravity-agent"));
+    }
+    for key in HOME_ENV_VARS {
+        if let Some(home) = non_empty(key) {
+            crate::modules::logger::log_warn(&format!(
+                "System home directory unavailable, using {}={}",
+                key, home
+            ));
+            return Ok(PathBuf::from(home).join(".antigravity-agent"));
+        }
+    }
+
+    let mut tried = vec![
+        format!("{} (not set)", V1_DATA_DIR_ENV),
+        "system home directory (unavailable)".to_string(),
+    ];
+    tried.extend(HOME_ENV_VARS.iter().map(|key| format!("{} (not set)", key)));
+    Err(format!(
+        "Failed to locate V1 data directory, tried: {}",
+        tried.join(", ")
+    ))
+}
+
+/// Read `current_account_id` from the first V1 index that can be parsed
+fn read_v1_current_account_id(v1_dir: &Path) -> Result<String, String> {
+    let mut found_index = false;
+    for index_filename in V1_INDEX_FILES {
+        let path = v1_dir.join(index_filename);
+        if !path.exists() {
+            continue;
+        }
+        found_index = true;
+        let Some(index) = read_v1_file(&path)
+            .ok()
+            .and_then(|content| parse_v1_json(&content).ok())
+        else {
+            continue;
+        };
+        if let Some(id) = index
+            .get("current_account_id")
+            .and_then(|v| v.as_str())
+            .filter(|id| !id.trim().is_empty())
+        {
+            return Ok(id.to_string());
+        }
+    }
+
+    if found_index {
+        Err("V1 index does not record current_account_id".to_string())
+    } else {
+        Err("V1 account data file not found".to_string())
+    }
+}
+
+async fn import_v1_scoped(scope: V1ImportScope) -> Result<V1ImportReport, String> {
     use crate::modules::oauth;
 
-    let home = dirs::home_dir().ok_or("Failed to get home directory")?;
-    
-    // V1 data directory (confirmed cross-platform consistency from utils.py)
-    let v1_dir = home.join(".antigravity-agent");
-    
+    import_v1_from_dir_with(
+        &v1_data_dir()?,
+        scope,
+        oauth::check_connectivity,
+        |rt| async move { oauth::refresh_access_token(&rt, None).await },
+        |at| async move { oauth::get_user_info(&at, None).await },
+        account::upsert_account_from_source,
+    )
+    .await
+}
+
+/// Which V1 index entries to import
+#[derive(Debug, Clone, Default)]
+struct V1ImportScope {
+    filter: Option<EmailFilter>,
+    max_accounts: Option<usize>,
+    /// Only import this index entry (other entries are ignored, not reported as skipped)
+    only_account_id: Option<String>,
+}
+
+/// `check` 在处理任何账号前执行一次；`refresh` / `user_info` 对每个账号调用；`save` 写入解析完成的账号
+async fn import_v1_from_dir_with<C, CFut, R, RFut, U, UFut, S>(
+    v1_dir: &Path,
+    scope: V1ImportScope,
+    check: C,
+    refresh: R,
+    user_info: U,
+    mut save: S,
+) -> Result<V1ImportReport, String>
+where
+    C: FnOnce() -> CFut,
+    CFut: std::future::Future<Output = crate::error::AppResult<()>>,
+    R: Fn(String) -> RFut,
+    RFut: std::future::Future<Output = Result<crate::modules::oauth::TokenResponse, String>>,
+    U: Fn(String) -> UFut,
+    UFut: std::future::Future<Output = Result<crate::modules::oauth::UserInfo, String>>,
+    S: FnMut(String, Option<String>, TokenData, Option<String>, AccountSource) -> Result<Account, String>,
+{
+    use tracing::Instrument;
+
+    let started = std::time::Instant::now();
+    let V1ImportScope {
+        filter,
+        max_accounts,
+        only_account_id,
+    } = scope;
+
+    if let Some(ref f) = filter {
+        f.validate()?;
+    }
+
     let mut imported_accounts = Vec::new();
+    let mut skipped_accounts = Vec::new();
+    let mut pending: Vec<PendingImport> = Vec::new();
+    let mut refreshed_count = 0usize;
     
     // Try multiple possible filenames
-    let index_files = vec![
-        "antigravity_accounts.json", // Directly use string literal
-        "accounts.json",
-    ];
+    if !V1_INDEX_FILES.iter().any(|name| v1_dir.join(name).exists()) {
+        return Err("V1 account data file not found".to_string());
+    }
+
+    // 批量导入会对每个账号发起 OAuth 请求：网络不可达时提前失败，避免逐个超时
+    if let Err(e) = check().await {
+        crate::modules::logger::log_error(&format!("V1 import aborted: {}", e));
+        return Err(format!(
+            "Cannot reach Google OAuth endpoint, check network/proxy settings before importing: {}",
+            e
+        ));
+    }
     
     let mut found_index = false;
 
-    for index_filename in index_files {
+    for index_filename in V1_INDEX_FILES {
         let v1_accounts_path = v1_dir.join(index_filename);
         
         if !v1_accounts_path.exists() {
@@ -42,7 +842,7 @@ pub async fn import_from_v1() -> Result<Vec<Account>, String> {
         found_index = true;
         crate::modules::logger::log_info(&format!("V1 data discovered: {:?}", v1_accounts_path));
         
-        let content = match fs::read_to_string(&v1_accounts_path) {
+        let content = match read_v1_file(&v1_accounts_path) {
             Ok(c) => c,
             Err(e) => {
                 crate::modules::logger::log_warn(&format!("Failed to read index: {}", e));
@@ -50,14 +850,18 @@ pub async fn import_from_v1() -> Result<Vec<Account>, String> {
             }
         };
         
-        let v1_index: Value = match serde_json::from_str(&content) {
+        let v1_index = match parse_v1_json(&content) {
             Ok(v) => v,
             Err(e) => {
                 crate::modules::logger::log_warn(&format!("Failed to parse index JSON: {}", e));
                 continue;
             }
         };
-        
+
+        for issue in validate_v1_index(&v1_index) {
+            crate::modules::logger::log_warn(&format!("V1 index {}: {}", index_filename, issue));
+        }
+
         // Compatible with two formats: direct map, or contains "accounts" field
         let accounts_map = if let Some(map) = v1_index.as_object() {
             if let Some(accounts) = map.get("accounts").and_then(|v| v.as_object()) {
@@ -69,7 +873,19 @@ pub async fn import_from_v1() -> Result<Vec<Account>, String> {
             continue;
         };
         
-        for (id, acc_info) in accounts_map {
+        let (mut entries, filtered_out) = filter_v1_index_entries(accounts_map, filter.as_ref());
+        if let Some(ref only_id) = only_account_id {
+            entries.retain(|(id, _)| *id == only_id);
+        }
+        if !filtered_out.is_empty() {
+            crate::modules::logger::log_info(&format!(
+                "Skipping {} V1 account(s) not matching email filter",
+                filtered_out.len()
+            ));
+        }
+        skipped_accounts.extend(filtered_out);
+
+        for (id, acc_info) in entries {
             let email_placeholder = acc_info.get("email").and_then(|v| v.as_str()).unwrap_or("Unknown").to_string();
             
             // Skip non-account keys (e.g. "current_account_id")
@@ -81,42 +897,19 @@ pub async fn import_from_v1() -> Result<Vec<Account>, String> {
             let data_file_str = acc_info.get("data_file").and_then(|v| v.as_str());
             
             // Prefer backup_file, then data_file
-            let target_file = backup_file_str.or(data_file_str);
-            
-            if target_file.is_none() {
+            let Some(target_file) = backup_file_str.or(data_file_str) else {
                 crate::modules::logger::log_warn(&format!("Account {} ({}) missing data file path", id, email_placeholder));
                 continue;
-            }
-            
-            let mut backup_path = PathBuf::from(target_file.unwrap());
-            
-            // If relative path, try joining with v1_dir
-            if !backup_path.exists() {
-                 backup_path = v1_dir.join(backup_path.file_name().unwrap_or_default());
-            }
-            
-            // Try joining data/ or backups/ subdirectories again
-            if !backup_path.exists() {
-                 let file_name = backup_path.file_name().unwrap_or_default();
-                 let try_backups = v1_dir.join("backups").join(file_name);
-                 if try_backups.exists() {
-                     backup_path = try_backups;
-                 } else {
-                     let try_accounts = v1_dir.join("accounts").join(file_name);
-                     if try_accounts.exists() {
-                         backup_path = try_accounts;
-                     }
-                 }
-            }
+            };
             
-            if !backup_path.exists() {
-                crate::modules::logger::log_warn(&format!("Account {} ({}) backup file not found: {:?}", id, email_placeholder, backup_path));
+            let Some(backup_path) = resolve_v1_backup_path(v1_dir, target_file) else {
+                crate::modules::logger::log_warn(&format!("Account {} ({}) backup file not found: {:?}", id, email_placeholder, target_file));
                 continue;
-            }
+            };
             
             // Read backup file
-            if let Ok(backup_content) = fs::read_to_string(&backup_path) {
-                if let Ok(backup_json) = serde_json::from_str::<Value>(&backup_content) {
+            if let Ok(backup_content) = read_v1_file(&backup_path) {
+                if let Ok(backup_json) = parse_v1_json(&backup_content) {
                     
                     // Compatible with two formats:
                     // 1. V1 backup: jetskiStateSync.agentManagerInitState -> Protobuf
@@ -125,22 +918,22 @@ pub async fn import_from_v1() -> Result<Vec<Account>, String> {
                     let mut refresh_token_opt = None;
                     
                     // Try format 2
-                    if let Some(token_data) = backup_json.get("token") {
-                        if let Some(rt) = token_data.get("refresh_token").and_then(|v| v.as_str()) {
-                            refresh_token_opt = Some(rt.to_string());
-                        }
+                    if let Some((rt, path)) = find_refresh_token_in_json(&backup_json) {
+                        crate::modules::logger::log_info(&format!(
+                            "Account {}: refresh_token found at '{}'",
+                            email_placeholder, path
+                        ));
+                        refresh_token_opt = Some(rt);
                     }
                     
                     // Try format 1
                     if refresh_token_opt.is_none() {
-                         if let Some(state_b64) = backup_json.get("jetskiStateSync.agentManagerInitState").and_then(|v| v.as_str()) {
+                         if let Some(state_b64) = backup_json.get(LEGACY_AGENT_STATE_KEY).and_then(|v| v.as_str()) {
                             // Parse Protobuf
-                            if let Ok(blob) = general_purpose::STANDARD.decode(state_b64) {
-                                if let Ok(Some(oauth_data)) = protobuf::find_field(&blob, 6) {
-                                    if let Ok(Some(refresh_bytes)) = protobuf::find_field(&oauth_data, 3) {
-                                        if let Ok(rt) = String::from_utf8(refresh_bytes) {
-                                            refresh_token_opt = Some(rt);
-                                        }
+                            if let Ok(blob) = decode_base64_blob(state_b64) {
+                                if let Some(paths) = field_paths_for(DbFormat::V1BackupJson) {
+                                    if let Ok(info) = extract_oauth_info_with_paths(&blob, &paths) {
+                                        refresh_token_opt = Some(info.refresh_token);
                                     }
                                 }
                             }
@@ -148,67 +941,45 @@ pub async fn import_from_v1() -> Result<Vec<Account>, String> {
                     }
                     
                     if let Some(refresh_token) = refresh_token_opt {
+                        // Enough valid accounts already: skip without hitting the network
+                        if max_accounts.is_some_and(|max| refreshed_count >= max) {
+                            skipped_accounts.push(SkippedAccount {
+                                email: email_placeholder,
+                                reason: ImportSkipReason::CapReached,
+                            });
+                            continue;
+                        }
                         crate::modules::logger::log_info(&format!(
                             "Importing account: {}",
                             email_placeholder
                         ));
-                        let (email, access_token, expires_in, oauth_client_key) =
-                            match oauth::refresh_access_token(&refresh_token, None).await {
-                             Ok(token_resp) => {
-                                    let oauth_client_key = token_resp.oauth_client_key.clone();
-                                    match oauth::get_user_info(&token_resp.access_token, None).await
-                                    {
-                                        Ok(user_info) => (
-                                            user_info.email,
-                                            token_resp.access_token,
-                                            token_resp.expires_in,
-                                            oauth_client_key,
-                                        ),
-                                        Err(_) => (
-                                            email_placeholder.clone(),
-                                            token_resp.access_token,
-                                            token_resp.expires_in,
-                                            oauth_client_key,
-                                        ),
-                                    }
-                                 }
-                            Err(e) => {
-                                    crate::modules::logger::log_warn(&format!(
-                                        "Token refresh failed (likely expired): {}",
-                                        e
-                                    ));
-                                    (
-                                        email_placeholder.clone(),
-                                        "imported_access_token".to_string(),
-                                        0,
-                                        None,
-                                    )
-                                }
-                        };
-                        let token_data = TokenData::new(
-                            access_token, 
+                        let account_span = tracing::info_span!(
+                            "v1_import_account",
+                            email = %email_placeholder,
+                            phase = "refresh"
+                        );
+                        let resolved = resolve_v1_account_with(
                             refresh_token,
-                            expires_in,
-                            Some(email.clone()),
-                            None, // project_id will be fetched on demand
-                            None, // session_id
-                            true, // V1 tokens are Antigravity Google OAuth tokens
+                            &email_placeholder,
+                            &refresh,
+                            &user_info,
                         )
-                        .with_oauth_client_key(oauth_client_key);
-                        // Name already fetched in get_user_info at line 153, but outside match scope, use None to be safe
-                        match account::upsert_account(email.clone(), None, token_data) {
-                            Ok(acc) => {
-                                crate::modules::logger::log_info(&format!(
-                                    "Import successful: {}",
-                                    email
-                                ));
-                                imported_accounts.push(acc);
+                        .instrument(account_span)
+                        .await;
+                        // Email may only be known after get_user_info
+                        if let Some(ref f) = filter {
+                            if !f.matches(&resolved.email) {
+                                skipped_accounts.push(SkippedAccount {
+                                    email: resolved.email,
+                                    reason: ImportSkipReason::FilteredOut,
+                                });
+                                continue;
+                            }
                         }
-                            Err(e) => crate::modules::logger::log_error(&format!(
-                                "Import save failed {}: {}",
-                                email, e
-                            )),
+                        if resolved.refreshed {
+                            refreshed_count += 1;
                         }
+                        pending.push(resolved);
                     } else {
                         crate::modules::logger::log_warn(&format!(
                             "Account {} data file missing Refresh Token",
@@ -223,54 +994,384 @@ pub async fn import_from_v1() -> Result<Vec<Account>, String> {
     if !found_index {
         return Err("V1 account data file not found".to_string());
     }
+
+    // Deterministic save order (and cap selection) independent of index key order
+    pending.sort_by(|a, b| a.email.cmp(&b.email));
+    let (to_import, over_cap) = split_at_cap(pending, max_accounts, |p| p.refreshed);
+    if !over_cap.is_empty() {
+        crate::modules::logger::log_info(&format!(
+            "Import cap {:?} reached, skipping {} account(s)",
+            max_accounts,
+            over_cap.len()
+        ));
+    }
+    skipped_accounts.extend(over_cap.into_iter().map(|p| SkippedAccount {
+        email: p.email,
+        reason: ImportSkipReason::CapReached,
+    }));
+
+    for PendingImport { email, name, subject, token_data, .. } in to_import {
+        let _span = tracing::info_span!("v1_import_account", email = %email, phase = "save").entered();
+        match save(email.clone(), name, token_data, subject, AccountSource::V1Migration) {
+            Ok(acc) => {
+                crate::modules::logger::log_info(&format!("Import successful: {}", email));
+                imported_accounts.push(acc);
+            }
+            Err(e) => crate::modules::logger::log_error(&format!(
+                "Import save failed {}: {}",
+                email, e
+            )),
+        }
+    }
     
-    Ok(imported_accounts)
+    let span = tracing::Span::current();
+    span.record("imported", imported_accounts.len());
+    span.record("skipped", skipped_accounts.len());
+    span.record("elapsed_ms", started.elapsed().as_millis() as u64);
+    tracing::info!(
+        imported = imported_accounts.len(),
+        skipped = skipped_accounts.len(),
+        "V1 import finished"
+    );
+
+    Ok(V1ImportReport {
+        imported: imported_accounts,
+        skipped: skipped_accounts,
+    }
+    .sorted())
 }
 
 /// Import account from custom database path
 pub async fn import_from_custom_db_path(path_str: String) -> Result<Account, String> {
-    use crate::modules::oauth;
-
     let path = PathBuf::from(path_str);
     if !path.exists() {
         return Err(format!("File does not exist: {:?}", path));
     }
 
     let oauth_state = extract_oauth_state_from_file(&path)?;
-    let refresh_token = oauth_state.refresh_token.clone();
-        
-    // 3. Use Refresh Token to get latest Access Token and user info
-    crate::modules::logger::log_info("Getting user info using Refresh Token...");
-    let token_resp = oauth::refresh_access_token(&refresh_token, None).await?;
-    let user_info = oauth::get_user_info(&token_resp.access_token, None).await?;
-    
-    let email = user_info.email;
-    
-    crate::modules::logger::log_info(&format!("Successfully retrieved account info: {}", email));
-    
-    let token_data = TokenData::new(
-        token_resp.access_token,
-        refresh_token,
-        token_resp.expires_in,
-        Some(email.clone()),
-        oauth_state.project_id,
-        None, // session_id will be generated in token_manager
-        oauth_state.is_gcp_tos,
-    )
-    .with_oauth_client_key(token_resp.oauth_client_key);
-    // 4. Add or update account
-    account::upsert_account(email.clone(), user_info.name, token_data)
+    import_oauth_state(oauth_state).await
 }
 
-/// Import current logged-in account from default IDE database
+/// Re-extract credentials from the live IDE database and save them only when they
+/// belong to one of `account_ids` (used to repair accounts with an empty refresh token)
+pub async fn repair_from_live_db(account_ids: &[String]) -> Result<Account, String> {
+    use crate::modules::oauth;
+
+    let db_path = db::get_db_path()?;
+    let oauth_state = extract_oauth_state_from_file(&db_path)?;
+    import_oauth_state_with(
+        oauth_state,
+        |refresh_token| async move { oauth::refresh_access_token(&refresh_token, None).await },
+        |access_token| async move { oauth::get_user_info(&access_token, None).await },
+        |email, name, token, subject, source| {
+            match account::find_account_id_by_identity(&email, subject.as_deref()) {
+                Some(id) if account_ids.contains(&id) => {
+                    account::upsert_account_from_source(email, name, token, subject, source)
+                }
+                _ => Err(format!(
+                    "Live database account {} does not match any account to repair",
+                    email
+                )),
+            }
+        },
+        || extract_oauth_info_from_file(&db_path),
+    )
+    .await
+}
+
+/// Import an account directly from a pasted refresh token string
+pub async fn import_single_refresh_token(refresh_token: String) -> Result<Account, String> {
+    import_oauth_state(pasted_refresh_token_state(&refresh_token)?).await
+}
+
+fn pasted_refresh_token_state(refresh_token: &str) -> Result<ImportedOAuthState, String> {
+    let refresh_token = refresh_token.trim().to_string();
+    if refresh_token.is_empty() {
+        return Err("Refresh token is empty".to_string());
+    }
+
+    Ok(ImportedOAuthState {
+        oauth: OAuthInfo::from_refresh_token(refresh_token),
+        is_gcp_tos: true,
+        project_id: None, // project_id will be fetched on demand
+        from_legacy_format: false,
+        source: AccountSource::ManualToken,
+    })
+}
+
+/// Verify the refresh token via OAuth, fetch user info and upsert the account
+async fn import_oauth_state(oauth_state: ImportedOAuthState) -> Result<Account, String> {
+    use crate::modules::oauth;
+
+    import_oauth_state_with(
+        oauth_state,
+        |refresh_token| async move { oauth::refresh_access_token(&refresh_token, None).await },
+        |access_token| async move { oauth::get_user_info(&access_token, None).await },
+        account::upsert_account_from_source,
+        || db::get_db_path().and_then(|path| extract_oauth_info_from_file(&path)),
+    )
+    .await
+}
+
+/// `read_live_db` re-reads the OAuth info from the live IDE database (new format first);
+/// it is only used to retry a failed refresh of a token taken from the legacy format
+async fn import_oauth_state_with<R, RFut, U, UFut, S, L>(
+    oauth_state: ImportedOAuthState,
+    refresh: R,
+    user_info: U,
+    save: S,
+    read_live_db: L,
+) -> Result<Account, String>
+where
+    R: Fn(String) -> RFut,
+    RFut: std::future::Future<Output = Result<crate::modules::oauth::TokenResponse, String>>,
+    U: FnOnce(String) -> UFut,
+    UFut: std::future::Future<Output = Result<crate::modules::oauth::UserInfo, String>>,
+    S: FnOnce(String, Option<String>, TokenData, Option<String>, AccountSource) -> Result<Account, String>,
+    L: FnOnce() -> Result<OAuthInfo, String>,
+{
+    let mut refresh_token = oauth_state.oauth.refresh_token.clone();
+
+    // 3. Reuse the stored Access Token while it is valid, otherwise refresh
+    let (access_token, expires_in, scopes, oauth_client_key) = match oauth_state
+        .oauth
+        .reusable_access_token(crate::utils::clock::now())
+    {
+        Some((access_token, expires_in)) => {
+            crate::modules::logger::log_info("Stored Access Token is still valid, skipping refresh");
+            (access_token, expires_in, None, None)
+        }
+        None => {
+            crate::modules::logger::log_info("Getting user info using Refresh Token...");
+            let token_resp = match refresh(refresh_token.clone()).await {
+                Ok(resp) => resp,
+                Err(e) if oauth_state.from_legacy_format => {
+                    // 备份之后 IDE 可能已迁移到新格式并轮换了令牌：从当前数据库重新提取后重试一次
+                    let live = read_live_db()
+                        .map_err(|live_err| format!("{} (re-reading live database failed: {})", e, live_err))?;
+                    if live.refresh_token == refresh_token {
+                        return Err(e);
+                    }
+                    crate::modules::logger::log_warn(&format!(
+                        "Legacy Refresh Token rejected ({}), retrying with token from live database",
+                        e
+                    ));
+                    refresh_token = live.refresh_token;
+                    refresh(refresh_token.clone()).await?
+                }
+                Err(e) => return Err(e),
+            };
+            let scopes = token_resp.scopes();
+            (token_resp.access_token, token_resp.expires_in, scopes, token_resp.oauth_client_key)
+        }
+    };
+    let (email, name, subject, email_unverified) = match user_info(access_token.clone()).await {
+        Ok(info) => {
+            crate::modules::logger::log_info(&format!(
+                "Successfully retrieved account info: {}",
+                info.email
+            ));
+            (info.email, info.name, info.id, false)
+        }
+        // 访问令牌是带 email 声明的 JWT 时无需再次请求，email 标记为未验证
+        Err(e) => match crate::utils::jwt::unverified_email(&access_token) {
+            Some(email) => {
+                crate::modules::logger::log_warn(&format!(
+                    "Failed to get user info ({}), using unverified email from token claims: {}",
+                    e, email
+                ));
+                (email, None, None, true)
+            }
+            None => return Err(e),
+        },
+    };
+
+    let token_data = TokenData::new(
+        access_token,
+        refresh_token,
+        expires_in,
+        Some(email.clone()),
+        oauth_state.project_id,
+        None, // session_id will be generated in token_manager
+        oauth_state.is_gcp_tos,
+    )
+    .with_scopes(scopes)
+    .with_oauth_client_key(oauth_client_key)
+    .with_email_unverified(email_unverified);
+    // 4. Add or update account
+    save(email, name, token_data, subject, oauth_state.source)
+}
+
+/// Import current logged-in account from default IDE database
 pub async fn import_from_db() -> Result<Account, String> {
     let db_path = db::get_db_path()?;
     import_from_custom_db_path(db_path.to_string_lossy().to_string()).await
 }
 
+/// Path of the auth state endpoint exposed by a running IDE on localhost
+const LOCAL_IDE_AUTH_PATH: &str = "/auth/state";
+
+/// Environment variable holding the IDE local API port, used when no port is given
+const LOCAL_IDE_API_PORT_ENV: &str = "ABV_IDE_API_PORT";
+
+/// Timeout for probing the IDE local API; a closed port fails immediately
+const LOCAL_IDE_API_TIMEOUT_SECS: u64 = 3;
+
+/// Import the logged-in account through the running IDE's local API, falling back to
+/// reading the IDE database when the endpoint is not reachable (IDE not running,
+/// older version, or no port configured)
+pub async fn import_from_local_ide_api(port: Option<u16>) -> Result<Account, String> {
+    let port = port.or_else(|| {
+        std::env::var(LOCAL_IDE_API_PORT_ENV)
+            .ok()
+            .and_then(|value| value.trim().parse().ok())
+    });
+    import_from_local_ide_api_with(port, import_oauth_state, import_from_db).await
+}
+
+async fn import_from_local_ide_api_with<I, IFut, F, FFut>(
+    port: Option<u16>,
+    import: I,
+    fallback: F,
+) -> Result<Account, String>
+where
+    I: FnOnce(ImportedOAuthState) -> IFut,
+    IFut: std::future::Future<Output = Result<Account, String>>,
+    F: FnOnce() -> FFut,
+    FFut: std::future::Future<Output = Result<Account, String>>,
+{
+    let Some(port) = port else {
+        crate::modules::logger::log_info("No IDE local API port configured, importing from database");
+        return fallback().await;
+    };
+
+    // Only an unreachable / unusable endpoint falls back; a rejected token is a real error
+    match fetch_local_ide_oauth_state(port).await {
+        Ok(oauth_state) => {
+            crate::modules::logger::log_info(&format!(
+                "Retrieved auth state from IDE local API on port {}",
+                port
+            ));
+            import(oauth_state).await
+        }
+        Err(e) => {
+            crate::modules::logger::log_warn(&format!(
+                "IDE local API unavailable ({}), falling back to database import",
+                e
+            ));
+            fallback().await
+        }
+    }
+}
+
+/// Query the IDE local API for its auth state
+async fn fetch_local_ide_oauth_state(port: u16) -> Result<ImportedOAuthState, String> {
+    // 本地回环请求：不走上游代理
+    let client = reqwest::Client::builder()
+        .no_proxy()
+        .timeout(std::time::Duration::from_secs(LOCAL_IDE_API_TIMEOUT_SECS))
+        .build()
+        .map_err(|e| format!("Failed to create local API client: {}", e))?;
+    let url = format!("http://127.0.0.1:{}{}", port, LOCAL_IDE_AUTH_PATH);
+    let response = client
+        .get(&url)
+        .send()
+        .await
+        .map_err(|e| format!("Request to {} failed: {}", url, e))?;
+    if !response.status().is_success() {
+        return Err(format!("{} returned {}", url, response.status()));
+    }
+    let json: Value = response
+        .json()
+        .await
+        .map_err(|e| format!("Invalid auth state from {}: {}", url, e))?;
+    parse_local_ide_auth_state(&json)
+}
+
+/// Parse the auth state payload; key layouts follow the V2 JSON exports
+fn parse_local_ide_auth_state(json: &Value) -> Result<ImportedOAuthState, String> {
+    let (refresh_token, _) = find_refresh_token_in_json(json)
+        .ok_or_else(|| "Refresh Token not found in IDE auth state".to_string())?;
+    let token = json.get("token").unwrap_or(json);
+    let str_field = |keys: &[&str]| {
+        keys.iter()
+            .find_map(|key| token.get(*key).and_then(Value::as_str))
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+
+    Ok(ImportedOAuthState {
+        oauth: OAuthInfo {
+            access_token: str_field(&["access_token", "accessToken"]),
+            refresh_token,
+            expiry: ["expiry", "expires_at", "expiresAt"]
+                .iter()
+                .find_map(|key| token.get(*key).and_then(Value::as_i64)),
+        },
+        is_gcp_tos: json.get("is_gcp_tos").and_then(Value::as_bool).unwrap_or(true),
+        project_id: json
+            .get("project_id")
+            .and_then(Value::as_str)
+            .map(|s| s.to_string()),
+        from_legacy_format: false,
+        source: AccountSource::LiveDb,
+    })
+}
+
 /// Get current Refresh Token from database (common logic)
 pub fn extract_refresh_token_from_file(db_path: &PathBuf) -> Result<String, String> {
-    extract_oauth_state_from_file(db_path).map(|state| state.refresh_token)
+    extract_oauth_info_from_file(db_path).map(|info| info.refresh_token)
+}
+
+/// Get the stored OAuth credentials (access token, refresh token, expiry) from database
+pub fn extract_oauth_info_from_file(db_path: &PathBuf) -> Result<OAuthInfo, String> {
+    extract_oauth_state_from_file(db_path).map(|state| state.oauth)
+}
+
+/// Locate the OAuthInfo inside a format's container message and parse it
+fn extract_oauth_info_with_paths(container: &[u8], paths: &OAuthFieldPaths) -> Result<OAuthInfo, String> {
+    let oauth_info = protobuf::find_field_path(container, &paths.oauth_info)?
+        .ok_or_else(|| format!("OAuth data not found (Field path {:?})", paths.oauth_info))?;
+    parse_oauth_info(&oauth_info, paths)
+}
+
+/// Parse an `OAuthTokenInfo` message using the given field paths
+fn parse_oauth_info(blob: &[u8], paths: &OAuthFieldPaths) -> Result<OAuthInfo, String> {
+    if paths.refresh_token.is_empty() {
+        return Err("Refresh Token field path is empty".to_string());
+    }
+    let refresh_bytes = protobuf::find_field_path(blob, &paths.refresh_token)?.ok_or_else(|| {
+        format!(
+            "Refresh Token not found in OAuthInfo (Field path {:?})",
+            paths.refresh_token
+        )
+    })?;
+    let refresh_token = String::from_utf8(refresh_bytes)
+        .map_err(|_| "Refresh Token is not UTF-8 encoded".to_string())?;
+
+    // 缺失或无法解析的 Access Token 不影响导入，只是无法跳过刷新
+    let access_token = if paths.access_token.is_empty() {
+        None
+    } else {
+        protobuf::find_field_path(blob, &paths.access_token)?
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .filter(|token| !token.is_empty())
+    };
+    let expiry = match paths.expiry_seconds.split_last() {
+        Some((&seconds_field, message_path)) => match protobuf::find_field_path(blob, message_path)? {
+            Some(timestamp) => {
+                protobuf::find_varint_field(&timestamp, seconds_field)?.map(|secs| secs as i64)
+            }
+            None => None,
+        },
+        None => None,
+    };
+
+    Ok(OAuthInfo {
+        access_token,
+        refresh_token,
+        expiry,
+    })
 }
 
 fn extract_enterprise_project_id_from_conn(
@@ -306,9 +1407,138 @@ fn extract_enterprise_project_id_from_conn(
     }
 }
 
+fn read_item_value(conn: &rusqlite::Connection, key: &str) -> Option<String> {
+    query_item_value(conn, key).ok().flatten()
+}
+
+/// Like `read_item_value`, but keeps query errors (corrupt pages, not a database, bad value type)
+/// apart from a missing key so the caller can fall back and report both
+fn query_item_value(conn: &rusqlite::Connection, key: &str) -> Result<Option<String>, String> {
+    match conn.query_row("SELECT value FROM ItemTable WHERE key = ?", [key], |row| row.get(0)) {
+        Ok(value) => Ok(Some(value)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(describe_sqlite_error(&e)),
+    }
+}
+
+/// Fail early with a clear message when the database has no `ItemTable`
+/// (e.g. the user picked another application's SQLite file).
+/// If the schema itself cannot be read, the normal item queries report the error instead.
+fn ensure_item_table(conn: &rusqlite::Connection) -> Result<(), String> {
+    let tables: Vec<String> = match conn
+        .prepare("SELECT name FROM sqlite_master WHERE type = 'table' ORDER BY name")
+        .and_then(|mut stmt| {
+            stmt.query_map([], |row| row.get(0))?
+                .collect::<Result<Vec<String>, _>>()
+        }) {
+        Ok(tables) => tables,
+        Err(_) => return Ok(()),
+    };
+
+    if tables.iter().any(|name| name == "ItemTable") {
+        return Ok(());
+    }
+    let found = if tables.is_empty() {
+        "none".to_string()
+    } else {
+        tables.join(", ")
+    };
+    Err(format!(
+        "This does not look like an Antigravity state DB (no ItemTable). Tables found: {}",
+        found
+    ))
+}
+
+fn describe_sqlite_error(e: &rusqlite::Error) -> String {
+    match e.sqlite_error_code() {
+        Some(rusqlite::ErrorCode::DatabaseCorrupt) => format!("database is corrupt (SQLITE_CORRUPT): {}", e),
+        Some(rusqlite::ErrorCode::NotADatabase) => format!("file is not a database (SQLITE_NOTADB): {}", e),
+        _ => e.to_string(),
+    }
+}
+
+/// Raw login state found in the IDE database
+#[derive(Debug, PartialEq, Eq)]
+enum StoredLoginState {
+    /// Base64 value of `antigravityUnifiedStateSync.oauthToken`
+    Unified(String),
+    /// Base64 value of `jetskiStateSync.agentManagerInitState`
+    Legacy(String),
+}
+
+/// Prefer the unified key; when it is missing or unreadable, try the legacy key.
+/// If neither yields a value, the error names what went wrong with each key.
+fn pick_login_state(
+    unified: Result<Option<String>, String>,
+    read_legacy: impl FnOnce() -> Result<Option<String>, String>,
+) -> Result<StoredLoginState, String> {
+    let unified_error = match unified {
+        Ok(Some(value)) => return Ok(StoredLoginState::Unified(value)),
+        Ok(None) => None,
+        Err(e) => {
+            crate::modules::logger::log_warn(&format!(
+                "Failed to read {}, trying legacy format: {}",
+                UNIFIED_OAUTH_TOKEN_KEY, e
+            ));
+            Some(e)
+        }
+    };
+
+    let legacy_error = match read_legacy() {
+        Ok(Some(value)) => return Ok(StoredLoginState::Legacy(value)),
+        Ok(None) => None,
+        Err(e) => Some(e),
+    };
+
+    if unified_error.is_none() && legacy_error.is_none() {
+        return Err("Login state data not found in either format".to_string());
+    }
+    let describe = |error: Option<String>| error.unwrap_or_else(|| "not found".to_string());
+    Err(format!(
+        "Login state data unreadable: {}: {}; {}: {}",
+        UNIFIED_OAUTH_TOKEN_KEY,
+        describe(unified_error),
+        LEGACY_AGENT_STATE_KEY,
+        describe(legacy_error)
+    ))
+}
+
+/// Detect which format a DB / backup file is in without extracting any token
+pub fn detect_format(path: &PathBuf) -> Result<DbFormat, String> {
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read file {:?}: {}", path, e))?;
+
+    if bytes.starts_with(SQLITE_HEADER) {
+        let conn = rusqlite::Connection::open_with_flags(
+            path,
+            rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY,
+        )
+        .map_err(|e| format!("Failed to open database: {}", e))?;
+
+        return Ok(if read_item_value(&conn, UNIFIED_OAUTH_TOKEN_KEY).is_some() {
+            DbFormat::NewUnified
+        } else if read_item_value(&conn, LEGACY_AGENT_STATE_KEY).is_some() {
+            DbFormat::LegacyJetski
+        } else {
+            DbFormat::Unknown
+        });
+    }
+
+    let is_v1_backup = decode_v1_bytes(&bytes)
+        .ok()
+        .and_then(|text| parse_v1_json(&text).ok())
+        .is_some_and(|json| {
+            find_refresh_token_in_json(&json).is_some()
+                || json.get(LEGACY_AGENT_STATE_KEY).is_some_and(|v| v.is_string())
+        });
+
+    Ok(if is_v1_backup {
+        DbFormat::V1BackupJson
+    } else {
+        DbFormat::Unknown
+    })
+}
+
 fn extract_oauth_state_from_file(db_path: &PathBuf) -> Result<ImportedOAuthState, String> {
-    use base64::{engine::general_purpose, Engine as _};
-    
     if !db_path.exists() {
         return Err(format!("Database file not found: {:?}", db_path));
     }
@@ -316,78 +1546,101 @@ fn extract_oauth_state_from_file(db_path: &PathBuf) -> Result<ImportedOAuthState
     // Connect to database
     let conn = rusqlite::Connection::open(db_path)
         .map_err(|e| format!("Failed to open database: {}", e))?;
-        
-    // 1. 尝试新版格式 (>= 1.16.5)
-    // 键: antigravityUnifiedStateSync.oauthToken
-    // 结构: Outer(F1) -> Inner(F2) -> Inner2(F1) -> Base64 -> OAuthInfo
-    let new_format_data: Option<String> = conn
-        .query_row(
-            "SELECT value FROM ItemTable WHERE key = ?",
-            ["antigravityUnifiedStateSync.oauthToken"],
-            |row| row.get(0),
-        )
-        .ok();
+    ensure_item_table(&conn)?;
 
-    if let Some(outer_b64) = new_format_data {
-        crate::modules::logger::log_info(
-            "Detected new format database (antigravityUnifiedStateSync.oauthToken)",
-        );
-        let (sentinel_key, oauth_info_blob) = protobuf::decode_unified_state_entry(&outer_b64)?;
-        if sentinel_key != "oauthTokenInfoSentinelKey" {
-            return Err(format!("Unexpected OAuth sentinel key: {}", sentinel_key));
-        }
-            
-        // 解析 OAuthInfo (Field 3) -> Refresh Token
-        let refresh_bytes = protobuf::find_field(&oauth_info_blob, 3)
-            .map_err(|e| format!("Parsing OAuthInfo Field 3 failed: {}", e))?
-            .ok_or("Refresh Token not found in OAuthInfo (Field 3)")?;
-            
-        let refresh_token = String::from_utf8(refresh_bytes)
-            .map_err(|_| "Refresh Token is not UTF-8 encoded".to_string())?;
-        let is_gcp_tos = protobuf::find_varint_field(&oauth_info_blob, 6)?.unwrap_or(1) != 0;
-        let project_id = extract_enterprise_project_id_from_conn(&conn)?;
+    // 新版格式优先；新版键读取失败 (如数据库部分损坏) 时回退旧版格式
+    let login_state = pick_login_state(query_item_value(&conn, UNIFIED_OAUTH_TOKEN_KEY), || {
+        query_item_value(&conn, LEGACY_AGENT_STATE_KEY)
+    })?;
 
-        return Ok(ImportedOAuthState {
-            refresh_token,
-            is_gcp_tos,
-            project_id,
-        });
+    match login_state {
+        StoredLoginState::Unified(outer_b64) => extract_unified_oauth_state(&conn, &outer_b64),
+        StoredLoginState::Legacy(value) => {
+            crate::modules::logger::log_info(
+                "Falling back to old format database (jetskiStateSync.agentManagerInitState)",
+            );
+            extract_legacy_oauth_state(&conn, &value)
+        }
     }
+}
 
-    // 2. 尝试旧版格式 (< 1.16.5)
+/// 新版格式 (>= 1.16.5)
+/// 键: antigravityUnifiedStateSync.oauthToken
+/// 结构: Outer(F1) -> Inner(F2) -> Inner2(F1) -> Base64 -> OAuthInfo
+fn extract_unified_oauth_state(
+    conn: &rusqlite::Connection,
+    outer_b64: &str,
+) -> Result<ImportedOAuthState, String> {
     crate::modules::logger::log_info(
-        "Falling back to old format database (jetskiStateSync.agentManagerInitState)",
+        "Detected new format database (antigravityUnifiedStateSync.oauthToken)",
     );
-    let current_data: String = conn
-        .query_row(
-            "SELECT value FROM ItemTable WHERE key = ?",
-            ["jetskiStateSync.agentManagerInitState"],
-            |row| row.get(0),
-        )
-        .map_err(|_| "Login state data not found in either format".to_string())?;
-        
-    // Base64 decode
+    let (sentinel_key, oauth_info_blob) = protobuf::decode_unified_state_entry(outer_b64)?;
+    if sentinel_key != "oauthTokenInfoSentinelKey" {
+        return Err(format!("Unexpected OAuth sentinel key: {}", sentinel_key));
+    }
+
+    // 解析 OAuthInfo -> Access Token / Refresh Token / Expiry
+    let paths = field_paths_for(DbFormat::NewUnified)
+        .ok_or("No field paths for the unified format")?;
+    let oauth = extract_oauth_info_with_paths(&oauth_info_blob, &paths)
+        .map_err(|e| format!("Parsing OAuthInfo failed: {}", e))?;
+    let is_gcp_tos = protobuf::find_varint_field(&oauth_info_blob, 6)?.unwrap_or(1) != 0;
+    let project_id = extract_enterprise_project_id_from_conn(conn)?;
+
+    Ok(ImportedOAuthState {
+        oauth,
+        is_gcp_tos,
+        project_id,
+        from_legacy_format: false,
+        source: AccountSource::LiveDb,
+    })
+}
+
+/// Base64 解码 protobuf blob，兼容被重复编码 (base64 套 base64) 的值
+///
+/// 部分第三方工具会对 blob 再做一次 Base64：首次解码结果本身是合法 Base64 文本、
+/// 且无法按 protobuf 解析时，再解码一次。
+fn decode_base64_blob(encoded: &str) -> Result<Vec<u8>, String> {
     let blob = general_purpose::STANDARD
-        .decode(&current_data)
+        .decode(encoded.trim())
         .map_err(|e| format!("Base64 decoding failed: {}", e))?;
-        
-    // 1. Find oauthTokenInfo (Field 6)
-    let oauth_data = protobuf::find_field(&blob, 6)
-        .map_err(|e| format!("Protobuf parsing failed: {}", e))?
-        .ok_or("OAuth data not found (Field 6)")?;
-        
-    // 2. Extract refresh_token (Field 3)
-    let refresh_bytes = protobuf::find_field(&oauth_data, 3)
-        .map_err(|e| format!("OAuth data parsing failed: {}", e))?
-        .ok_or("Refresh Token not included in data (Field 3)")?;
-        
-    let refresh_token = String::from_utf8(refresh_bytes)
-        .map_err(|_| "Refresh Token is not UTF-8 encoded".to_string())?;
+    if protobuf::scan_fields(&blob).is_ok() {
+        return Ok(blob);
+    }
+
+    match general_purpose::STANDARD.decode(&blob) {
+        Ok(inner) if protobuf::scan_fields(&inner).is_ok() => {
+            crate::modules::logger::log_info(&format!(
+                "Protobuf blob was double Base64-encoded, decoded twice ({} -> {} bytes)",
+                blob.len(),
+                inner.len()
+            ));
+            Ok(inner)
+        }
+        _ => Ok(blob),
+    }
+}
+
+/// 旧版格式 (< 1.16.5)：jetskiStateSync.agentManagerInitState -> Base64 -> Protobuf
+fn extract_legacy_oauth_state(
+    conn: &rusqlite::Connection,
+    current_data: &str,
+) -> Result<ImportedOAuthState, String> {
+    // Base64 decode
+    let blob = decode_base64_blob(current_data)?;
+
+    // Find oauthTokenInfo (Field 6 by default) and extract access_token / refresh_token / expiry
+    let paths = field_paths_for(DbFormat::LegacyJetski)
+        .ok_or("No field paths for the legacy format")?;
+    let oauth = extract_oauth_info_with_paths(&blob, &paths)
+        .map_err(|e| format!("OAuth data parsing failed: {}", e))?;
 
     Ok(ImportedOAuthState {
-        refresh_token,
+        oauth,
         is_gcp_tos: true,
-        project_id: extract_enterprise_project_id_from_conn(&conn)?,
+        project_id: extract_enterprise_project_id_from_conn(conn)?,
+        from_legacy_format: true,
+        source: AccountSource::LiveDb,
     })
 }
 
@@ -396,3 +1649,1457 @@ pub fn get_refresh_token_from_db() -> Result<String, String> {
     let db_path = db::get_db_path()?;
     extract_refresh_token_from_file(&db_path)
 }
+
+/// Locate a backup file recorded in the V1 index on this machine.
+///
+/// The index may hold a relative path, or an absolute path from another machine
+/// (possibly a Windows path). When the recorded path does not exist, the file name
+/// is looked up in `v1_dir`, `v1_dir/backups` and `v1_dir/accounts`, first exactly
+/// and then case-insensitively.
+fn resolve_v1_backup_path(v1_dir: &Path, recorded: &str) -> Option<PathBuf> {
+    let recorded_path = PathBuf::from(recorded);
+    if recorded_path.is_file() {
+        return Some(recorded_path);
+    }
+
+    // 同时按 / 和 \ 拆分，兼容在其他系统上记录的路径
+    let file_name = recorded
+        .rsplit(['/', '\\'])
+        .next()
+        .filter(|name| !name.is_empty())?;
+    let search_dirs = [v1_dir.to_path_buf(), v1_dir.join("backups"), v1_dir.join("accounts")];
+
+    if let Some(exact) = search_dirs
+        .iter()
+        .map(|dir| dir.join(file_name))
+        .find(|candidate| candidate.is_file())
+    {
+        return Some(exact);
+    }
+
+    // 大小写敏感的文件系统上，Windows 记录的文件名大小写可能与实际不一致
+    let wanted = file_name.to_lowercase();
+    search_dirs.iter().find_map(|dir| {
+        fs::read_dir(dir)
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .find(|path| {
+                path.is_file()
+                    && path
+                        .file_name()
+                        .and_then(|name| name.to_str())
+                        .is_some_and(|name| name.to_lowercase() == wanted)
+            })
+    })
+}
+
+/// Read a V1 index/backup file, honouring UTF-16 BOMs written by some Windows scripts
+fn read_v1_file(path: &Path) -> Result<String, String> {
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    decode_v1_bytes(&bytes).map_err(|e| format!("Failed to decode {:?}: {}", path, e))
+}
+
+/// Decode raw file bytes: UTF-16 LE/BE when a BOM is present, otherwise UTF-8
+fn decode_v1_bytes(bytes: &[u8]) -> Result<String, String> {
+    let (payload, big_endian) = match bytes {
+        [0xFF, 0xFE, rest @ ..] => (rest, false),
+        [0xFE, 0xFF, rest @ ..] => (rest, true),
+        _ => {
+            return String::from_utf8(bytes.to_vec())
+                .map_err(|e| format!("invalid UTF-8: {}", e))
+        }
+    };
+
+    if payload.len() % 2 != 0 {
+        return Err("UTF-16 content has an odd number of bytes".to_string());
+    }
+    let units: Vec<u16> = payload
+        .chunks_exact(2)
+        .map(|pair| {
+            let pair = [pair[0], pair[1]];
+            if big_endian {
+                u16::from_be_bytes(pair)
+            } else {
+                u16::from_le_bytes(pair)
+            }
+        })
+        .collect();
+    String::from_utf16(&units).map_err(|e| format!("invalid UTF-16: {}", e))
+}
+
+/// Parse a V1 JSON file, tolerating a leading UTF-8 BOM and surrounding whitespace.
+/// Hand-edited files with comments or trailing commas are retried with a tolerant pass.
+fn parse_v1_json(content: &str) -> Result<Value, serde_json::Error> {
+    let content = content.strip_prefix('\u{feff}').unwrap_or(content).trim();
+    match serde_json::from_str(content) {
+        Ok(v) => Ok(v),
+        Err(strict_err) => match serde_json::from_str(&strip_json_comments_and_trailing_commas(content)) {
+            Ok(v) => {
+                crate::modules::logger::log_info(&format!(
+                    "V1 JSON parsed with tolerant fallback (comments/trailing commas) after strict error: {}",
+                    strict_err
+                ));
+                Ok(v)
+            }
+            Err(_) => Err(strict_err),
+        },
+    }
+}
+
+/// Remove `//` and `/* */` comments plus trailing commas before `}` / `]`, leaving string literals untouched
+fn strip_json_comments_and_trailing_commas(input: &str) -> String {
+    let chars: Vec<char> = input.chars().collect();
+    let mut without_comments = String::with_capacity(input.len());
+    let mut i = 0;
+    let mut in_string = false;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if in_string {
+            without_comments.push(c);
+            if c == '\\' && i + 1 < chars.len() {
+                without_comments.push(chars[i + 1]);
+                i += 1;
+            } else if c == '"' {
+                in_string = false;
+            }
+            i += 1;
+            continue;
+        }
+
+        match (c, chars.get(i + 1)) {
+            ('"', _) => {
+                in_string = true;
+                without_comments.push(c);
+                i += 1;
+            }
+            ('/', Some('/')) => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            ('/', Some('*')) => {
+                i += 2;
+                while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                    i += 1;
+                }
+                i += 2;
+            }
+            _ => {
+                without_comments.push(c);
+                i += 1;
+            }
+        }
+    }
+
+    // 第二遍：去掉紧跟 `}` / `]` 的逗号
+    let chars: Vec<char> = without_comments.chars().collect();
+    let mut output = String::with_capacity(chars.len());
+    let mut in_string = false;
+    let mut escaped = false;
+    for (idx, &c) in chars.iter().enumerate() {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+        } else if c == '"' {
+            in_string = true;
+        } else if c == ',' {
+            let next = chars[idx + 1..].iter().find(|ch| !ch.is_whitespace());
+            if matches!(next, Some('}') | Some(']')) {
+                continue;
+            }
+        }
+        output.push(c);
+    }
+    output
+}
+
+/// Key paths tried in order when looking for a refresh token in V2/script-generated JSON
+const REFRESH_TOKEN_KEY_PATHS: &[&[&str]] = &[
+    &["token", "refresh_token"],
+    &["token", "refreshToken"],
+    &["token", "refresh"],
+    &["tokens", "refresh_token"],
+    &["tokens", "refreshToken"],
+    &["refresh_token"],
+    &["refreshToken"],
+    &["refresh"],
+];
+
+/// Find a refresh token in JSON by trying the known key layouts.
+/// Returns the token and the dotted path that matched.
+fn find_refresh_token_in_json(json: &Value) -> Option<(String, String)> {
+    REFRESH_TOKEN_KEY_PATHS.iter().find_map(|path| {
+        let value = path.iter().try_fold(json, |node, key| node.get(*key))?;
+        let token = value.as_str()?.trim();
+        if token.is_empty() {
+            return None;
+        }
+        Some((token.to_string(), path.join(".")))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_refresh_token_key_layouts() {
+        let layouts = vec![
+            (serde_json::json!({ "token": { "refresh_token": "1//rt" } }), "token.refresh_token"),
+            (serde_json::json!({ "token": { "refreshToken": "1//rt" } }), "token.refreshToken"),
+            (serde_json::json!({ "token": { "refresh": "1//rt" } }), "token.refresh"),
+            (serde_json::json!({ "tokens": { "refresh_token": "1//rt" } }), "tokens.refresh_token"),
+            (serde_json::json!({ "tokens": { "refreshToken": "1//rt" } }), "tokens.refreshToken"),
+            (serde_json::json!({ "refresh_token": "1//rt" }), "refresh_token"),
+            (serde_json::json!({ "refreshToken": "1//rt" }), "refreshToken"),
+            (serde_json::json!({ "refresh": "1//rt" }), "refresh"),
+        ];
+
+        for (json, expected_path) in layouts {
+            assert_eq!(
+                find_refresh_token_in_json(&json),
+                Some(("1//rt".to_string(), expected_path.to_string())),
+                "layout {}",
+                expected_path
+            );
+        }
+    }
+
+    #[test]
+    fn test_find_refresh_token_priority_and_misses() {
+        // Nested snake_case wins over top-level keys
+        let json = serde_json::json!({
+            "refresh_token": "top",
+            "token": { "refresh_token": "nested", "refreshToken": "camel" }
+        });
+        assert_eq!(find_refresh_token_in_json(&json).unwrap().0, "nested");
+
+        // Empty / non-string values are skipped
+        let json = serde_json::json!({ "token": { "refresh_token": "" }, "refreshToken": "fallback" });
+        assert_eq!(find_refresh_token_in_json(&json).unwrap().0, "fallback");
+        assert!(find_refresh_token_in_json(&serde_json::json!({ "token": { "refresh_token": 1 } })).is_none());
+        assert!(find_refresh_token_in_json(&serde_json::json!({})).is_none());
+    }
+
+    fn create_item_db(path: &PathBuf, key: Option<&str>) {
+        let conn = rusqlite::Connection::open(path).unwrap();
+        conn.execute("CREATE TABLE ItemTable (key TEXT UNIQUE ON CONFLICT REPLACE, value BLOB)", [])
+            .unwrap();
+        if let Some(key) = key {
+            conn.execute("INSERT INTO ItemTable (key, value) VALUES (?, ?)", [key, "payload"])
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_detect_format() {
+        let dir = std::env::temp_dir().join(format!("ag_detect_format_{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let new_db = dir.join("new.vscdb");
+        create_item_db(&new_db, Some(UNIFIED_OAUTH_TOKEN_KEY));
+        assert_eq!(detect_format(&new_db).unwrap(), DbFormat::NewUnified);
+
+        let legacy_db = dir.join("legacy.vscdb");
+        create_item_db(&legacy_db, Some(LEGACY_AGENT_STATE_KEY));
+        assert_eq!(detect_format(&legacy_db).unwrap(), DbFormat::LegacyJetski);
+
+        let empty_db = dir.join("empty.vscdb");
+        create_item_db(&empty_db, None);
+        assert_eq!(detect_format(&empty_db).unwrap(), DbFormat::Unknown);
+
+        let backup = dir.join("backup.json");
+        fs::write(&backup, r#"{"token": {"refresh_token": "1//rt"}}"#).unwrap();
+        assert_eq!(detect_format(&backup).unwrap(), DbFormat::V1BackupJson);
+
+        let legacy_backup = dir.join("legacy_backup.json");
+        fs::write(&legacy_backup, format!(r#"{{"{}": "AAAA"}}"#, LEGACY_AGENT_STATE_KEY)).unwrap();
+        assert_eq!(detect_format(&legacy_backup).unwrap(), DbFormat::V1BackupJson);
+
+        let other = dir.join("other.json");
+        fs::write(&other, r#"{"theme": "dark"}"#).unwrap();
+        assert_eq!(detect_format(&other).unwrap(), DbFormat::Unknown);
+
+        assert!(detect_format(&dir.join("missing.vscdb")).is_err());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_database_without_item_table_reports_clear_error() {
+        let dir = std::env::temp_dir().join(format!("ag_no_item_table_{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let db_path = dir.join("other_ide.vscdb");
+        let conn = rusqlite::Connection::open(&db_path).unwrap();
+        conn.execute("CREATE TABLE settings (key TEXT, value TEXT)", []).unwrap();
+        conn.execute("CREATE TABLE cache (id INTEGER)", []).unwrap();
+        drop(conn);
+
+        let err = extract_refresh_token_from_file(&db_path).unwrap_err();
+        assert!(err.contains("does not look like an Antigravity state DB (no ItemTable)"), "{}", err);
+        assert!(err.contains("Tables found: cache, settings"), "{}", err);
+
+        let empty_path = dir.join("empty.vscdb");
+        rusqlite::Connection::open(&empty_path)
+            .unwrap()
+            .execute_batch("PRAGMA user_version = 1;")
+            .unwrap();
+        let err = extract_refresh_token_from_file(&empty_path).unwrap_err();
+        assert!(err.contains("Tables found: none"), "{}", err);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    fn create_oauth_db(path: &PathBuf, key: &str, value: &str) {
+        create_item_db(path, None);
+        let conn = rusqlite::Connection::open(path).unwrap();
+        conn.execute("INSERT INTO ItemTable (key, value) VALUES (?, ?)", [key, value])
+            .unwrap();
+    }
+
+    #[test]
+    fn test_double_base64_encoded_legacy_blob_is_decoded() {
+        use base64::{engine::general_purpose, Engine as _};
+
+        let dir = std::env::temp_dir().join(format!("ag_double_b64_{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let legacy_blob = protobuf::create_oauth_field("ya29.double", "1//double", 1_900_000_000);
+        let once = general_purpose::STANDARD.encode(&legacy_blob);
+        let twice = general_purpose::STANDARD.encode(&once);
+        assert_eq!(decode_base64_blob(&once).unwrap(), legacy_blob);
+        assert_eq!(decode_base64_blob(&twice).unwrap(), legacy_blob);
+
+        let db = dir.join("double.vscdb");
+        create_oauth_db(&db, LEGACY_AGENT_STATE_KEY, &twice);
+        let state = extract_oauth_state_from_file(&db).unwrap();
+        assert!(state.from_legacy_format);
+        assert_eq!(state.oauth.refresh_token, "1//double");
+        assert_eq!(state.oauth.access_token.as_deref(), Some("ya29.double"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_unreadable_unified_key_falls_back_to_legacy() {
+        use base64::{engine::general_purpose, Engine as _};
+
+        let dir = std::env::temp_dir().join(format!("ag_partial_corrupt_{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+
+        // 新版键的值损坏 (非 UTF-8 BLOB，读取时报错)，旧版键仍可读
+        let db = dir.join("partial.vscdb");
+        let legacy_blob = protobuf::create_oauth_field("ya29.legacy", "1//legacy", 1_900_000_000);
+        create_oauth_db(&db, LEGACY_AGENT_STATE_KEY, &general_purpose::STANDARD.encode(legacy_blob));
+        let conn = rusqlite::Connection::open(&db).unwrap();
+        conn.execute(
+            "INSERT INTO ItemTable (key, value) VALUES (?, ?)",
+            rusqlite::params![UNIFIED_OAUTH_TOKEN_KEY, vec![0xffu8, 0xfe, 0x00]],
+        )
+        .unwrap();
+        drop(conn);
+
+        assert!(query_item_value(
+            &rusqlite::Connection::open(&db).unwrap(),
+            UNIFIED_OAUTH_TOKEN_KEY
+        )
+        .is_err());
+        let state = extract_oauth_state_from_file(&db).unwrap();
+        assert!(state.from_legacy_format);
+        assert_eq!(state.oauth.refresh_token, "1//legacy");
+
+        // 不是数据库：两个键都失败，错误中分别说明
+        let not_db = dir.join("garbage.vscdb");
+        fs::write(&not_db, vec![0x42u8; 4096]).unwrap();
+        let err = extract_oauth_state_from_file(&not_db).unwrap_err();
+        assert!(err.contains(UNIFIED_OAUTH_TOKEN_KEY), "{}", err);
+        assert!(err.contains(LEGACY_AGENT_STATE_KEY), "{}", err);
+        assert!(err.contains("SQLITE_NOTADB"), "{}", err);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_pick_login_state_branches() {
+        let missing = || Ok(None);
+        assert_eq!(
+            pick_login_state(Ok(Some("u".to_string())), || panic!("legacy not read")),
+            Ok(StoredLoginState::Unified("u".to_string()))
+        );
+        assert_eq!(
+            pick_login_state(Err("corrupt".to_string()), || Ok(Some("l".to_string()))),
+            Ok(StoredLoginState::Legacy("l".to_string()))
+        );
+        assert_eq!(
+            pick_login_state(Ok(None), missing),
+            Err("Login state data not found in either format".to_string())
+        );
+
+        let err = pick_login_state(Err("corrupt".to_string()), missing).unwrap_err();
+        assert!(err.contains("corrupt") && err.contains("not found"), "{}", err);
+        let err = pick_login_state(Ok(None), || Err("notadb".to_string())).unwrap_err();
+        assert!(err.contains("not found") && err.contains("notadb"), "{}", err);
+    }
+
+    #[test]
+    fn test_extract_oauth_info_reads_all_fields() {
+        use base64::{engine::general_purpose, Engine as _};
+
+        let dir = std::env::temp_dir().join(format!("ag_oauth_info_{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let expected = OAuthInfo {
+            access_token: Some("ya29.from-db".to_string()),
+            refresh_token: "1//from-db".to_string(),
+            expiry: Some(1_900_000_000),
+        };
+
+        let new_db = dir.join("new.vscdb");
+        let oauth_info = protobuf::create_oauth_info("ya29.from-db", "1//from-db", 1_900_000_000, true);
+        create_oauth_db(
+            &new_db,
+            UNIFIED_OAUTH_TOKEN_KEY,
+            &protobuf::create_unified_state_entry("oauthTokenInfoSentinelKey", &oauth_info),
+        );
+        assert_eq!(extract_oauth_info_from_file(&new_db).unwrap(), expected);
+        assert_eq!(extract_refresh_token_from_file(&new_db).unwrap(), "1//from-db");
+
+        let legacy_db = dir.join("legacy.vscdb");
+        let legacy_blob = protobuf::create_oauth_field("ya29.from-db", "1//from-db", 1_900_000_000);
+        create_oauth_db(
+            &legacy_db,
+            LEGACY_AGENT_STATE_KEY,
+            &general_purpose::STANDARD.encode(legacy_blob),
+        );
+        assert_eq!(extract_oauth_info_from_file(&legacy_db).unwrap(), expected);
+
+        // 仅含 Refresh Token 的 OAuthInfo 仍可导入
+        let minimal = protobuf::encode_string_field(3, "1//only-refresh");
+        assert_eq!(
+            parse_oauth_info(&minimal, &builtin_field_paths(DbFormat::NewUnified).unwrap())
+                .unwrap(),
+            OAuthInfo::from_refresh_token("1//only-refresh".to_string())
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_overridden_field_paths_extract_token_from_shifted_layout() {
+        // 假设新版本把 OAuthInfo 移到 F9.F2，并把 Refresh Token 挪到 F5、过期时间挪到 F7.F1
+        let shifted_info = [
+            protobuf::encode_string_field(1, "ya29.shifted"),
+            protobuf::encode_string_field(5, "1//shifted"),
+            protobuf::encode_len_delim_field(7, &protobuf::encode_varint_field(1, 1_900_000_000)),
+        ]
+        .concat();
+        let container =
+            protobuf::encode_len_delim_field(9, &protobuf::encode_len_delim_field(2, &shifted_info));
+
+        // 内置路径无法解析新结构
+        let builtin = builtin_field_paths(DbFormat::LegacyJetski).unwrap();
+        assert!(extract_oauth_info_with_paths(&container, &builtin).is_err());
+
+        let overrides = TokenFieldPathsConfig {
+            new_unified: None,
+            legacy_jetski: Some(OAuthFieldPaths {
+                oauth_info: vec![9, 2],
+                refresh_token: vec![5],
+                access_token: vec![1],
+                expiry_seconds: vec![7, 1],
+            }),
+            max_nesting_depth: None,
+        };
+        let paths = field_paths_with(&overrides, DbFormat::LegacyJetski).unwrap();
+        assert_eq!(
+            extract_oauth_info_with_paths(&container, &paths).unwrap(),
+            OAuthInfo {
+                access_token: Some("ya29.shifted".to_string()),
+                refresh_token: "1//shifted".to_string(),
+                expiry: Some(1_900_000_000),
+            }
+        );
+        // V1 备份共用旧版覆盖；未覆盖的格式仍使用内置路径
+        assert_eq!(field_paths_with(&overrides, DbFormat::V1BackupJson), Some(paths));
+        assert_eq!(
+            field_paths_with(&overrides, DbFormat::NewUnified),
+            builtin_field_paths(DbFormat::NewUnified)
+        );
+
+        // 配置中省略的可选路径按空处理
+        let parsed: OAuthFieldPaths =
+            serde_json::from_str(r#"{"oauth_info": [9, 2], "refresh_token": [5]}"#).unwrap();
+        assert_eq!(
+            extract_oauth_info_with_paths(&container, &parsed).unwrap(),
+            OAuthInfo::from_refresh_token("1//shifted".to_string())
+        );
+    }
+
+    #[test]
+    fn test_absolute_backup_path_from_other_machine_falls_back_to_file_name() {
+        let dir = std::env::temp_dir().join(format!("ag_v1_paths_{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(dir.join("backups")).unwrap();
+        fs::write(dir.join("backups").join("a1.json"), "{}").unwrap();
+        fs::write(dir.join("B2.json"), "{}").unwrap();
+
+        // 本机存在的路径原样使用
+        let local = dir.join("B2.json");
+        assert_eq!(
+            resolve_v1_backup_path(&dir, local.to_str().unwrap()),
+            Some(local.clone())
+        );
+
+        // 其他机器上的绝对路径：按文件名在 v1_dir 及子目录中查找
+        assert_eq!(
+            resolve_v1_backup_path(&dir, "/home/someone-else/.antigravity/backups/a1.json"),
+            Some(dir.join("backups").join("a1.json"))
+        );
+        assert_eq!(
+            resolve_v1_backup_path(&dir, r"C:\Users\bob\.antigravity\B2.json"),
+            Some(local)
+        );
+
+        // 文件名大小写不一致时也能找到
+        let found = resolve_v1_backup_path(&dir, r"D:\old\backups\A1.JSON").unwrap();
+        assert!(found.to_string_lossy().to_lowercase().ends_with("a1.json"));
+
+        assert_eq!(resolve_v1_backup_path(&dir, "/elsewhere/missing.json"), None);
+        assert_eq!(resolve_v1_backup_path(&dir, r"C:\trailing\"), None);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_bom_prefixed_v1_files_parse() {
+        let dir = std::env::temp_dir().join(format!("ag_v1_bom_{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let index_path = dir.join("antigravity_accounts.json");
+        fs::write(
+            &index_path,
+            "\u{feff}{\"accounts\": {\"a1\": {\"email\": \"alice@example.com\", \"backup_file\": \"a1.json\"}}}\n\n  ",
+        )
+        .unwrap();
+        let backup_path = dir.join("a1.json");
+        fs::write(&backup_path, "\u{feff}  {\"token\": {\"refresh_token\": \"rt-bom\"}}\r\n").unwrap();
+
+        let raw_index = fs::read_to_string(&index_path).unwrap();
+        assert!(serde_json::from_str::<Value>(&raw_index).is_err());
+
+        let index = parse_v1_json(&raw_index).unwrap();
+        let accounts = index["accounts"].as_object().unwrap();
+        let (kept, skipped) = filter_v1_index_entries(accounts, None);
+        assert_eq!(kept_ids(&kept), vec!["a1"]);
+        assert!(skipped.is_empty());
+
+        let backup = parse_v1_json(&fs::read_to_string(&backup_path).unwrap()).unwrap();
+        assert_eq!(find_refresh_token_in_json(&backup).unwrap().0, "rt-bom");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_utf16_v1_files_are_decoded() {
+        let encode_utf16le = |text: &str| {
+            let mut bytes = vec![0xFF, 0xFE];
+            bytes.extend(text.encode_utf16().flat_map(|unit| unit.to_le_bytes()));
+            bytes
+        };
+
+        let dir = std::env::temp_dir().join(format!("ag_v1_utf16_{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let index_path = dir.join("antigravity_accounts.json");
+        fs::write(
+            &index_path,
+            encode_utf16le(r#"{"accounts": {"a1": {"email": "zoë@example.com", "backup_file": "a1.json"}}}"#),
+        )
+        .unwrap();
+        let backup_path = dir.join("a1.json");
+        fs::write(&backup_path, encode_utf16le("{\"token\": {\"refresh_token\": \"rt-utf16\"}}\r\n")).unwrap();
+
+        assert!(fs::read_to_string(&index_path).is_err());
+
+        let index = parse_v1_json(&read_v1_file(&index_path).unwrap()).unwrap();
+        let accounts = index["accounts"].as_object().unwrap();
+        let (kept, _) = filter_v1_index_entries(accounts, None);
+        assert_eq!(kept_ids(&kept), vec!["a1"]);
+        assert_eq!(kept[0].1["email"], "zoë@example.com");
+
+        let backup = parse_v1_json(&read_v1_file(&backup_path).unwrap()).unwrap();
+        assert_eq!(find_refresh_token_in_json(&backup).unwrap().0, "rt-utf16");
+        assert_eq!(detect_format(&backup_path).unwrap(), DbFormat::V1BackupJson);
+
+        // UTF-16BE 与截断的 UTF-16 内容
+        let mut be = vec![0xFE, 0xFF];
+        be.extend("{}".encode_utf16().flat_map(|unit| unit.to_be_bytes()));
+        assert_eq!(decode_v1_bytes(&be).unwrap(), "{}");
+        assert!(decode_v1_bytes(&[0xFF, 0xFE, b'{']).is_err());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_tolerant_parse_of_hand_edited_backup() {
+        let backup = r#"{
+            // exported by hand
+            "email": "alice@example.com", /* old: "a@x" */
+            "token": {
+                "refresh_token": "rt-//-kept",
+                "note": "a, }",
+            },
+            "tags": ["a", "b",],
+        }"#;
+        assert!(serde_json::from_str::<Value>(backup).is_err());
+
+        let json = parse_v1_json(backup).unwrap();
+        assert_eq!(json["email"], "alice@example.com");
+        assert_eq!(json["token"]["note"], "a, }");
+        assert_eq!(json["tags"], serde_json::json!(["a", "b"]));
+        assert_eq!(find_refresh_token_in_json(&json).unwrap().0, "rt-//-kept");
+
+        // 无法修复的内容仍返回严格解析的错误
+        assert!(parse_v1_json("{\"email\": }").is_err());
+    }
+
+    fn multi_account_index() -> serde_json::Map<String, Value> {
+        serde_json::json!({
+            "a1": { "email": "alice@example.com", "backup_file": "a1.json" },
+            "a2": { "email": "bob@Example.COM", "backup_file": "a2.json" },
+            "a3": { "email": "carol@other.org", "backup_file": "a3.json" },
+            "a4": { "email": "dave@notexample.com", "backup_file": "a4.json" },
+            "a5": { "backup_file": "a5.json" },
+            "current_account_id": "a1"
+        })
+        .as_object()
+        .unwrap()
+        .clone()
+    }
+
+    fn kept_ids(entries: &[(&String, &Value)]) -> Vec<String> {
+        let mut ids: Vec<String> = entries.iter().map(|(id, _)| id.to_string()).collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn test_email_filter_by_domain() {
+        let index = multi_account_index();
+        let filter = EmailFilter::Domain("example.com".to_string());
+        let (kept, skipped) = filter_v1_index_entries(&index, Some(&filter));
+
+        // Unknown email (a5) and non-account keys are kept for later handling
+        assert_eq!(kept_ids(&kept), vec!["a1", "a2", "a5", "current_account_id"]);
+        let mut skipped_emails: Vec<&str> = skipped.iter().map(|s| s.email.as_str()).collect();
+        skipped_emails.sort();
+        assert_eq!(skipped_emails, vec!["carol@other.org", "dave@notexample.com"]);
+        assert!(skipped.iter().all(|s| s.reason == ImportSkipReason::FilteredOut));
+    }
+
+    #[test]
+    fn test_email_filter_by_substring() {
+        let index = multi_account_index();
+        let filter = EmailFilter::Contains("EXAMPLE".to_string());
+        let (kept, skipped) = filter_v1_index_entries(&index, Some(&filter));
+
+        assert_eq!(kept_ids(&kept), vec!["a1", "a2", "a4", "a5", "current_account_id"]);
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].email, "carol@other.org");
+
+        // No filter keeps everything
+        let (kept, skipped) = filter_v1_index_entries(&index, None);
+        assert_eq!(kept.len(), index.len());
+        assert!(skipped.is_empty());
+    }
+
+    #[test]
+    fn test_validate_v1_index_reports_missing_fields_per_entry() {
+        let index = serde_json::json!({
+            "accounts": {
+                "ok": { "email": "ok@example.com", "backup_file": "ok.json" },
+                "legacy": { "email": "legacy@example.com", "data_file": "legacy.json" },
+                "no-backup": { "email": "nobackup@example.com" },
+                "no-email": { "backup_file": "no-email.json" },
+                "bad-types": { "email": 42, "backup_file": "  " },
+                "junk": "not an entry"
+            },
+            "current_account_id": "ok"
+        });
+        let issue = |id: &str, field: Option<&str>, problem| IndexIssue {
+            account_id: Some(id.to_string()),
+            field: field.map(str::to_string),
+            problem,
+        };
+
+        assert_eq!(
+            validate_v1_index(&index),
+            vec![
+                issue("no-backup", Some("backup_file"), IndexProblem::MissingField),
+                issue("no-email", Some("email"), IndexProblem::MissingField),
+                issue(
+                    "bad-types",
+                    Some("email"),
+                    IndexProblem::WrongType {
+                        expected: "string".to_string()
+                    }
+                ),
+                issue("bad-types", Some("backup_file"), IndexProblem::Empty),
+                issue("junk", None, IndexProblem::NotAnObject),
+            ]
+        );
+        assert_eq!(
+            issue("no-email", Some("email"), IndexProblem::MissingField).to_string(),
+            "account no-email field 'email': missing"
+        );
+
+        // 直接映射格式：current_account_id 不被当作账号
+        assert!(validate_v1_index(&Value::Object(multi_account_index()))
+            .iter()
+            .all(|i| i.account_id.as_deref() == Some("a5")));
+        assert_eq!(
+            validate_v1_index(&serde_json::json!([])),
+            vec![IndexIssue {
+                account_id: None,
+                field: None,
+                problem: IndexProblem::NotAnObject,
+            }]
+        );
+
+        let dir = std::env::temp_dir().join(format!("ag_v1_validate_{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        assert!(validate_v1_index_files(&dir).is_err());
+        fs::write(dir.join("accounts.json"), index.to_string()).unwrap();
+        let reports = validate_v1_index_files(&dir).unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].index_file, "accounts.json");
+        assert_eq!(reports[0].issues.len(), 5);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_import_cap_prefers_valid_tokens() {
+        // (email, refreshable)
+        let candidates = vec![
+            ("expired1@example.com", false),
+            ("ok1@example.com", true),
+            ("expired2@example.com", false),
+            ("ok2@example.com", true),
+            ("ok3@example.com", true),
+        ];
+
+        let (kept, over_cap) = split_at_cap(candidates.clone(), Some(3), |c| c.1);
+        assert_eq!(kept.len(), 3);
+        assert!(kept.iter().all(|c| c.1), "valid tokens fill the cap first");
+        assert_eq!(
+            over_cap.iter().map(|c| c.0).collect::<Vec<_>>(),
+            vec!["expired1@example.com", "expired2@example.com"]
+        );
+
+        let (kept, over_cap) = split_at_cap(candidates.clone(), Some(4), |c| c.1);
+        assert_eq!(kept.len(), 4);
+        assert_eq!(kept[3].0, "expired1@example.com");
+        assert_eq!(over_cap.len(), 1);
+
+        let (kept, over_cap) = split_at_cap(candidates.clone(), None, |c| c.1);
+        assert_eq!(kept, candidates);
+        assert!(over_cap.is_empty());
+
+        let (kept, over_cap) = split_at_cap(candidates, Some(0), |c| c.1);
+        assert!(kept.is_empty());
+        assert_eq!(over_cap.len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_import_single_refresh_token_creates_account() {
+        let state = ImportedOAuthState {
+            oauth: OAuthInfo::from_refresh_token("1//pasted-token".to_string()),
+            is_gcp_tos: true,
+            project_id: None,
+            from_legacy_format: false,
+            source: AccountSource::ManualToken,
+        };
+
+        let account = import_oauth_state_with(
+            state,
+            |refresh_token| async move {
+                assert_eq!(refresh_token, "1//pasted-token");
+                serde_json::from_value::<crate::modules::oauth::TokenResponse>(serde_json::json!({
+                    "access_token": "ya29.mock",
+                    "expires_in": 3599,
+                    "token_type": "Bearer",
+                    "scope": "openid https://www.googleapis.com/auth/cloud-platform"
+                }))
+                .map_err(|e| e.to_string())
+            },
+            |access_token| async move {
+                assert_eq!(access_token, "ya29.mock");
+                serde_json::from_value::<crate::modules::oauth::UserInfo>(serde_json::json!({
+                    "id": "1234567890",
+                    "email": "pasted@example.com",
+                    "name": "Pasted User"
+                }))
+                .map_err(|e| e.to_string())
+            },
+            |email, name, token, subject, source| {
+                assert_eq!(subject.as_deref(), Some("1234567890"));
+                assert_eq!(source, AccountSource::ManualToken);
+                let mut account = Account::new("acc-pasted".to_string(), email, token);
+                account.name = name;
+                Ok(account)
+            },
+            || panic!("live database must not be read for pasted tokens"),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(account.email, "pasted@example.com");
+        assert_eq!(account.name.as_deref(), Some("Pasted User"));
+        assert_eq!(account.token.refresh_token, "1//pasted-token");
+        assert_eq!(account.token.access_token, "ya29.mock");
+        assert_eq!(account.token.email.as_deref(), Some("pasted@example.com"));
+        assert!(account.token.has_scope("openid"));
+    }
+
+    #[tokio::test]
+    async fn test_import_skips_refresh_while_access_token_valid() {
+        let now = crate::utils::clock::now();
+        let import = |expiry: i64| {
+            let refreshed = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+            let refreshed_flag = refreshed.clone();
+            let state = ImportedOAuthState {
+                oauth: OAuthInfo {
+                    access_token: Some("ya29.from-db".to_string()),
+                    refresh_token: "1//from-db".to_string(),
+                    expiry: Some(expiry),
+                },
+                is_gcp_tos: true,
+                project_id: None,
+                from_legacy_format: false,
+                source: AccountSource::LiveDb,
+            };
+            async move {
+                let account = import_oauth_state_with(
+                    state,
+                    |_| {
+                        refreshed_flag.store(true, std::sync::atomic::Ordering::SeqCst);
+                        async { mock_token_response() }
+                    },
+                    |access_token| async move {
+                        serde_json::from_value::<crate::modules::oauth::UserInfo>(serde_json::json!({
+                            "email": format!("{}@example.com", access_token)
+                        }))
+                        .map_err(|e| e.to_string())
+                    },
+                    |email, _, token, _, _| Ok(Account::new("acc-db".to_string(), email, token)),
+                    || Err("no live database".to_string()),
+                )
+                .await
+                .unwrap();
+                (account, refreshed.load(std::sync::atomic::Ordering::SeqCst))
+            }
+        };
+
+        let (account, refreshed) = import(now + 3000).await;
+        assert!(!refreshed);
+        assert_eq!(account.token.access_token, "ya29.from-db");
+        assert_eq!(account.email, "ya29.from-db@example.com");
+        assert!((account.token.expiry_timestamp - (now + 3000)).abs() <= 2);
+
+        // 临近过期 (刷新提前量以内) 时仍走刷新
+        let (account, refreshed) = import(now + 60).await;
+        assert!(refreshed);
+        assert_eq!(account.token.access_token, "ya29.v1");
+        assert_eq!(account.token.refresh_token, "1//from-db");
+    }
+
+    #[tokio::test]
+    async fn test_stale_legacy_token_recovers_from_live_unified_db() {
+        let dir = std::env::temp_dir().join(format!("ag_live_db_{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let live_db = dir.join("state.vscdb");
+        let oauth_info = protobuf::create_oauth_info("ya29.expired", "1//fresh-unified", 0, true);
+        create_oauth_db(
+            &live_db,
+            UNIFIED_OAUTH_TOKEN_KEY,
+            &protobuf::create_unified_state_entry("oauthTokenInfoSentinelKey", &oauth_info),
+        );
+
+        let attempts = std::sync::Mutex::new(Vec::new());
+        let import = |from_legacy_format: bool| {
+            import_oauth_state_with(
+                ImportedOAuthState {
+                    oauth: OAuthInfo::from_refresh_token("1//stale-legacy".to_string()),
+                    is_gcp_tos: true,
+                    project_id: None,
+                    from_legacy_format,
+                    source: AccountSource::LiveDb,
+                },
+                |refresh_token: String| {
+                    attempts.lock().unwrap().push(refresh_token.clone());
+                    async move {
+                        if refresh_token == "1//fresh-unified" {
+                            mock_token_response()
+                        } else {
+                            Err(r#"{"error": "invalid_grant"}"#.to_string())
+                        }
+                    }
+                },
+                |_| async {
+                    serde_json::from_value::<crate::modules::oauth::UserInfo>(serde_json::json!({
+                        "email": "migrated@example.com"
+                    }))
+                    .map_err(|e| e.to_string())
+                },
+                |email, _, token, _, _| Ok(Account::new("acc-live".to_string(), email, token)),
+                || extract_oauth_info_from_file(&live_db),
+            )
+        };
+
+        // 旧格式令牌已失效：重新读取当前数据库的新格式令牌后导入成功
+        let account = import(true).await.unwrap();
+        assert_eq!(account.token.refresh_token, "1//fresh-unified");
+        assert_eq!(account.token.access_token, "ya29.v1");
+        assert_eq!(
+            *attempts.lock().unwrap(),
+            vec!["1//stale-legacy".to_string(), "1//fresh-unified".to_string()]
+        );
+
+        // 非旧格式来源不重试
+        attempts.lock().unwrap().clear();
+        let err = import(false).await.unwrap_err();
+        assert!(err.contains("invalid_grant"));
+        assert_eq!(attempts.lock().unwrap().len(), 1);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_each_import_path_tags_account_source() {
+        let dir = std::env::temp_dir().join(format!("ag_import_source_{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+
+        // 粘贴 refresh_token
+        assert_eq!(pasted_refresh_token_state(" 1//pasted ").unwrap().source, AccountSource::ManualToken);
+        assert!(pasted_refresh_token_state("   ").is_err());
+
+        // IDE 数据库 / 本地 API
+        let db = dir.join("state.vscdb");
+        let oauth_info = protobuf::create_oauth_info("ya29.db", "1//from-db", 0, true);
+        create_oauth_db(
+            &db,
+            UNIFIED_OAUTH_TOKEN_KEY,
+            &protobuf::create_unified_state_entry("oauthTokenInfoSentinelKey", &oauth_info),
+        );
+        let db_state = extract_oauth_state_from_file(&db).unwrap();
+        assert_eq!(db_state.source, AccountSource::LiveDb);
+        let api_state =
+            parse_local_ide_auth_state(&serde_json::json!({ "token": { "refresh_token": "1//api" } })).unwrap();
+        assert_eq!(api_state.source, AccountSource::LiveDb);
+
+        // 来源随导入状态传给保存逻辑
+        let saved = std::sync::Mutex::new(Vec::new());
+        for state in [pasted_refresh_token_state("1//pasted").unwrap(), db_state] {
+            import_oauth_state_with(
+                state,
+                |_| async { mock_token_response() },
+                |_| async {
+                    serde_json::from_value::<crate::modules::oauth::UserInfo>(serde_json::json!({
+                        "email": "source@example.com"
+                    }))
+                    .map_err(|e| e.to_string())
+                },
+                |email, _, token, _, source| {
+                    saved.lock().unwrap().push(source);
+                    Ok(Account::new("acc-source".to_string(), email, token))
+                },
+                || Err("no live database".to_string()),
+            )
+            .await
+            .unwrap();
+        }
+
+        // V1 迁移
+        fs::write(
+            dir.join("antigravity_accounts.json"),
+            r#"{"a1": {"email": "v1@example.com", "backup_file": "a1.json"}}"#,
+        )
+        .unwrap();
+        fs::write(dir.join("a1.json"), r#"{"token": {"refresh_token": "rt-v1"}}"#).unwrap();
+        import_v1_from_dir_with(
+            &dir,
+            V1ImportScope::default(),
+            || async { Ok(()) },
+            |_| async { mock_token_response() },
+            |_| async { Err::<crate::modules::oauth::UserInfo, String>("403".to_string()) },
+            |email, _name, token, _subject, source| {
+                saved.lock().unwrap().push(source);
+                Ok(Account::new(format!("id-{}", email), email, token))
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            *saved.lock().unwrap(),
+            vec![AccountSource::ManualToken, AccountSource::LiveDb, AccountSource::V1Migration]
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_local_ide_api_import_uses_endpoint_and_falls_back_to_db() {
+        use axum::{routing::get, Json, Router};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let app = Router::new().route(
+            LOCAL_IDE_AUTH_PATH,
+            get(|| async {
+                Json(serde_json::json!({
+                    "token": {
+                        "access_token": "ya29.local",
+                        "refresh_token": "1//local-api",
+                        "expiry": 4_102_444_800i64
+                    },
+                    "project_id": "proj-local"
+                }))
+            }),
+        );
+        let server = tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let imported = import_from_local_ide_api_with(
+            Some(port),
+            |state| async move {
+                assert_eq!(state.oauth.access_token.as_deref(), Some("ya29.local"));
+                assert_eq!(state.oauth.expiry, Some(4_102_444_800));
+                assert_eq!(state.project_id.as_deref(), Some("proj-local"));
+                let token = TokenData::new(
+                    state.oauth.access_token.unwrap(),
+                    state.oauth.refresh_token,
+                    3600,
+                    None,
+                    state.project_id,
+                    None,
+                    state.is_gcp_tos,
+                );
+                Ok(Account::new("local".to_string(), "local@example.com".to_string(), token))
+            },
+            || async { Err::<Account, String>("fallback should not run".to_string()) },
+        )
+        .await
+        .unwrap();
+        assert_eq!(imported.token.refresh_token, "1//local-api");
+        server.abort();
+
+        // 端口无人监听时回退到数据库导入
+        let closed_port = {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.local_addr().unwrap().port()
+        };
+        for port in [Some(closed_port), None] {
+            let fallback = import_from_local_ide_api_with(
+                port,
+                |_| async { Err::<Account, String>("endpoint should not be used".to_string()) },
+                || async {
+                    Ok(Account::new(
+                        "db".to_string(),
+                        "db@example.com".to_string(),
+                        TokenData::new("at".to_string(), "1//db".to_string(), 3600, None, None, None, true),
+                    ))
+                },
+            )
+            .await
+            .unwrap();
+            assert_eq!(fallback.token.refresh_token, "1//db");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_v1_scan_uses_home_env_when_home_dir_unavailable() {
+        let home = std::env::temp_dir().join(format!("ag_v1_home_{}", uuid::Uuid::new_v4()));
+        let v1_dir = home.join(".antigravity-agent");
+        fs::create_dir_all(&v1_dir).unwrap();
+        fs::write(
+            v1_dir.join("antigravity_accounts.json"),
+            r#"{"a1": {"email": "env@example.com", "backup_file": "a1.json"}}"#,
+        )
+        .unwrap();
+        fs::write(v1_dir.join("a1.json"), r#"{"token": {"refresh_token": "rt-env"}}"#).unwrap();
+
+        let home_str = home.to_string_lossy().to_string();
+        let resolved = v1_data_dir_with(
+            |key| (key == "HOME").then(|| home_str.clone()),
+            || None,
+        )
+        .unwrap();
+        assert_eq!(resolved, v1_dir);
+
+        let report = import_v1_from_dir_with(
+            &resolved,
+            V1ImportScope::default(),
+            || async { Ok(()) },
+            |_| async { mock_token_response() },
+            |_| async { Err::<crate::modules::oauth::UserInfo, String>("403".to_string()) },
+            |email, _name, token, _subject, _source| Ok(Account::new(format!("id-{}", email), email, token)),
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.imported.len(), 1);
+        assert_eq!(report.imported[0].token.refresh_token, "rt-env");
+
+        // 显式覆盖优先；全部不可用时错误中列出尝试过的来源
+        let overridden = v1_data_dir_with(
+            |key| (key == V1_DATA_DIR_ENV).then(|| "/custom/v1".to_string()),
+            || Some(PathBuf::from("/home/ignored")),
+        )
+        .unwrap();
+        assert_eq!(overridden, PathBuf::from("/custom/v1"));
+        let err = v1_data_dir_with(|_| None, || None).unwrap_err();
+        for tried in [V1_DATA_DIR_ENV, "system home directory", "HOME", "USERPROFILE"] {
+            assert!(err.contains(tried), "{}", err);
+        }
+
+        let _ = fs::remove_dir_all(&home);
+    }
+
+    fn mock_token_response() -> Result<crate::modules::oauth::TokenResponse, String> {
+        serde_json::from_value(serde_json::json!({ "access_token": "ya29.v1", "expires_in": 3599 }))
+            .map_err(|e| e.to_string())
+    }
+
+    #[tokio::test]
+    async fn test_verify_import_reports_failing_account_unverified() {
+        let accounts: Vec<Account> = ["a", "b", "c"]
+            .iter()
+            .map(|name| {
+                let email = format!("{}@example.com", name);
+                let token = TokenData::new(
+                    "ya29.old".to_string(),
+                    format!("1//rt-{}", name),
+                    0,
+                    Some(email.clone()),
+                    None,
+                    None,
+                    true,
+                );
+                Account::new(format!("acc-{}", name), email, token)
+            })
+            .collect();
+
+        let refresh = |refresh_token: String, _account_id: String| async move {
+            if refresh_token == "1//rt-b" {
+                Err("invalid_grant".to_string())
+            } else {
+                mock_token_response()
+            }
+        };
+        let user_info = |_access_token: String, _account_id: String| async {
+            serde_json::from_value::<crate::modules::oauth::UserInfo>(serde_json::json!({
+                "email": "real@example.com"
+            }))
+            .map_err(|e| e.to_string())
+        };
+
+        let report = verify_import_with(
+            &accounts,
+            2,
+            tokio_util::sync::CancellationToken::new(),
+            refresh,
+            user_info,
+        )
+        .await;
+        assert_eq!((report.verified, report.total), (2, 3));
+        assert_eq!(report.summary(), "2 of 3 accounts verified working");
+        assert!(!report.cancelled);
+        let verified: Vec<(&str, bool)> = report
+            .accounts
+            .iter()
+            .map(|a| (a.account_id.as_str(), a.verified))
+            .collect();
+        assert_eq!(verified, [("acc-a", true), ("acc-b", false), ("acc-c", true)]);
+        assert!(report.accounts[1].reason.as_deref().unwrap().contains("invalid_grant"));
+
+        // Cancelled before starting: nothing is verified
+        let cancel = tokio_util::sync::CancellationToken::new();
+        cancel.cancel();
+        let report = verify_import_with(&accounts, 2, cancel, refresh, user_info).await;
+        assert!(report.cancelled);
+        assert_eq!(report.verified, 0);
+        assert!(report.accounts.iter().all(|a| !a.verified));
+    }
+
+    #[tokio::test]
+    async fn test_v1_import_keeps_user_info_name() {
+        let pending = resolve_v1_account_with(
+            "1//v1-token".to_string(),
+            "index@example.com",
+            |_| async { mock_token_response() },
+            |_| async {
+                serde_json::from_value::<crate::modules::oauth::UserInfo>(serde_json::json!({
+                    "email": "real@example.com",
+                    "name": "Real Name"
+                }))
+                .map_err(|e| e.to_string())
+            },
+        )
+        .await;
+
+        assert!(pending.refreshed);
+        assert_eq!(pending.email, "real@example.com");
+        assert_eq!(pending.name.as_deref(), Some("Real Name"));
+        assert_eq!(pending.token_data.access_token, "ya29.v1");
+        assert_eq!(pending.token_data.refresh_token, "1//v1-token");
+    }
+
+    #[tokio::test]
+    async fn test_v1_import_falls_back_without_user_info() {
+        let pending = resolve_v1_account_with(
+            "1//v1-token".to_string(),
+            "index@example.com",
+            |_| async { mock_token_response() },
+            |_| async { Err::<crate::modules::oauth::UserInfo, String>("403".to_string()) },
+        )
+        .await;
+        assert!(pending.refreshed);
+        assert_eq!(pending.email, "index@example.com");
+        assert!(pending.name.is_none());
+        assert!(!pending.token_data.email_unverified);
+
+        // 访问令牌为带 email 声明的 JWT 时，优先使用其中的 email 并标记为未验证
+        let claims = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(r#"{"email":"jwt@example.com"}"#);
+        let jwt = format!("eyJhbGciOiJSUzI1NiJ9.{}.sig", claims);
+        let from_jwt = resolve_v1_account_with(
+            "1//v1-token".to_string(),
+            "index@example.com",
+            |_| async move {
+                serde_json::from_value::<crate::modules::oauth::TokenResponse>(
+                    serde_json::json!({ "access_token": jwt, "expires_in": 3599 }),
+                )
+                .map_err(|e| e.to_string())
+            },
+            |_| async { Err::<crate::modules::oauth::UserInfo, String>("403".to_string()) },
+        )
+        .await;
+        assert_eq!(from_jwt.email, "jwt@example.com");
+        assert_eq!(from_jwt.token_data.email.as_deref(), Some("jwt@example.com"));
+        assert!(from_jwt.token_data.email_unverified);
+        assert!(from_jwt.subject.is_none());
+
+        let expired = resolve_v1_account_with(
+            "1//expired".to_string(),
+            "index@example.com",
+            |_| async { Err::<crate::modules::oauth::TokenResponse, String>("invalid_grant".to_string()) },
+            |_| async { Err::<crate::modules::oauth::UserInfo, String>("not called".to_string()) },
+        )
+        .await;
+        assert!(!expired.refreshed);
+        assert_eq!(expired.token_data.access_token, IMPORTED_ACCESS_TOKEN_PLACEHOLDER);
+    }
+
+    #[tokio::test]
+    async fn test_import_current_v1_account_imports_only_that_account() {
+        let dir = std::env::temp_dir().join(format!("ag_v1_current_{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("antigravity_accounts.json"),
+            r#"{"current_account_id": "a2",
+                "a1": {"email": "a@example.com", "backup_file": "a1.json"},
+                "a2": {"email": "b@example.com", "backup_file": "a2.json"},
+                "a3": {"email": "c@example.com", "backup_file": "a3.json"}}"#,
+        )
+        .unwrap();
+        for id in ["a1", "a2", "a3"] {
+            fs::write(
+                dir.join(format!("{}.json", id)),
+                format!(r#"{{"token": {{"refresh_token": "rt-{}"}}}}"#, id),
+            )
+            .unwrap();
+        }
+
+        let current_id = read_v1_current_account_id(&dir).unwrap();
+        assert_eq!(current_id, "a2");
+
+        let saved = std::sync::Mutex::new(Vec::new());
+        let report = import_v1_from_dir_with(
+            &dir,
+            V1ImportScope {
+                only_account_id: Some(current_id),
+                ..Default::default()
+            },
+            || async { Ok(()) },
+            |_| async { mock_token_response() },
+            |_| async { Err::<crate::modules::oauth::UserInfo, String>("403".to_string()) },
+            |email, _name, token, _subject, _source| {
+                saved.lock().unwrap().push(token.refresh_token.clone());
+                Ok(Account::new(format!("id-{}", email), email, token))
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(*saved.lock().unwrap(), vec!["rt-a2".to_string()]);
+        assert_eq!(report.imported.len(), 1);
+        assert_eq!(report.imported[0].email, "b@example.com");
+        assert!(report.skipped.is_empty());
+
+        // 索引未记录 current_account_id 时明确报错
+        fs::write(
+            dir.join("antigravity_accounts.json"),
+            r#"{"a1": {"email": "a@example.com", "backup_file": "a1.json"}}"#,
+        )
+        .unwrap();
+        let err = read_v1_current_account_id(&dir).unwrap_err();
+        assert!(err.contains("current_account_id"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_v1_import_aborts_when_oauth_unreachable() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let dir = std::env::temp_dir().join(format!("ag_v1_offline_{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("antigravity_accounts.json"),
+            r#"{"a1": {"email": "a@example.com", "backup_file": "a1.json"},
+                "a2": {"email": "b@example.com", "backup_file": "a2.json"}}"#,
+        )
+        .unwrap();
+        fs::write(dir.join("a1.json"), r#"{"token": {"refresh_token": "rt-1"}}"#).unwrap();
+        fs::write(dir.join("a2.json"), r#"{"token": {"refresh_token": "rt-2"}}"#).unwrap();
+
+        let refresh_calls = AtomicUsize::new(0);
+        let result = import_v1_from_dir_with(
+            &dir,
+            V1ImportScope::default(),
+            || async {
+                Err(crate::error::AppError::Network(
+                    "dns error: failed to lookup address".to_string(),
+                    None,
+                ))
+            },
+            |_| {
+                refresh_calls.fetch_add(1, Ordering::SeqCst);
+                async { mock_token_response() }
+            },
+            |_| async { Err::<crate::modules::oauth::UserInfo, String>("not called".to_string()) },
+            |_, _, _, _, _| Err("not called".to_string()),
+        )
+        .await;
+
+        let err = result.unwrap_err();
+        assert!(err.contains("Cannot reach Google OAuth endpoint"));
+        assert!(err.contains("dns error"));
+        assert_eq!(refresh_calls.load(Ordering::SeqCst), 0);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_import_report_order_ignores_index_order() {
+        // 同一组账号以不同的索引键 (即不同的 Map 遍历顺序) 写入 V1 数据
+        async fn run_import(emails: &[&str]) -> (V1ImportReport, Vec<String>) {
+            let dir = std::env::temp_dir().join(format!("ag_v1_order_{}", uuid::Uuid::new_v4()));
+            fs::create_dir_all(&dir).unwrap();
+            let index: serde_json::Map<String, Value> = emails
+                .iter()
+                .enumerate()
+                .map(|(i, email)| {
+                    let id = format!("a{}", i);
+                    fs::write(
+                        dir.join(format!("{}.json", id)),
+                        format!(r#"{{"token": {{"refresh_token": "rt-{}"}}}}"#, email),
+                    )
+                    .unwrap();
+                    (id.clone(), serde_json::json!({ "email": email, "backup_file": format!("{}.json", id) }))
+                })
+                .collect();
+            fs::write(dir.join("antigravity_accounts.json"), Value::Object(index).to_string()).unwrap();
+
+            let saved = std::sync::Mutex::new(Vec::new());
+            let report = import_v1_from_dir_with(
+                &dir,
+                V1ImportScope {
+                    filter: Some(EmailFilter::Domain("example.com".to_string())),
+                    ..Default::default()
+                },
+                || async { Ok(()) },
+                |_| async { mock_token_response() },
+                |_| async { Err::<crate::modules::oauth::UserInfo, String>("403".to_string()) },
+                |email, _name, token, _subject, _source| {
+                    saved.lock().unwrap().push(email.clone());
+                    Ok(Account::new(format!("id-{}", email), email, token))
+                },
+            )
+            .await
+            .unwrap();
+
+            let _ = fs::remove_dir_all(&dir);
+            (report, saved.into_inner().unwrap())
+        }
+        let emails = |report: &V1ImportReport| {
+            (
+                report.imported.iter().map(|a| a.email.clone()).collect::<Vec<_>>(),
+                report.skipped.iter().map(|s| s.email.clone()).collect::<Vec<_>>(),
+            )
+        };
+
+        let (forward, forward_saved) = run_import(&[
+            "carol@example.com",
+            "alice@example.com",
+            "zed@other.com",
+            "bob@example.com",
+            "amy@other.com",
+        ])
+        .await;
+        let (reversed, reversed_saved) = run_import(&[
+            "amy@other.com",
+            "bob@example.com",
+            "zed@other.com",
+            "alice@example.com",
+            "carol@example.com",
+        ])
+        .await;
+
+        let expected_imported = vec![
+            "alice@example.com".to_string(),
+            "bob@example.com".to_string(),
+            "carol@example.com".to_string(),
+        ];
+        assert_eq!(emails(&forward), emails(&reversed));
+        assert_eq!(
+            emails(&forward),
+            (
+                expected_imported.clone(),
+                vec!["amy@other.com".to_string(), "zed@other.com".to_string()],
+            )
+        );
+        // 保存顺序同样与索引顺序无关
+        assert_eq!(forward_saved, expected_imported);
+        assert_eq!(reversed_saved, expected_imported);
+    }
+
+    #[tokio::test]
+    async fn test_import_single_refresh_token_rejects_empty() {
+        assert!(import_single_refresh_token("   ".to_string()).await.is_err());
+    }
+
+    #[test]
+    fn test_email_filter_regex() {
+        let filter = EmailFilter::Regex(r"^(alice|carol)@".to_string());
+        assert!(filter.validate().is_ok());
+        assert!(filter.matches("alice@example.com"));
+        assert!(!filter.matches("bob@example.com"));
+        assert!(EMAIL_FILTER_REGEXES.lock().unwrap().contains_key(r"^(alice|carol)@"));
+        assert!(EmailFilter::Regex("(".to_string()).validate().is_err());
+    }
+}
@@ -5,24 +5,113 @@ const CLIENT_ID: &str = "1071006060591-tmhssin2h21lcre235vtolojh4g403ep.apps.goo
 const CLIENT_SECRET: &str = "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";
 const TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
 const USERINFO_URL: &str = "https://www.googleapis.com/oauth2/v2/userinfo";
-const TOKEN_REFRESH_SKEW_SECONDS: i64 = 900;
+pub(crate) const TOKEN_REFRESH_SKEW_SECONDS: i64 = 900;
+/// Timeout for the reachability probe run before bulk imports
+const CONNECTIVITY_TIMEOUT_SECS: u64 = 5;
 
 const AUTH_URL: &str = "https://accounts.google.com/o/oauth2/v2/auth";
 
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(try_from = "RawTokenResponse")]
 pub struct TokenResponse {
     pub access_token: String,
+    /// 剩余有效期 (秒)；响应中缺省时由 `expires_at` 推算
     pub expires_in: i64,
-    #[serde(default)]
+    /// 绝对过期时间 (秒)，部分非标准服务端会额外返回
+    pub expires_at: Option<i64>,
     pub token_type: String,
-    #[serde(default)]
     pub refresh_token: Option<String>,
+    /// 空格分隔的已授予 scope (刷新响应中可能缺省)
+    pub scope: Option<String>,
     #[serde(skip)]
     pub oauth_client_key: Option<String>,
 }
 
+/// 服务端原始响应：`expires_in` 与 `expires_at` 至少需要其一
+#[derive(Deserialize)]
+struct RawTokenResponse {
+    access_token: String,
+    #[serde(default, deserialize_with = "deserialize_lenient_opt_i64")]
+    expires_in: Option<i64>,
+    #[serde(default, deserialize_with = "deserialize_lenient_opt_i64")]
+    expires_at: Option<i64>,
+    #[serde(default)]
+    token_type: String,
+    #[serde(default)]
+    refresh_token: Option<String>,
+    #[serde(default)]
+    scope: Option<String>,
+}
+
+impl TryFrom<RawTokenResponse> for TokenResponse {
+    type Error = String;
+
+    fn try_from(raw: RawTokenResponse) -> Result<Self, Self::Error> {
+        let expires_in = match (raw.expires_in, raw.expires_at) {
+            (Some(expires_in), _) => expires_in,
+            // 已过期的绝对时间按 0 处理，交由调用方刷新
+            (None, Some(expires_at)) => (expires_at - crate::utils::clock::now()).max(0),
+            (None, None) => return Err("missing field `expires_in` or `expires_at`".to_string()),
+        };
+        Ok(Self {
+            access_token: raw.access_token,
+            expires_in,
+            expires_at: raw.expires_at,
+            token_type: raw.token_type,
+            refresh_token: raw.refresh_token,
+            scope: raw.scope,
+            oauth_client_key: None,
+        })
+    }
+}
+
+/// 数字或数字字符串 (部分服务端把 `expires_in` 编码为 `"3600"`)
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LenientI64 {
+    Number(i64),
+    Text(String),
+}
+
+impl LenientI64 {
+    fn into_i64<E: serde::de::Error>(self) -> Result<i64, E> {
+        match self {
+            LenientI64::Number(n) => Ok(n),
+            LenientI64::Text(s) => s
+                .trim()
+                .parse()
+                .map_err(|_| E::custom(format!("expected an integer, got string {:?}", s))),
+        }
+    }
+}
+
+fn deserialize_lenient_opt_i64<'de, D>(deserializer: D) -> Result<Option<i64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Option::<LenientI64>::deserialize(deserializer)?
+        .map(LenientI64::into_i64)
+        .transpose()
+}
+
+impl TokenResponse {
+    /// 将空格分隔的 scope 字符串拆分为列表
+    pub fn scopes(&self) -> Option<Vec<String>> {
+        let scopes: Vec<String> = self
+            .scope
+            .as_deref()?
+            .split_whitespace()
+            .map(|s| s.to_string())
+            .collect();
+        (!scopes.is_empty()).then_some(scopes)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UserInfo {
+    /// Google 账号的稳定标识 (v2 userinfo `id`，OIDC 中为 `sub`)
+    #[serde(default, alias = "sub")]
+    pub id: Option<String>,
     pub email: String,
     pub name: Option<String>,
     pub given_name: Option<String>,
@@ -50,7 +139,7 @@ impl UserInfo {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 struct OAuthClientConfig {
     key: String,
     label: String,
@@ -59,6 +148,30 @@ struct OAuthClientConfig {
     is_builtin: bool,
 }
 
+// client_secret 不得出现在日志中
+impl std::fmt::Debug for OAuthClientConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OAuthClientConfig")
+            .field("key", &self.key)
+            .field("label", &self.label)
+            .field("client_id", &self.client_id)
+            .field("client_secret", &"<redacted>")
+            .field("is_builtin", &self.is_builtin)
+            .finish()
+    }
+}
+
+/// OAuth client credentials file (JSON). Without `key` it replaces the built-in client.
+#[derive(Deserialize)]
+struct OAuthClientFile {
+    #[serde(default)]
+    key: Option<String>,
+    #[serde(default)]
+    label: Option<String>,
+    client_id: String,
+    client_secret: String,
+}
+
 #[derive(Debug, Clone)]
 struct OAuthClientRegistry {
     clients: Vec<OAuthClientConfig>,
@@ -75,6 +188,12 @@ pub struct OAuthClientDescriptor {
 }
 
 const OAUTH_CLIENTS_ENV: &str = "ANTIGRAVITY_OAUTH_CLIENTS";
+/// Path of an OAuth client credentials file (defaults to `<data_dir>/oauth_client.json` if present)
+const OAUTH_CLIENT_FILE_ENV: &str = "ANTIGRAVITY_OAUTH_CLIENT_FILE";
+const OAUTH_CLIENT_FILE_NAME: &str = "oauth_client.json";
+/// Override the built-in client's credentials without a file
+const OAUTH_CLIENT_ID_ENV: &str = "ANTIGRAVITY_OAUTH_CLIENT_ID";
+const OAUTH_CLIENT_SECRET_ENV: &str = "ANTIGRAVITY_OAUTH_CLIENT_SECRET";
 const ACTIVE_OAUTH_CLIENT_ENV: &str = "ANTIGRAVITY_OAUTH_CLIENT_KEY";
 const DEFAULT_OAUTH_CLIENT_KEY: &str = "antigravity_enterprise";
 
@@ -86,6 +205,79 @@ fn normalize_client_key(key: &str) -> String {
 }
 
 fn build_registry() -> OAuthClientRegistry {
+    let default_file = crate::modules::account::get_data_dir()
+        .ok()
+        .map(|dir| dir.join(OAUTH_CLIENT_FILE_NAME));
+    build_registry_with(|key| std::env::var(key).ok(), default_file)
+}
+
+/// Insert a client, replacing any existing client with the same key
+fn upsert_client(clients: &mut Vec<OAuthClientConfig>, client: OAuthClientConfig, source: &str) {
+    match clients.iter().position(|c| c.key == client.key) {
+        Some(existing_index) => {
+            crate::modules::logger::log_info(&format!(
+                "OAuth client '{}' overridden by {}",
+                client.key, source
+            ));
+            clients[existing_index] = client;
+        }
+        None => {
+            crate::modules::logger::log_info(&format!(
+                "OAuth client '{}' loaded from {}",
+                client.key, source
+            ));
+            clients.push(client);
+        }
+    }
+}
+
+/// Load the credentials file; a missing default file is not an error
+fn load_client_file(path: &std::path::Path) -> Option<OAuthClientConfig> {
+    let content = std::fs::read_to_string(path).ok()?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if let Ok(meta) = std::fs::metadata(path) {
+            if meta.permissions().mode() & 0o077 != 0 {
+                crate::modules::logger::log_warn(&format!(
+                    "OAuth client file {:?} is readable by other users; consider chmod 600",
+                    path
+                ));
+            }
+        }
+    }
+
+    // 解析错误信息可能包含文件内容片段，不记录具体错误
+    let Ok(file) = serde_json::from_str::<OAuthClientFile>(&content) else {
+        crate::modules::logger::log_warn(&format!("Ignored invalid OAuth client file {:?}", path));
+        return None;
+    };
+    if file.client_id.trim().is_empty() || file.client_secret.trim().is_empty() {
+        crate::modules::logger::log_warn(&format!("Ignored incomplete OAuth client file {:?}", path));
+        return None;
+    }
+
+    let key = file
+        .key
+        .map(|k| normalize_client_key(&k))
+        .filter(|k| !k.is_empty())
+        .unwrap_or_else(|| normalize_client_key(DEFAULT_OAUTH_CLIENT_KEY));
+    Some(OAuthClientConfig {
+        label: file.label.unwrap_or_else(|| key.clone()),
+        key,
+        client_id: file.client_id.trim().to_string(),
+        client_secret: file.client_secret.trim().to_string(),
+        is_builtin: false,
+    })
+}
+
+/// Precedence (later wins for the same key): built-in, credentials file,
+/// `ANTIGRAVITY_OAUTH_CLIENT_ID`/`_SECRET`, `ANTIGRAVITY_OAUTH_CLIENTS`
+fn build_registry_with<E>(env: E, default_file: Option<std::path::PathBuf>) -> OAuthClientRegistry
+where
+    E: Fn(&str) -> Option<String>,
+{
     let mut clients: Vec<OAuthClientConfig> = vec![OAuthClientConfig {
         key: normalize_client_key(DEFAULT_OAUTH_CLIENT_KEY),
         label: "Antigravity Enterprise".to_string(),
@@ -94,7 +286,53 @@ fn build_registry() -> OAuthClientRegistry {
         is_builtin: true,
     }];
 
-    if let Ok(raw_extra_clients) = std::env::var(OAUTH_CLIENTS_ENV) {
+    let client_file = env(OAUTH_CLIENT_FILE_ENV)
+        .filter(|p| !p.trim().is_empty())
+        .map(std::path::PathBuf::from);
+    if let Some(path) = &client_file {
+        if !path.exists() {
+            crate::modules::logger::log_warn(&format!(
+                "OAuth client file {:?} from {} does not exist",
+                path, OAUTH_CLIENT_FILE_ENV
+            ));
+        }
+    }
+    if let Some(path) = client_file.or(default_file) {
+        if let Some(client) = load_client_file(&path) {
+            upsert_client(&mut clients, client, &format!("{:?}", path));
+        }
+    }
+
+    let env_id = env(OAUTH_CLIENT_ID_ENV).filter(|v| !v.trim().is_empty());
+    let env_secret = env(OAUTH_CLIENT_SECRET_ENV).filter(|v| !v.trim().is_empty());
+    match (env_id, env_secret) {
+        (Some(client_id), Some(client_secret)) => {
+            let key = normalize_client_key(DEFAULT_OAUTH_CLIENT_KEY);
+            let label = clients
+                .iter()
+                .find(|c| c.key == key)
+                .map(|c| c.label.clone())
+                .unwrap_or_else(|| key.clone());
+            upsert_client(
+                &mut clients,
+                OAuthClientConfig {
+                    key,
+                    label,
+                    client_id: client_id.trim().to_string(),
+                    client_secret: client_secret.trim().to_string(),
+                    is_builtin: false,
+                },
+                OAUTH_CLIENT_ID_ENV,
+            );
+        }
+        (None, None) => {}
+        _ => crate::modules::logger::log_warn(&format!(
+            "Both {} and {} must be set; ignoring OAuth client override",
+            OAUTH_CLIENT_ID_ENV, OAUTH_CLIENT_SECRET_ENV
+        )),
+    }
+
+    if let Some(raw_extra_clients) = env(OAUTH_CLIENTS_ENV) {
         for entry in raw_extra_clients.split(';') {
             let trimmed = entry.trim();
             if trimmed.is_empty() {
@@ -103,10 +341,11 @@ fn build_registry() -> OAuthClientRegistry {
 
             // Expected format: key|client_id|client_secret|optional_label
             let parts: Vec<&str> = trimmed.split('|').map(|v| v.trim()).collect();
+            // 条目中含 client_secret，日志只记录 key
             if parts.len() < 3 {
                 crate::modules::logger::log_warn(&format!(
                     "Ignored invalid OAuth client entry in {}: {}",
-                    OAUTH_CLIENTS_ENV, trimmed
+                    OAUTH_CLIENTS_ENV, parts[0]
                 ));
                 continue;
             }
@@ -115,7 +354,7 @@ fn build_registry() -> OAuthClientRegistry {
             if key.is_empty() || parts[1].is_empty() || parts[2].is_empty() {
                 crate::modules::logger::log_warn(&format!(
                     "Ignored incomplete OAuth client entry in {}: {}",
-                    OAUTH_CLIENTS_ENV, trimmed
+                    OAUTH_CLIENTS_ENV, parts[0]
                 ));
                 continue;
             }
@@ -134,24 +373,11 @@ fn build_registry() -> OAuthClientRegistry {
                 is_builtin: false,
             };
 
-            if let Some(existing_index) = clients.iter().position(|c| c.key == key) {
-                clients[existing_index] = custom_client;
-                crate::modules::logger::log_info(&format!(
-                    "OAuth client '{}' overridden by {}",
-                    key, OAUTH_CLIENTS_ENV
-                ));
-            } else {
-                clients.push(custom_client);
-                crate::modules::logger::log_info(&format!(
-                    "OAuth client '{}' loaded from {}",
-                    key, OAUTH_CLIENTS_ENV
-                ));
-            }
+            upsert_client(&mut clients, custom_client, OAUTH_CLIENTS_ENV);
         }
     }
 
-    let mut active_key = std::env::var(ACTIVE_OAUTH_CLIENT_ENV)
-        .ok()
+    let mut active_key = env(ACTIVE_OAUTH_CLIENT_ENV)
         .map(|v| normalize_client_key(&v))
         .filter(|v| !v.is_empty())
         .unwrap_or_else(|| normalize_client_key(DEFAULT_OAUTH_CLIENT_KEY));
@@ -363,18 +589,106 @@ pub fn get_auth_url(redirect_uri: &str, state: &str) -> String {
         .expect("Failed to build OAuth URL")
 }
 
+/// HTTP response as seen by the OAuth flow (status + raw body)
+#[derive(Debug, Clone)]
+pub struct OAuthHttpResponse {
+    pub status: reqwest::StatusCode,
+    pub body: String,
+}
+
+/// Request failed before any HTTP response was received
+#[derive(Debug, Clone)]
+pub struct OAuthTransportError {
+    pub message: String,
+    /// DNS/connect/proxy failure or timeout (shown with a proxy hint)
+    pub is_connect_or_timeout: bool,
+}
+
+/// Minimal HTTP transport the OAuth functions depend on.
+/// `HttpOAuthTransport` is used in production; tests provide a mock to run without network.
+pub trait OAuthTransport: Send + Sync {
+    /// POST an `application/x-www-form-urlencoded` body
+    fn post_form(
+        &self,
+        url: &str,
+        form: &[(&str, &str)],
+        account_id: Option<&str>,
+    ) -> impl std::future::Future<Output = Result<OAuthHttpResponse, OAuthTransportError>> + Send;
+
+    /// GET with a bearer token
+    fn get_bearer(
+        &self,
+        url: &str,
+        access_token: &str,
+        account_id: Option<&str>,
+    ) -> impl std::future::Future<Output = Result<OAuthHttpResponse, OAuthTransportError>> + Send;
+}
+
+/// Default transport: proxy pool client (per account) or shared client, behind the upstream throttle
+pub struct HttpOAuthTransport;
+
+impl HttpOAuthTransport {
+    async fn read_response(
+        response: Result<rquest::Response, rquest::Error>,
+    ) -> Result<OAuthHttpResponse, OAuthTransportError> {
+        let response = response.map_err(|e| OAuthTransportError {
+            is_connect_or_timeout: e.is_connect() || e.is_timeout(),
+            message: e.to_string(),
+        })?;
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        Ok(OAuthHttpResponse { status, body })
+    }
+}
+
+impl OAuthTransport for HttpOAuthTransport {
+    async fn post_form(
+        &self,
+        url: &str,
+        form: &[(&str, &str)],
+        account_id: Option<&str>,
+    ) -> Result<OAuthHttpResponse, OAuthTransportError> {
+        // [PHASE 2] 根据 account_id 使用对应的代理；登录时尚无 account_id，使用全局池阶梯逻辑
+        let client = if let Some(pool) = crate::proxy::proxy_pool::get_global_proxy_pool() {
+            pool.get_effective_standard_client(account_id, 60).await
+        } else {
+            crate::utils::http::get_long_standard_client()
+        };
+
+        crate::proxy::upstream::throttle::acquire_upstream_permit().await;
+        let response = client
+            .post(url)
+            .header(rquest::header::USER_AGENT, crate::constants::NATIVE_OAUTH_USER_AGENT.as_str())
+            .form(form)
+            .send()
+            .await;
+        Self::read_response(response).await
+    }
+
+    async fn get_bearer(
+        &self,
+        url: &str,
+        access_token: &str,
+        account_id: Option<&str>,
+    ) -> Result<OAuthHttpResponse, OAuthTransportError> {
+        let client = if let Some(pool) = crate::proxy::proxy_pool::get_global_proxy_pool() {
+            pool.get_effective_client(account_id, 15).await
+        } else {
+            crate::utils::http::get_client()
+        };
+
+        crate::proxy::upstream::throttle::acquire_upstream_permit().await;
+        let response = client.get(url).bearer_auth(access_token).send().await;
+        Self::read_response(response).await
+    }
+}
+
 async fn exchange_code_once(
+    transport: &impl OAuthTransport,
     code: &str,
     redirect_uri: &str,
     client_cfg: &OAuthClientConfig,
 ) -> Result<TokenResponse, (Option<reqwest::StatusCode>, String)> {
-    // [PHASE 2] 对于登录行为，尚未有 account_id，使用全局池阶梯逻辑
-    let client = if let Some(pool) = crate::proxy::proxy_pool::get_global_proxy_pool() {
-        pool.get_effective_standard_client(None, 60).await
-    } else {
-        crate::utils::http::get_long_standard_client()
-    };
-    
     let params = [
         ("client_id", client_cfg.client_id.as_str()),
         ("client_secret", client_cfg.client_secret.as_str()),
@@ -388,30 +702,25 @@ async fn exchange_code_once(
         crate::constants::NATIVE_OAUTH_USER_AGENT.as_str()
     );
 
-    let response = client
-        .post(TOKEN_URL)
-        .header(rquest::header::USER_AGENT, crate::constants::NATIVE_OAUTH_USER_AGENT.as_str())
-        .form(&params)
-        .send()
+    let response = transport
+        .post_form(TOKEN_URL, &params, None)
         .await
         .map_err(|e| {
-            if e.is_connect() || e.is_timeout() {
+            if e.is_connect_or_timeout {
                 (
                     None,
                     format!(
                         "Token exchange request failed: {}. 请检查你的网络代理设置，确保可以稳定连接 Google 服务。",
-                        e
+                        e.message
                     ),
                 )
             } else {
-                (None, format!("Token exchange request failed: {}", e))
+                (None, format!("Token exchange request failed: {}", e.message))
             }
         })?;
 
-    if response.status().is_success() {
-        let mut token_res = response
-            .json::<TokenResponse>()
-            .await
+    if response.status.is_success() {
+        let mut token_res = serde_json::from_str::<TokenResponse>(&response.body)
             .map_err(|e| (None, format!("Token parsing failed: {}", e)))?;
         token_res.oauth_client_key = Some(client_cfg.key.clone());
         
@@ -439,11 +748,9 @@ async fn exchange_code_once(
         
         Ok(token_res)
     } else {
-        let status = response.status();
-        let error_text = response.text().await.unwrap_or_default();
         Err((
-            Some(status),
-            format!("Token exchange failed: {}", error_text),
+            Some(response.status),
+            format!("Token exchange failed: {}", response.body),
         ))
     }
 }
@@ -463,7 +770,7 @@ pub async fn exchange_code_with_client(
     let mut attempt_errors: Vec<String> = Vec::new();
 
     for (idx, client_cfg) in candidates.iter().enumerate() {
-        match exchange_code_once(code, redirect_uri, client_cfg).await {
+        match exchange_code_once(&HttpOAuthTransport, code, redirect_uri, client_cfg).await {
             Ok(token_res) => {
                 if idx > 0 {
                     crate::modules::logger::log_info(&format!(
@@ -508,23 +815,12 @@ pub async fn exchange_code(code: &str, redirect_uri: &str) -> Result<TokenRespon
 }
 
 async fn refresh_access_token_once(
+    transport: &impl OAuthTransport,
     refresh_token: &str,
     account_id: Option<&str>,
     client_cfg: &OAuthClientConfig,
 ) -> Result<TokenResponse, (Option<reqwest::StatusCode>, String)> {
-    // [PHASE 2] 根据 account_id 使用对应的代理
-    let client = if let Some(pool) = crate::proxy::proxy_pool::get_global_proxy_pool() {
-        pool.get_effective_standard_client(account_id, 60).await
-    } else {
-        crate::utils::http::get_long_standard_client()
-    };
-    
-    let params = [
-        ("client_id", client_cfg.client_id.as_str()),
-        ("client_secret", client_cfg.client_secret.as_str()),
-        ("refresh_token", refresh_token),
-        ("grant_type", "refresh_token"),
-    ];
+    let params = refresh_request_params(refresh_token, client_cfg);
 
     // [FIX #1583] 提供更详细的日志，帮助诊断 Docker 环境下的代理问题
     if let Some(id) = account_id {
@@ -538,30 +834,25 @@ async fn refresh_access_token_once(
         crate::constants::NATIVE_OAUTH_USER_AGENT.as_str()
     );
 
-    let response = client
-        .post(TOKEN_URL)
-        .header(rquest::header::USER_AGENT, crate::constants::NATIVE_OAUTH_USER_AGENT.as_str())
-        .form(&params)
-        .send()
+    let response = transport
+        .post_form(TOKEN_URL, &params, account_id)
         .await
         .map_err(|e| {
-            if e.is_connect() || e.is_timeout() {
+            if e.is_connect_or_timeout {
                 (
                     None,
                     format!(
                         "Refresh request failed: {}. 无法连接 Google 授权服务器，请检查代理设置。",
-                        e
+                        e.message
                     ),
                 )
             } else {
-                (None, format!("Refresh request failed: {}", e))
+                (None, format!("Refresh request failed: {}", e.message))
             }
         })?;
 
-    if response.status().is_success() {
-        let mut token_data = response
-            .json::<TokenResponse>()
-            .await
+    if response.status.is_success() {
+        let mut token_data = serde_json::from_str::<TokenResponse>(&response.body)
             .map_err(|e| (None, format!("Refresh data parsing failed: {}", e)))?;
         token_data.oauth_client_key = Some(client_cfg.key.clone());
         
@@ -571,15 +862,72 @@ async fn refresh_access_token_once(
         ));
         Ok(token_data)
     } else {
-        let status = response.status();
-        let error_text = response.text().await.unwrap_or_default();
-        Err((Some(status), format!("Refresh failed: {}", error_text)))
+        Err((Some(response.status), format!("Refresh failed: {}", response.body)))
     }
 }
 
+/// Form parameters of a refresh_token grant request
+fn refresh_request_params<'a>(
+    refresh_token: &'a str,
+    client_cfg: &'a OAuthClientConfig,
+) -> [(&'static str, &'a str); 4] {
+    [
+        ("client_id", client_cfg.client_id.as_str()),
+        ("client_secret", client_cfg.client_secret.as_str()),
+        ("refresh_token", refresh_token),
+        ("grant_type", "refresh_token"),
+    ]
+}
+
 /// Refresh access_token using refresh_token with optional preferred OAuth client key.
 /// If client mismatch occurs, it retries with other configured clients.
+/// `email` is only used to label the `oauth_refresh` span.
 pub async fn refresh_access_token_with_client(
+    refresh_token: &str,
+    account_id: Option<&str>,
+    email: Option<&str>,
+    preferred_client_key: Option<&str>,
+) -> Result<TokenResponse, String> {
+    refresh_with_transport(
+        &HttpOAuthTransport,
+        refresh_token,
+        account_id,
+        email,
+        preferred_client_key,
+    )
+    .await
+}
+
+// 注意：span 字段中不得包含 refresh_token / access_token
+#[tracing::instrument(
+    name = "oauth_refresh",
+    skip_all,
+    fields(
+        email = email.unwrap_or("-"),
+        account_id = account_id.unwrap_or("-"),
+        client = preferred_client_key.unwrap_or("default"),
+        elapsed_ms = tracing::field::Empty,
+    )
+)]
+async fn refresh_with_transport(
+    transport: &impl OAuthTransport,
+    refresh_token: &str,
+    account_id: Option<&str>,
+    email: Option<&str>,
+    preferred_client_key: Option<&str>,
+) -> Result<TokenResponse, String> {
+    let started = std::time::Instant::now();
+    let result =
+        refresh_with_candidate_clients(transport, refresh_token, account_id, preferred_client_key)
+            .await;
+    let elapsed_ms = started.elapsed().as_millis() as u64;
+    tracing::Span::current().record("elapsed_ms", elapsed_ms);
+    tracing::debug!(elapsed_ms, ok = result.is_ok(), "OAuth refresh finished");
+    result
+}
+
+async fn refresh_with_candidate_clients(
+    transport: &impl OAuthTransport,
     refresh_token: &str,
     account_id: Option<&str>,
     preferred_client_key: Option<&str>,
@@ -592,7 +940,7 @@ pub async fn refresh_access_token_with_client(
     let mut attempt_errors: Vec<String> = Vec::new();
 
     for (idx, client_cfg) in candidates.iter().enumerate() {
-        match refresh_access_token_once(refresh_token, account_id, client_cfg).await {
+        match refresh_access_token_once(transport, refresh_token, account_id, client_cfg).await {
             Ok(token_res) => {
                 if idx > 0 {
                     crate::modules::logger::log_info(&format!(
@@ -636,41 +984,75 @@ pub async fn refresh_access_token(
     refresh_token: &str,
     account_id: Option<&str>,
 ) -> Result<TokenResponse, String> {
-    refresh_access_token_with_client(refresh_token, account_id, None).await
+    refresh_access_token_with_client(refresh_token, account_id, None, None).await
 }
 
-/// Get user info
-pub async fn get_user_info(access_token: &str, account_id: Option<&str>) -> Result<UserInfo, String> {
+/// Fast reachability probe against the token endpoint.
+/// Any HTTP response counts as reachable; only DNS/connect/proxy failures and timeouts fail.
+pub async fn check_connectivity() -> crate::error::AppResult<()> {
     let client = if let Some(pool) = crate::proxy::proxy_pool::get_global_proxy_pool() {
-        pool.get_effective_client(account_id, 15).await
+        pool.get_effective_client(None, CONNECTIVITY_TIMEOUT_SECS).await
     } else {
         crate::utils::http::get_client()
     };
-    
-    let response = client
-        .get(USERINFO_URL)
-        .bearer_auth(access_token)
-        .send()
+
+    crate::proxy::upstream::throttle::acquire_upstream_permit().await;
+    let probe = client.head(TOKEN_URL).send();
+    match tokio::time::timeout(std::time::Duration::from_secs(CONNECTIVITY_TIMEOUT_SECS), probe).await {
+        Ok(Ok(response)) => {
+            tracing::debug!("[OAuth] Connectivity check OK (HTTP {})", response.status());
+            Ok(())
+        }
+        Ok(Err(e)) => Err(crate::error::AppError::Network(
+            format!("Token endpoint unreachable: {}", e),
+            None,
+        )),
+        Err(_) => Err(crate::error::AppError::Network(
+            format!("Token endpoint did not respond within {}s", CONNECTIVITY_TIMEOUT_SECS),
+            None,
+        )),
+    }
+}
+
+/// Get user info
+pub async fn get_user_info(access_token: &str, account_id: Option<&str>) -> Result<UserInfo, String> {
+    get_user_info_with(&HttpOAuthTransport, access_token, account_id).await
+}
+
+async fn get_user_info_with(
+    transport: &impl OAuthTransport,
+    access_token: &str,
+    account_id: Option<&str>,
+) -> Result<UserInfo, String> {
+    let response = transport
+        .get_bearer(USERINFO_URL, access_token, account_id)
         .await
-        .map_err(|e| format!("User info request failed: {}", e))?;
+        .map_err(|e| format!("User info request failed: {}", e.message))?;
 
-    if response.status().is_success() {
-        response.json::<UserInfo>()
-            .await
+    if response.status.is_success() {
+        serde_json::from_str::<UserInfo>(&response.body)
             .map_err(|e| format!("User info parsing failed: {}", e))
     } else {
-        let error_text = response.text().await.unwrap_or_default();
-        Err(format!("Failed to get user info: {}", error_text))
+        Err(format!("Failed to get user info: {}", response.body))
     }
 }
 
 /// Check and refresh Token if needed
 /// Returns the latest access_token
+#[tracing::instrument(
+    name = "ensure_fresh_token",
+    skip_all,
+    fields(
+        email = current_token.email.as_deref().unwrap_or("-"),
+        account_id = account_id.unwrap_or("-"),
+        phase = "check",
+    )
+)]
 pub async fn ensure_fresh_token(
     current_token: &crate::models::TokenData,
     account_id: Option<&str>,
 ) -> Result<crate::models::TokenData, String> {
-    let now = chrono::Local::now().timestamp();
+    let now = crate::utils::clock::now();
     
     // Keep enough validity to avoid immediate post-switch refresh failure.
     if current_token.expiry_timestamp > now + TOKEN_REFRESH_SKEW_SECONDS {
@@ -678,16 +1060,19 @@ pub async fn ensure_fresh_token(
     }
     
     // Need to refresh
+    tracing::Span::current().record("phase", "refresh");
     crate::modules::logger::log_info(&format!("Token expiring soon for account {:?}, refreshing...", account_id));
     let response = refresh_access_token_with_client(
         &current_token.refresh_token,
         account_id,
+        current_token.email.as_deref(),
         current_token.oauth_client_key.as_deref(),
     )
     .await?;
 
     let oauth_client_key =
         normalize_refreshed_oauth_client_key(current_token, response.oauth_client_key.clone());
+    let scopes = response.scopes().or_else(|| current_token.scopes.clone());
     
     // Construct new TokenData
     Ok(crate::models::TokenData::new(
@@ -699,13 +1084,146 @@ pub async fn ensure_fresh_token(
         None,  // session_id will be generated in token_manager
         current_token.is_gcp_tos,
     )
-    .with_oauth_client_key(oauth_client_key))
+    .with_oauth_client_key(oauth_client_key)
+    .with_scopes(scopes))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_client_file_overrides_default_client_in_refresh_request() {
+        let dir = std::env::temp_dir().join(format!("ag_oauth_client_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(OAUTH_CLIENT_FILE_NAME);
+        std::fs::write(
+            &path,
+            r#"{"client_id": "custom-id.apps.googleusercontent.com", "client_secret": "custom-secret"}"#,
+        )
+        .unwrap();
+        let path_str = path.to_string_lossy().to_string();
+
+        let registry = build_registry_with(
+            |key| (key == OAUTH_CLIENT_FILE_ENV).then(|| path_str.clone()),
+            None,
+        );
+        let client = active_or_first_client(&registry).unwrap();
+        assert_eq!(client.key, DEFAULT_OAUTH_CLIENT_KEY);
+        assert!(!client.is_builtin);
+        let params = refresh_request_params("1//rt", &client);
+        assert!(params.contains(&("client_id", "custom-id.apps.googleusercontent.com")));
+        assert!(params.contains(&("client_secret", "custom-secret")));
+        assert!(params.contains(&("refresh_token", "1//rt")));
+        // secret 不会出现在调试输出中
+        assert!(!format!("{:?}", client).contains("custom-secret"));
+
+        // 默认位置的文件同样生效；没有任何配置时回退到内置凭据
+        let from_default = build_registry_with(|_| None, Some(path.clone()));
+        assert_eq!(
+            active_or_first_client(&from_default).unwrap().client_id,
+            "custom-id.apps.googleusercontent.com"
+        );
+        let builtin = build_registry_with(|_| None, Some(dir.join("missing.json")));
+        let builtin_client = active_or_first_client(&builtin).unwrap();
+        assert_eq!(builtin_client.client_id, CLIENT_ID);
+        assert!(builtin_client.is_builtin);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// (URL, [(字段名, 字段值)], account_id)
+    type RecordedRequest = (String, Vec<(String, String)>, Option<String>);
+
+    /// 记录请求并按顺序返回预设响应的传输层
+    #[derive(Default)]
+    struct MockTransport {
+        responses: std::sync::Mutex<std::collections::VecDeque<OAuthHttpResponse>>,
+        requests: std::sync::Mutex<Vec<RecordedRequest>>,
+    }
+
+    impl MockTransport {
+        fn respond(self, status: u16, body: &str) -> Self {
+            self.responses.lock().unwrap().push_back(OAuthHttpResponse {
+                status: reqwest::StatusCode::from_u16(status).unwrap(),
+                body: body.to_string(),
+            });
+            self
+        }
+
+        fn next(
+            &self,
+            url: &str,
+            fields: Vec<(String, String)>,
+            account_id: Option<&str>,
+        ) -> Result<OAuthHttpResponse, OAuthTransportError> {
+            self.requests
+                .lock()
+                .unwrap()
+                .push((url.to_string(), fields, account_id.map(|s| s.to_string())));
+            self.responses.lock().unwrap().pop_front().ok_or(OAuthTransportError {
+                message: "no mock response".to_string(),
+                is_connect_or_timeout: true,
+            })
+        }
+    }
+
+    impl OAuthTransport for MockTransport {
+        async fn post_form(
+            &self,
+            url: &str,
+            form: &[(&str, &str)],
+            account_id: Option<&str>,
+        ) -> Result<OAuthHttpResponse, OAuthTransportError> {
+            let fields = form.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+            self.next(url, fields, account_id)
+        }
+
+        async fn get_bearer(
+            &self,
+            url: &str,
+            access_token: &str,
+            account_id: Option<&str>,
+        ) -> Result<OAuthHttpResponse, OAuthTransportError> {
+            let fields = vec![("bearer".to_string(), access_token.to_string())];
+            self.next(url, fields, account_id)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refresh_and_user_info_through_mock_transport() {
+        let transport = MockTransport::default()
+            .respond(200, r#"{"access_token": "ya29.mock", "expires_in": 3599, "token_type": "Bearer"}"#)
+            .respond(200, r#"{"email": "mock@test.com", "name": "Mock User"}"#);
+
+        let token = refresh_with_candidate_clients(&transport, "1//mock-refresh", Some("acc-mock"), None)
+            .await
+            .unwrap();
+        assert_eq!(token.access_token, "ya29.mock");
+        assert_eq!(token.expires_in, 3599);
+        assert!(token.oauth_client_key.is_some());
+
+        let info = get_user_info_with(&transport, &token.access_token, Some("acc-mock"))
+            .await
+            .unwrap();
+        assert_eq!(info.email, "mock@test.com");
+
+        let requests = transport.requests.lock().unwrap().clone();
+        assert_eq!(requests.len(), 2);
+        let (url, form, account_id) = &requests[0];
+        assert_eq!(url, TOKEN_URL);
+        assert_eq!(account_id.as_deref(), Some("acc-mock"));
+        assert!(form.contains(&("grant_type".to_string(), "refresh_token".to_string())));
+        assert!(form.contains(&("refresh_token".to_string(), "1//mock-refresh".to_string())));
+        assert_eq!(requests[1].0, USERINFO_URL);
+        assert_eq!(requests[1].1, vec![("bearer".to_string(), "ya29.mock".to_string())]);
+
+        // 非 2xx 响应体透传为错误信息
+        let failing = MockTransport::default().respond(500, "backend error");
+        let err = get_user_info_with(&failing, "ya29.mock", None).await.unwrap_err();
+        assert!(err.contains("backend error"));
+    }
+
     #[test]
     fn test_get_auth_url_contains_state() {
         let redirect_uri = "http://localhost:8080/callback";
@@ -717,4 +1235,176 @@ mod tests {
         assert!(url.contains("response_type=code"));
     }
 
+    #[test]
+    fn test_token_response_scope_parsed_into_token_data() {
+        let response: TokenResponse = serde_json::from_str(
+            r#"{
+                "access_token": "ya29.test",
+                "expires_in": 3599,
+                "token_type": "Bearer",
+                "scope": "https://www.googleapis.com/auth/cloud-platform  openid https://www.googleapis.com/auth/userinfo.email"
+            }"#,
+        )
+        .unwrap();
+
+        let scopes = response.scopes().unwrap();
+        assert_eq!(
+            scopes,
+            vec![
+                "https://www.googleapis.com/auth/cloud-platform",
+                "openid",
+                "https://www.googleapis.com/auth/userinfo.email",
+            ]
+        );
+
+        let token = crate::models::TokenData::new(
+            response.access_token.clone(),
+            "rt".to_string(),
+            response.expires_in,
+            None,
+            None,
+            None,
+            true,
+        )
+        .with_scopes(response.scopes());
+        assert!(token.has_scope("openid"));
+        assert!(!token.has_scope("https://www.googleapis.com/auth/userinfo.profile"));
+
+        // 持久化后可还原；旧数据无该字段时为 None
+        let restored: crate::models::TokenData =
+            serde_json::from_value(serde_json::to_value(&token).unwrap()).unwrap();
+        assert_eq!(restored.scopes, token.scopes);
+
+        let without_scope: TokenResponse =
+            serde_json::from_str(r#"{"access_token": "a", "expires_in": 1}"#).unwrap();
+        assert!(without_scope.scopes().is_none());
+    }
+
+    #[test]
+    fn test_token_response_accepts_numeric_string_expiry() {
+        let numeric: TokenResponse =
+            serde_json::from_str(r#"{"access_token": "a", "expires_in": 3600}"#).unwrap();
+        assert_eq!(numeric.expires_in, 3600);
+        assert_eq!(numeric.expires_at, None);
+
+        let text: TokenResponse = serde_json::from_str(
+            r#"{"access_token": "a", "expires_in": "3600", "expires_at": "1900000000"}"#,
+        )
+        .unwrap();
+        assert_eq!(text.expires_in, 3600);
+        assert_eq!(text.expires_at, Some(1_900_000_000));
+
+        let null_expires_at: TokenResponse = serde_json::from_str(
+            r#"{"access_token": "a", "expires_in": 1, "expires_at": null}"#,
+        )
+        .unwrap();
+        assert_eq!(null_expires_at.expires_at, None);
+
+        assert!(serde_json::from_str::<TokenResponse>(
+            r#"{"access_token": "a", "expires_in": "soon"}"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_token_response_derives_expiry_from_expires_at() {
+        let now = crate::utils::clock::now();
+        let only_expires_at: TokenResponse = serde_json::from_str(&format!(
+            r#"{{"access_token": "a", "expires_at": {}}}"#,
+            now + 3600
+        ))
+        .unwrap();
+        assert_eq!(only_expires_at.expires_at, Some(now + 3600));
+        assert!((3590..=3600).contains(&only_expires_at.expires_in));
+
+        // 已过期的绝对时间不产生负数有效期
+        let expired: TokenResponse = serde_json::from_str(&format!(
+            r#"{{"access_token": "a", "expires_at": "{}"}}"#,
+            now - 60
+        ))
+        .unwrap();
+        assert_eq!(expired.expires_in, 0);
+
+        // expires_in 优先于 expires_at
+        let both: TokenResponse = serde_json::from_str(&format!(
+            r#"{{"access_token": "a", "expires_in": 120, "expires_at": {}}}"#,
+            now + 3600
+        ))
+        .unwrap();
+        assert_eq!(both.expires_in, 120);
+
+        assert!(serde_json::from_str::<TokenResponse>(r#"{"access_token": "a"}"#).is_err());
+    }
+
+    /// (span 名称, [(字段名, 字段值)])
+    type RecordedSpan = (String, Vec<(String, String)>);
+
+    /// 记录每个新建 span 的名称与字段
+    #[derive(Clone, Default)]
+    struct SpanRecorder {
+        spans: std::sync::Arc<std::sync::Mutex<Vec<RecordedSpan>>>,
+    }
+
+    struct FieldVisitor<'a>(&'a mut Vec<(String, String)>);
+
+    impl tracing::field::Visit for FieldVisitor<'_> {
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+            self.0.push((field.name().to_string(), format!("{:?}", value)));
+        }
+
+        fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+            self.0.push((field.name().to_string(), value.to_string()));
+        }
+    }
+
+    impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for SpanRecorder {
+        fn on_new_span(
+            &self,
+            attrs: &tracing::span::Attributes<'_>,
+            _id: &tracing::span::Id,
+            _ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            let mut fields = Vec::new();
+            attrs.record(&mut FieldVisitor(&mut fields));
+            self.spans
+                .lock()
+                .unwrap()
+                .push((attrs.metadata().name().to_string(), fields));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refresh_span_records_email_without_tokens() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let recorder = SpanRecorder::default();
+        let subscriber = tracing_subscriber::registry().with(recorder.clone());
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let transport = MockTransport::default().respond(
+            200,
+            r#"{"access_token": "ya29.secret-access", "expires_in": 3599, "token_type": "Bearer"}"#,
+        );
+        let token = refresh_with_transport(
+            &transport,
+            "1//secret-refresh",
+            Some("acc-span"),
+            Some("span@test.com"),
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(token.access_token, "ya29.secret-access");
+
+        let spans = recorder.spans.lock().unwrap().clone();
+        let (_, fields) = spans
+            .iter()
+            .find(|(name, _)| name == "oauth_refresh")
+            .expect("refresh span should be created");
+        assert!(fields.contains(&("email".to_string(), "span@test.com".to_string())));
+        assert!(fields.contains(&("account_id".to_string(), "acc-span".to_string())));
+
+        let all_values: Vec<&String> = spans.iter().flat_map(|(_, f)| f.iter().map(|(_, v)| v)).collect();
+        assert!(all_values.iter().all(|v| !v.contains("secret")), "tokens must not be span fields");
+    }
 }
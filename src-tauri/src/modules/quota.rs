@@ -118,6 +118,7 @@ async fn fetch_project_id(access_token: &str, email: &str, account_id: Option<&s
     let client = create_standard_client(account_id).await;
     let meta = json!({"metadata": {"ideType": "ANTIGRAVITY"}});
 
+    crate::proxy::upstream::throttle::acquire_upstream_permit().await;
     let res = client
         .post(format!("{}/v1internal:loadCodeAssist", CLOUD_CODE_BASE_URL))
         .header(rquest::header::AUTHORIZATION, format!("Bearer {}", access_token))
@@ -217,6 +218,7 @@ pub async fn fetch_quota_with_cache(
     for (ep_idx, ep_url) in QUOTA_API_ENDPOINTS.iter().enumerate() {
         let has_next = ep_idx + 1 < QUOTA_API_ENDPOINTS.len();
 
+        crate::proxy::upstream::throttle::acquire_upstream_permit().await;
         match client
             .post(*ep_url)
             .bearer_auth(access_token)
@@ -273,6 +275,8 @@ pub async fn fetch_quota_with_cache(
                         let percentage = quota_info.remaining_fraction
                             .map(|f| (f * 100.0) as i32)
                             .unwrap_or(0);
+                        // 上游只返回剩余比例，换算为百分比后满额即 100
+                        let quota_max = quota_info.remaining_fraction.map(|_| 100);
                         
                         let reset_time = quota_info.reset_time.clone().unwrap_or_default();
                         
@@ -282,6 +286,7 @@ pub async fn fetch_quota_with_cache(
                                 name,
                                 percentage,
                                 reset_time,
+                                quota_max,
                                 display_name: info.display_name,
                                 supports_images: info.supports_images,
                                 supports_thinking: info.supports_thinking,
@@ -517,7 +522,7 @@ pub async fn warm_up_all_accounts() -> Result<String, String> {
             tokio::spawn(async move {
                 let mut success = 0;
                 let batch_size = 3;
-                let now_ts = chrono::Utc::now().timestamp();
+                let now_ts = crate::utils::clock::now();
                 
                 for (batch_idx, batch) in warmup_items.chunks(batch_size).enumerate() {
                     let mut handles = Vec::new();
@@ -623,7 +628,7 @@ pub async fn warm_up_account(account_id: &str) -> Result<String, String> {
         for (name, pct) in models_to_warm {
             if warmup_model_directly(&token, &name, &pid, &email, pct, Some(&account_id_clone)).await {
                 let history_key = format!("{}:{}:100", email, name);
-                let now_ts = chrono::Utc::now().timestamp();
+                let now_ts = crate::utils::clock::now();
                 crate::modules::scheduler::record_warmup_history(&history_key, now_ts);
             }
             tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
@@ -0,0 +1,168 @@
+// 模型能力缓存：持久化每个账号可服务的模型 (model_quotas 的键) 及记录时间
+//
+// 重启后在首次实时刷新前即可按能力选号；超过 TTL 的记录由后台任务重新拉取配额。
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+/// 缓存文件名 (位于数据目录下)
+pub const CAPABILITY_CACHE_FILE: &str = "model_capabilities.json";
+
+/// 能力记录的默认有效期 (秒)
+pub const DEFAULT_CAPABILITY_TTL_SECS: i64 = 24 * 60 * 60;
+
+/// 单个账号的能力记录
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CapabilityEntry {
+    /// 标准化后的模型 ID
+    pub models: BTreeSet<String>,
+    /// 记录时间 (Unix 秒)
+    pub updated_at: i64,
+}
+
+impl CapabilityEntry {
+    /// 记录时间早于 `now - ttl_secs` 时视为过期
+    pub fn is_stale(&self, now: i64, ttl_secs: i64) -> bool {
+        now - self.updated_at > ttl_secs
+    }
+}
+
+/// 所有账号的能力记录 (account_id -> 记录)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CapabilityCache {
+    #[serde(default)]
+    pub accounts: BTreeMap<String, CapabilityEntry>,
+}
+
+impl CapabilityCache {
+    /// 从文件加载；文件不存在或损坏时返回空缓存 (损坏时记录警告)
+    pub fn load(path: &Path) -> Self {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Self::default(),
+            Err(e) => {
+                tracing::warn!("[Capability] Failed to read {:?}: {}", path, e);
+                return Self::default();
+            }
+        };
+        serde_json::from_str(&content).unwrap_or_else(|e| {
+            tracing::warn!("[Capability] Ignoring corrupt cache {:?}: {}", path, e);
+            Self::default()
+        })
+    }
+
+    /// 写入文件 (先写临时文件再重命名，避免中途崩溃留下半截内容)
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize capability cache: {}", e))?;
+        let tmp_path = path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, json)
+            .map_err(|e| format!("Failed to write capability cache: {}", e))?;
+        std::fs::rename(&tmp_path, path)
+            .map_err(|e| format!("Failed to replace capability cache: {}", e))
+    }
+
+    /// 记录账号当前可服务的模型
+    pub fn record<I>(&mut self, account_id: &str, models: I, now: i64)
+    where
+        I: IntoIterator<Item = String>,
+    {
+        self.accounts.insert(
+            account_id.to_string(),
+            CapabilityEntry {
+                models: models.into_iter().collect(),
+                updated_at: now,
+            },
+        );
+    }
+
+    pub fn get(&self, account_id: &str) -> Option<&CapabilityEntry> {
+        self.accounts.get(account_id)
+    }
+
+    /// 删除账号的记录，返回是否存在
+    pub fn remove(&mut self, account_id: &str) -> bool {
+        self.accounts.remove(account_id).is_some()
+    }
+
+    /// 只保留 `keep` 返回 true 的账号，返回删除的记录数
+    pub fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) -> usize {
+        let before = self.accounts.len();
+        self.accounts.retain(|id, _| keep(id));
+        before - self.accounts.len()
+    }
+
+    /// 给定账号中没有记录或记录已过期的账号
+    pub fn stale_accounts<'a, I>(&self, account_ids: I, now: i64, ttl_secs: i64) -> Vec<String>
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        account_ids
+            .into_iter()
+            .filter(|id| !matches!(self.get(id), Some(entry) if !entry.is_stale(now, ttl_secs)))
+            .map(|id| id.to_string())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn models(names: &[&str]) -> Vec<String> {
+        names.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_capability_cache_persists_and_reloads() {
+        let dir = std::env::temp_dir().join(format!("ag_capability_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(CAPABILITY_CACHE_FILE);
+
+        // 文件不存在时为空
+        assert_eq!(CapabilityCache::load(&path), CapabilityCache::default());
+
+        let mut cache = CapabilityCache::default();
+        cache.record("acc1", models(&["gemini-3-flash", "claude"]), 1_000);
+        cache.record("acc2", models(&["gemini-3-flash"]), 2_000);
+        cache.save(&path).unwrap();
+
+        let reloaded = CapabilityCache::load(&path);
+        assert_eq!(reloaded, cache);
+        let entry = reloaded.get("acc1").unwrap();
+        assert_eq!(entry.updated_at, 1_000);
+        assert!(entry.models.contains("claude"));
+        assert!(!dir.join("model_capabilities.json.tmp").exists());
+
+        // 损坏的文件被忽略
+        std::fs::write(&path, "{ not json").unwrap();
+        assert_eq!(CapabilityCache::load(&path), CapabilityCache::default());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_capability_staleness_follows_ttl() {
+        let mut cache = CapabilityCache::default();
+        cache.record("fresh", models(&["claude"]), 10_000);
+        cache.record("old", models(&["claude"]), 10_000 - 3_601);
+
+        let entry = cache.get("fresh").unwrap();
+        assert!(!entry.is_stale(10_000 + 3_600, 3_600));
+        assert!(entry.is_stale(10_000 + 3_601, 3_600));
+
+        // 无记录的账号同样需要刷新
+        assert_eq!(
+            cache.stale_accounts(["fresh", "old", "unknown"], 10_000, 3_600),
+            vec!["old".to_string(), "unknown".to_string()]
+        );
+
+        // 已删除账号的记录被清理
+        assert_eq!(cache.retain(|id| id != "old"), 1);
+        assert!(cache.remove("fresh"));
+        assert!(!cache.remove("fresh"));
+        assert_eq!(cache, CapabilityCache::default());
+    }
+}
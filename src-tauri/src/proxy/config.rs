@@ -94,6 +94,34 @@ pub fn update_global_system_prompt_config(config: GlobalSystemPromptConfig) {
     }
 }
 
+// ============================================================================
+// 全局配额保护配置存储
+// 账号预留在每次请求时读取，避免在请求路径上反复读取配置文件
+// ============================================================================
+static GLOBAL_QUOTA_PROTECTION_CONFIG: OnceLock<RwLock<crate::models::QuotaProtectionConfig>> =
+    OnceLock::new();
+
+/// 配额保护是否启用 (未初始化时视为关闭)
+pub fn is_quota_protection_enabled() -> bool {
+    GLOBAL_QUOTA_PROTECTION_CONFIG
+        .get()
+        .and_then(|lock| lock.read().ok())
+        .is_some_and(|cfg| cfg.enabled)
+}
+
+/// 更新全局配额保护配置
+pub fn update_quota_protection_config(config: crate::models::QuotaProtectionConfig) {
+    if let Some(lock) = GLOBAL_QUOTA_PROTECTION_CONFIG.get() {
+        if let Ok(mut cfg) = lock.write() {
+            *cfg = config;
+            tracing::info!("[Quota-Protection] Config updated: enabled={}", cfg.enabled);
+        }
+    } else {
+        tracing::info!("[Quota-Protection] Config initialized: enabled={}", config.enabled);
+        let _ = GLOBAL_QUOTA_PROTECTION_CONFIG.set(RwLock::new(config));
+    }
+}
+
 // ============================================================================
 // 全局图像思维模式配置存储
 // ============================================================================
@@ -387,6 +415,38 @@ impl Default for DebugLoggingConfig {
     }
 }
 
+/// 全局上游限流配置 (所有账号共享的令牌桶)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpstreamRateLimitConfig {
+    /// 是否启用
+    #[serde(default)]
+    pub enabled: bool,
+    /// 每秒允许发往上游的请求数
+    #[serde(default = "default_upstream_requests_per_second")]
+    pub requests_per_second: f64,
+    /// 允许的突发请求数 (令牌桶容量)
+    #[serde(default = "default_upstream_burst")]
+    pub burst: u32,
+}
+
+impl Default for UpstreamRateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            requests_per_second: default_upstream_requests_per_second(),
+            burst: default_upstream_burst(),
+        }
+    }
+}
+
+fn default_upstream_requests_per_second() -> f64 {
+    10.0
+}
+
+fn default_upstream_burst() -> u32 {
+    10
+}
+
 /// IP 黑名单配置
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IpBlacklistConfig {
@@ -555,6 +615,14 @@ pub struct ProxyConfig {
     /// 代理池配置
     #[serde(default)]
     pub proxy_pool: ProxyPoolConfig,
+
+    /// 全局上游限流配置
+    #[serde(default)]
+    pub upstream_rate_limit: UpstreamRateLimitConfig,
+
+    /// 账号分组 (组名 -> 账号邮箱)，请求携带 `X-Account-Group` 头时只在该分组内选择账号
+    #[serde(default)]
+    pub account_groups: std::collections::HashMap<String, std::collections::HashSet<String>>,
 }
 
 /// 上游代理配置
@@ -592,6 +660,8 @@ impl Default for ProxyConfig {
             global_system_prompt: GlobalSystemPromptConfig::default(),
             proxy_pool: ProxyPoolConfig::default(),
             image_thinking_mode: None,
+            upstream_rate_limit: UpstreamRateLimitConfig::default(),
+            account_groups: std::collections::HashMap::new(),
         }
     }
 }
@@ -1300,9 +1300,7 @@ pub async fn handle_messages(
                     "[Claude] VALIDATION_REQUIRED detected on account {}, temporarily blocking",
                     email
                 );
-                let block_minutes = 10i64;
-                let block_until = chrono::Utc::now().timestamp() + (block_minutes * 60);
-                if let Err(e) = token_manager.set_validation_block_public(&account_id, block_until, &error_text).await {
+                if let Err(e) = token_manager.block_validation(&account_id, &error_text).await {
                     tracing::error!("Failed to set validation block: {}", e);
                 }
             }
@@ -1795,7 +1793,14 @@ async fn call_gemini_sync(
     
     debug!("[{}] Calling Gemini API: {}", trace_id, model);
     
-    let response = reqwest::Client::new()
+    // 与其他上游请求一致：按账号使用代理池客户端，并受全局上游限速约束
+    let client = if let Some(pool) = crate::proxy::proxy_pool::get_global_proxy_pool() {
+        pool.get_effective_client(Some(account_id.as_str()), 60).await
+    } else {
+        crate::utils::http::get_long_client()
+    };
+    crate::proxy::upstream::throttle::acquire_upstream_permit().await;
+    let response = client
         .post(&upstream_url)
         .header("Authorization", format!("Bearer {}", access_token))
         .header("Content-Type", "application/json")
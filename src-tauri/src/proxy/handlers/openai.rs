@@ -714,12 +714,9 @@ pub async fn handle_chat_completions(
                             "[OpenAI] VALIDATION_REQUIRED detected on account {}, temporarily blocking",
                             email
                         );
-                        // Block for 10 minutes (default, configurable via config file)
-                        let block_minutes = 10i64;
-                        let block_until = chrono::Utc::now().timestamp() + (block_minutes * 60);
-
+                        // 连续封禁时逐级延长 (circuit_breaker.validation_block_steps)
                         if let Err(e) = token_manager
-                            .set_validation_block_public(&acc_id, block_until, &error_text)
+                            .block_validation(&acc_id, &error_text)
                             .await
                         {
                             tracing::error!("Failed to set validation block: {}", e);
@@ -99,93 +99,20 @@ pub async fn handle_warmup(
         };
 
     // ===== 步骤 2: 根据模型类型构建请求体 =====
-    let is_claude = req.model.to_lowercase().contains("claude");
-    let is_image = req.model.to_lowercase().contains("image");
-
-    let body: Value = if is_claude {
-        // Claude 模型：使用 transform_claude_request_in 转换
-        let session_id = format!(
-            "warmup_{}_{}",
-            chrono::Utc::now().timestamp_millis(),
-            &uuid::Uuid::new_v4().to_string()[..8]
-        );
-        let claude_request = crate::proxy::mappers::claude::models::ClaudeRequest {
-            model: req.model.clone(),
-            messages: vec![crate::proxy::mappers::claude::models::Message {
-                role: "user".to_string(),
-                content: crate::proxy::mappers::claude::models::MessageContent::String(
-                    "ping".to_string(),
-                ),
-            }],
-            max_tokens: Some(1),
-            stream: false,
-            system: None,
-            temperature: None,
-            top_p: None,
-            top_k: None,
-            tools: None,
-            metadata: Some(crate::proxy::mappers::claude::models::Metadata {
-                user_id: Some(session_id),
-            }),
-            thinking: None,
-            output_config: None,
-            size: None,
-            quality: None,
-        };
-
-        match crate::proxy::mappers::claude::transform_claude_request_in(
-            &claude_request,
-            &project_id,
-            false,
-            None,
-            "warmup",
-            None, // [NEW] No token for warmup
-        ) {
-            Ok(transformed) => transformed,
-            Err(e) => {
-                warn!("[Warmup-API] Step 2 FAILED: Claude transform error: {}", e);
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(WarmupResponse {
-                        success: false,
-                        message: format!("Transform error: {}", e),
-                        error: Some(e),
-                    }),
-                )
-                    .into_response();
-            }
+    let body = match build_ping_body(&req.model, &project_id, "warmup", None) {
+        Ok(body) => body,
+        Err(e) => {
+            warn!("[Warmup-API] Step 2 FAILED: Claude transform error: {}", e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(WarmupResponse {
+                    success: false,
+                    message: format!("Transform error: {}", e),
+                    error: Some(e),
+                }),
+            )
+                .into_response();
         }
-    } else {
-        // Gemini 模型：使用 wrap_request
-        let session_id = format!(
-            "warmup_{}_{}",
-            chrono::Utc::now().timestamp_millis(),
-            &uuid::Uuid::new_v4().to_string()[..8]
-        );
-
-        let base_request = if is_image {
-            json!({
-                "model": req.model,
-                "contents": [{"role": "user", "parts": [{"text": "Say hi"}]}],
-                "generationConfig": {
-                    "maxOutputTokens": 10,
-                    "temperature": 0,
-                    "responseModalities": ["TEXT"]
-                },
-                "session_id": session_id
-            })
-        } else {
-            json!({
-                "model": req.model,
-                "contents": [{"role": "user", "parts": [{"text": "Say hi"}]}],
-                "generationConfig": {
-                    "temperature": 0
-                },
-                "session_id": session_id
-            })
-        };
-
-        wrap_request(&base_request, &project_id, &req.model, None, Some(&session_id), None) // [FIX] Added None for token param
     };
 
     // ===== 步骤 3: 调用 UpstreamClient =====
@@ -377,3 +304,77 @@ pub async fn handle_warmup(
         }
     }
 }
+
+/// 构建最小的 "ping" 请求体 (预热 / 模型探测共用)
+///
+/// Claude 模型走 `transform_claude_request_in` (max_tokens = 1)，其余走 Gemini `wrap_request`。
+/// `max_output_tokens` 为 None 时保持预热的默认行为 (图像模型限制为 10)。
+pub(crate) fn build_ping_body(
+    model: &str,
+    project_id: &str,
+    session_prefix: &str,
+    max_output_tokens: Option<u32>,
+) -> Result<Value, String> {
+    let model_lower = model.to_lowercase();
+    let session_id = format!(
+        "{}_{}_{}",
+        session_prefix,
+        chrono::Utc::now().timestamp_millis(),
+        &uuid::Uuid::new_v4().to_string()[..8]
+    );
+
+    if model_lower.contains("claude") {
+        // Claude 模型：使用 transform_claude_request_in 转换
+        let claude_request = crate::proxy::mappers::claude::models::ClaudeRequest {
+            model: model.to_string(),
+            messages: vec![crate::proxy::mappers::claude::models::Message {
+                role: "user".to_string(),
+                content: crate::proxy::mappers::claude::models::MessageContent::String(
+                    "ping".to_string(),
+                ),
+            }],
+            max_tokens: Some(1),
+            stream: false,
+            system: None,
+            temperature: None,
+            top_p: None,
+            top_k: None,
+            tools: None,
+            metadata: Some(crate::proxy::mappers::claude::models::Metadata {
+                user_id: Some(session_id),
+            }),
+            thinking: None,
+            output_config: None,
+            size: None,
+            quality: None,
+        };
+
+        return crate::proxy::mappers::claude::transform_claude_request_in(
+            &claude_request,
+            project_id,
+            false,
+            None,
+            session_prefix,
+            None, // [NEW] No token for warmup
+        );
+    }
+
+    // Gemini 模型：使用 wrap_request
+    let is_image = model_lower.contains("image");
+    let mut generation_config = json!({ "temperature": 0 });
+    if is_image {
+        generation_config["responseModalities"] = json!(["TEXT"]);
+    }
+    if let Some(max_tokens) = max_output_tokens.or(is_image.then_some(10)) {
+        generation_config["maxOutputTokens"] = json!(max_tokens);
+    }
+
+    let base_request = json!({
+        "model": model,
+        "contents": [{"role": "user", "parts": [{"text": "Say hi"}]}],
+        "generationConfig": generation_config,
+        "session_id": session_id
+    });
+
+    Ok(wrap_request(&base_request, project_id, model, None, Some(&session_id), None)) // [FIX] Added None for token param
+}
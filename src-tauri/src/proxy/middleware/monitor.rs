@@ -9,6 +9,7 @@ use crate::proxy::server::AppState;
 use crate::proxy::monitor::ProxyRequestLog;
 use serde_json::Value;
 use crate::proxy::middleware::auth::UserTokenIdentity;
+use crate::proxy::reservation::{RequestReservationSlot, TokenReservation};
 use futures::StreamExt;
 
 const MAX_REQUEST_LOG_SIZE: usize = 100 * 1024 * 1024; // 100MB
@@ -33,6 +34,25 @@ fn record_user_token_usage(
     }
 }
 
+/// 请求结束时结算账号预留：失败的请求不扣减配额；成功的按响应报告的 token 用量
+/// 换算为配额百分点扣减，无法换算 (未报告用量或未配置换算比例) 时按模型成本估算扣减
+fn settle_reservation(reservation: Option<TokenReservation>, log: &ProxyRequestLog) {
+    let Some(reservation) = reservation else {
+        return;
+    };
+    let actual_units = if log.status >= 400 {
+        Some(0)
+    } else {
+        let reported = log.input_tokens.is_some() || log.output_tokens.is_some();
+        let total_tokens =
+            log.input_tokens.unwrap_or(0) as u64 + log.output_tokens.unwrap_or(0) as u64;
+        reported
+            .then(|| crate::proxy::selection::get_selection_config().usage_points(total_tokens))
+            .flatten()
+    };
+    reservation.reconcile(actual_units);
+}
+
 pub async fn monitor_middleware(
     State(state): State<AppState>,
     request: Request,
@@ -85,6 +105,14 @@ pub async fn monitor_middleware(
     // [FIX] 从请求 extensions 提取 UserTokenIdentity (由 Auth 中间件注入)
     // 必须在处理 request body 之前提取，因为 into_parts() 后需要保留这个值
     let user_token_identity = request.extensions().get::<UserTokenIdentity>().cloned();
+
+    // 请求头限定的账号分组，处理器选号时只在该分组内进行
+    let account_group = request
+        .headers()
+        .get(crate::proxy::selection::ACCOUNT_GROUP_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty());
     
     let request = if method == "POST" {
         let (parts, body) = request.into_parts();
@@ -112,7 +140,14 @@ pub async fn monitor_middleware(
         request
     };
     
-    let response = next.run(request).await;
+    // 处理器选中账号时占用的预留挂在该请求上，响应结束后按结果结算
+    let reservation_slot = RequestReservationSlot::default();
+    let response = crate::proxy::reservation::scope_request(
+        reservation_slot.clone(),
+        crate::proxy::selection::scope_account_group(account_group, next.run(request)),
+    )
+    .await;
+    let reservation = crate::proxy::reservation::take_request_reservation(&reservation_slot);
     
     // user_token_identity 已在上面从请求 extensions 中提取
     
@@ -429,6 +464,7 @@ pub async fn monitor_middleware(
             if log.status >= 400 {
                 log.error = Some("Stream Error or Failed".to_string());
             }
+            settle_reservation(reservation, &log);
 
             // Record User Token Usage
             record_user_token_usage(&user_token_identity, &log, user_agent.clone());
@@ -472,6 +508,7 @@ pub async fn monitor_middleware(
                 if log.status >= 400 {
                     log.error = log.response_body.clone();
                 }
+                settle_reservation(reservation, &log);
 
                 // Record User Token Usage
                 record_user_token_usage(&user_token_identity, &log, user_agent.clone());
@@ -481,6 +518,7 @@ pub async fn monitor_middleware(
             }
             Err(_) => {
                 log.response_body = Some("[Response too large (>100MB)]".to_string());
+                settle_reservation(reservation, &log);
 
                 // Record User Token Usage (even if too large)
                 record_user_token_usage(&user_token_identity, &log, user_agent.clone());
@@ -491,6 +529,7 @@ pub async fn monitor_middleware(
         }
     } else {
         log.response_body = Some(format!("[{}]", content_type));
+        settle_reservation(reservation, &log);
 
         // Record User Token Usage
         record_user_token_usage(&user_token_identity, &log, user_agent);
@@ -9,6 +9,7 @@ pub mod token_manager;
 
 // 新架构模块
 pub mod audio; // 音频处理模块
+pub mod capability_cache; // 模型能力缓存 (跨重启持久化)
 pub mod cli_sync; // CLI 配置同步 (v3.3.35)
 pub mod droid_sync; // Droid (Factory CLI) 配置同步
 pub mod common; // 公共工具
@@ -18,9 +19,13 @@ pub mod mappers; // 协议转换器
 pub mod middleware; // Axum 中间件
 pub mod monitor; // 监控
 pub mod opencode_sync; // OpenCode 配置同步
+pub mod probe; // 模型探测
 pub mod providers; // Extra upstream providers (z.ai, etc.)
 pub mod proxy_pool; // 代理池管理器
+pub mod quota_snapshot; // 配额快照与消耗差异
 pub mod rate_limit; // 限流跟踪
+pub mod reservation; // 账号预留 (并发防超额分配)
+pub mod selection; // 账号选择排序
 pub mod model_specs; // 模型规格管理 (v4.1.29)
 pub mod session_manager; // 会话指纹管理
 pub mod signature_cache; // Signature Cache (v3.3.16)
@@ -32,12 +37,20 @@ pub mod zai_vision_tools; // Built-in Vision MCP tools (z.ai vision API) // 调
 pub use config::update_global_system_prompt_config;
 pub use config::update_thinking_budget_config;
 pub use config::update_image_thinking_mode;
+pub use config::update_quota_protection_config;
+pub use upstream::throttle::update_upstream_rate_limit;
 pub use config::ProxyAuthMode;
 pub use config::ProxyConfig;
 pub use config::ProxyPoolConfig;
 pub use config::ZaiConfig;
 pub use config::ZaiDispatchMode;
+pub use probe::{probe_model, ProbeResult};
+pub use quota_snapshot::{diff_snapshots, snapshot_quotas, QuotaDiff, QuotaSnapshot};
 pub use security::ProxySecurityConfig;
+pub use selection::{
+    export_selection_config, import_selection_config, model_capacity, pin_account, pinned_accounts,
+    unpin_account, update_account_groups, ModelCapacity, SelectionTrace,
+};
 pub use server::AxumServer;
 pub use signature_cache::SignatureCache;
 pub use token_manager::TokenManager;
@@ -0,0 +1,245 @@
+// 模型探测：使用指定账号真实发送一次最小请求，验证该账号上的模型是否可用
+
+use std::future::Future;
+use std::time::Instant;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use super::handlers::warmup::build_ping_body;
+use super::token_manager::TokenManager;
+use super::upstream::client::UpstreamClient;
+
+/// 探测结果中保留的上游错误最大长度
+const MAX_ERROR_LEN: usize = 500;
+
+/// 单次模型探测结果
+#[derive(Debug, Clone, Serialize)]
+pub struct ProbeResult {
+    pub email: String,
+    pub model: String,
+    pub success: bool,
+    /// 上游 HTTP 状态码 (网络错误时为 None)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    pub latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// 使用 `email` 对应账号向 `model` 发送一次最小请求 (max_tokens = 1)
+///
+/// 失败时同步更新该账号的健康分；403 按反代处理器的规则封禁账号。
+pub async fn probe_model(
+    token_manager: &TokenManager,
+    upstream: &UpstreamClient,
+    email: &str,
+    model: &str,
+) -> Result<ProbeResult, String> {
+    probe_model_with(token_manager, email, model, |access_token, body, account_id| async move {
+        let result = upstream
+            .call_v1_internal("generateContent", &access_token, body, None, Some(&account_id))
+            .await?;
+        let status = result.response.status().as_u16();
+        let text = result.response.text().await.unwrap_or_default();
+        Ok((status, text))
+    })
+    .await
+}
+
+/// `send` 接收 (access_token, 请求体, account_id)，返回上游 (状态码, 响应文本)
+async fn probe_model_with<F, Fut>(
+    token_manager: &TokenManager,
+    email: &str,
+    model: &str,
+    send: F,
+) -> Result<ProbeResult, String>
+where
+    F: FnOnce(String, Value, String) -> Fut,
+    Fut: Future<Output = Result<(u16, String), String>>,
+{
+    let (access_token, project_id, _, account_id, _) =
+        token_manager.get_token_by_email(email).await?;
+    let body = build_ping_body(model, &project_id, "probe", Some(1))?;
+
+    let start = Instant::now();
+    let outcome = send(access_token, body, account_id.clone()).await;
+    let latency_ms = start.elapsed().as_millis() as u64;
+
+    let mut result = ProbeResult {
+        email: email.to_string(),
+        model: model.to_string(),
+        success: false,
+        status: None,
+        latency_ms,
+        error: None,
+    };
+
+    match outcome {
+        Ok((status, _)) if (200..300).contains(&status) => {
+            token_manager.record_success(&account_id);
+            result.success = true;
+            result.status = Some(status);
+            tracing::info!("[Probe] {} / {} OK ({}ms)", email, model, latency_ms);
+        }
+        Ok((status, error_text)) => {
+            token_manager.record_failure(&account_id);
+            tracing::warn!("[Probe] {} / {} failed: HTTP {}", email, model, status);
+
+            if status == 403 {
+                block_forbidden_account(token_manager, &account_id, &error_text).await;
+            } else if status == 429 {
+                token_manager
+                    .mark_rate_limited_async(email, status, None, &error_text, Some(model))
+                    .await;
+            }
+
+            result.status = Some(status);
+            result.error = Some(truncate_error(&error_text));
+        }
+        Err(e) => {
+            token_manager.record_failure(&account_id);
+            tracing::warn!("[Probe] {} / {} request error: {}", email, model, e);
+            result.error = Some(truncate_error(&e));
+        }
+    }
+
+    Ok(result)
+}
+
+/// 与反代处理器一致：需要验证时临时封禁，并标记账号为 forbidden
+async fn block_forbidden_account(token_manager: &TokenManager, account_id: &str, error_text: &str) {
+    if error_text.contains("VALIDATION_REQUIRED")
+        || error_text.contains("verify your account")
+        || error_text.contains("validation_url")
+    {
+        if let Err(e) = token_manager
+            .block_validation(account_id, error_text)
+            .await
+        {
+            tracing::error!("[Probe] Failed to set validation block: {}", e);
+        }
+    }
+
+    if let Err(e) = token_manager.set_forbidden(account_id, error_text).await {
+        tracing::error!("[Probe] Failed to set forbidden status for {}: {}", account_id, e);
+    }
+}
+
+fn truncate_error(text: &str) -> String {
+    if text.len() <= MAX_ERROR_LEN {
+        return text.to_string();
+    }
+    let mut end = MAX_ERROR_LEN;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}...", &text[..end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn setup_manager(account_id: &str, email: &str) -> (TokenManager, std::path::PathBuf) {
+        let tmp_root = std::env::temp_dir().join(format!(
+            "antigravity-probe-test-{}",
+            uuid::Uuid::new_v4()
+        ));
+        let accounts_dir = tmp_root.join("accounts");
+        std::fs::create_dir_all(&accounts_dir).unwrap();
+
+        let now = chrono::Utc::now().timestamp();
+        let json = serde_json::json!({
+            "id": account_id,
+            "email": email,
+            "token": {
+                "access_token": "atk-probe",
+                "refresh_token": "rtk",
+                "expires_in": 3600,
+                "expiry_timestamp": now + 3600,
+                "project_id": "proj-probe"
+            },
+            "created_at": now,
+            "last_used": now
+        });
+        std::fs::write(
+            accounts_dir.join(format!("{}.json", account_id)),
+            serde_json::to_string_pretty(&json).unwrap(),
+        )
+        .unwrap();
+
+        let manager = TokenManager::new(tmp_root.clone());
+        manager.load_accounts().await.unwrap();
+        (manager, tmp_root)
+    }
+
+    #[tokio::test]
+    async fn test_probe_success_reports_latency_and_health() {
+        let account_id = format!("probe-{}", uuid::Uuid::new_v4());
+        let (manager, tmp_root) = setup_manager(&account_id, "probe@test.com").await;
+        manager.record_failure(&account_id);
+        let health_before = manager.get_health_score(&account_id);
+
+        let result = probe_model_with(
+            &manager,
+            "probe@test.com",
+            "gemini-3-flash",
+            |access_token, body, _account_id| async move {
+                assert_eq!(access_token, "atk-probe");
+                assert_eq!(body["project"], "proj-probe");
+                tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+                Ok((200, "{}".to_string()))
+            },
+        )
+        .await
+        .unwrap();
+
+        assert!(result.success);
+        assert_eq!(result.status, Some(200));
+        assert!(result.latency_ms >= 5);
+        assert!(result.error.is_none());
+        assert!(manager.get_health_score(&account_id) > health_before);
+
+        let _ = std::fs::remove_dir_all(&tmp_root);
+    }
+
+    #[tokio::test]
+    async fn test_probe_403_blocks_token() {
+        let account_id = format!("probe-{}", uuid::Uuid::new_v4());
+        let (manager, tmp_root) = setup_manager(&account_id, "blocked@test.com").await;
+
+        let result = probe_model_with(
+            &manager,
+            "blocked@test.com",
+            "claude-sonnet-4-6",
+            |_access_token, _body, _account_id| async move {
+                Ok((
+                    403,
+                    r#"{"error":{"status":"PERMISSION_DENIED","message":"VALIDATION_REQUIRED"}}"#
+                        .to_string(),
+                ))
+            },
+        )
+        .await
+        .unwrap();
+
+        assert!(!result.success);
+        assert_eq!(result.status, Some(403));
+        assert!(result.error.unwrap().contains("VALIDATION_REQUIRED"));
+        assert!(manager.get_health_score(&account_id) < 1.0);
+
+        let token = manager.get_token_by_id(&account_id).unwrap();
+        assert!(token.validation_blocked);
+        assert!(token.validation_blocked_until > chrono::Utc::now().timestamp());
+
+        let on_disk: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string(tmp_root.join("accounts").join(format!("{}.json", account_id)))
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(on_disk["validation_blocked"], true);
+
+        let _ = std::fs::remove_dir_all(&tmp_root);
+    }
+}
@@ -13,6 +13,7 @@ pub async fn fetch_project_id(access_token: &str) -> Result<String, String> {
     });
     
     let client = crate::utils::http::get_client();
+    crate::proxy::upstream::throttle::acquire_upstream_permit().await;
     let response = client
         .post(url)
         .bearer_auth(access_token)
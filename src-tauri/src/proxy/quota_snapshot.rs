@@ -0,0 +1,248 @@
+// 配额快照与差异：定期记录账号池各模型的剩余配额，比较两次快照得出消耗速度
+//
+// 两次快照之间若发生配额刷新 (reset_time 向后推进，或剩余配额不降反升)，
+// 刷新前的消耗无法观测，只统计刷新后从满额降到当前值的部分，不会出现负消耗。
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::token_manager::ProxyToken;
+
+/// 单个账号在快照时刻的配额状态
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountQuotaSnapshot {
+    pub email: String,
+    pub reset_time: Option<i64>,
+    /// 各模型 (标准化 ID) 剩余配额
+    pub model_quotas: BTreeMap<String, i32>,
+    /// 各模型配额满额 (上游未提供时缺失)
+    pub quota_max: BTreeMap<String, i32>,
+}
+
+/// 账号池配额快照 (按 account_id)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotaSnapshot {
+    pub taken_at: i64,
+    pub accounts: BTreeMap<String, AccountQuotaSnapshot>,
+}
+
+/// 单个模型在两次快照之间的消耗
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ModelConsumption {
+    pub model: String,
+    pub before: i32,
+    pub after: i32,
+    /// 消耗量，始终非负
+    pub consumed: i32,
+    /// 两次快照之间发生了配额刷新
+    pub reset: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AccountQuotaDiff {
+    pub account_id: String,
+    pub email: String,
+    pub models: Vec<ModelConsumption>,
+}
+
+/// 两次快照之间的配额消耗
+///
+/// 只比较两次快照中都存在的账号与模型；新增 / 移除的账号不计入。
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct QuotaDiff {
+    pub elapsed_secs: i64,
+    pub accounts: Vec<AccountQuotaDiff>,
+    /// 各模型在所有账号上的消耗之和
+    pub total_consumed: BTreeMap<String, i64>,
+}
+
+/// 记录账号池当前的配额状态 (使用校正后的当前时间)
+pub fn snapshot_quotas(pool: &[ProxyToken]) -> QuotaSnapshot {
+    snapshot_quotas_at(pool, crate::utils::clock::now())
+}
+
+/// 同 `snapshot_quotas`，使用指定的快照时间
+pub fn snapshot_quotas_at(pool: &[ProxyToken], taken_at: i64) -> QuotaSnapshot {
+    let accounts = pool
+        .iter()
+        .map(|token| {
+            let snapshot = AccountQuotaSnapshot {
+                email: token.email.clone(),
+                reset_time: token.reset_time,
+                model_quotas: token.model_quotas.iter().map(|(m, q)| (m.clone(), *q)).collect(),
+                quota_max: token.quota_max.iter().map(|(m, q)| (m.clone(), *q)).collect(),
+            };
+            (token.account_id.clone(), snapshot)
+        })
+        .collect();
+    QuotaSnapshot { taken_at, accounts }
+}
+
+/// 比较两次快照，得到每个账号、每个模型的消耗
+pub fn diff_snapshots(old: &QuotaSnapshot, new: &QuotaSnapshot) -> QuotaDiff {
+    let mut diff = QuotaDiff {
+        elapsed_secs: new.taken_at - old.taken_at,
+        ..QuotaDiff::default()
+    };
+
+    for (account_id, after) in &new.accounts {
+        let Some(before) = old.accounts.get(account_id) else {
+            continue;
+        };
+        // 新快照的刷新时间晚于旧快照：这期间配额已刷新
+        // (仅凭旧 reset_time 已过期不能判断，过期的 reset_time 可能一直未更新)
+        let reset_advanced = matches!(
+            (before.reset_time, after.reset_time),
+            (Some(before_reset), Some(after_reset)) if after_reset > before_reset
+        );
+
+        let models: Vec<ModelConsumption> = after
+            .model_quotas
+            .iter()
+            .filter_map(|(model, &after_quota)| {
+                let before_quota = *before.model_quotas.get(model)?;
+                // 剩余配额上升同样说明发生了刷新 (reset_time 未知或不准时)
+                let reset = reset_advanced || after_quota > before_quota;
+                let consumed = if reset {
+                    after
+                        .quota_max
+                        .get(model)
+                        .map_or(0, |max| (max - after_quota).max(0))
+                } else {
+                    before_quota - after_quota
+                };
+                Some(ModelConsumption {
+                    model: model.clone(),
+                    before: before_quota,
+                    after: after_quota,
+                    consumed,
+                    reset,
+                })
+            })
+            .collect();
+
+        for consumption in &models {
+            *diff
+                .total_consumed
+                .entry(consumption.model.clone())
+                .or_default() += i64::from(consumption.consumed);
+        }
+        diff.accounts.push(AccountQuotaDiff {
+            account_id: account_id.clone(),
+            email: after.email.clone(),
+            models,
+        });
+    }
+
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLAUDE: &str = "claude";
+    const FLASH: &str = "gemini-3-flash";
+
+    fn token(email: &str, reset_time: Option<i64>, quotas: &[(&str, i32)]) -> ProxyToken {
+        let mut builder = ProxyToken::builder(email, "at", "rt", email)
+            .expires(3600, 0)
+            .reset_time(reset_time);
+        for &(model, quota) in quotas {
+            builder = builder.model_quota(model, quota).quota_max(model, 100);
+        }
+        builder.build()
+    }
+
+    #[test]
+    fn test_diff_reports_consumption_per_account_and_model() {
+        let old = snapshot_quotas_at(
+            &[
+                token("a@test.com", Some(10_000), &[(CLAUDE, 80), (FLASH, 90)]),
+                token("b@test.com", Some(10_000), &[(CLAUDE, 50)]),
+                token("gone@test.com", None, &[(CLAUDE, 10)]),
+            ],
+            1_000,
+        );
+        let new = snapshot_quotas_at(
+            &[
+                token("a@test.com", Some(10_000), &[(CLAUDE, 60), (FLASH, 90)]),
+                token("b@test.com", Some(10_000), &[(CLAUDE, 45)]),
+                token("new@test.com", None, &[(CLAUDE, 100)]),
+            ],
+            1_600,
+        );
+
+        let diff = diff_snapshots(&old, &new);
+        assert_eq!(diff.elapsed_secs, 600);
+        let accounts: Vec<&str> = diff.accounts.iter().map(|a| a.email.as_str()).collect();
+        assert_eq!(accounts, ["a@test.com", "b@test.com"]);
+
+        let a = &diff.accounts[0];
+        assert_eq!(
+            a.models,
+            vec![
+                ModelConsumption {
+                    model: CLAUDE.to_string(),
+                    before: 80,
+                    after: 60,
+                    consumed: 20,
+                    reset: false,
+                },
+                ModelConsumption {
+                    model: FLASH.to_string(),
+                    before: 90,
+                    after: 90,
+                    consumed: 0,
+                    reset: false,
+                },
+            ]
+        );
+        assert_eq!(diff.total_consumed[CLAUDE], 25);
+        assert_eq!(diff.total_consumed[FLASH], 0);
+    }
+
+    #[test]
+    fn test_reset_between_snapshots_is_not_negative_consumption() {
+        // 旧快照时 reset_time 为 2000，新快照 (3000) 时配额已刷新并又用掉一部分
+        let old = snapshot_quotas_at(&[token("a@test.com", Some(2_000), &[(CLAUDE, 5)])], 1_000);
+        let new = snapshot_quotas_at(&[token("a@test.com", Some(20_000), &[(CLAUDE, 70)])], 3_000);
+
+        let diff = diff_snapshots(&old, &new);
+        let consumption = &diff.accounts[0].models[0];
+        assert!(consumption.reset);
+        assert_eq!((consumption.before, consumption.after), (5, 70));
+        assert_eq!(consumption.consumed, 30, "only the usage since the reset is counted");
+
+        // reset_time 未知时，剩余配额上升也视为刷新；满额未知则不计消耗
+        let mut old = snapshot_quotas_at(&[token("b@test.com", None, &[(CLAUDE, 10)])], 1_000);
+        let mut new = snapshot_quotas_at(&[token("b@test.com", None, &[(CLAUDE, 90)])], 2_000);
+        assert_eq!(diff_snapshots(&old, &new).accounts[0].models[0].consumed, 10);
+        old.accounts.get_mut("b@test.com").unwrap().quota_max.clear();
+        new.accounts.get_mut("b@test.com").unwrap().quota_max.clear();
+        let consumption = &diff_snapshots(&old, &new).accounts[0].models[0];
+        assert!(consumption.reset);
+        assert_eq!(consumption.consumed, 0);
+    }
+
+    #[test]
+    fn test_expired_reset_time_without_refresh_is_not_a_reset() {
+        // 两次快照完全相同，reset_time 早已过期但未更新：没有刷新，也没有消耗
+        let pool = [token("a@test.com", Some(500), &[(CLAUDE, 40)])];
+        let old = snapshot_quotas_at(&pool, 1_000);
+        let new = snapshot_quotas_at(&pool, 2_000);
+
+        let diff = diff_snapshots(&old, &new);
+        let consumption = &diff.accounts[0].models[0];
+        assert!(!consumption.reset);
+        assert_eq!(consumption.consumed, 0);
+        assert_eq!(diff.total_consumed[CLAUDE], 0);
+
+        // 过期的 reset_time 下配额继续下降，按普通消耗计算
+        let new = snapshot_quotas_at(&[token("a@test.com", Some(500), &[(CLAUDE, 25)])], 2_000);
+        let consumption = &diff_snapshots(&old, &new).accounts[0].models[0];
+        assert!(!consumption.reset);
+        assert_eq!(consumption.consumed, 15);
+    }
+}
@@ -69,7 +69,7 @@ impl RateLimitTracker {
     /// 获取账号剩余的等待时间(秒)
     /// 支持检查账号级和模型级锁
     pub fn get_remaining_wait(&self, account_id: &str, model: Option<&str>) -> u64 {
-        let now = SystemTime::now();
+        let now = crate::utils::clock::system_now();
         
         // 1. 检查全局账号锁
         if let Some(info) = self.limits.get(account_id) {
@@ -114,7 +114,7 @@ impl RateLimitTracker {
     /// # 参数
     /// - `model`: 可选的模型名称,用于模型级别限流。None 表示账号级别限流
     pub fn set_lockout_until(&self, account_id: &str, reset_time: SystemTime, reason: RateLimitReason, model: Option<String>) {
-        let now = SystemTime::now();
+        let now = crate::utils::clock::system_now();
         let retry_sec = reset_time
             .duration_since(now)
             .map(|d| d.as_secs())
@@ -229,7 +229,7 @@ impl RateLimitTracker {
                 // [FIX] ServerError (5xx) 不累加 failure_count，避免污染 429 的退避阶梯
                 let failure_count = if reason != RateLimitReason::ServerError {
                     // 只有非 ServerError 才累加失败计数（用于指数退避）
-                    let now = SystemTime::now();
+                    let now = crate::utils::clock::system_now();
                     // 这里我们使用 account_id 作为 key，不区分模型，
                     // 因为这里是为了计算连续"账号级"问题的退避。
                     // 如果需要针对模型的连续失败计数，可能需要改变 failure_counts 的 key。
@@ -295,9 +295,9 @@ impl RateLimitTracker {
         };
         
         let info = RateLimitInfo {
-            reset_time: SystemTime::now() + Duration::from_secs(retry_sec),
+            reset_time: crate::utils::clock::system_now() + Duration::from_secs(retry_sec),
             retry_after_sec: retry_sec,
-            detected_at: SystemTime::now(),
+            detected_at: crate::utils::clock::system_now(),
             reason,
             model: model.clone(),
         };
@@ -515,7 +515,7 @@ impl RateLimitTracker {
     pub fn get_reset_seconds(&self, account_id: &str) -> Option<u64> {
         if let Some(info) = self.get(account_id) {
             info.reset_time
-                .duration_since(SystemTime::now())
+                .duration_since(crate::utils::clock::system_now())
                 .ok()
                 .map(|d| d.as_secs())
         } else {
@@ -526,7 +526,7 @@ impl RateLimitTracker {
     /// 清除过期的限流记录
     #[allow(dead_code)]
     pub fn cleanup_expired(&self) -> usize {
-        let now = SystemTime::now();
+        let now = crate::utils::clock::system_now();
         let mut count = 0;
         
         self.limits.retain(|_k, v| {
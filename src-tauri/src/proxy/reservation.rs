@@ -0,0 +1,209 @@
+// 账号预留 (Reservation)：防止并发请求在消耗配额前重复选中同一账号
+//
+// 配额单位与 `model_quotas` 相同，均为该模型配额的百分点 (0-100)。
+
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use dashmap::DashMap;
+
+use super::token_manager::ProxyToken;
+
+/// (account_id, 归一化模型名) -> 进行中预留占用的配额单位
+#[derive(Debug, Clone, Default)]
+pub struct ReservationLedger {
+    in_flight: Arc<DashMap<(String, String), u32>>,
+}
+
+impl ReservationLedger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 当前账号在该模型上进行中预留占用的配额单位
+    pub fn in_flight(&self, account_id: &str, model: &str) -> u32 {
+        self.in_flight
+            .get(&(account_id.to_string(), model.to_string()))
+            .map(|v| *v)
+            .unwrap_or(0)
+    }
+
+    /// 在持有条目锁的情况下检查容量并占用 `units` 个配额单位
+    ///
+    /// `capacity` 在锁内求值，保证 "检查 + 递增" 的原子性
+    pub(crate) fn try_acquire(
+        &self,
+        account_id: &str,
+        model: &str,
+        units: u32,
+        capacity: impl FnOnce() -> u32,
+    ) -> bool {
+        let mut entry = self
+            .in_flight
+            .entry((account_id.to_string(), model.to_string()))
+            .or_insert(0);
+        if *entry + units > capacity() {
+            return false;
+        }
+        *entry += units;
+        true
+    }
+
+    /// 不检查容量直接占用 `units` 个配额单位 (账号已由调用方选定)
+    pub(crate) fn acquire(&self, account_id: &str, model: &str, units: u32) {
+        *self
+            .in_flight
+            .entry((account_id.to_string(), model.to_string()))
+            .or_insert(0) += units;
+    }
+
+    /// 在持有条目锁的情况下执行 `f` 并释放 `units` 个配额单位
+    fn release_with(&self, account_id: &str, model: &str, units: u32, f: impl FnOnce()) {
+        let key = (account_id.to_string(), model.to_string());
+        if let Some(mut entry) = self.in_flight.get_mut(&key) {
+            f();
+            *entry = entry.saturating_sub(units);
+        }
+        self.in_flight.remove_if(&key, |_, v| *v == 0);
+    }
+}
+
+/// 已预留的账号。`commit` / `reconcile` 表示请求已结束并按估算 / 实际用量扣减配额，
+/// `release` (或 Drop) 表示放弃。
+#[derive(Debug)]
+pub struct TokenReservation {
+    token: ProxyToken,
+    model: String,
+    cost_units: i32,
+    ledger: ReservationLedger,
+    tokens: Arc<DashMap<String, ProxyToken>>,
+    finished: bool,
+}
+
+impl TokenReservation {
+    pub(crate) fn new(
+        token: ProxyToken,
+        model: String,
+        cost_units: i32,
+        ledger: ReservationLedger,
+        tokens: Arc<DashMap<String, ProxyToken>>,
+    ) -> Self {
+        Self {
+            token,
+            model,
+            cost_units,
+            ledger,
+            tokens,
+            finished: false,
+        }
+    }
+
+    /// 预留时的账号快照
+    pub fn token(&self) -> &ProxyToken {
+        &self.token
+    }
+
+    /// 归一化后的目标模型名
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// 单次请求扣减的配额单位 (由模型成本表决定)
+    pub fn cost_units(&self) -> i32 {
+        self.cost_units
+    }
+
+    /// 确认消耗：按模型成本扣减池中该账号的模型配额，并释放预留
+    pub fn commit(self) {
+        let cost_units = self.cost_units;
+        self.reconcile(Some(cost_units));
+    }
+
+    /// 请求结束时按实际用量结算：释放预留的估算单位，再扣减实际消耗
+    ///
+    /// - `Some(n)`：响应头 / 响应体报告的实际消耗；少于估算时多预留的部分不再扣减，
+    ///   超出估算时额外扣减差额 (请求失败时传 `Some(0)`)
+    /// - `None`：上游未报告用量，按估算成本扣减 (同 `commit`)
+    ///
+    /// 返回结算后的剩余配额；账号已被移除或没有该模型配额时返回 None
+    pub fn reconcile(mut self, actual_units: Option<i32>) -> Option<i32> {
+        let tokens = self.tokens.clone();
+        let (account_id, model) = (self.token.account_id.clone(), self.model.clone());
+        let reserved_units = self.cost_units;
+        let actual_units = actual_units.unwrap_or(reserved_units).max(0);
+        if actual_units != reserved_units {
+            tracing::debug!(
+                "[Reservation] {} {}: reserved {} units, actual usage {}",
+                self.token.email,
+                model,
+                reserved_units,
+                actual_units
+            );
+        }
+
+        let mut remaining = None;
+        self.ledger.release_with(&account_id, &model, reserved_units as u32, || {
+            if let Some(mut entry) = tokens.get_mut(&account_id) {
+                if let Some(quota) = entry.model_quotas.get_mut(&model) {
+                    *quota = (*quota - actual_units).max(0);
+                    remaining = Some(*quota);
+                }
+            }
+        });
+        self.finished = true;
+        remaining
+    }
+
+    /// 放弃预留，不扣减配额
+    pub fn release(mut self) {
+        self.release_inner();
+    }
+
+    fn release_inner(&mut self) {
+        if !self.finished {
+            self.ledger.release_with(
+                &self.token.account_id,
+                &self.model,
+                self.cost_units as u32,
+                || {},
+            );
+            self.finished = true;
+        }
+    }
+}
+
+impl Drop for TokenReservation {
+    fn drop(&mut self) {
+        self.release_inner();
+    }
+}
+
+// ============================================================================
+// 请求级预留：反代请求在选中账号时占用预留，请求结束时由 monitor 中间件结算
+// ============================================================================
+
+/// 单个请求持有的预留 (重试换号时替换，旧预留随 Drop 释放)
+pub type RequestReservationSlot = Arc<Mutex<Option<TokenReservation>>>;
+
+tokio::task_local! {
+    static REQUEST_RESERVATION: RequestReservationSlot;
+}
+
+/// 在请求级预留作用域内执行 `f`，`f` 内选中账号时产生的预留保存在 `slot` 中
+pub async fn scope_request<F: Future>(slot: RequestReservationSlot, f: F) -> F::Output {
+    REQUEST_RESERVATION.scope(slot, f).await
+}
+
+/// 将预留挂到当前请求上，替换 (并释放) 之前的预留；不在请求作用域内时直接释放
+pub(crate) fn attach_to_request(reservation: TokenReservation) {
+    let _ = REQUEST_RESERVATION.try_with(|slot| {
+        if let Ok(mut current) = slot.lock() {
+            *current = Some(reservation);
+        }
+    });
+}
+
+/// 取出请求持有的预留 (请求结束时调用)
+pub fn take_request_reservation(slot: &RequestReservationSlot) -> Option<TokenReservation> {
+    slot.lock().ok().and_then(|mut current| current.take())
+}
@@ -0,0 +1,1251 @@
+// 账号选择排序逻辑 (与 TokenManager 状态无关的纯函数)
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+use std::sync::{Arc, OnceLock, RwLock};
+
+use serde::{Deserialize, Serialize};
+
+use super::sticky_config::SelectionPolicy;
+use super::token_manager::{HealthClass, ProxyToken};
+
+/// reset_time 差值小于该阈值时视为相同 (10 分钟)
+pub const RESET_TIME_THRESHOLD_SECS: i64 = 600;
+
+/// 配额耗尽但距刷新不足该时长 (秒) 时仍保留为候选
+pub const RESET_GRACE_SECS: i64 = 60;
+
+/// 需要 Ultra 订阅才能稳定服务的高端模型 (子串匹配，大小写不敏感)
+pub const ULTRA_REQUIRED_MODELS: &[&str] = &["claude-opus-4-6", "claude-opus-4-5", "opus"];
+
+/// 内置等级同义词 (子串 -> tier_weights 中的等级名)
+pub const DEFAULT_TIER_SYNONYMS: &[(&str, &str)] = &[
+    ("premium", "ultra"),
+    ("enterprise", "ultra"),
+    ("plus", "pro"),
+    ("team", "pro"),
+];
+
+/// 上游 API 的模型资源名前缀 (如 `models/gemini-3-flash`)
+pub const MODEL_RESOURCE_PREFIX: &str = "models/";
+
+/// 默认剥离 `models/` 前缀的模型家族：Claude 配额 key 本身不带前缀，出现前缀只可能是传输层附加的
+pub const DEFAULT_PREFIX_STRIP_FAMILIES: &[&str] = &["claude"];
+
+/// 账号选择配置的持久化文件名 (位于数据目录下)
+pub const SELECTION_CONFIG_FILE: &str = "selection_config.json";
+
+/// 未匹配任何已知等级时的优先级 (始终排在最后)
+pub const UNKNOWN_TIER_PRIORITY: u8 = u8::MAX;
+
+/// 未配置成本的模型默认消耗
+pub const DEFAULT_MODEL_COST: f32 = 1.0;
+
+/// 默认健康分级阈值 (Healthy >= 0.8, Degraded >= 0.5)
+pub const DEFAULT_HEALTHY_THRESHOLD: f32 = 0.8;
+pub const DEFAULT_DEGRADED_THRESHOLD: f32 = 0.5;
+
+/// 最近失败过的账号在该窗口 (秒) 内降低优先级
+pub const RECENT_FAILURE_WINDOW_SECS: i64 = 30;
+
+/// 同等级账号之间的排序键顺序
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SecondaryOrder {
+    /// 先比较目标模型剩余配额，再比较健康分
+    #[default]
+    QuotaThenHealth,
+    /// 先比较健康分 (可靠性优先)，再比较剩余配额
+    HealthThenQuota,
+}
+
+/// "最近失败" 降权在排序键中的位置 (均在订阅等级之后)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum RecentFailureStage {
+    /// 在配额 / 健康分之前比较：最近失败的账号排在同等级其他账号之后
+    #[default]
+    BeforeQuota,
+    /// 在配额 / 健康分之后比较：仅在配额与健康分相同时降权
+    AfterQuota,
+}
+
+/// `select_top_n` 返回结果的顺序
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum FanOutOrder {
+    /// 严格按排序结果 (高等级账号全部排在前面)
+    #[default]
+    Strict,
+    /// 按订阅等级轮流产出 (Ultra, Pro, Ultra, Pro, ...)，避免批量任务先耗尽高等级账号
+    TierInterleaved,
+}
+
+/// 账号选择配置 (策略 / 等级权重 / Ultra 专属模型 / 模型别名 / 阈值)
+///
+/// 可通过 `export_selection_config` / `import_selection_config` 导出与恢复，
+/// 便于在多台机器间复现同一套调度行为。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SelectionConfig {
+    /// 账号选择策略
+    pub policy: SelectionPolicy,
+    /// 等级相同时配额与健康分的比较顺序
+    pub secondary_order: SecondaryOrder,
+    /// 订阅等级权重 (子串匹配，数值越小越优先)
+    pub tier_weights: BTreeMap<String, u8>,
+    /// 等级同义词 (子串匹配，大小写不敏感 -> tier_weights 中的等级名)，仅在未直接命中等级名时使用
+    pub tier_synonyms: BTreeMap<String, String>,
+    /// 需要 Ultra 账号的模型 (子串匹配，大小写不敏感)
+    pub ultra_required_models: Vec<String>,
+    /// 模型别名 (请求模型名 -> 目标模型名)，在标准化之前生效
+    pub model_aliases: BTreeMap<String, String>,
+    /// 标准化时剥离 `models/` 前缀的模型家族 (子串匹配，大小写不敏感)；
+    /// 其余家族 (如 Gemini) 的前缀视为 key 的一部分保留，避免不同家族的 key 冲突
+    pub prefix_strip_families: Vec<String>,
+    /// reset_time 差值阈值 (秒)
+    pub reset_time_threshold_secs: i64,
+    /// 配额为 0 且 reset_time 在该窗口 (秒) 内时不排除账号，押注即将刷新
+    pub reset_grace_secs: i64,
+    /// 模型单次请求预计消耗的配额百分点 (与 model_quotas 同单位；精确匹配优先，其次最长子串匹配)，
+    /// 未配置时为 1.0
+    pub model_cost: HashMap<String, f32>,
+    /// 每个配额百分点大约对应的 token 数，用于将响应中报告的用量换算为配额百分点；
+    /// 0 表示未知，请求结束时按 `model_cost` 估算扣减
+    pub tokens_per_quota_point: u64,
+    /// 按 project_id 分组轮转 (同一项目的账号共享配额，视为一个配额桶)
+    pub balance_projects: bool,
+    /// 健康分不低于该值视为 Healthy
+    pub healthy_threshold: f32,
+    /// 健康分不低于该值视为 Degraded，否则为 Unhealthy
+    pub degraded_threshold: f32,
+    /// 缓存的等级/配额超过该时长 (秒) 时，选中前先刷新该账号；0 表示不检查
+    pub max_state_age_secs: i64,
+    /// 模型固定账号 (标准化模型 ID -> 账号邮箱)，该账号可服务时无视等级/配额排序优先使用
+    pub pinned_accounts: BTreeMap<String, String>,
+    /// 没有常规账号可服务目标模型时，尝试即时刷新冷账号 (导入时刷新失败、仍为占位 token 的账号)
+    pub refresh_cold_tokens: bool,
+    /// 强制最低等级：Ultra 专属模型只选 Ultra 账号，即使其他等级账号上报了该模型的配额
+    pub enforce_min_tier: bool,
+    /// 最近失败窗口 (秒)：窗口内失败过的账号降低优先级但不排除；0 表示关闭
+    pub recent_failure_window_secs: i64,
+    /// 最近失败降权所在的排序阶段
+    pub recent_failure_stage: RecentFailureStage,
+    /// 尚未获取能力 (model_quotas 为空) 的账号暂时视为可服务任意模型，首次使用时刷新确认；
+    /// 关闭时这类账号在能力过滤中被排除
+    pub assume_capable_when_unknown: bool,
+    /// 保留最高等级的最后一个可用账号：非 Ultra 专属模型只有在低等级账号都无法服务时才使用它
+    pub reserve_top_tier: bool,
+    /// 账号分组 (组名 -> 账号邮箱)，一个账号可属于多个组
+    ///
+    /// 唯一的持久化来源是反代配置 `account_groups` (经 `update_account_groups` 同步到内存)，
+    /// 因此不参与导出/导入，也不写入选择配置文件
+    #[serde(skip)]
+    pub groups: HashMap<String, HashSet<String>>,
+}
+
+impl Default for SelectionConfig {
+    fn default() -> Self {
+        Self {
+            policy: SelectionPolicy::default(),
+            secondary_order: SecondaryOrder::default(),
+            tier_weights: [("ultra", 0), ("pro", 1), ("free", 2)]
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect(),
+            tier_synonyms: DEFAULT_TIER_SYNONYMS
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            ultra_required_models: ULTRA_REQUIRED_MODELS.iter().map(|s| s.to_string()).collect(),
+            model_aliases: BTreeMap::new(),
+            prefix_strip_families: DEFAULT_PREFIX_STRIP_FAMILIES.iter().map(|s| s.to_string()).collect(),
+            reset_time_threshold_secs: RESET_TIME_THRESHOLD_SECS,
+            reset_grace_secs: RESET_GRACE_SECS,
+            model_cost: HashMap::new(),
+            tokens_per_quota_point: 0,
+            balance_projects: false,
+            healthy_threshold: DEFAULT_HEALTHY_THRESHOLD,
+            degraded_threshold: DEFAULT_DEGRADED_THRESHOLD,
+            max_state_age_secs: 0,
+            pinned_accounts: BTreeMap::new(),
+            refresh_cold_tokens: false,
+            enforce_min_tier: false,
+            recent_failure_window_secs: RECENT_FAILURE_WINDOW_SECS,
+            recent_failure_stage: RecentFailureStage::default(),
+            assume_capable_when_unknown: false,
+            reserve_top_tier: false,
+            groups: HashMap::new(),
+        }
+    }
+}
+
+impl SelectionConfig {
+    /// 订阅等级优先级 (按权重从小到大匹配第一个命中的等级)
+    ///
+    /// 未直接命中任何等级名时，再按同义词映射到对应等级 (如 "premium" -> ultra)
+    pub fn tier_priority(&self, tier: &Option<String>) -> u8 {
+        let t = tier.as_deref().unwrap_or("").to_lowercase();
+        if t.is_empty() {
+            return UNKNOWN_TIER_PRIORITY;
+        }
+
+        let direct = self
+            .tier_weights
+            .iter()
+            .filter(|(name, _)| t.contains(name.to_lowercase().as_str()))
+            .map(|(_, w)| *w)
+            .min();
+        direct
+            .or_else(|| {
+                self.tier_synonyms
+                    .iter()
+                    .filter(|(synonym, _)| t.contains(synonym.to_lowercase().as_str()))
+                    .filter_map(|(_, target)| self.tier_weight(target))
+                    .min()
+            })
+            .unwrap_or(UNKNOWN_TIER_PRIORITY)
+    }
+
+    /// 按等级名 (大小写不敏感) 查找权重
+    fn tier_weight(&self, name: &str) -> Option<u8> {
+        self.tier_weights
+            .iter()
+            .find(|(tier, _)| tier.eq_ignore_ascii_case(name))
+            .map(|(_, w)| *w)
+    }
+
+    /// 判断模型是否属于 Ultra 专属高端模型
+    pub fn is_ultra_required_model(&self, model: &str) -> bool {
+        let lower = model.to_lowercase();
+        self.ultra_required_models
+            .iter()
+            .any(|m| lower.contains(m.to_lowercase().as_str()))
+    }
+
+    /// 账号等级是否满足模型要求的最低等级 (仅在 `enforce_min_tier` 开启时限制 Ultra 专属模型)
+    pub fn meets_min_tier(&self, token: &ProxyToken, requested_model: &str) -> bool {
+        if !self.enforce_min_tier || !self.is_ultra_required_model(requested_model) {
+            return true;
+        }
+        let ultra = self.tier_weight("ultra").unwrap_or(0);
+        self.tier_priority(token.effective_tier()) <= ultra
+    }
+
+    /// 账号能力尚未获取 (既无配额也无缓存的能力记录) 且按配置暂时视为可服务
+    pub fn assumes_capable(&self, token: &ProxyToken) -> bool {
+        self.assume_capable_when_unknown
+            && token.model_quotas.is_empty()
+            && token.cached_capabilities.is_empty()
+    }
+
+    /// 能力过滤：拥有目标模型配额，或缓存的能力记录包含该模型 (尚无配额数据)，
+    /// 或能力未知且开启了 `assume_capable_when_unknown`
+    pub fn has_capability(&self, token: &ProxyToken, normalized_target: &str) -> bool {
+        token.model_quotas.contains_key(normalized_target)
+            || token.cached_capabilities.contains(normalized_target)
+            || self.assumes_capable(token)
+    }
+
+    /// 账号能否服务目标模型：通过能力过滤且满足最低等级要求
+    pub fn can_serve(&self, token: &ProxyToken, requested_model: &str, normalized_target: &str) -> bool {
+        self.has_capability(token, normalized_target) && self.meets_min_tier(token, requested_model)
+    }
+
+    /// 等级保留：最高等级只剩一个可用账号且有低等级账号可服务时，将其移到候选末尾
+    ///
+    /// 可用指目标模型仍有配额 (或配额为 0 但即将在宽限窗口内刷新) 且当前未被封禁 / 耗尽；
+    /// Ultra 专属模型不受影响。
+    /// 返回被保留账号的 account_id。
+    pub fn apply_tier_reserve(
+        &self,
+        candidates: &mut Vec<ProxyToken>,
+        requested_model: &str,
+        normalized_target: &str,
+    ) -> Option<String> {
+        if !self.reserve_top_tier || self.is_ultra_required_model(requested_model) {
+            return None;
+        }
+        let now = crate::utils::clock::now();
+        let usable = |t: &ProxyToken| {
+            t.has_model_quota_with(normalized_target, now, self.reset_grace_secs)
+                && t.unusable_until_with(now, self.reset_grace_secs).is_none()
+        };
+        let usable_tiers: Vec<u8> = candidates
+            .iter()
+            .filter(|t| usable(t))
+            .map(|t| self.tier_priority(t.effective_tier()))
+            .filter(|p| *p != UNKNOWN_TIER_PRIORITY)
+            .collect();
+        let top = usable_tiers.iter().copied().min()?;
+        let top_count = usable_tiers.iter().filter(|p| **p == top).count();
+        if top_count != 1 || !usable_tiers.iter().any(|p| *p > top) {
+            return None;
+        }
+
+        let idx = candidates
+            .iter()
+            .position(|t| usable(t) && self.tier_priority(t.effective_tier()) == top)?;
+        let reserved = candidates.remove(idx);
+        tracing::debug!(
+            "[Selection] Keeping {} in reserve for {}",
+            reserved.email,
+            normalized_target
+        );
+        let account_id = reserved.account_id.clone();
+        candidates.push(reserved);
+        Some(account_id)
+    }
+
+    /// 账号是否属于指定分组 (邮箱大小写不敏感)；分组不存在时为 false
+    pub fn in_group(&self, token: &ProxyToken, group: &str) -> bool {
+        self.groups
+            .get(group)
+            .is_some_and(|members| members.iter().any(|m| crate::utils::emails_match(m, &token.email)))
+    }
+
+    /// 只保留指定分组内的账号；分组不存在时返回错误 (不回退到全池)
+    pub fn scope_to_group(&self, tokens: &mut Vec<ProxyToken>, group: &str) -> Result<(), String> {
+        if !self.groups.contains_key(group) {
+            return Err(format!("Unknown account group: {}", group));
+        }
+        tokens.retain(|t| self.in_group(t, group));
+        Ok(())
+    }
+
+    /// 应用别名后归一化为标准 ID
+    pub fn normalize_target(&self, model: &str) -> String {
+        let aliased = self
+            .model_aliases
+            .get(model)
+            .or_else(|| self.model_aliases.get(&model.to_lowercase()))
+            .map(|s| s.as_str())
+            .unwrap_or(model);
+        self.quota_key(aliased)
+    }
+
+    /// 上游返回的模型名 -> model_quotas 的 key
+    ///
+    /// 仅对 `prefix_strip_families` 中的家族剥离 `models/` 前缀，之后再归一化为标准 ID；
+    /// 无法归一化时保留 (按需剥离后的) 原名。
+    pub fn quota_key(&self, model_name: &str) -> String {
+        let name = self.strip_model_prefix(model_name);
+        crate::proxy::common::model_mapping::normalize_to_standard_id(name)
+            .unwrap_or_else(|| name.to_string())
+    }
+
+    fn strip_model_prefix<'a>(&self, model_name: &'a str) -> &'a str {
+        let Some(rest) = model_name.strip_prefix(MODEL_RESOURCE_PREFIX) else {
+            return model_name;
+        };
+        let lower = rest.to_lowercase();
+        let strip = self
+            .prefix_strip_families
+            .iter()
+            .any(|family| lower.contains(family.to_lowercase().as_str()));
+        if strip {
+            rest
+        } else {
+            model_name
+        }
+    }
+
+    /// 模型单次请求预计消耗的配额百分点
+    pub fn model_cost(&self, model: &str) -> f32 {
+        let lower = model.to_lowercase();
+        let cost = self.model_cost.get(model).copied().or_else(|| {
+            self.model_cost
+                .iter()
+                .filter(|(k, _)| lower.contains(k.to_lowercase().as_str()))
+                .max_by_key(|(k, _)| k.len())
+                .map(|(_, v)| *v)
+        });
+        match cost {
+            Some(c) if c.is_finite() && c > 0.0 => c,
+            _ => DEFAULT_MODEL_COST,
+        }
+    }
+
+    /// 单次请求预留 / 扣减的整数配额百分点 (成本向上取整，至少为 1)
+    pub fn cost_units(&self, model: &str) -> i32 {
+        (self.model_cost(model).ceil() as i32).max(1)
+    }
+
+    /// 将响应报告的 token 用量换算为配额百分点 (向上取整)；未配置换算比例时返回 None
+    pub fn usage_points(&self, total_tokens: u64) -> Option<i32> {
+        if self.tokens_per_quota_point == 0 {
+            return None;
+        }
+        let points = total_tokens.div_ceil(self.tokens_per_quota_point);
+        Some(points.min(i32::MAX as u64) as i32)
+    }
+
+    /// 按模型成本估算剩余配额还能支撑的请求数
+    pub fn projected_requests(&self, remaining_quota: i32, model: &str) -> i32 {
+        remaining_quota.max(0) / self.cost_units(model)
+    }
+
+    /// 目标模型 (已标准化) 固定的账号邮箱
+    pub fn pinned_email(&self, normalized_target: &str) -> Option<&str> {
+        self.pinned_accounts.get(normalized_target).map(|s| s.as_str())
+    }
+
+    /// 按本配置的策略比较两个账号 (Less 表示 a 更优先)
+    ///
+    /// `requested_model` 用于策略判断 (如 Ultra 专属模型)，`normalized_target` 用于读取配额
+    /// (Claude 系列会被归一化为同一个配额组，无法再区分 Opus / Sonnet)。
+    /// `now` 用于判断最近失败；排序前读取一次后传入，保证整次排序使用同一时刻 (比较结果一致)。
+    pub fn compare(
+        &self,
+        a: &ProxyToken,
+        b: &ProxyToken,
+        requested_model: &str,
+        normalized_target: &str,
+        now: i64,
+    ) -> Ordering {
+        compare_with(self, self.policy, a, b, requested_model, normalized_target, now)
+    }
+}
+
+static GLOBAL_SELECTION_CONFIG: OnceLock<RwLock<SelectionConfig>> = OnceLock::new();
+
+fn default_selection_config() -> &'static SelectionConfig {
+    static DEFAULT: OnceLock<SelectionConfig> = OnceLock::new();
+    DEFAULT.get_or_init(SelectionConfig::default)
+}
+
+/// 获取当前账号选择配置
+pub fn get_selection_config() -> SelectionConfig {
+    GLOBAL_SELECTION_CONFIG
+        .get()
+        .and_then(|lock| lock.read().ok())
+        .map(|cfg| cfg.clone())
+        .unwrap_or_default()
+}
+
+/// 更新全局账号选择配置 (账号分组来自反代配置，整体替换时保留当前分组)
+pub fn update_selection_config(config: SelectionConfig) {
+    if let Some(lock) = GLOBAL_SELECTION_CONFIG.get() {
+        if let Ok(mut cfg) = lock.write() {
+            let groups = std::mem::take(&mut cfg.groups);
+            *cfg = SelectionConfig { groups, ..config };
+            tracing::info!("[Selection] Config updated: policy={:?}", cfg.policy);
+        }
+    } else {
+        tracing::info!("[Selection] Config initialized: policy={:?}", config.policy);
+        let _ = GLOBAL_SELECTION_CONFIG.set(RwLock::new(config));
+    }
+}
+
+/// 在写锁内修改全局账号选择配置，避免并发的 "读取-修改-写回" 互相覆盖
+pub fn modify_selection_config<R>(f: impl FnOnce(&mut SelectionConfig) -> R) -> R {
+    let lock = GLOBAL_SELECTION_CONFIG.get_or_init(|| RwLock::new(SelectionConfig::default()));
+    let mut cfg = lock.write().unwrap_or_else(|e| e.into_inner());
+    f(&mut cfg)
+}
+
+/// 仅更新选择策略 (调度配置变更时同步)
+pub fn update_selection_policy(policy: SelectionPolicy) {
+    modify_selection_config(|config| {
+        if config.policy != policy {
+            config.policy = policy;
+            tracing::info!("[Selection] Policy updated: {:?}", policy);
+        }
+    });
+}
+
+/// 同步账号分组 (启动反代 / 配置热更新时调用)
+pub fn update_account_groups(groups: HashMap<String, HashSet<String>>) {
+    modify_selection_config(|config| {
+        if config.groups != groups {
+            tracing::info!("[Selection] Account groups updated: {} group(s)", groups.len());
+            config.groups = groups;
+        }
+    });
+}
+
+/// 为模型固定账号：该账号可服务此模型且未被封禁时始终优先选中，否则回退到正常排序 (持久化)
+pub fn pin_account(model: &str, email: &str) -> Result<(), String> {
+    let email = crate::utils::normalize_email(email);
+    modify_selection_config(|config| {
+        let target = config.normalize_target(model);
+        let previous = config.pinned_accounts.insert(target.clone(), email.clone());
+        if let Err(e) = persist_selection_config(config) {
+            // 落盘失败时撤销，保持内存与文件一致
+            match previous {
+                Some(previous) => config.pinned_accounts.insert(target, previous),
+                None => config.pinned_accounts.remove(&target),
+            };
+            return Err(e);
+        }
+        tracing::info!("[Selection] Pinned {} for model {}", email, target);
+        Ok(())
+    })
+}
+
+/// 取消模型的固定账号 (持久化)，返回之前是否存在固定
+pub fn unpin_account(model: &str) -> Result<bool, String> {
+    modify_selection_config(|config| {
+        let target = config.normalize_target(model);
+        let Some(previous) = config.pinned_accounts.remove(&target) else {
+            return Ok(false);
+        };
+        if let Err(e) = persist_selection_config(config) {
+            config.pinned_accounts.insert(target, previous);
+            return Err(e);
+        }
+        tracing::info!("[Selection] Unpinned model {}", target);
+        Ok(true)
+    })
+}
+
+/// 当前所有模型固定账号 (标准化模型 ID -> 账号邮箱)
+pub fn pinned_accounts() -> BTreeMap<String, String> {
+    get_selection_config().pinned_accounts
+}
+
+/// 导出当前账号选择配置为 JSON (用于备份 / 跨机器复现 / 附加到问题反馈)
+pub fn export_selection_config() -> serde_json::Value {
+    serde_json::to_value(get_selection_config()).unwrap_or(serde_json::Value::Null)
+}
+
+/// 解析并校验账号选择配置 JSON，缺失字段使用默认值 (不修改全局配置)
+pub fn parse_selection_config(value: serde_json::Value) -> Result<SelectionConfig, String> {
+    let config: SelectionConfig = serde_json::from_value(value)
+        .map_err(|e| format!("Invalid selection config: {}", e))?;
+    if config.reset_time_threshold_secs < 0 {
+        return Err("reset_time_threshold_secs must not be negative".to_string());
+    }
+    if config.reset_grace_secs < 0 {
+        return Err("reset_grace_secs must not be negative".to_string());
+    }
+    if config.max_state_age_secs < 0 {
+        return Err("max_state_age_secs must not be negative".to_string());
+    }
+    if let Some((synonym, target)) = config
+        .tier_synonyms
+        .iter()
+        .find(|(_, target)| config.tier_weight(target).is_none())
+    {
+        return Err(format!(
+            "tier synonym '{}' maps to unknown tier '{}'",
+            synonym, target
+        ));
+    }
+    if !(0.0..=1.0).contains(&config.degraded_threshold)
+        || !(0.0..=1.0).contains(&config.healthy_threshold)
+        || config.degraded_threshold > config.healthy_threshold
+    {
+        return Err(
+            "health thresholds must satisfy 0 <= degraded_threshold <= healthy_threshold <= 1"
+                .to_string(),
+        );
+    }
+    Ok(config)
+}
+
+/// 从 JSON 导入账号选择配置并持久化，缺失字段使用默认值
+///
+/// 选择策略以调度配置 (`scheduling.selection_policy`) 为准，导入时一并写回主配置，
+/// 避免下次同步调度配置时被旧策略覆盖。
+pub fn import_selection_config(value: serde_json::Value) -> Result<SelectionConfig, String> {
+    let config = parse_selection_config(value)?;
+    save_selection_config_to(&selection_config_path()?, &config)?;
+
+    let mut app_config = crate::modules::config::load_app_config()?;
+    if app_config.proxy.scheduling.selection_policy != config.policy {
+        app_config.proxy.scheduling.selection_policy = config.policy;
+        crate::modules::config::save_app_config(&app_config)?;
+    }
+
+    update_selection_config(config.clone());
+    Ok(config)
+}
+
+fn selection_config_path() -> Result<std::path::PathBuf, String> {
+    Ok(crate::modules::account::get_data_dir()?.join(SELECTION_CONFIG_FILE))
+}
+
+/// 写入持久化的账号选择配置 (调用方持有写锁，保证落盘顺序与内存修改一致)
+fn persist_selection_config(config: &SelectionConfig) -> Result<(), String> {
+    save_selection_config_to(&selection_config_path()?, config)
+}
+
+/// 写入文件 (先写临时文件再重命名，避免中途崩溃留下半截内容)
+pub fn save_selection_config_to(path: &std::path::Path, config: &SelectionConfig) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(config)
+        .map_err(|e| format!("Failed to serialize selection config: {}", e))?;
+    let tmp_path = path.with_extension("json.tmp");
+    std::fs::write(&tmp_path, json)
+        .map_err(|e| format!("Failed to write selection config: {}", e))?;
+    std::fs::rename(&tmp_path, path)
+        .map_err(|e| format!("Failed to replace selection config: {}", e))
+}
+
+/// 从文件读取并校验；文件不存在时返回 Ok(None)
+pub fn load_selection_config_from(path: &std::path::Path) -> Result<Option<SelectionConfig>, String> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Failed to read selection config: {}", e)),
+    };
+    let value: serde_json::Value = serde_json::from_str(&content)
+        .map_err(|e| format!("Invalid selection config: {}", e))?;
+    parse_selection_config(value).map(Some)
+}
+
+/// 启动时恢复持久化的账号选择配置 (之后再同步调度策略与账号分组)
+pub fn restore_selection_config() {
+    let loaded = selection_config_path().and_then(|path| load_selection_config_from(&path));
+    match loaded {
+        Ok(Some(config)) => update_selection_config(config),
+        Ok(None) => {}
+        Err(e) => tracing::warn!("[Selection] Failed to restore selection config: {}", e),
+    }
+}
+
+// ============================================================================
+// 请求级账号分组：反代请求可通过请求头限定只在某个分组内选号
+// ============================================================================
+
+/// 限定账号分组的请求头 (值为反代配置 `account_groups` 中的组名)
+pub const ACCOUNT_GROUP_HEADER: &str = "x-account-group";
+
+tokio::task_local! {
+    static REQUEST_ACCOUNT_GROUP: Option<String>;
+}
+
+/// 在指定分组作用域内执行 `f`，`f` 内的 `TokenManager::get_token` 只在该分组内选号
+pub async fn scope_account_group<F: std::future::Future>(group: Option<String>, f: F) -> F::Output {
+    REQUEST_ACCOUNT_GROUP.scope(group, f).await
+}
+
+/// 当前请求限定的账号分组；不在请求作用域内或未限定时为 None
+pub fn current_account_group() -> Option<String> {
+    REQUEST_ACCOUNT_GROUP.try_with(|group| group.clone()).ok().flatten()
+}
+
+// ============================================================================
+// 选择事件回调：供外部接入自定义日志 / 指标，不依赖 tracing
+// ============================================================================
+
+/// 一次账号选择的结果
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SelectionEvent {
+    /// 请求的模型名 (未归一化)
+    pub model: String,
+    pub account_id: String,
+    pub email: String,
+    /// 参与排序的候选账号数
+    pub candidate_count: usize,
+    pub policy: SelectionPolicy,
+}
+
+type SelectionHook = Arc<dyn Fn(&SelectionEvent) + Send + Sync>;
+
+static SELECTION_HOOKS: OnceLock<RwLock<Vec<(u64, SelectionHook)>>> = OnceLock::new();
+static NEXT_HOOK_ID: AtomicU64 = AtomicU64::new(1);
+
+/// 注册选择回调，每次选出账号后调用；返回值可用于 `remove_selection_hook`
+pub fn on_selection<F>(callback: F) -> u64
+where
+    F: Fn(&SelectionEvent) + Send + Sync + 'static,
+{
+    let id = NEXT_HOOK_ID.fetch_add(1, AtomicOrdering::Relaxed);
+    let lock = SELECTION_HOOKS.get_or_init(|| RwLock::new(Vec::new()));
+    if let Ok(mut hooks) = lock.write() {
+        hooks.push((id, Arc::new(callback)));
+    }
+    id
+}
+
+/// 注销选择回调，返回是否找到该回调
+pub fn remove_selection_hook(id: u64) -> bool {
+    let Some(lock) = SELECTION_HOOKS.get() else {
+        return false;
+    };
+    let Ok(mut hooks) = lock.write() else {
+        return false;
+    };
+    let before = hooks.len();
+    hooks.retain(|(hook_id, _)| *hook_id != id);
+    hooks.len() != before
+}
+
+/// 通知所有已注册回调 (在锁外调用，回调内可再注册 / 注销)
+pub(crate) fn notify_selection(
+    model: &str,
+    chosen: &ProxyToken,
+    candidate_count: usize,
+    policy: SelectionPolicy,
+) {
+    let hooks: Vec<SelectionHook> = match SELECTION_HOOKS.get().and_then(|lock| lock.read().ok()) {
+        Some(hooks) if !hooks.is_empty() => hooks.iter().map(|(_, hook)| hook.clone()).collect(),
+        _ => return,
+    };
+
+    let event = SelectionEvent {
+        model: model.to_string(),
+        account_id: chosen.account_id.clone(),
+        email: chosen.email.clone(),
+        candidate_count,
+        policy,
+    };
+    for hook in hooks {
+        hook(&event);
+    }
+}
+
+/// 按排序结果依次产出可服务目标模型的账号 (使用当前全局配置)
+///
+/// 供重试循环使用：当前账号请求失败时直接取下一个候选，无需重新执行完整选择。
+pub fn select_iter(pool: &[ProxyToken], model: &str) -> impl Iterator<Item = ProxyToken> {
+    select_iter_with(pool, model, &get_selection_config())
+}
+
+/// 同 `select_iter`，使用指定配置 (`balance_projects` 开启时按项目轮转)
+#[tracing::instrument(
+    level = "debug",
+    name = "select_candidates",
+    skip(pool, config),
+    fields(pool_size = pool.len(), balance_projects = config.balance_projects)
+)]
+pub fn select_iter_with(
+    pool: &[ProxyToken],
+    model: &str,
+    config: &SelectionConfig,
+) -> impl Iterator<Item = ProxyToken> {
+    if config.balance_projects {
+        select_balancing_projects_with(pool, model, config)
+    } else {
+        ranked_candidates(pool, model, config)
+    }
+    .into_iter()
+}
+
+/// 为批量任务选出最多 `n` 个账号并发使用 (使用当前全局配置)
+pub fn select_top_n(pool: &[ProxyToken], model: &str, n: usize, order: FanOutOrder) -> Vec<ProxyToken> {
+    select_top_n_with(pool, model, n, order, &get_selection_config())
+}
+
+/// 同 `select_top_n`，使用指定配置
+///
+/// `TierInterleaved` 时同一等级内部仍保持排序结果的先后，等级之间按优先级轮转。
+pub fn select_top_n_with(
+    pool: &[ProxyToken],
+    model: &str,
+    n: usize,
+    order: FanOutOrder,
+    config: &SelectionConfig,
+) -> Vec<ProxyToken> {
+    let ranked = select_iter_with(pool, model, config);
+    match order {
+        FanOutOrder::Strict => ranked.take(n).collect(),
+        FanOutOrder::TierInterleaved => {
+            let mut tiers: BTreeMap<u8, Vec<ProxyToken>> = BTreeMap::new();
+            for token in ranked {
+                let priority = config.tier_priority(token.effective_tier());
+                tiers.entry(priority).or_default().push(token);
+            }
+            let mut result = round_robin(tiers.into_values().collect());
+            result.truncate(n);
+            result
+        }
+    }
+}
+
+/// 依次从每组取一个，直到所有组取完
+fn round_robin(groups: Vec<Vec<ProxyToken>>) -> Vec<ProxyToken> {
+    let mut groups: Vec<std::vec::IntoIter<ProxyToken>> =
+        groups.into_iter().map(|group| group.into_iter()).collect();
+
+    let mut result = Vec::new();
+    loop {
+        let before = result.len();
+        for group in groups.iter_mut() {
+            if let Some(token) = group.next() {
+                result.push(token);
+            }
+        }
+        if result.len() == before {
+            break;
+        }
+    }
+    result
+}
+
+/// 仅在目标模型剩余配额不少于 `min` 的账号中选择最优者 (使用当前全局配置)
+///
+/// 适用于已知开销较大的批量任务：没有账号满足要求时直接返回 None，
+/// 而不是选中一个会在中途耗尽配额的账号。
+pub fn select_with_min_quota(pool: &[ProxyToken], model: &str, min: i32) -> Option<ProxyToken> {
+    select_with_min_quota_with(pool, model, min, &get_selection_config())
+}
+
+/// 同 `select_with_min_quota`，使用指定配置
+pub fn select_with_min_quota_with(
+    pool: &[ProxyToken],
+    model: &str,
+    min: i32,
+    config: &SelectionConfig,
+) -> Option<ProxyToken> {
+    let normalized_target = config.normalize_target(model);
+    let eligible: Vec<ProxyToken> = pool
+        .iter()
+        .filter(|t| {
+            t.model_quotas
+                .get(&normalized_target)
+                .is_some_and(|quota| *quota >= min)
+        })
+        .cloned()
+        .collect();
+    let ranked = ranked_candidates(&eligible, model, config);
+    let chosen = ranked.first().cloned()?;
+    notify_selection(model, &chosen, ranked.len(), config.policy);
+    Some(chosen)
+}
+
+fn ranked_candidates(pool: &[ProxyToken], model: &str, config: &SelectionConfig) -> Vec<ProxyToken> {
+    let normalized_target = config.normalize_target(model);
+    let mut candidates: Vec<ProxyToken> = pool
+        .iter()
+        .filter(|t| t.enabled && config.can_serve(t, model, &normalized_target))
+        .cloned()
+        .collect();
+    let now = crate::utils::clock::now();
+    candidates.sort_by(|a, b| config.compare(a, b, model, &normalized_target, now));
+    config.apply_tier_reserve(&mut candidates, model, &normalized_target);
+    promote_pinned(&mut candidates, &normalized_target, config);
+    candidates
+}
+
+/// 将固定账号移到首位 (仍有目标模型配额或即将在宽限窗口内刷新，且当前可用时)；不满足条件时保持原排序
+///
+/// 返回被提到首位的固定账号 ID
+pub fn promote_pinned(
+    candidates: &mut Vec<ProxyToken>,
+    normalized_target: &str,
+    config: &SelectionConfig,
+) -> Option<String> {
+    let pinned = config.pinned_email(normalized_target)?;
+    let now = crate::utils::clock::now();
+    let position = candidates.iter().position(|t| {
+        crate::utils::emails_match(&t.email, pinned)
+            && t.has_model_quota_with(normalized_target, now, config.reset_grace_secs)
+            && t.unusable_until_with(now, config.reset_grace_secs).is_none()
+    });
+    match position {
+        Some(idx) => {
+            let token = candidates.remove(idx);
+            let account_id = token.account_id.clone();
+            candidates.insert(0, token);
+            Some(account_id)
+        }
+        None => {
+            tracing::debug!(
+                "[Selection] Pinned account {} cannot serve {}, falling back to ranking",
+                pinned,
+                normalized_target
+            );
+            None
+        }
+    }
+}
+
+/// 账号所属的配额桶：有 project_id 时按项目分组，否则每个账号单独成桶
+fn quota_bucket_key(token: &ProxyToken) -> String {
+    match token.project_id.as_deref().filter(|p| !p.is_empty()) {
+        Some(pid) => format!("project:{}", pid),
+        None => format!("account:{}", token.account_id),
+    }
+}
+
+/// 按项目均衡的候选顺序 (使用当前全局配置)
+///
+/// 同一 project_id 的账号共享上游配额，视为一个配额桶 (桶配额取成员最小值)。
+/// 桶之间按代表账号排序后轮转产出，避免连续请求堆积在同一项目上。
+pub fn select_balancing_projects(pool: &[ProxyToken], model: &str) -> Vec<ProxyToken> {
+    select_balancing_projects_with(pool, model, &get_selection_config())
+}
+
+/// 同 `select_balancing_projects`，使用指定配置
+pub fn select_balancing_projects_with(
+    pool: &[ProxyToken],
+    model: &str,
+    config: &SelectionConfig,
+) -> Vec<ProxyToken> {
+    let normalized_target = config.normalize_target(model);
+
+    // 桶内成员保持单账号排序
+    let mut buckets: Vec<Vec<ProxyToken>> = Vec::new();
+    let mut bucket_index: HashMap<String, usize> = HashMap::new();
+    for token in ranked_candidates(pool, model, config) {
+        let key = quota_bucket_key(&token);
+        match bucket_index.get(&key) {
+            Some(&idx) => buckets[idx].push(token),
+            None => {
+                bucket_index.insert(key, buckets.len());
+                buckets.push(vec![token]);
+            }
+        }
+    }
+
+    // 用代表账号 + 共享配额比较桶的优先级
+    let mut representatives: Vec<(usize, ProxyToken)> = buckets
+        .iter()
+        .enumerate()
+        .map(|(idx, members)| {
+            let pooled_quota = members
+                .iter()
+                .filter_map(|t| t.model_quotas.get(&normalized_target).copied())
+                .min()
+                .unwrap_or(0);
+            let mut rep = members[0].clone();
+            rep.model_quotas.insert(normalized_target.clone(), pooled_quota);
+            (idx, rep)
+        })
+        .collect();
+    let now = crate::utils::clock::now();
+    representatives.sort_by(|(_, a), (_, b)| config.compare(a, b, model, &normalized_target, now));
+
+    let ordered_buckets: Vec<Vec<ProxyToken>> = representatives
+        .into_iter()
+        .map(|(idx, _)| std::mem::take(&mut buckets[idx]))
+        .collect();
+    round_robin(ordered_buckets)
+}
+
+/// 请求路径 (`get_token`) 的候选排序：按策略排序，开启 `balance_projects` 时再按项目轮转，
+/// 最后应用等级保留；返回被保留账号的 account_id
+pub fn rank_for_request(
+    candidates: &mut Vec<ProxyToken>,
+    requested_model: &str,
+    normalized_target: &str,
+    config: &SelectionConfig,
+) -> Option<String> {
+    if config.balance_projects {
+        *candidates = select_balancing_projects_with(candidates, requested_model, config);
+    } else {
+        let now = crate::utils::clock::now();
+        candidates.sort_by(|a, b| config.compare(a, b, requested_model, normalized_target, now));
+    }
+    config.apply_tier_reserve(candidates, requested_model, normalized_target)
+}
+
+/// 未设置订阅等级的账号在容量统计中的分组名
+pub const UNKNOWN_TIER_LABEL: &str = "UNKNOWN";
+
+/// 单个等级的容量
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct TierCapacity {
+    pub total_quota: i64,
+    pub eligible_accounts: usize,
+    pub projected_requests: i64,
+}
+
+/// 账号池对某个模型的总可用容量 (用于仪表盘)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ModelCapacity {
+    /// 目标模型剩余配额之和
+    pub total_quota: i64,
+    /// 可服务该模型且未被封禁的账号数
+    pub eligible_accounts: usize,
+    /// 按模型成本估算剩余配额还能支撑的请求数 (逐账号向下取整后求和)
+    pub projected_requests: i64,
+    /// 按订阅等级 (大写，无等级时为 UNKNOWN) 分组
+    pub by_tier: BTreeMap<String, TierCapacity>,
+}
+
+/// 统计账号池对目标模型的总可用容量 (使用当前全局配置)
+pub fn model_capacity(pool: &[ProxyToken], model: &str) -> ModelCapacity {
+    model_capacity_with(pool, model, &get_selection_config(), crate::utils::clock::now())
+}
+
+/// 同 `model_capacity`，使用指定配置与当前时间
+///
+/// 只统计已启用、拥有目标模型配额 (能力过滤) 且在 `now` 时刻未被验证封禁的账号。
+pub fn model_capacity_with(
+    pool: &[ProxyToken],
+    model: &str,
+    config: &SelectionConfig,
+    now: i64,
+) -> ModelCapacity {
+    let normalized_target = config.normalize_target(model);
+    let mut capacity = ModelCapacity::default();
+    for token in pool
+        .iter()
+        .filter(|t| t.enabled && !t.is_blocked(now) && config.meets_min_tier(t, model))
+    {
+        let Some(quota) = token.model_quotas.get(&normalized_target) else {
+            continue;
+        };
+        let projected = i64::from(config.projected_requests(*quota, model));
+        let quota = i64::from((*quota).max(0));
+        let tier = token
+            .effective_tier()
+            .as_deref()
+            .filter(|t| !t.is_empty())
+            .map(|t| t.to_uppercase())
+            .unwrap_or_else(|| UNKNOWN_TIER_LABEL.to_string());
+
+        capacity.total_quota += quota;
+        capacity.eligible_accounts += 1;
+        capacity.projected_requests += projected;
+        let entry = capacity.by_tier.entry(tier).or_default();
+        entry.total_quota += quota;
+        entry.eligible_accounts += 1;
+        entry.projected_requests += projected;
+    }
+    capacity
+}
+
+/// 账号未进入候选排序的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TraceSkipReason {
+    /// 用户已停用
+    Disabled,
+    /// 没有目标模型的配额 (能力过滤)
+    NoCapability,
+    /// 低于目标模型要求的最低等级 (`enforce_min_tier`)
+    BelowMinTier,
+    /// 该账号针对目标模型的熔断 (限流锁定) 尚未解除
+    ModelBreakerOpen,
+}
+
+/// 选择诊断中单个账号的状态
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CandidateTrace {
+    pub account_id: String,
+    pub email: String,
+    pub tier: Option<String>,
+    pub model_quota: Option<i32>,
+    /// 不区分模型的健康分级
+    pub health_class: HealthClass,
+    /// 针对请求模型的健康分级：该模型熔断中时为 Blocked
+    pub model_health_class: HealthClass,
+    /// 该模型熔断剩余秒数 (0 表示未熔断)
+    pub model_breaker_wait_secs: u64,
+    /// 在候选排序中的位置 (从 0 开始)，被跳过时为 None
+    pub rank: Option<usize>,
+    pub skipped: Option<TraceSkipReason>,
+}
+
+/// 一次选择的诊断信息：说明每个账号为何被选中、排在后面或被跳过
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SelectionTrace {
+    pub model: String,
+    pub normalized_target: String,
+    /// 按排序位置在前、被跳过的账号在后
+    pub candidates: Vec<CandidateTrace>,
+}
+
+/// 生成目标模型的选择诊断 (使用当前全局配置)
+///
+/// `model_breaker_wait` 按 account_id 返回账号针对本次请求模型的熔断剩余秒数
+/// (由 TokenManager 的限流记录提供)。
+pub fn trace_selection<W>(pool: &[ProxyToken], model: &str, model_breaker_wait: W) -> SelectionTrace
+where
+    W: Fn(&str) -> u64,
+{
+    trace_selection_with(
+        pool,
+        model,
+        &get_selection_config(),
+        crate::utils::clock::now(),
+        model_breaker_wait,
+    )
+}
+
+/// 同 `trace_selection`，使用指定配置与当前时间
+pub fn trace_selection_with<W>(
+    pool: &[ProxyToken],
+    model: &str,
+    config: &SelectionConfig,
+    now: i64,
+    model_breaker_wait: W,
+) -> SelectionTrace
+where
+    W: Fn(&str) -> u64,
+{
+    let normalized_target = config.normalize_target(model);
+    let mut eligible = Vec::new();
+    let mut eligible_traces = HashMap::new();
+    let mut skipped = Vec::new();
+    for token in pool {
+        let wait = model_breaker_wait(&token.account_id);
+        let reason = if !token.enabled {
+            Some(TraceSkipReason::Disabled)
+        } else if !config.has_capability(token, &normalized_target) {
+            Some(TraceSkipReason::NoCapability)
+        } else if !config.meets_min_tier(token, model) {
+            Some(TraceSkipReason::BelowMinTier)
+        } else if wait > 0 {
+            Some(TraceSkipReason::ModelBreakerOpen)
+        } else {
+            None
+        };
+        let trace = CandidateTrace {
+            account_id: token.account_id.clone(),
+            email: token.email.clone(),
+            tier: token.effective_tier().clone(),
+            model_quota: token.model_quotas.get(&normalized_target).copied(),
+            health_class: token.health_class_with(now, config),
+            model_health_class: token.model_health_class_with(now, config, wait > 0),
+            model_breaker_wait_secs: wait,
+            rank: None,
+            skipped: reason,
+        };
+        match reason {
+            Some(_) => skipped.push(trace),
+            None => {
+                eligible.push(token.clone());
+                eligible_traces.insert(token.account_id.clone(), trace);
+            }
+        }
+    }
+
+    let mut candidates: Vec<CandidateTrace> = ranked_candidates(&eligible, model, config)
+        .iter()
+        .enumerate()
+        .filter_map(|(rank, token)| {
+            let mut trace = eligible_traces.remove(&token.account_id)?;
+            trace.rank = Some(rank);
+            Some(trace)
+        })
+        .collect();
+    candidates.extend(skipped);
+
+    SelectionTrace {
+        model: model.to_string(),
+        normalized_target,
+        candidates,
+    }
+}
+
+/// 判断模型是否属于 Ultra 专属高端模型 (使用当前全局配置)
+pub fn is_ultra_required_model(model: &str) -> bool {
+    get_selection_config().is_ultra_required_model(model)
+}
+
+/// 订阅等级优先级 (默认权重)：ULTRA(0) > PRO(1) > FREE(2) > 未知
+pub fn tier_priority(tier: &Option<String>) -> u8 {
+    default_selection_config().tier_priority(tier)
+}
+
+/// 将请求模型名归一化为标准 ID (与 model_quotas 的 key 一致)，会应用配置中的模型别名
+pub fn normalize_target(model: &str) -> String {
+    get_selection_config().normalize_target(model)
+}
+
+/// 针对目标模型比较两个账号的优先级 (默认配置，Less 表示 a 更优先)
+///
+/// 1. 订阅等级 (ULTRA > PRO > FREE)
+/// 2. 目标模型剩余配额 (高者优先)
+/// 3. 健康分 (高者优先)
+/// 4. 配额刷新时间 (早者优先，差值小于 10 分钟视为相同)
+pub fn compare_tokens_for_model(a: &ProxyToken, b: &ProxyToken, normalized_target: &str) -> Ordering {
+    compare_with(
+        default_selection_config(),
+        SelectionPolicy::TierFirst,
+        a,
+        b,
+        normalized_target,
+        normalized_target,
+        crate::utils::clock::now(),
+    )
+}
+
+/// 按指定策略比较两个账号的优先级 (默认配置)
+///
+/// `ConserveHighTier` 仅对非 Ultra 专属模型反转等级顺序 (FREE > PRO > ULTRA)，
+/// 未知等级始终排在最后；其余排序键与 `compare_tokens_for_model` 一致。
+pub fn compare_tokens_with_policy(
+    a: &ProxyToken,
+    b: &ProxyToken,
+    requested_model: &str,
+    normalized_target: &str,
+    policy: SelectionPolicy,
+) -> Ordering {
+    compare_with(
+        default_selection_config(),
+        policy,
+        a,
+        b,
+        requested_model,
+        normalized_target,
+        crate::utils::clock::now(),
+    )
+}
+
+fn compare_with(
+    config: &SelectionConfig,
+    policy: SelectionPolicy,
+    a: &ProxyToken,
+    b: &ProxyToken,
+    requested_model: &str,
+    normalized_target: &str,
+    now: i64,
+) -> Ordering {
+    let tier_a = config.tier_priority(a.effective_tier());
+    let tier_b = config.tier_priority(b.effective_tier());
+    let tier_cmp = match policy {
+        SelectionPolicy::ConserveHighTier if !config.is_ultra_required_model(requested_model) => {
+            conserve_tier_key(tier_a).cmp(&conserve_tier_key(tier_b))
+        }
+        _ => tier_a.cmp(&tier_b),
+    };
+    if tier_cmp != Ordering::Equal {
+        return tier_cmp;
+    }
+
+    let recent_failure_cmp = || {
+        let window = config.recent_failure_window_secs;
+        if window <= 0 {
+            return Ordering::Equal;
+        }
+        a.failed_recently(now, window).cmp(&b.failed_recently(now, window))
+    };
+    if config.recent_failure_stage == RecentFailureStage::BeforeQuota {
+        let cmp = recent_failure_cmp();
+        if cmp != Ordering::Equal {
+            return cmp;
+        }
+    }
+
+    // 配额按模型成本折算为还能支撑的请求数：只够相同请求数的账号视为配额相同，由健康分决定
+    let projected = |t: &ProxyToken| {
+        let quota = t.model_quotas.get(normalized_target).copied().unwrap_or(0);
+        config.projected_requests(quota, requested_model)
+    };
+    let quota_cmp = projected(b).cmp(&projected(a));
+    let health_cmp = b
+        .health_score
+        .partial_cmp(&a.health_score)
+        .unwrap_or(Ordering::Equal);
+    let secondary_cmp = match config.secondary_order {
+        SecondaryOrder::QuotaThenHealth => quota_cmp.then(health_cmp),
+        SecondaryOrder::HealthThenQuota => health_cmp.then(quota_cmp),
+    };
+    if secondary_cmp != Ordering::Equal {
+        return secondary_cmp;
+    }
+
+    if config.recent_failure_stage == RecentFailureStage::AfterQuota {
+        let cmp = recent_failure_cmp();
+        if cmp != Ordering::Equal {
+            return cmp;
+        }
+    }
+
+    let reset_a = a.reset_time.unwrap_or(i64::MAX);
+    let reset_b = b.reset_time.unwrap_or(i64::MAX);
+    if (reset_a - reset_b).abs() >= config.reset_time_threshold_secs {
+        reset_a.cmp(&reset_b)
+    } else {
+        Ordering::Equal
+    }
+}
+
+/// 节约模式下的等级排序键：已知等级按权重反转 (FREE > PRO > ULTRA)，未知等级排最后
+fn conserve_tier_key(priority: u8) -> (bool, std::cmp::Reverse<u8>) {
+    (priority == UNKNOWN_TIER_PRIORITY, std::cmp::Reverse(priority))
+}
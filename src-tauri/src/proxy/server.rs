@@ -276,6 +276,11 @@ impl AxumServer {
         tracing::info!("调试日志配置已热更新");
     }
 
+    /// 共享的上游客户端 (模型探测等管理操作复用)
+    pub fn upstream_client(&self) -> Arc<crate::proxy::upstream::client::UpstreamClient> {
+        self.upstream.clone()
+    }
+
     pub async fn update_user_agent(&self, config: &crate::proxy::config::ProxyConfig) {
         self.upstream
             .set_user_agent_override(config.user_agent_override.clone())
@@ -1009,6 +1014,8 @@ async fn admin_delete_account(
 #[serde(rename_all = "camelCase")]
 struct SwitchRequest {
     account_id: String,
+    #[serde(default)]
+    force: Option<bool>,
 }
 
 async fn admin_switch_account(
@@ -1035,7 +1042,10 @@ async fn admin_switch_account(
     let account_id = payload.account_id.clone();
     logger::log_info(&format!("[API] Starting account switch: {}", account_id));
 
-    let result = state.account_service.switch_account(&account_id).await;
+    let result = state
+        .account_service
+        .switch_account(&account_id, payload.force.unwrap_or(false))
+        .await;
 
     {
         let mut switching = state.switching.write().await;
@@ -1354,6 +1364,9 @@ async fn admin_save_config(
         *pool = new_config.clone().proxy.proxy_pool;
     }
 
+    // 更新全局配额保护配置
+    crate::proxy::update_quota_protection_config(new_config.quota_protection.clone());
+
     Ok(StatusCode::OK)
 }
 
@@ -2655,7 +2668,7 @@ async fn admin_open_folder() -> Result<impl IntoResponse, (StatusCode, Json<Erro
 async fn admin_import_v1_accounts(
     State(state): State<AppState>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
-    let accounts = migration::import_from_v1().await.map_err(|e| {
+    let report = migration::import_from_v1_with_report(None, None).await.map_err(|e| {
         (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(ErrorResponse { error: e }),
@@ -2671,11 +2684,15 @@ async fn admin_import_v1_accounts(
             Json(ErrorResponse { error: e }),
         )
     })?;
-    let responses: Vec<AccountResponse> = accounts
+    let responses: Vec<AccountResponse> = report
+        .imported
         .iter()
         .map(|a| to_account_response(a, &current_id))
         .collect();
-    Ok(Json(responses))
+    Ok(Json(serde_json::json!({
+        "imported": responses,
+        "skipped": report.skipped,
+    })))
 }
 
 async fn admin_import_from_db(
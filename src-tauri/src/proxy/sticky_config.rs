@@ -1,4 +1,9 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// 粘性绑定的默认有效期 (秒)
+pub const DEFAULT_STICKY_TTL_SECONDS: u64 = 1800;
 
 /// 调度模式枚举
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
@@ -17,6 +22,17 @@ impl Default for SchedulingMode {
     }
 }
 
+/// 账号选择策略 (决定候选账号的订阅等级排序方向)
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum SelectionPolicy {
+    /// 等级优先 (Tier-first): 始终优先使用高等级订阅 (ULTRA > PRO > FREE)
+    #[default]
+    TierFirst,
+    /// 节约高等级配额 (Conserve high tier): 非 Ultra 专属模型优先使用最低可用等级
+    /// (FREE > PRO > ULTRA)，将 Ultra 配额留给 Opus 等高端模型
+    ConserveHighTier,
+}
+
 /// 粘性会话配置
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
@@ -25,6 +41,13 @@ pub struct StickySessionConfig {
     pub mode: SchedulingMode,
     /// 缓存优先模式下的最大等待时间 (秒)
     pub max_wait_seconds: u64,
+    /// 账号选择策略
+    pub selection_policy: SelectionPolicy,
+    /// 会话粘性绑定的默认有效期 (秒)，自上次命中起计算
+    pub sticky_ttl_seconds: u64,
+    /// 按模型覆盖的粘性有效期 (秒)。键为模型名或模型名片段 (如 "opus"、"haiku")，
+    /// 多个键命中时取最长者 (完整模型名自然优先)
+    pub model_sticky_ttl_seconds: HashMap<String, u64>,
 }
 
 impl Default for StickySessionConfig {
@@ -32,6 +55,25 @@ impl Default for StickySessionConfig {
         Self {
             mode: SchedulingMode::Balance,
             max_wait_seconds: 60,
+            selection_policy: SelectionPolicy::TierFirst,
+            sticky_ttl_seconds: DEFAULT_STICKY_TTL_SECONDS,
+            model_sticky_ttl_seconds: HashMap::new(),
         }
     }
 }
+
+impl StickySessionConfig {
+    /// 指定模型的粘性有效期
+    pub fn sticky_ttl(&self, model: &str) -> Duration {
+        let model = model.to_lowercase();
+        let secs = self
+            .model_sticky_ttl_seconds
+            .iter()
+            .map(|(key, secs)| (key.to_lowercase(), *secs))
+            .filter(|(key, _)| !key.is_empty() && model.contains(key.as_str()))
+            .max_by_key(|(key, _)| key.len())
+            .map(|(_, secs)| secs)
+            .unwrap_or(self.sticky_ttl_seconds);
+        Duration::from_secs(secs)
+    }
+}
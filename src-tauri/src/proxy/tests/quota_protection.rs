@@ -21,26 +21,13 @@ mod tests {
         protected_models: Vec<&str>,
         remaining_quota: Option<i32>,
     ) -> ProxyToken {
-        ProxyToken {
-            account_id: account_id.to_string(),
-            access_token: format!("mock_access_token_{}", account_id),
-            refresh_token: format!("mock_refresh_token_{}", account_id),
-            expires_in: 3600,
-            timestamp: chrono::Utc::now().timestamp() + 3600,
-            email: email.to_string(),
-            account_path: PathBuf::from(format!("/tmp/test_accounts/{}.json", account_id)),
-            project_id: Some("test-project".to_string()),
-            subscription_tier: Some("PRO".to_string()),
+        create_mock_token_with_path(
+            account_id,
+            email,
+            protected_models,
             remaining_quota,
-            protected_models: protected_models.iter().map(|s| s.to_string()).collect(),
-            health_score: 1.0,
-            reset_time: None,
-            validation_blocked: false,
-            validation_blocked_until: 0,
-            validation_url: None,
-            model_quotas: std::collections::HashMap::new(),
-            model_limits: std::collections::HashMap::new(),
-        }
+            PathBuf::from(format!("/tmp/test_accounts/{}.json", account_id)),
+        )
     }
 
     // ==================================================================================
@@ -1126,25 +1113,18 @@ mod tests {
         remaining_quota: Option<i32>,
         account_path: PathBuf,
     ) -> ProxyToken {
-        ProxyToken {
-            account_id: account_id.to_string(),
-            access_token: format!("mock_access_token_{}", account_id),
-            refresh_token: format!("mock_refresh_token_{}", account_id),
-            expires_in: 3600,
-            timestamp: chrono::Utc::now().timestamp() + 3600,
-            email: email.to_string(),
-            account_path,
-            project_id: Some("test-project".to_string()),
-            subscription_tier: Some("PRO".to_string()),
-            remaining_quota,
-            protected_models: protected_models.iter().map(|s| s.to_string()).collect(),
-            health_score: 1.0,
-            reset_time: None,
-            validation_blocked: false,
-            validation_blocked_until: 0,
-            validation_url: None,
-            model_quotas: std::collections::HashMap::new(),
-            model_limits: std::collections::HashMap::new(),
-        }
+        ProxyToken::builder(
+            account_id,
+            format!("mock_access_token_{}", account_id),
+            format!("mock_refresh_token_{}", account_id),
+            email,
+        )
+        .expires(3600, chrono::Utc::now().timestamp() + 3600)
+        .account_path(account_path)
+        .project_id(Some("test-project".to_string()))
+        .subscription_tier(Some("PRO".to_string()))
+        .remaining_quota(remaining_quota)
+        .protected_models(protected_models)
+        .build()
     }
 }
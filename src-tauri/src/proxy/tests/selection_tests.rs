@@ -0,0 +1,832 @@
+//! Selection Policy Tests
+//!
+//! 验证 `proxy::selection` 中的账号排序策略。
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::proxy::selection::{
+    compare_tokens_with_policy, load_selection_config_from, model_capacity_with, normalize_target, on_selection,
+    parse_selection_config, rank_for_request, remove_selection_hook, save_selection_config_to, select_balancing_projects_with,
+    select_iter_with, select_top_n_with, select_with_min_quota_with, trace_selection_with,
+    FanOutOrder, RecentFailureStage, SecondaryOrder, SelectionConfig, SelectionEvent, TraceSkipReason,
+    SELECTION_CONFIG_FILE, UNKNOWN_TIER_PRIORITY,
+};
+use crate::proxy::sticky_config::SelectionPolicy;
+use crate::proxy::token_manager::{HealthClass, ProxyToken};
+
+const SONNET: &str = "claude-sonnet-4-6";
+const OPUS: &str = "claude-opus-4-6";
+/// Claude 系列归一化后的配额组
+const CLAUDE_GROUP: &str = "claude";
+
+fn create_test_token(email: &str, tier: Option<&str>, quota: i32, models: &[&str]) -> ProxyToken {
+    let model_quotas: HashMap<String, i32> = models.iter().map(|m| (m.to_string(), quota)).collect();
+
+    ProxyToken::builder(email, "test_token", "test_refresh", email)
+        .expires(3600, chrono::Utc::now().timestamp() + 3600)
+        .account_path("/tmp/test")
+        .subscription_tier(tier.map(|s| s.to_string()))
+        .remaining_quota(Some(quota))
+        .model_quotas(model_quotas)
+        .build()
+}
+
+/// 模拟 get_token_internal: 先按能力过滤，再按策略排序，取第一个
+fn pick(tokens: &[ProxyToken], model: &str, policy: SelectionPolicy) -> String {
+    let normalized = normalize_target(model);
+    let mut candidates: Vec<&ProxyToken> = tokens
+        .iter()
+        .filter(|t| t.model_quotas.contains_key(&normalized))
+        .collect();
+    candidates.sort_by(|a, b| compare_tokens_with_policy(a, b, model, &normalized, policy));
+    candidates[0].email.clone()
+}
+
+fn mixed_pool() -> Vec<ProxyToken> {
+    vec![
+        create_test_token("ultra@test.com", Some("ULTRA"), 90, &[CLAUDE_GROUP]),
+        create_test_token("pro@test.com", Some("PRO"), 50, &[CLAUDE_GROUP]),
+        create_test_token("free@test.com", Some("FREE"), 30, &[CLAUDE_GROUP]),
+        create_test_token("unknown@test.com", None, 100, &[CLAUDE_GROUP]),
+    ]
+}
+
+#[test]
+fn test_conserve_high_tier_routes_sonnet_to_lowest_tier() {
+    let tokens = mixed_pool();
+    assert_eq!(pick(&tokens, SONNET, SelectionPolicy::ConserveHighTier), "free@test.com");
+
+    // 没有 Free 账号时降到 Pro，而不是 Ultra
+    let without_free: Vec<ProxyToken> = tokens
+        .into_iter()
+        .filter(|t| t.email != "free@test.com")
+        .collect();
+    assert_eq!(pick(&without_free, SONNET, SelectionPolicy::ConserveHighTier), "pro@test.com");
+}
+
+#[test]
+fn test_conserve_high_tier_keeps_ultra_for_opus() {
+    // Pro / Free 同样拥有 claude 配额组，但 Ultra 专属模型仍然优先 Ultra
+    let tokens = mixed_pool();
+
+    assert_eq!(pick(&tokens, OPUS, SelectionPolicy::ConserveHighTier), "ultra@test.com");
+}
+
+#[test]
+fn test_tier_first_policy_unchanged() {
+    let tokens = mixed_pool();
+    assert_eq!(pick(&tokens, SONNET, SelectionPolicy::TierFirst), "ultra@test.com");
+    assert_eq!(pick(&tokens, OPUS, SelectionPolicy::TierFirst), "ultra@test.com");
+}
+
+#[test]
+fn test_selection_config_export_import_round_trip() {
+    let mut config = SelectionConfig {
+        policy: SelectionPolicy::ConserveHighTier,
+        reset_time_threshold_secs: 1800,
+        ..SelectionConfig::default()
+    };
+    config.tier_weights.insert("enterprise".to_string(), 1);
+    config.model_cost.insert("opus".to_string(), 4.0);
+    config.ultra_required_models.push("round-trip-test-model".to_string());
+    config
+        .model_aliases
+        .insert("round-trip-alias".to_string(), "claude-sonnet-4-6".to_string());
+    assert_ne!(config, SelectionConfig::default());
+
+    // 使用局部配置与临时文件，不修改全局配置 (测试并行运行)
+    let serialized = serde_json::to_value(&config).unwrap();
+    let imported = parse_selection_config(serialized).unwrap();
+    assert_eq!(imported, config);
+
+    let dir = std::env::temp_dir().join(format!("ag_selection_{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(SELECTION_CONFIG_FILE);
+    assert_eq!(load_selection_config_from(&path).unwrap(), None);
+    save_selection_config_to(&path, &imported).unwrap();
+    assert_eq!(load_selection_config_from(&path).unwrap(), Some(config));
+
+    // 缺失字段回落到默认值，非法阈值被拒绝
+    let partial = parse_selection_config(serde_json::json!({ "policy": "TierFirst" })).unwrap();
+    assert_eq!(partial, SelectionConfig::default());
+    assert!(parse_selection_config(serde_json::json!({ "reset_time_threshold_secs": -1 })).is_err());
+    std::fs::write(&path, r#"{ "reset_grace_secs": -5 }"#).unwrap();
+    assert!(load_selection_config_from(&path).is_err());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_tier_synonyms_classify_real_world_tier_strings() {
+    let config = SelectionConfig::default();
+    let ultra = config.tier_priority(&Some("ULTRA".to_string()));
+    let pro = config.tier_priority(&Some("PRO".to_string()));
+    let free = config.tier_priority(&Some("FREE".to_string()));
+
+    for (tier, expected) in [
+        ("g1-ultra-tier", ultra),
+        ("Google One AI Premium", ultra),
+        ("ENTERPRISE", ultra),
+        ("g1-pro-tier", pro),
+        ("Gemini Plus", pro),
+        ("workspace-team", pro),
+        ("free-tier", free),
+        ("standard-tier", UNKNOWN_TIER_PRIORITY),
+    ] {
+        assert_eq!(config.tier_priority(&Some(tier.to_string())), expected, "{}", tier);
+    }
+
+    // 可配置：新增同义词；直接命中的等级名优先于同义词
+    let mut custom = SelectionConfig::default();
+    custom.tier_synonyms.insert("standard".to_string(), "PRO".to_string());
+    custom.tier_weights.insert("enterprise".to_string(), 1);
+    assert_eq!(custom.tier_priority(&Some("standard-tier".to_string())), pro);
+    assert_eq!(custom.tier_priority(&Some("enterprise".to_string())), 1);
+
+    // 指向未知等级的同义词在导入时被拒绝
+    assert!(parse_selection_config(serde_json::json!({
+        "tier_synonyms": { "gold": "platinum" }
+    }))
+    .is_err());
+}
+
+#[test]
+fn test_select_iter_yields_ranked_eligible_tokens() {
+    let config = SelectionConfig::default();
+    let mut tokens = mixed_pool();
+    tokens.push(create_test_token("flash-only@test.com", Some("ULTRA"), 100, &["gemini-3-flash"]));
+    tokens.push(create_test_token("pro-2@test.com", Some("PRO"), 80, &[CLAUDE_GROUP]));
+
+    let normalized = config.normalize_target(SONNET);
+    let mut expected: Vec<ProxyToken> = tokens
+        .iter()
+        .filter(|t| t.model_quotas.contains_key(&normalized))
+        .cloned()
+        .collect();
+    let now = chrono::Utc::now().timestamp();
+    expected.sort_by(|a, b| config.compare(a, b, SONNET, &normalized, now));
+    let expected: Vec<String> = expected.into_iter().map(|t| t.email).collect();
+
+    let mut iter = select_iter_with(&tokens, SONNET, &config);
+    let mut yielded = Vec::new();
+    for token in iter.by_ref() {
+        yielded.push(token.email);
+    }
+
+    assert_eq!(yielded, expected);
+    assert_eq!(
+        yielded,
+        vec!["ultra@test.com", "pro-2@test.com", "pro@test.com", "free@test.com", "unknown@test.com"]
+    );
+    assert!(!yielded.contains(&"flash-only@test.com".to_string()));
+    assert!(iter.next().is_none());
+}
+
+#[test]
+fn test_pinned_account_wins_when_eligible_and_falls_back_otherwise() {
+    let mut config = SelectionConfig::default();
+    let target = config.normalize_target(SONNET);
+    config
+        .pinned_accounts
+        .insert(target.clone(), "free@test.com".to_string());
+
+    // 固定账号无视等级排序优先，其余账号保持原排序
+    let tokens = mixed_pool();
+    assert_eq!(
+        emails(select_iter_with(&tokens, SONNET, &config).collect()),
+        vec!["free@test.com", "ultra@test.com", "pro@test.com", "unknown@test.com"]
+    );
+
+    // 邮箱大小写不同也能匹配
+    config
+        .pinned_accounts
+        .insert(target.clone(), "PRO@Test.com".to_string());
+    assert_eq!(
+        select_iter_with(&tokens, SONNET, &config).next().unwrap().email,
+        "pro@test.com"
+    );
+
+    // 固定账号被封禁 / 该模型配额耗尽 / 无法服务该模型时回退到正常排序
+    let mut blocked = mixed_pool();
+    for token in blocked.iter_mut().filter(|t| t.email == "pro@test.com") {
+        token.validation_blocked = true;
+        token.validation_blocked_until = chrono::Utc::now().timestamp() + 600;
+    }
+    let mut exhausted = mixed_pool();
+    for token in exhausted.iter_mut().filter(|t| t.email == "pro@test.com") {
+        token.model_quotas.insert(target.clone(), 0);
+    }
+    let cannot_serve: Vec<ProxyToken> = mixed_pool()
+        .into_iter()
+        .map(|t| {
+            if t.email == "pro@test.com" {
+                create_test_token(&t.email, Some("PRO"), 100, &["gemini-3-flash"])
+            } else {
+                t
+            }
+        })
+        .collect();
+    for pool in [blocked, exhausted, cannot_serve] {
+        assert_eq!(
+            select_iter_with(&pool, SONNET, &config).next().unwrap().email,
+            "ultra@test.com"
+        );
+    }
+}
+
+#[test]
+fn test_model_capacity_sums_eligible_accounts_by_tier() {
+    let config = SelectionConfig::default();
+    let now = chrono::Utc::now().timestamp();
+    let mut tokens = mixed_pool();
+    tokens.push(create_test_token("pro-2@test.com", Some("pro"), 40, &[CLAUDE_GROUP]));
+    tokens.push(create_test_token("flash-only@test.com", Some("ULTRA"), 100, &["gemini-3-flash"]));
+    let mut blocked = create_test_token("blocked@test.com", Some("ULTRA"), 70, &[CLAUDE_GROUP]);
+    blocked.validation_blocked = true;
+    blocked.validation_blocked_until = now + 600;
+    tokens.push(blocked);
+
+    let capacity = model_capacity_with(&tokens, SONNET, &config, now);
+    assert_eq!(capacity.total_quota, 90 + 50 + 30 + 100 + 40);
+    assert_eq!(capacity.eligible_accounts, 5);
+
+    let tier = |name: &str| capacity.by_tier.get(name).cloned().unwrap_or_default();
+    assert_eq!(capacity.by_tier.len(), 4);
+    assert_eq!((tier("ULTRA").total_quota, tier("ULTRA").eligible_accounts), (90, 1));
+    assert_eq!((tier("PRO").total_quota, tier("PRO").eligible_accounts), (90, 2));
+    assert_eq!((tier("FREE").total_quota, tier("FREE").eligible_accounts), (30, 1));
+    assert_eq!((tier("UNKNOWN").total_quota, tier("UNKNOWN").eligible_accounts), (100, 1));
+
+    // 封禁到期后重新计入
+    let later = model_capacity_with(&tokens, SONNET, &config, now + 601);
+    assert_eq!(later.eligible_accounts, 6);
+    assert_eq!(later.by_tier["ULTRA"].total_quota, 160);
+}
+
+#[test]
+fn test_model_cost_ranks_and_sizes_by_projected_requests() {
+    let mut config = SelectionConfig::default();
+    config.model_cost.insert("opus".to_string(), 4.0);
+    let mut more_quota = create_test_token("more-quota@test.com", Some("PRO"), 11, &[CLAUDE_GROUP]);
+    more_quota.health_score = 0.6;
+    let healthier = create_test_token("healthier@test.com", Some("PRO"), 8, &[CLAUDE_GROUP]);
+    let tokens = vec![more_quota, healthier];
+
+    // Opus 每次消耗 4 点：11 与 8 都只够 2 次请求，由健康分决定
+    let ranked = emails(select_iter_with(&tokens, OPUS, &config).collect());
+    assert_eq!(ranked, vec!["healthier@test.com", "more-quota@test.com"]);
+    // Sonnet 按默认成本 1 计，剩余配额更多者优先
+    let ranked = emails(select_iter_with(&tokens, SONNET, &config).collect());
+    assert_eq!(ranked, vec!["more-quota@test.com", "healthier@test.com"]);
+
+    let now = chrono::Utc::now().timestamp();
+    let capacity = model_capacity_with(&tokens, OPUS, &config, now);
+    assert_eq!(capacity.total_quota, 19);
+    assert_eq!(capacity.projected_requests, 4);
+    assert_eq!(capacity.by_tier["PRO"].projected_requests, 4);
+    assert_eq!(model_capacity_with(&tokens, SONNET, &config, now).projected_requests, 19);
+}
+
+#[test]
+fn test_disabled_account_is_excluded_from_selection() {
+    let config = SelectionConfig::default();
+    let mut tokens = mixed_pool();
+    for token in tokens.iter_mut().filter(|t| t.email == "ultra@test.com") {
+        token.enabled = false;
+    }
+
+    let ranked = emails(select_iter_with(&tokens, SONNET, &config).collect());
+    assert_eq!(ranked, vec!["pro@test.com", "free@test.com", "unknown@test.com"]);
+    assert_eq!(
+        select_with_min_quota_with(&tokens, SONNET, 80, &config).map(|t| t.email),
+        Some("unknown@test.com".to_string())
+    );
+
+    // 固定账号被停用时同样跳过
+    let mut pinned = config.clone();
+    pinned
+        .pinned_accounts
+        .insert(config.normalize_target(SONNET), "ultra@test.com".to_string());
+    assert_eq!(
+        select_iter_with(&tokens, SONNET, &pinned).next().unwrap().email,
+        "pro@test.com"
+    );
+
+    let capacity = model_capacity_with(&tokens, SONNET, &config, chrono::Utc::now().timestamp());
+    assert_eq!(capacity.eligible_accounts, 3);
+    assert!(!capacity.by_tier.contains_key("ULTRA"));
+}
+
+#[test]
+fn test_select_top_n_strict_and_tier_interleaved() {
+    let config = SelectionConfig::default();
+    let tokens = vec![
+        create_test_token("ultra-1@test.com", Some("ULTRA"), 90, &[CLAUDE_GROUP]),
+        create_test_token("ultra-2@test.com", Some("ULTRA"), 80, &[CLAUDE_GROUP]),
+        create_test_token("ultra-3@test.com", Some("ULTRA"), 70, &[CLAUDE_GROUP]),
+        create_test_token("pro-1@test.com", Some("PRO"), 60, &[CLAUDE_GROUP]),
+        create_test_token("pro-2@test.com", Some("PRO"), 50, &[CLAUDE_GROUP]),
+        create_test_token("flash-only@test.com", Some("ULTRA"), 100, &["gemini-3-flash"]),
+    ];
+
+    // 默认严格排序：Ultra 全部在前
+    let strict = select_top_n_with(&tokens, SONNET, 4, FanOutOrder::default(), &config);
+    assert_eq!(
+        emails(strict),
+        vec!["ultra-1@test.com", "ultra-2@test.com", "ultra-3@test.com", "pro-1@test.com"]
+    );
+
+    // 按等级轮转：Ultra / Pro 交替，某一等级用完后剩余账号依次补上
+    let interleaved = select_top_n_with(&tokens, SONNET, 10, FanOutOrder::TierInterleaved, &config);
+    assert_eq!(
+        emails(interleaved),
+        vec![
+            "ultra-1@test.com",
+            "pro-1@test.com",
+            "ultra-2@test.com",
+            "pro-2@test.com",
+            "ultra-3@test.com",
+        ]
+    );
+
+    let first_two = select_top_n_with(&tokens, SONNET, 2, FanOutOrder::TierInterleaved, &config);
+    assert_eq!(emails(first_two), vec!["ultra-1@test.com", "pro-1@test.com"]);
+    assert!(select_top_n_with(&tokens, SONNET, 0, FanOutOrder::Strict, &config).is_empty());
+}
+
+fn with_project(mut token: ProxyToken, project_id: &str) -> ProxyToken {
+    token.project_id = Some(project_id.to_string());
+    token
+}
+
+fn emails(tokens: Vec<ProxyToken>) -> Vec<String> {
+    tokens.into_iter().map(|t| t.email).collect()
+}
+
+#[test]
+fn test_shared_project_is_one_quota_bucket() {
+    let config = SelectionConfig::default();
+    // shared-a / shared-b 共享同一个项目配额，单看账号配额它们都排在前面
+    let tokens = vec![
+        with_project(create_test_token("shared-a@test.com", Some("PRO"), 80, &[CLAUDE_GROUP]), "proj-shared"),
+        with_project(create_test_token("shared-b@test.com", Some("PRO"), 80, &[CLAUDE_GROUP]), "proj-shared"),
+        with_project(create_test_token("solo@test.com", Some("PRO"), 60, &[CLAUDE_GROUP]), "proj-solo"),
+    ];
+
+    assert_eq!(
+        emails(select_iter_with(&tokens, SONNET, &config).collect()),
+        vec!["shared-a@test.com", "shared-b@test.com", "solo@test.com"]
+    );
+
+    // 按项目均衡：取完 proj-shared 的一个账号后轮到 proj-solo，而不是继续堆在同一项目
+    assert_eq!(
+        emails(select_balancing_projects_with(&tokens, SONNET, &config)),
+        vec!["shared-a@test.com", "solo@test.com", "shared-b@test.com"]
+    );
+
+    let balanced = SelectionConfig {
+        balance_projects: true,
+        ..SelectionConfig::default()
+    };
+    assert_eq!(
+        emails(select_iter_with(&tokens, SONNET, &balanced).collect()),
+        vec!["shared-a@test.com", "solo@test.com", "shared-b@test.com"]
+    );
+
+    // get_token 的候选排序同样按项目轮转
+    let normalized = balanced.normalize_target(SONNET);
+    let mut candidates = tokens.clone();
+    assert_eq!(rank_for_request(&mut candidates, SONNET, &normalized, &balanced), None);
+    assert_eq!(emails(candidates), vec!["shared-a@test.com", "solo@test.com", "shared-b@test.com"]);
+    let mut candidates = tokens.clone();
+    rank_for_request(&mut candidates, SONNET, &normalized, &config);
+    assert_eq!(emails(candidates), vec!["shared-a@test.com", "shared-b@test.com", "solo@test.com"]);
+}
+
+#[test]
+fn test_project_bucket_uses_pooled_quota() {
+    let config = SelectionConfig::default();
+    // 同项目账号上报的配额不一致时取最小值作为共享配额 (20 < 50)
+    let tokens = vec![
+        with_project(create_test_token("shared-high@test.com", Some("PRO"), 90, &[CLAUDE_GROUP]), "proj-shared"),
+        with_project(create_test_token("shared-low@test.com", Some("PRO"), 20, &[CLAUDE_GROUP]), "proj-shared"),
+        with_project(create_test_token("solo@test.com", Some("PRO"), 50, &[CLAUDE_GROUP]), "proj-solo"),
+        create_test_token("no-project@test.com", Some("PRO"), 40, &[CLAUDE_GROUP]),
+    ];
+
+    assert_eq!(
+        emails(select_balancing_projects_with(&tokens, SONNET, &config)),
+        vec!["solo@test.com", "no-project@test.com", "shared-high@test.com", "shared-low@test.com"]
+    );
+}
+
+#[test]
+fn test_min_quota_selects_only_qualifying_account() {
+    let config = SelectionConfig::default();
+    let tokens = mixed_pool();
+
+    // 只有 unknown (100) 满足 >= 95，即使其等级排序最靠后
+    let picked = select_with_min_quota_with(&tokens, SONNET, 95, &config).unwrap();
+    assert_eq!(picked.email, "unknown@test.com");
+
+    // 门槛降低后恢复正常排序
+    let picked = select_with_min_quota_with(&tokens, SONNET, 50, &config).unwrap();
+    assert_eq!(picked.email, "ultra@test.com");
+}
+
+#[test]
+fn test_min_quota_returns_none_when_no_account_qualifies() {
+    let config = SelectionConfig::default();
+    let tokens = mixed_pool();
+
+    assert!(select_with_min_quota_with(&tokens, SONNET, 101, &config).is_none());
+    // 不支持该模型的账号不参与
+    assert!(select_with_min_quota_with(&tokens, "gemini-3-flash", 0, &config).is_none());
+}
+
+#[test]
+fn test_tier_override_changes_ranking() {
+    let config = SelectionConfig::default();
+    let ultra = create_test_token("ultra@test.com", Some("ULTRA"), 40, &[CLAUDE_GROUP]);
+    let grandfathered = create_test_token("grandfathered@test.com", Some("FREE"), 90, &[CLAUDE_GROUP]);
+    let tokens = vec![ultra.clone(), grandfathered.clone()];
+
+    // 按上游返回的等级：Ultra 优先
+    assert_eq!(
+        emails(select_iter_with(&tokens, SONNET, &config).collect()),
+        vec!["ultra@test.com", "grandfathered@test.com"]
+    );
+
+    // 视为 Ultra 后同等级比较配额，剩余更多的账号排到前面
+    let mut pinned = grandfathered;
+    pinned.tier_override = Some("ULTRA".to_string());
+    assert_eq!(pinned.effective_tier().as_deref(), Some("ULTRA"));
+    let tokens = vec![ultra, pinned];
+    assert_eq!(
+        emails(select_iter_with(&tokens, SONNET, &config).collect()),
+        vec!["grandfathered@test.com", "ultra@test.com"]
+    );
+}
+
+#[test]
+fn test_selection_hook_receives_event() {
+    let config = SelectionConfig {
+        policy: SelectionPolicy::ConserveHighTier,
+        ..SelectionConfig::default()
+    };
+    let tokens = vec![
+        create_test_token("hook-ultra@test.com", Some("ULTRA"), 90, &[CLAUDE_GROUP]),
+        create_test_token("hook-free@test.com", Some("FREE"), 60, &[CLAUDE_GROUP]),
+        create_test_token("hook-gemini@test.com", Some("PRO"), 80, &["gemini-3-flash"]),
+    ];
+
+    // 回调为全局注册，只收集本测试账号的事件 (其他测试可能并行选择)
+    let events: Arc<Mutex<Vec<SelectionEvent>>> = Arc::new(Mutex::new(Vec::new()));
+    let sink = events.clone();
+    let hook_id = on_selection(move |event| {
+        if event.email.starts_with("hook-") {
+            sink.lock().unwrap().push(event.clone());
+        }
+    });
+
+    let picked = select_with_min_quota_with(&tokens, SONNET, 50, &config).unwrap();
+    assert_eq!(picked.email, "hook-free@test.com");
+    assert!(remove_selection_hook(hook_id));
+
+    // 注销后不再触发
+    select_with_min_quota_with(&tokens, SONNET, 50, &config).unwrap();
+    assert!(!remove_selection_hook(hook_id));
+
+    let events = events.lock().unwrap();
+    assert_eq!(
+        *events,
+        vec![SelectionEvent {
+            model: SONNET.to_string(),
+            account_id: "hook-free@test.com".to_string(),
+            email: "hook-free@test.com".to_string(),
+            candidate_count: 2,
+            policy: SelectionPolicy::ConserveHighTier,
+        }]
+    );
+}
+
+#[test]
+fn test_secondary_order_decides_between_quota_and_health() {
+    // 同为 PRO：一个配额多但不稳定，一个配额少但健康
+    let mut quota_rich = create_test_token("quota-rich@test.com", Some("PRO"), 90, &[CLAUDE_GROUP]);
+    quota_rich.health_score = 0.4;
+    let mut reliable = create_test_token("reliable@test.com", Some("PRO"), 30, &[CLAUDE_GROUP]);
+    reliable.health_score = 1.0;
+    let tokens = vec![quota_rich, reliable];
+
+    for (order, expected) in [
+        (SecondaryOrder::QuotaThenHealth, ["quota-rich@test.com", "reliable@test.com"]),
+        (SecondaryOrder::HealthThenQuota, ["reliable@test.com", "quota-rich@test.com"]),
+    ] {
+        let config = SelectionConfig {
+            secondary_order: order,
+            ..SelectionConfig::default()
+        };
+        assert_eq!(
+            emails(select_iter_with(&tokens, SONNET, &config).collect()),
+            expected,
+            "secondary order {:?}",
+            order
+        );
+    }
+
+    // 默认保持配额优先
+    assert_eq!(SelectionConfig::default().secondary_order, SecondaryOrder::QuotaThenHealth);
+}
+
+#[test]
+fn test_selection_trace_reports_per_model_breaker_health() {
+    let config = SelectionConfig::default();
+    let now = chrono::Utc::now().timestamp();
+    // Ultra 账号的 Opus 熔断中，Sonnet 不受影响
+    let tokens = vec![
+        create_test_token("ultra@test.com", Some("ULTRA"), 90, &[CLAUDE_GROUP]),
+        create_test_token("pro@test.com", Some("PRO"), 60, &[CLAUDE_GROUP]),
+    ];
+    let breaker_for = |model: &'static str| {
+        move |account_id: &str| {
+            if account_id == "ultra@test.com" && model == OPUS {
+                120
+            } else {
+                0
+            }
+        }
+    };
+
+    let opus = trace_selection_with(&tokens, OPUS, &config, now, breaker_for(OPUS));
+    assert_eq!(opus.normalized_target, CLAUDE_GROUP);
+    let ultra = opus.candidates.iter().find(|c| c.email == "ultra@test.com").unwrap();
+    assert_eq!(ultra.health_class, HealthClass::Healthy);
+    assert_eq!(ultra.model_health_class, HealthClass::Blocked);
+    assert_eq!(ultra.model_breaker_wait_secs, 120);
+    assert_eq!(ultra.skipped, Some(TraceSkipReason::ModelBreakerOpen));
+    assert_eq!(ultra.rank, None);
+    assert_eq!(opus.candidates[0].email, "pro@test.com");
+    assert_eq!(opus.candidates[0].rank, Some(0));
+
+    let sonnet = trace_selection_with(&tokens, SONNET, &config, now, breaker_for(SONNET));
+    let ultra = &sonnet.candidates[0];
+    assert_eq!(ultra.email, "ultra@test.com");
+    assert_eq!(ultra.model_health_class, HealthClass::Healthy);
+    assert_eq!(ultra.model_breaker_wait_secs, 0);
+    assert_eq!((ultra.rank, ultra.skipped), (Some(0), None));
+
+    // 无能力 / 已停用的账号同样出现在诊断中
+    let mut pool = tokens.clone();
+    pool.push(create_test_token("flash@test.com", Some("PRO"), 100, &["gemini-3-flash"]));
+    pool[1].enabled = false;
+    let trace = trace_selection_with(&pool, SONNET, &config, now, breaker_for(SONNET));
+    let reason = |email: &str| trace.candidates.iter().find(|c| c.email == email).unwrap().skipped;
+    assert_eq!(reason("flash@test.com"), Some(TraceSkipReason::NoCapability));
+    assert_eq!(reason("pro@test.com"), Some(TraceSkipReason::Disabled));
+}
+
+#[test]
+fn test_recently_failed_account_ranks_below_equivalent_healthy_one() {
+    let now = crate::utils::clock::now();
+    let mut failed = create_test_token("failed@test.com", Some("PRO"), 80, &[CLAUDE_GROUP]);
+    failed.last_failure = Some(now - 5);
+    let healthy = create_test_token("healthy@test.com", Some("PRO"), 80, &[CLAUDE_GROUP]);
+    let config = SelectionConfig::default();
+
+    let tokens = vec![failed.clone(), healthy.clone()];
+    assert_eq!(
+        emails(select_iter_with(&tokens, SONNET, &config).collect()),
+        ["healthy@test.com", "failed@test.com"]
+    );
+
+    // 仅剩最近失败的账号时仍可被选中
+    let only = vec![failed.clone()];
+    assert_eq!(emails(select_iter_with(&only, SONNET, &config).collect()), ["failed@test.com"]);
+
+    // 订阅等级仍然优先于最近失败
+    let ultra_failed = ProxyToken {
+        subscription_tier: Some("ULTRA".to_string()),
+        ..failed.clone()
+    };
+    let tokens = vec![healthy.clone(), ultra_failed];
+    assert_eq!(select_iter_with(&tokens, SONNET, &config).next().unwrap().email, "failed@test.com");
+
+    // 配额更高时：BeforeQuota 仍降权，AfterQuota 由配额决定
+    failed.model_quotas.insert(CLAUDE_GROUP.to_string(), 95);
+    let tokens = vec![failed.clone(), healthy.clone()];
+    for (stage, expected) in [
+        (RecentFailureStage::BeforeQuota, "healthy@test.com"),
+        (RecentFailureStage::AfterQuota, "failed@test.com"),
+    ] {
+        let config = SelectionConfig {
+            recent_failure_stage: stage,
+            ..SelectionConfig::default()
+        };
+        assert_eq!(
+            select_iter_with(&tokens, SONNET, &config).next().unwrap().email,
+            expected,
+            "stage {:?}",
+            stage
+        );
+    }
+
+    // 窗口为 0 关闭该阶段；失败时间超出窗口同样不再降权
+    let disabled = SelectionConfig {
+        recent_failure_window_secs: 0,
+        ..SelectionConfig::default()
+    };
+    assert_eq!(select_iter_with(&tokens, SONNET, &disabled).next().unwrap().email, "failed@test.com");
+    failed.last_failure = Some(now - config.recent_failure_window_secs - 1);
+    let tokens = vec![failed, healthy.clone()];
+    assert_eq!(select_iter_with(&tokens, SONNET, &config).next().unwrap().email, "failed@test.com");
+
+    // 比较使用调用方传入的时刻：同一次排序内窗口边界不会随时间漂移
+    let normalized = config.normalize_target(SONNET);
+    let mut failed = create_test_token("failed@test.com", Some("PRO"), 80, &[CLAUDE_GROUP]);
+    failed.last_failure = Some(now);
+    let inside = now + config.recent_failure_window_secs - 1;
+    let outside = now + config.recent_failure_window_secs;
+    assert_eq!(config.compare(&failed, &healthy, SONNET, &normalized, inside), std::cmp::Ordering::Greater);
+    assert_eq!(config.compare(&failed, &healthy, SONNET, &normalized, outside), std::cmp::Ordering::Equal);
+}
+
+#[test]
+fn test_unknown_capability_policy_for_empty_quota_accounts() {
+    // 刚导入、尚未获取能力的账号：model_quotas 为空
+    let fresh = create_test_token("fresh@test.com", Some("PRO"), 0, &[]);
+    assert!(fresh.model_quotas.is_empty());
+    let known = create_test_token("known@test.com", Some("PRO"), 60, &[CLAUDE_GROUP]);
+    let now = chrono::Utc::now().timestamp();
+
+    // 默认保持原行为：能力未知的账号被能力过滤排除
+    let strict = SelectionConfig::default();
+    assert!(!strict.assume_capable_when_unknown);
+    assert!(select_iter_with(std::slice::from_ref(&fresh), SONNET, &strict).next().is_none());
+    let trace = trace_selection_with(std::slice::from_ref(&fresh), SONNET, &strict, now, |_| 0);
+    assert_eq!(trace.candidates[0].skipped, Some(TraceSkipReason::NoCapability));
+
+    // 开启后暂时视为可服务任意模型，但排在已确认有配额的账号之后
+    let lenient = SelectionConfig {
+        assume_capable_when_unknown: true,
+        ..SelectionConfig::default()
+    };
+    assert!(lenient.assumes_capable(&fresh));
+    assert!(!lenient.assumes_capable(&known));
+    assert_eq!(
+        emails(select_iter_with(std::slice::from_ref(&fresh), "gemini-3-flash", &lenient).collect()),
+        ["fresh@test.com"]
+    );
+    assert_eq!(
+        emails(select_iter_with(&[fresh.clone(), known.clone()], SONNET, &lenient).collect()),
+        ["known@test.com", "fresh@test.com"]
+    );
+    let trace = trace_selection_with(std::slice::from_ref(&fresh), SONNET, &lenient, now, |_| 0);
+    assert_eq!(trace.candidates[0].skipped, None);
+
+    // 已有能力数据的账号不受影响：缺少目标模型时仍被排除
+    assert!(select_iter_with(&[known], "gemini-3-flash", &lenient).next().is_none());
+}
+
+#[test]
+fn test_reserve_top_tier_keeps_last_ultra_for_required_models() {
+    let ultra = create_test_token("ultra@test.com", Some("ULTRA"), 80, &[CLAUDE_GROUP]);
+    let pro_a = create_test_token("pro-a@test.com", Some("PRO"), 50, &[CLAUDE_GROUP]);
+    let pro_b = create_test_token("pro-b@test.com", Some("PRO"), 40, &[CLAUDE_GROUP]);
+    let pool = vec![ultra.clone(), pro_a.clone(), pro_b.clone()];
+
+    // 默认关闭：Ultra 优先
+    assert!(!SelectionConfig::default().reserve_top_tier);
+    assert_eq!(
+        emails(select_iter_with(&pool, SONNET, &SelectionConfig::default()).collect())[0],
+        "ultra@test.com"
+    );
+
+    let config = SelectionConfig {
+        reserve_top_tier: true,
+        ..SelectionConfig::default()
+    };
+    // Sonnet 走 Pro，唯一的 Ultra 留作后备
+    assert_eq!(
+        emails(select_iter_with(&pool, SONNET, &config).collect()),
+        ["pro-a@test.com", "pro-b@test.com", "ultra@test.com"]
+    );
+    // Opus 等 Ultra 专属模型不受影响
+    assert_eq!(
+        emails(select_iter_with(&pool, OPUS, &config).collect())[0],
+        "ultra@test.com"
+    );
+
+    // Pro 耗尽后，Ultra 重新参与 Sonnet 的调度
+    let exhausted: Vec<ProxyToken> = [pro_a, pro_b]
+        .into_iter()
+        .map(|mut t| {
+            t.model_quotas.insert(CLAUDE_GROUP.to_string(), 0);
+            t
+        })
+        .collect();
+    let mut drained_pool = vec![ultra.clone()];
+    drained_pool.extend(exhausted);
+    assert_eq!(
+        emails(select_iter_with(&drained_pool, SONNET, &config).collect())[0],
+        "ultra@test.com"
+    );
+
+    // 有两个 Ultra 时无需保留
+    let ultra_b = create_test_token("ultra-b@test.com", Some("ULTRA"), 70, &[CLAUDE_GROUP]);
+    let mut two_ultras = pool.clone();
+    two_ultras.push(ultra_b);
+    assert_eq!(
+        emails(select_iter_with(&two_ultras, SONNET, &config).collect())[..2],
+        ["ultra@test.com", "ultra-b@test.com"]
+    );
+}
+
+#[test]
+fn test_models_prefix_is_stripped_only_for_configured_families() {
+    let config = SelectionConfig::default();
+
+    // 已知模型：两个家族都解析到标准 ID
+    assert_eq!(config.quota_key("models/gemini-2.0-pro"), "gemini-3-pro-high");
+    assert_eq!(config.quota_key("claude-opus-4-6"), CLAUDE_GROUP);
+    assert_eq!(config.quota_key("models/claude-opus-4-6"), CLAUDE_GROUP);
+
+    // Claude 家族的前缀是传输层附加的，剥离；Gemini 家族保留前缀作为 key 的一部分
+    assert_eq!(config.quota_key("models/claude-next"), CLAUDE_GROUP);
+    assert_eq!(config.quota_key("models/gemini-embedding-001"), "models/gemini-embedding-001");
+    assert_ne!(
+        config.quota_key("models/gemini-embedding-001"),
+        config.quota_key("gemini-embedding-001")
+    );
+    assert_eq!(config.quota_key("models/text-embedding-004"), "models/text-embedding-004");
+    // 请求侧与存储侧使用同一规则
+    assert_eq!(
+        config.normalize_target("models/gemini-embedding-001"),
+        config.quota_key("models/gemini-embedding-001")
+    );
+
+    // 可按配置为其他家族开启剥离
+    let strip_gemini = SelectionConfig {
+        prefix_strip_families: vec!["claude".to_string(), "Gemini".to_string()],
+        ..SelectionConfig::default()
+    };
+    assert_eq!(strip_gemini.quota_key("models/gemini-embedding-001"), "gemini-embedding-001");
+    assert_eq!(strip_gemini.quota_key("models/text-embedding-004"), "models/text-embedding-004");
+}
+
+#[test]
+fn test_selection_scoped_to_group_only_considers_its_accounts() {
+    let ultra = create_test_token("ultra@test.com", Some("ULTRA"), 90, &[CLAUDE_GROUP]);
+    let pro = create_test_token("pro@test.com", Some("PRO"), 80, &[CLAUDE_GROUP]);
+    let free = create_test_token("free@test.com", Some("FREE"), 70, &[CLAUDE_GROUP]);
+    let pool = vec![ultra, pro, free];
+
+    let group = |emails: &[&str]| emails.iter().map(|e| e.to_string()).collect();
+    let config = SelectionConfig {
+        groups: [
+            ("opus-reserved".to_string(), group(&["Ultra@Test.com"])),
+            ("bulk".to_string(), group(&["pro@test.com", "free@test.com"])),
+            // 账号可同时属于多个分组
+            ("free-tier".to_string(), group(&["free@test.com"])),
+        ]
+        .into_iter()
+        .collect(),
+        ..SelectionConfig::default()
+    };
+
+    let select = |model: &str, group: Option<&str>| -> Result<Option<String>, String> {
+        let mut scoped = pool.clone();
+        if let Some(group) = group {
+            config.scope_to_group(&mut scoped, group)?;
+        }
+        Ok(select_iter_with(&scoped, model, &config).next().map(|t| t.email))
+    };
+
+    // 不限定分组时按全池排序
+    assert_eq!(select(SONNET, None).unwrap().as_deref(), Some("ultra@test.com"));
+    // 限定分组后只在组内排序
+    assert_eq!(select(SONNET, Some("bulk")).unwrap().as_deref(), Some("pro@test.com"));
+    assert_eq!(select(SONNET, Some("free-tier")).unwrap().as_deref(), Some("free@test.com"));
+    assert_eq!(select(OPUS, Some("opus-reserved")).unwrap().as_deref(), Some("ultra@test.com"));
+    assert!(config.in_group(&pool[2], "bulk") && config.in_group(&pool[2], "free-tier"));
+
+    // 组内账号都无法服务 / 分组不存在时不回退到全池
+    assert_eq!(select("gemini-3-flash", Some("bulk")).unwrap(), None);
+    assert!(select(SONNET, Some("missing")).unwrap_err().contains("missing"));
+}
+
+#[test]
+fn test_account_groups_are_not_exported() {
+    let config = SelectionConfig {
+        groups: [("bulk".to_string(), ["pro@test.com".to_string()].into_iter().collect())]
+            .into_iter()
+            .collect(),
+        ..SelectionConfig::default()
+    };
+    // 分组只以反代配置 account_groups 持久化：导出/落盘内容不含分组
+    let json = serde_json::to_value(&config).unwrap();
+    assert!(json.get("groups").is_none());
+    let parsed: SelectionConfig = serde_json::from_value(json).unwrap();
+    assert!(parsed.groups.is_empty());
+}
@@ -14,10 +14,9 @@
 //! - `test_ultra_priority_for_high_end_models`: 验证 Ultra 优先于 Pro（即使 Pro 配额更高）
 //! - `test_ultra_accounts_sorted_by_quota`: 验证同为 Ultra 时按配额排序
 //! - `test_full_sorting_mixed_accounts`: 验证混合账号池的完整排序
+//! - `test_enforce_min_tier_with_future_pro`: 验证强制最低等级开关 (宽松 / 强制模式)
 
 use std::cmp::Ordering;
-use std::collections::{HashMap, HashSet};
-use std::path::PathBuf;
 
 use crate::proxy::token_manager::ProxyToken;
 
@@ -30,32 +29,18 @@ fn create_test_token(
     remaining_quota: Option<i32>,
     supported_models: Vec<&str>,
 ) -> ProxyToken {
-    let mut model_quotas = HashMap::new();
+    let mut builder = ProxyToken::builder(email, "test_token", "test_refresh", email)
+        .expires(3600, chrono::Utc::now().timestamp() + 3600)
+        .account_path("/tmp/test")
+        .subscription_tier(tier.map(|s| s.to_string()))
+        .remaining_quota(remaining_quota)
+        .health_score(health_score)
+        .reset_time(reset_time);
     // 模拟配额：所有支持的模型都给予相同的剩余配额
     for m in supported_models {
-        model_quotas.insert(m.to_string(), remaining_quota.unwrap_or(100));
-    }
-
-    ProxyToken {
-        account_id: email.to_string(),
-        access_token: "test_token".to_string(),
-        refresh_token: "test_refresh".to_string(),
-        expires_in: 3600,
-        timestamp: chrono::Utc::now().timestamp() + 3600,
-        email: email.to_string(),
-        account_path: PathBuf::from("/tmp/test"),
-        project_id: None,
-        subscription_tier: tier.map(|s| s.to_string()),
-        remaining_quota,
-        protected_models: HashSet::new(),
-        health_score,
-        reset_time,
-        validation_blocked: false,
-        validation_blocked_until: 0,
-        validation_url: None,
-        model_quotas,
-        model_limits: std::collections::HashMap::new(),
+        builder = builder.model_quota(m, remaining_quota.unwrap_or(100));
     }
+    builder.build()
 }
 
 /// 需要 Ultra 账号的高端模型列表
@@ -238,3 +223,42 @@ fn test_full_sorting_mixed_accounts() {
         "Sonnet should now sort Ultra first, then Pro, then Free"
     );
 }
+
+/// 测试强制最低等级：开启后上报了 Opus 能力的 Pro 账号也被排除
+#[test]
+fn test_enforce_min_tier_with_future_pro() {
+    use crate::proxy::selection::{select_iter_with, SelectionConfig};
+
+    const OPUS: &str = "claude-opus-4-6";
+    let permissive = SelectionConfig::default();
+    let enforced = SelectionConfig {
+        enforce_min_tier: true,
+        ..SelectionConfig::default()
+    };
+    assert!(!permissive.enforce_min_tier, "Permissive mode should be the default");
+
+    let target = permissive.normalize_target(OPUS);
+    let ultra = create_test_token("ultra@test.com", Some("ULTRA"), 1.0, None, Some(100), vec![target.as_str()]);
+    let future_pro = create_test_token("future_pro@test.com", Some("PRO"), 1.0, None, Some(50), vec![target.as_str()]);
+    let pool = vec![ultra, future_pro.clone()];
+
+    let emails = |config: &SelectionConfig, model: &str| -> Vec<String> {
+        select_iter_with(&pool, model, config).map(|t| t.email).collect()
+    };
+
+    // 1. 宽松模式：按能力过滤，Future Pro 仍可作为后备
+    assert_eq!(emails(&permissive, OPUS), vec!["ultra@test.com", "future_pro@test.com"]);
+
+    // 2. 强制模式：Ultra 专属模型只保留 Ultra 账号
+    assert_eq!(emails(&enforced, OPUS), vec!["ultra@test.com"]);
+    assert!(!enforced.can_serve(&future_pro, OPUS, &target));
+    assert!(permissive.can_serve(&future_pro, OPUS, &target));
+
+    // 3. 非 Ultra 专属模型不受影响
+    assert!(enforced.meets_min_tier(&future_pro, "claude-sonnet-4-6"));
+
+    // 4. 手动指定为 Ultra 的账号满足要求
+    let mut pinned = future_pro;
+    pinned.tier_override = Some("ULTRA".to_string());
+    assert!(enforced.can_serve(&pinned, OPUS, &target));
+}
@@ -2,12 +2,31 @@
 use dashmap::DashMap;
 use std::collections::{HashSet, HashMap};
 use std::path::PathBuf;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 use tokio_util::sync::CancellationToken;
 
+use crate::proxy::capability_cache::{CapabilityCache, CAPABILITY_CACHE_FILE};
 use crate::proxy::rate_limit::RateLimitTracker;
+use crate::proxy::reservation::{ReservationLedger, TokenReservation};
 use crate::proxy::sticky_config::StickySessionConfig;
+use crate::utils::scheduler::TaskScheduler;
+
+/// 限流记录清理任务名与执行间隔
+const AUTO_CLEANUP_TASK: &str = "rate_limit_cleanup";
+const AUTO_CLEANUP_INTERVAL: std::time::Duration = std::time::Duration::from_secs(15);
+
+/// 能力缓存落盘任务名与执行间隔 (记录只更新内存并标记待写，由该任务批量写回)
+const CAPABILITY_FLUSH_TASK: &str = "capability_flush";
+/// 模型能力定期刷新任务名 (间隔由调用方指定)
+const CAPABILITY_REFRESH_TASK: &str = "capability_refresh";
+const CAPABILITY_FLUSH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// access_token 距过期不足该时长 (秒) 时在使用前刷新 (对齐官方客户端)
+const TOKEN_REFRESH_MARGIN_SECS: i64 = 90;
+
+/// 冷账号即时刷新失败后，再次尝试前的最小间隔 (秒)
+const COLD_REFRESH_COOLDOWN_SECS: i64 = 300;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum OnDiskAccountState {
@@ -27,6 +46,7 @@ pub struct ProxyToken {
     pub account_path: PathBuf, // 账号文件路径，用于更新
     pub project_id: Option<String>,
     pub subscription_tier: Option<String>, // "FREE" | "PRO" | "ULTRA"
+    pub tier_override: Option<String>,     // 手动指定的等级，优先于 subscription_tier
     pub remaining_quota: Option<i32>,      // [FIX #563] Remaining quota for priority sorting
     pub protected_models: HashSet<String>, // [NEW #621]
     pub health_score: f32,                 // [NEW] 健康分数 (0.0 - 1.0)
@@ -35,19 +55,403 @@ pub struct ProxyToken {
     pub validation_blocked_until: i64,     // [NEW] Timestamp until which the account is blocked
     pub validation_url: Option<String>,    // [NEW] Validation URL (#1522)
     pub model_quotas: HashMap<String, i32>, // [OPTIMIZATION] In-memory cache for model-specific quotas
+    pub quota_max: HashMap<String, i32>,    // 各模型 (标准化 ID) 配额满额，缺失表示上游未提供
     pub model_limits: HashMap<String, u64>, // [NEW] max_output_tokens per model from quota data
+    pub cached_capabilities: HashSet<String>, // 能力缓存中的可服务模型 (尚无配额数据时用于能力过滤，不含配额值)
+    pub last_refreshed: Option<i64>,       // 等级/配额最近一次从上游刷新的时间戳 (None 表示未知)
+    pub enabled: bool,                     // 用户软禁用开关 (false 时不参与选择)
+    pub last_failure: Option<i64>,         // 最近一次请求失败的时间戳，成功后清除 (仅内存)
+}
+
+/// 离散的健康状态分级，UI 徽标与日志统一使用
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub enum HealthClass {
+    Healthy,
+    Degraded,
+    Unhealthy,
+    Blocked,
+}
+
+/// 传给前端的账号运行时状态，不包含 access_token / refresh_token 等凭据
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ProxyTokenView {
+    pub account_id: String,
+    pub email: String,
+    pub project_id: Option<String>,
+    pub subscription_tier: Option<String>,
+    pub tier_override: Option<String>,
+    pub remaining_quota: Option<i32>,
+    pub health_score: f32,
+    pub health_class: HealthClass,
+    pub reset_time: Option<i64>,
+    pub validation_blocked_until: Option<i64>,
+    pub model_quotas: HashMap<String, i32>,
+    pub last_refreshed: Option<i64>,
+    pub last_failure: Option<i64>,
+}
+
+impl ProxyToken {
+    /// 参与排序的订阅等级：设置了 tier_override 时以其为准
+    pub fn effective_tier(&self) -> &Option<String> {
+        if self.tier_override.is_some() {
+            &self.tier_override
+        } else {
+            &self.subscription_tier
+        }
+    }
+
+    /// 可安全序列化给前端的视图 (不含任何令牌)
+    pub fn view(&self) -> ProxyTokenView {
+        let now = crate::utils::clock::now();
+        ProxyTokenView {
+            account_id: self.account_id.clone(),
+            email: self.email.clone(),
+            project_id: self.project_id.clone(),
+            subscription_tier: self.subscription_tier.clone(),
+            tier_override: self.tier_override.clone(),
+            remaining_quota: self.remaining_quota,
+            health_score: self.health_score,
+            health_class: self.health_class(now),
+            reset_time: self.reset_time,
+            validation_blocked_until: self
+                .is_blocked(now)
+                .then_some(self.validation_blocked_until),
+            model_quotas: self.model_quotas.clone(),
+            last_refreshed: self.last_refreshed,
+            last_failure: self.last_failure,
+        }
+    }
+
+    /// 某模型剩余配额占满额的百分比 (0-100)，供前端仪表盘使用
+    ///
+    /// 先按原始名称查找，再按标准化 ID 查找；满额未知 (或非正) 时返回 None
+    pub fn quota_percentage(&self, model: &str) -> Option<f32> {
+        let key = if self.model_quotas.contains_key(model) {
+            model.to_string()
+        } else {
+            crate::proxy::common::model_mapping::normalize_to_standard_id(model)?
+        };
+        let remaining = *self.model_quotas.get(&key)?;
+        let max = *self.quota_max.get(&key).filter(|max| **max > 0)?;
+        Some((remaining as f32 / max as f32 * 100.0).clamp(0.0, 100.0))
+    }
+
+    /// access_token 在 `now` 时已过期或距过期不足 `margin_secs` 秒
+    pub fn is_expiring(&self, now: i64, margin_secs: i64) -> bool {
+        now >= self.timestamp - margin_secs
+    }
+
+    /// 冷账号：导入时 refresh_token 兑换失败，仍持有占位 access_token
+    pub fn is_cold(&self) -> bool {
+        self.access_token == crate::modules::migration::IMPORTED_ACCESS_TOKEN_PLACEHOLDER
+    }
+
+    /// 在 `now` 之前 `window_secs` 秒内失败过
+    pub fn failed_recently(&self, now: i64, window_secs: i64) -> bool {
+        self.last_failure
+            .is_some_and(|failed_at| now - failed_at < window_secs)
+    }
+
+    /// 是否处于验证封禁期内 (VALIDATION_REQUIRED)
+    pub fn is_blocked(&self, now: i64) -> bool {
+        self.validation_blocked && self.validation_blocked_until > now
+    }
+
+    /// 按全局选择配置中的阈值对健康分分级，封禁状态优先
+    pub fn health_class(&self, now: i64) -> HealthClass {
+        self.health_class_with(now, &crate::proxy::selection::get_selection_config())
+    }
+
+    /// 同 `health_class`，使用指定配置
+    pub fn health_class_with(
+        &self,
+        now: i64,
+        config: &crate::proxy::selection::SelectionConfig,
+    ) -> HealthClass {
+        if self.is_blocked(now) {
+            HealthClass::Blocked
+        } else if self.health_score >= config.healthy_threshold {
+            HealthClass::Healthy
+        } else if self.health_score >= config.degraded_threshold {
+            HealthClass::Degraded
+        } else {
+            HealthClass::Unhealthy
+        }
+    }
+
+    /// 针对某个模型的健康分级：该模型熔断中时为 Blocked，否则同 `health_class_with`
+    pub fn model_health_class_with(
+        &self,
+        now: i64,
+        config: &crate::proxy::selection::SelectionConfig,
+        model_breaker_open: bool,
+    ) -> HealthClass {
+        if model_breaker_open {
+            HealthClass::Blocked
+        } else {
+            self.health_class_with(now, config)
+        }
+    }
+
+    /// 配额耗尽且刷新时间晚于 `now + grace_secs`
+    ///
+    /// 刷新时间落在宽限窗口内的账号仍视为候选，避免临近刷新时被迫降级到低等级账号
+    pub fn is_quota_exhausted(&self, now: i64, grace_secs: i64) -> bool {
+        self.remaining_quota == Some(0)
+            && self.reset_time.is_some_and(|reset| reset > now + grace_secs)
+    }
+
+    /// 所有模型配额均已耗尽 (无分模型配额时看 `remaining_quota`)，且刷新时间晚于 `now`
+    pub fn is_fully_exhausted(&self, now: i64) -> bool {
+        let exhausted = if self.model_quotas.is_empty() {
+            self.remaining_quota == Some(0)
+        } else {
+            self.model_quotas.values().all(|q| *q <= 0)
+        };
+        exhausted && self.reset_time.is_some_and(|reset| reset > now)
+    }
+
+    /// 目标模型配额已耗尽，且已知的刷新时间晚于 `now + grace_secs` (刷新时间未知时不视为耗尽)
+    pub fn is_model_exhausted_with(&self, normalized_target: &str, now: i64, grace_secs: i64) -> bool {
+        self.model_quotas.get(normalized_target).is_some_and(|q| *q <= 0)
+            && self.reset_time.is_some_and(|reset| reset > now + grace_secs)
+    }
+
+    /// 目标模型仍有配额，或配额为 0 但刷新时间落在宽限窗口内 (押注即将刷新)
+    pub fn has_model_quota_with(&self, normalized_target: &str, now: i64, grace_secs: i64) -> bool {
+        match self.model_quotas.get(normalized_target) {
+            Some(q) if *q > 0 => true,
+            Some(_) => self.reset_time.is_some_and(|reset| reset <= now + grace_secs),
+            None => false,
+        }
+    }
+
+    /// 账号在 `now` 时刻不可用时，返回恢复可用的时间戳 (使用全局配置的刷新宽限期)
+    ///
+    /// 不可用：配额耗尽且刷新时间在宽限窗口之外，或处于验证封禁期内；两者同时存在时取较晚者
+    pub fn unusable_until(&self, now: i64) -> Option<i64> {
+        self.unusable_until_with(
+            now,
+            crate::proxy::selection::get_selection_config().reset_grace_secs,
+        )
+    }
+
+    /// 同 `unusable_until`，使用指定的刷新宽限期 (秒)
+    pub fn unusable_until_with(&self, now: i64, grace_secs: i64) -> Option<i64> {
+        let blocked = self
+            .is_blocked(now)
+            .then_some(self.validation_blocked_until);
+        let exhausted = self
+            .reset_time
+            .filter(|_| self.is_quota_exhausted(now, grace_secs));
+        blocked.max(exhausted)
+    }
+
+    /// 缓存的等级/配额是否早于 `now - max_age_secs` (从未刷新过也视为过旧)；`max_age_secs <= 0` 时不检查
+    pub fn is_state_stale(&self, now: i64, max_age_secs: i64) -> bool {
+        max_age_secs > 0
+            && !matches!(self.last_refreshed, Some(refreshed) if now - refreshed <= max_age_secs)
+    }
+}
+
+/// `ProxyToken` 构建器：必填账号 ID / 凭据 / 邮箱，其余字段取默认值
+///
+/// 默认值：未知过期时间 (`expires_in = 0`，首次使用时刷新)、无等级与配额数据、
+/// 空的受保护模型集合、健康分 1.0、未封禁、已启用。
+#[derive(Debug, Clone)]
+pub struct ProxyTokenBuilder {
+    token: ProxyToken,
+}
+
+impl ProxyToken {
+    pub fn builder(
+        account_id: impl Into<String>,
+        access_token: impl Into<String>,
+        refresh_token: impl Into<String>,
+        email: impl Into<String>,
+    ) -> ProxyTokenBuilder {
+        ProxyTokenBuilder {
+            token: ProxyToken {
+                account_id: account_id.into(),
+                access_token: access_token.into(),
+                refresh_token: refresh_token.into(),
+                expires_in: 0,
+                timestamp: 0,
+                email: email.into(),
+                account_path: PathBuf::new(),
+                project_id: None,
+                subscription_tier: None,
+                tier_override: None,
+                remaining_quota: None,
+                protected_models: HashSet::new(),
+                health_score: 1.0,
+                reset_time: None,
+                validation_blocked: false,
+                validation_blocked_until: 0,
+                validation_url: None,
+                model_quotas: HashMap::new(),
+                quota_max: HashMap::new(),
+                model_limits: HashMap::new(),
+                cached_capabilities: HashSet::new(),
+                last_refreshed: None,
+                enabled: true,
+                last_failure: None,
+            },
+        }
+    }
+}
+
+impl ProxyTokenBuilder {
+    /// access_token 有效期 (秒) 及过期时间点 (Unix 秒)
+    pub fn expires(mut self, expires_in: i64, timestamp: i64) -> Self {
+        self.token.expires_in = expires_in;
+        self.token.timestamp = timestamp;
+        self
+    }
+
+    pub fn account_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.token.account_path = path.into();
+        self
+    }
+
+    pub fn project_id(mut self, project_id: Option<String>) -> Self {
+        self.token.project_id = project_id;
+        self
+    }
+
+    pub fn subscription_tier(mut self, tier: Option<String>) -> Self {
+        self.token.subscription_tier = tier;
+        self
+    }
+
+    pub fn tier_override(mut self, tier: Option<String>) -> Self {
+        self.token.tier_override = tier;
+        self
+    }
+
+    pub fn remaining_quota(mut self, quota: Option<i32>) -> Self {
+        self.token.remaining_quota = quota;
+        self
+    }
+
+    pub fn protected_models<I, S>(mut self, models: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.token.protected_models = models.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn health_score(mut self, score: f32) -> Self {
+        self.token.health_score = score;
+        self
+    }
+
+    pub fn reset_time(mut self, reset_time: Option<i64>) -> Self {
+        self.token.reset_time = reset_time;
+        self
+    }
+
+    /// 验证封禁至 `until` (Unix 秒)
+    pub fn validation_blocked(mut self, until: i64, url: Option<String>) -> Self {
+        self.token.validation_blocked = true;
+        self.token.validation_blocked_until = until;
+        self.token.validation_url = url;
+        self
+    }
+
+    /// 设置单个模型 (标准化 ID) 的剩余配额
+    pub fn model_quota(mut self, model: impl Into<String>, quota: i32) -> Self {
+        self.token.model_quotas.insert(model.into(), quota);
+        self
+    }
+
+    pub fn model_quotas(mut self, quotas: HashMap<String, i32>) -> Self {
+        self.token.model_quotas = quotas;
+        self
+    }
+
+    /// 设置单个模型 (标准化 ID) 的配额满额
+    pub fn quota_max(mut self, model: impl Into<String>, max: i32) -> Self {
+        self.token.quota_max.insert(model.into(), max);
+        self
+    }
+
+    /// 能力缓存中的可服务模型 (标准化 ID)
+    pub fn cached_capabilities<I, S>(mut self, models: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.token.cached_capabilities = models.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn model_limit(mut self, model: impl Into<String>, max_output_tokens: u64) -> Self {
+        self.token.model_limits.insert(model.into(), max_output_tokens);
+        self
+    }
+
+    pub fn last_refreshed(mut self, refreshed_at: Option<i64>) -> Self {
+        self.token.last_refreshed = refreshed_at;
+        self
+    }
+
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.token.enabled = enabled;
+        self
+    }
+
+    pub fn last_failure(mut self, failed_at: Option<i64>) -> Self {
+        self.token.last_failure = failed_at;
+        self
+    }
+
+    pub fn build(self) -> ProxyToken {
+        self.token
+    }
+}
+
+/// 批量配额刷新结果
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct QuotaRefreshReport {
+    pub total: usize,                  // 参与刷新的账号数
+    pub requests: usize,               // 实际发出的配额查询次数 (按 project_id 去重后)
+    pub updated: Vec<String>,          // 成功更新的 account_id
+    pub failed: Vec<(String, String)>, // (account_id, 错误信息)
+}
+
+/// 启动预热结果
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct WarmupReport {
+    pub total: usize,                  // 池中账号数
+    pub refreshed_tokens: Vec<String>, // 预热时刷新了 access_token 的 account_id
+    pub quota_updated: Vec<String>,    // 成功写入配额的 account_id
+    pub failed: Vec<(String, String)>, // (account_id, 错误信息)
+    pub cancelled: bool,               // 预热在全部完成前被取消
 }
 
 pub struct TokenManager {
-    tokens: Arc<DashMap<String, ProxyToken>>, // account_id -> ProxyToken
+    /// 活跃账号池 (account_id -> ProxyToken)
+    ///
+    /// 加锁约定：DashMap 按分片加读写锁。
+    /// - 读 (选号、容量统计、前端视图) 通过 `snapshot()` 克隆快照后在锁外处理，读与读互不阻塞；
+    /// - 写 (`upsert_token` / `consume_quota` / `apply_validation_block` / 移除) 在单个条目的写锁内原子完成，
+    ///   同一账号上的写操作串行执行；
+    /// - 持有 `get` / `get_mut` / `iter` 返回的引用期间不得再访问本 map (同分片重入会死锁)，
+    ///   也不得跨 `.await` 或磁盘 IO 持有，需要时先克隆所需字段。
+    tokens: Arc<DashMap<String, ProxyToken>>,
     current_index: Arc<AtomicUsize>,
     last_used_account: Arc<tokio::sync::Mutex<Option<(String, std::time::Instant)>>>,
     data_dir: PathBuf,
     rate_limit_tracker: Arc<RateLimitTracker>, // 新增: 限流跟踪器
     sticky_config: Arc<tokio::sync::RwLock<StickySessionConfig>>, // 新增：调度配置
     session_accounts: Arc<DashMap<String, String>>, // 新增：会话与账号映射 (SessionID -> AccountID)
+    session_last_seen: Arc<DashMap<String, i64>>, // 会话绑定上次命中时间 (SessionID -> Unix 秒)，用于粘性有效期
     preferred_account_id: Arc<tokio::sync::RwLock<Option<String>>>, // [FIX #820] 优先使用的账号ID（固定账号模式）
     health_scores: Arc<DashMap<String, f32>>,                       // account_id -> health_score
+    validation_block_counts: Arc<DashMap<String, u32>>,             // account_id -> 连续验证封禁次数 (成功请求后清零)
     circuit_breaker_config: Arc<tokio::sync::RwLock<crate::models::CircuitBreakerConfig>>, // [NEW] 熔断配置缓存
     
     // [NEW] 按账号分配的同步刷新锁。
@@ -58,9 +462,23 @@ pub struct TokenManager {
     // Key 为 account_id，Value 为结果观察者，确保并发请求共享同一个上游探测结果
     load_code_assist_inflight: Arc<DashMap<String, tokio::sync::watch::Receiver<Option<Result<String, String>>>>>,
 
-    /// 支持优雅关闭时主动 abort 后台任务
-    auto_cleanup_handle: Arc<tokio::sync::Mutex<Option<tokio::task::JoinHandle<()>>>>,
+    /// 周期性维护任务 (限流记录清理等)，优雅关闭时停止
+    maintenance: Arc<TaskScheduler>,
     cancel_token: CancellationToken,
+
+    /// 进行中的账号预留 (reserve_token)
+    reservations: ReservationLedger,
+
+    /// 已从活跃池剔除的配额耗尽 / 停用 / 吊销账号 (account_id -> ProxyToken)，由 restore_evicted 放回
+    evicted: Arc<DashMap<String, ProxyToken>>,
+
+    /// 持久化的模型能力缓存 (重启后首次刷新前用于按能力选号)
+    capabilities: Arc<std::sync::RwLock<CapabilityCache>>,
+    /// 能力缓存有尚未写回文件的修改
+    capabilities_dirty: Arc<AtomicBool>,
+
+    /// 冷账号最近一次即时刷新尝试时间 (account_id -> Unix 秒)，用于失败后的冷却
+    cold_refresh_attempts: Arc<DashMap<String, i64>>,
 }
 
 impl TokenManager {
@@ -74,51 +492,59 @@ impl TokenManager {
             rate_limit_tracker: Arc::new(RateLimitTracker::new()),
             sticky_config: Arc::new(tokio::sync::RwLock::new(StickySessionConfig::default())),
             session_accounts: Arc::new(DashMap::new()),
+            session_last_seen: Arc::new(DashMap::new()),
             preferred_account_id: Arc::new(tokio::sync::RwLock::new(None)), // [FIX #820]
             health_scores: Arc::new(DashMap::new()),
+            validation_block_counts: Arc::new(DashMap::new()),
             circuit_breaker_config: Arc::new(tokio::sync::RwLock::new(
                 crate::models::CircuitBreakerConfig::default(),
             )),
             refresh_locks: Arc::new(DashMap::new()),
             load_code_assist_inflight: Arc::new(DashMap::new()), // 初始化 inflight 表
-            auto_cleanup_handle: Arc::new(tokio::sync::Mutex::new(None)),
+            maintenance: Arc::new(TaskScheduler::default()),
             cancel_token: CancellationToken::new(),
+            reservations: ReservationLedger::new(),
+            evicted: Arc::new(DashMap::new()),
+            capabilities: Arc::new(std::sync::RwLock::new(CapabilityCache::default())),
+            capabilities_dirty: Arc::new(AtomicBool::new(false)),
+            cold_refresh_attempts: Arc::new(DashMap::new()),
         }
     }
 
-    /// 启动限流记录自动清理后台任务（每15秒检查并清除过期记录）
+    /// 启动限流记录自动清理后台任务（每15秒检查并清除过期记录），
+    /// 以及能力缓存的定期落盘任务 (每30秒写回有变化的记录)
+    ///
+    /// 任务运行在维护调度器上：首次清理在启动约 15 秒后执行 (不再在启动时立即执行)，
+    /// 期间已过期的限流记录不影响判断 (`get_remaining_wait` 按 reset 时间判定)。
+    /// 重复调用时替换已注册的同名任务，不会泄漏旧任务
     pub async fn start_auto_cleanup(&self) {
         let tracker = self.rate_limit_tracker.clone();
-        let cancel = self.cancel_token.child_token();
-
-        let handle = tokio::spawn(async move {
-            let mut interval = tokio::time::interval(std::time::Duration::from_secs(15));
-            loop {
-                tokio::select! {
-                    _ = cancel.cancelled() => {
-                        tracing::info!("Auto-cleanup task received cancel signal");
-                        break;
-                    }
-                    _ = interval.tick() => {
-                        let cleaned = tracker.cleanup_expired();
-                        if cleaned > 0 {
-                            tracing::info!(
-                                "Auto-cleanup: Removed {} expired rate limit record(s)",
-                                cleaned
-                            );
-                        }
+        self.maintenance
+            .register(AUTO_CLEANUP_TASK, AUTO_CLEANUP_INTERVAL, move || {
+                let tracker = tracker.clone();
+                async move {
+                    let cleaned = tracker.cleanup_expired();
+                    if cleaned > 0 {
+                        tracing::info!(
+                            "Auto-cleanup: Removed {} expired rate limit record(s)",
+                            cleaned
+                        );
                     }
                 }
-            }
-        });
-
-        // 先 abort 旧任务（防止任务泄漏），再存储新 handle
-        let mut guard = self.auto_cleanup_handle.lock().await;
-        if let Some(old) = guard.take() {
-            old.abort();
-            tracing::warn!("Aborted previous auto-cleanup task");
-        }
-        *guard = Some(handle);
+            });
+        let capabilities = self.capabilities.clone();
+        let dirty = self.capabilities_dirty.clone();
+        let path = self.capability_cache_path();
+        self.maintenance
+            .register(CAPABILITY_FLUSH_TASK, CAPABILITY_FLUSH_INTERVAL, move || {
+                let capabilities = capabilities.clone();
+                let dirty = dirty.clone();
+                let path = path.clone();
+                async move {
+                    Self::flush_capability_cache(&capabilities, &dirty, &path);
+                }
+            });
+        self.maintenance.start(&self.cancel_token);
 
         tracing::info!("Rate limit auto-cleanup task started (interval: 15s)");
     }
@@ -133,16 +559,25 @@ impl TokenManager {
 
         // Reload should reflect current on-disk state (accounts can be added/removed/disabled).
         self.tokens.clear();
+        self.evicted.clear();
         self.current_index.store(0, Ordering::SeqCst);
         {
             let mut last_used = self.last_used_account.lock().await;
             *last_used = None;
         }
 
+        // 先写回尚未落盘的记录，再以文件为准重新加载
+        self.flush_capabilities();
+        let capabilities = CapabilityCache::load(&self.capability_cache_path());
+        if let Ok(mut cache) = self.capabilities.write() {
+            *cache = capabilities;
+        }
+
         let entries = std::fs::read_dir(&accounts_dir)
             .map_err(|e| format!("读取账号目录失败: {}", e))?;
 
         let mut count = 0;
+        let mut on_disk: HashSet<String> = HashSet::new();
 
         for entry in entries {
             let entry = entry.map_err(|e| format!("读取目录项失败: {}", e))?;
@@ -151,12 +586,14 @@ impl TokenManager {
             if path.extension().and_then(|s| s.to_str()) != Some("json") {
                 continue;
             }
+            if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+                on_disk.insert(stem.to_string());
+            }
 
             // 尝试加载账号
             match self.load_single_account(&path).await {
                 Ok(Some(token)) => {
-                    let account_id = token.account_id.clone();
-                    self.tokens.insert(account_id, token);
+                    self.upsert_token(token);
                     count += 1;
                 }
                 Ok(None) => {
@@ -168,6 +605,15 @@ impl TokenManager {
             }
         }
 
+        // 清理已不在磁盘上的账号 (应用未运行期间被删除) 的能力记录
+        if let Ok(mut cache) = self.capabilities.write() {
+            let pruned = cache.retain(|id| on_disk.contains(id));
+            if pruned > 0 {
+                tracing::info!("[Capability] Pruned {} record(s) of deleted accounts", pruned);
+                self.capabilities_dirty.store(true, Ordering::Release);
+            }
+        }
+
         Ok(count)
     }
 
@@ -183,7 +629,9 @@ impl TokenManager {
 
         match self.load_single_account(&path).await {
             Ok(Some(token)) => {
-                self.tokens.insert(account_id.to_string(), token);
+                self.upsert_token(token);
+                // 磁盘状态优先：丢弃旧的剔除记录
+                self.evicted.remove(account_id);
                 // [NEW] 重新加载账号时自动清除该账号的限流记录
                 self.clear_rate_limit(account_id);
                 Ok(())
@@ -207,12 +655,23 @@ impl TokenManager {
         Ok(count)
     }
 
+    /// 新增或整体替换账号池中的账号 (按 account_id)
+    pub fn upsert_token(&self, token: ProxyToken) {
+        self.tokens.insert(token.account_id.clone(), token);
+    }
+
+    /// 账号池的一致性快照 (逐条克隆，不持有任何锁)
+    pub fn snapshot(&self) -> Vec<ProxyToken> {
+        self.tokens.iter().map(|e| e.value().clone()).collect()
+    }
+
     /// 从内存中彻底移除指定账号及其关联数据 (Issue #1477)
     pub fn remove_account(&self, account_id: &str) {
         // ... (省略原有逻辑)
         if self.tokens.remove(account_id).is_some() {
             tracing::info!("[Proxy] Removed account {} from memory cache", account_id);
         }
+        self.evicted.remove(account_id);
         self.health_scores.remove(account_id);
         self.clear_rate_limit(account_id);
         self.session_accounts.retain(|_, v| v != account_id);
@@ -224,6 +683,125 @@ impl TokenManager {
         }
     }
 
+    /// 将当前无法服务任何请求的账号移出活跃池，避免每次选择时重复过滤
+    ///
+    /// 剔除三类账号：所有模型配额耗尽且刷新时间晚于 `now`；内存中已停用 (`enabled = false`)；
+    /// 磁盘上已被标记为禁用 / 403 (例如配额刷新时遇到 invalid_grant)。验证封禁是短期状态，
+    /// 账号留在池中。被剔除的账号保存在非活跃集合中，由 `restore_evicted` 放回。
+    /// 返回被剔除账号的 email。
+    pub async fn evict_exhausted(&self, now: i64) -> Vec<String> {
+        let mut unusable = Vec::new();
+        for token in self.snapshot() {
+            if token.is_fully_exhausted(now)
+                || !token.enabled
+                || Self::get_account_state_on_disk(&token.account_path).await
+                    == OnDiskAccountState::Disabled
+            {
+                unusable.push(token.account_id);
+            }
+        }
+
+        let mut emails = Vec::with_capacity(unusable.len());
+        for account_id in unusable {
+            if let Some((id, token)) = self.tokens.remove(&account_id) {
+                self.session_accounts.retain(|_, v| *v != id);
+                emails.push(token.email.clone());
+                self.evicted.insert(id, token);
+            }
+        }
+
+        if !emails.is_empty() {
+            tracing::info!("[Proxy] Evicted {} unusable account(s) from pool: {:?}", emails.len(), emails);
+        }
+        emails
+    }
+
+    /// 将可以重新使用的被剔除账号放回活跃池，返回恢复账号的 email
+    ///
+    /// 因配额耗尽被剔除的账号在刷新时间到达 (`reset_time <= now`) 后直接放回，随后的定期刷新
+    /// 会更新其配额；停用 / 吊销的账号在磁盘上重新启用后从文件重新加载。
+    /// 账号文件已删除时直接丢弃剔除记录。
+    pub async fn restore_evicted(&self, now: i64) -> Vec<String> {
+        let evicted: Vec<ProxyToken> = self.evicted.iter().map(|entry| entry.value().clone()).collect();
+
+        let mut emails = Vec::new();
+        for token in evicted {
+            let account_id = token.account_id.clone();
+            // 配额耗尽且尚未到刷新时间
+            if token.is_fully_exhausted(now) {
+                continue;
+            }
+            if !token.account_path.exists() {
+                self.evicted.remove(&account_id);
+                continue;
+            }
+            if Self::get_account_state_on_disk(&token.account_path).await != OnDiskAccountState::Enabled {
+                continue;
+            }
+            if token.enabled {
+                // 因配额耗尽被剔除且已到刷新时间：直接放回 (剔除记录可能已被重新加载或删除清除)
+                if let Some((id, token)) = self.evicted.remove(&account_id) {
+                    emails.push(token.email.clone());
+                    self.tokens.insert(id, token);
+                }
+                continue;
+            }
+            // reload_account 会丢弃剔除记录；文件仍不可用时账号不会回到池中
+            if let Err(e) = self.reload_account(&account_id).await {
+                tracing::warn!("[Proxy] Failed to restore evicted account {}: {}", account_id, e);
+                continue;
+            }
+            if let Some(token) = self.tokens.get(&account_id) {
+                emails.push(token.email.clone());
+            }
+        }
+
+        if !emails.is_empty() {
+            tracing::info!("[Proxy] Restored {} evicted account(s) to pool: {:?}", emails.len(), emails);
+        }
+        emails
+    }
+
+    /// 账号池中所有账号的前端视图 (按 email 排序)
+    pub fn token_views(&self) -> Vec<ProxyTokenView> {
+        let mut views: Vec<ProxyTokenView> = self.tokens.iter().map(|e| e.value().view()).collect();
+        views.sort_by(|a, b| a.email.cmp(&b.email));
+        views
+    }
+
+    /// 账号池对目标模型的总可用容量
+    pub fn model_capacity(&self, model: &str) -> crate::proxy::ModelCapacity {
+        crate::proxy::model_capacity(&self.snapshot(), model)
+    }
+
+    /// 当前账号池的配额快照，与之后的快照比较可得出消耗速度 (`diff_snapshots`)
+    pub fn snapshot_quotas(&self) -> crate::proxy::QuotaSnapshot {
+        crate::proxy::snapshot_quotas(&self.snapshot())
+    }
+
+    /// 目标模型的选择诊断，包含每个账号针对该模型的熔断状态
+    pub fn selection_trace(&self, model: &str) -> crate::proxy::SelectionTrace {
+        // 熔断关闭时限流记录不参与选择
+        let circuit_breaker_enabled = self
+            .circuit_breaker_config
+            .try_read()
+            .map(|c| c.enabled)
+            .unwrap_or(true);
+        // 限流记录可能以请求模型名或归一化后的 ID 为键，两者取较长的剩余时间
+        let normalized_target = crate::proxy::selection::normalize_target(model);
+        crate::proxy::selection::trace_selection(&self.snapshot(), model, |account_id| {
+            if !circuit_breaker_enabled {
+                return 0;
+            }
+            self.rate_limit_tracker
+                .get_remaining_wait(account_id, Some(model))
+                .max(
+                    self.rate_limit_tracker
+                        .get_remaining_wait(account_id, Some(&normalized_target)),
+                )
+        })
+    }
+
     /// 根据账号 ID 获取完整的 ProxyToken 对象 (v4.1.29)
     pub fn get_token_by_id(&self, account_id: &str) -> Option<ProxyToken> {
         self.tokens.get(account_id).map(|t| t.clone())
@@ -262,7 +840,10 @@ impl TokenManager {
                 }
             };
 
-            let account = match serde_json::from_str::<serde_json::Value>(&content) {
+            let parsed = crate::modules::account_store::decode_account_content(content).and_then(
+                |json| serde_json::from_str::<serde_json::Value>(&json).map_err(|e| e.to_string()),
+            );
+            let account = match parsed {
                 Ok(v) => v,
                 Err(e) => {
                     if attempt < MAX_RETRIES {
@@ -286,6 +867,10 @@ impl TokenManager {
                     .get("proxy_disabled")
                     .and_then(|v| v.as_bool())
                     .unwrap_or(false)
+                || !account
+                    .get("enabled")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(true)
                 || account
                     .get("quota")
                     .and_then(|q| q.get("is_forbidden"))
@@ -304,7 +889,7 @@ impl TokenManager {
 
     /// 加载单个账号
     async fn load_single_account(&self, path: &PathBuf) -> Result<Option<ProxyToken>, String> {
-        let content = std::fs::read_to_string(path).map_err(|e| format!("读取文件失败: {}", e))?;
+        let content = crate::modules::account_store::read_account_file(path).map_err(|e| format!("读取文件失败: {}", e))?;
 
         let mut account: serde_json::Value =
             serde_json::from_str(&content).map_err(|e| format!("解析 JSON 失败: {}", e))?;
@@ -345,7 +930,7 @@ impl TokenManager {
                 .and_then(|v| v.as_i64())
                 .unwrap_or(0);
 
-            let now = chrono::Utc::now().timestamp();
+            let now = crate::utils::clock::now();
 
             if now < block_until {
                 // Still blocked
@@ -369,7 +954,7 @@ impl TokenManager {
 
                 let updated_json =
                     serde_json::to_string_pretty(&account).map_err(|e| e.to_string())?;
-                std::fs::write(path, updated_json).map_err(|e| e.to_string())?;
+                crate::modules::account_store::write_account_file(path, &updated_json)?;
                 tracing::info!(
                     "Validation block expired and cleared for account: {}",
                     account
@@ -380,6 +965,23 @@ impl TokenManager {
             }
         }
 
+        // 用户主动停用 (软禁用)
+        if !account
+            .get("enabled")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(true)
+        {
+            tracing::debug!(
+                "Skipping account disabled by user: {:?} (email={})",
+                path,
+                account
+                    .get("email")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("<unknown>")
+            );
+            return Ok(None);
+        }
+
         // 最终检查账号主开关
         if account
             .get("disabled")
@@ -472,6 +1074,11 @@ impl TokenManager {
             .and_then(|q| q.get("subscription_tier"))
             .and_then(|v| v.as_str())
             .map(|s| s.to_string());
+        let tier_override = account
+            .get("tier_override")
+            .and_then(|v| v.as_str())
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| s.to_string());
 
         // [FIX #563] 提取最大剩余配额百分比用于优先级排序 (Option<i32> now)
         let remaining_quota = account
@@ -497,15 +1104,19 @@ impl TokenManager {
         let reset_time = self.extract_earliest_reset_time(&account);
 
         // [OPTIMIZATION] 构建模型配额内存缓存，避免排序时读取磁盘
+        let selection_config = crate::proxy::selection::get_selection_config();
         let mut model_quotas = HashMap::new();
+        let mut quota_max = HashMap::new();
         // [NEW] 构建模型输出限额内存缓存 (max_output_tokens)
         let mut model_limits: HashMap<String, u64> = HashMap::new();
         if let Some(models) = account.get("quota").and_then(|q| q.get("models")).and_then(|m| m.as_array()) {
             for model in models {
                 if let (Some(name), Some(pct)) = (model.get("name").and_then(|v| v.as_str()), model.get("percentage").and_then(|v| v.as_i64())) {
-                    // Normalize name to standard ID
-                    let standard_id = crate::proxy::common::model_mapping::normalize_to_standard_id(name)
-                        .unwrap_or_else(|| name.to_string());
+                    // Normalize name to standard ID (按家族处理 models/ 前缀)
+                    let standard_id = selection_config.quota_key(name);
+                    if let Some(max) = model.get("quota_max").and_then(|v| v.as_i64()) {
+                        quota_max.insert(standard_id.clone(), max as i32);
+                    }
                     model_quotas.insert(standard_id, pct as i32);
                 }
                 // [NEW] 解析并缓存 max_output_tokens (按原始 model name，不归一化)
@@ -518,6 +1129,18 @@ impl TokenManager {
             }
         }
 
+        // 尚无配额数据时使用持久化的模型能力，保证首次实时刷新前也能按能力选号
+        // (只用于能力过滤，不伪造配额值；排序时视为配额未知)
+        let cached_capabilities: HashSet<String> = if model_quotas.is_empty() {
+            self.capabilities
+                .read()
+                .ok()
+                .and_then(|cache| cache.get(&account_id).map(|entry| entry.models.iter().cloned().collect()))
+                .unwrap_or_default()
+        } else {
+            HashSet::new()
+        };
+
         // [NEW] 启动时自动同步持久化的淘汰模型路由表，注入热更新拦截器
         if let Some(rules) = account.get("quota").and_then(|q| q.get("model_forwarding_rules")).and_then(|r| r.as_object()) {
             for (k, v) in rules {
@@ -530,6 +1153,16 @@ impl TokenManager {
             }
         }
 
+        // 同步账号级额外请求头，上游请求按 account_id 合并
+        let extra_headers: HashMap<String, String> = account
+            .get("extra_headers")
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default();
+        crate::proxy::upstream::client::set_account_extra_headers(&account_id, extra_headers);
+
+        // 重新加载单个账号时保留内存中的最近失败时间
+        let last_failure = self.tokens.get(&account_id).and_then(|t| t.last_failure);
+
         Ok(Some(ProxyToken {
             account_id,
             access_token,
@@ -540,6 +1173,7 @@ impl TokenManager {
             account_path: path.clone(),
             project_id,
             subscription_tier,
+            tier_override,
             remaining_quota,
             protected_models,
             health_score,
@@ -548,7 +1182,12 @@ impl TokenManager {
             validation_blocked_until: account.get("validation_blocked_until").and_then(|v| v.as_i64()).unwrap_or(0),
             validation_url: account.get("validation_url").and_then(|v| v.as_str()).map(|s| s.to_string()),
             model_quotas,
+            quota_max,
             model_limits,
+            cached_capabilities,
+            last_refreshed: account.get("last_refreshed").and_then(|v| v.as_i64()),
+            enabled: account.get("enabled").and_then(|v| v.as_bool()).unwrap_or(true),
+            last_failure,
         }))
     }
 
@@ -715,7 +1354,7 @@ impl TokenManager {
     /// * `model_name` - 目标模型名称（已标准化）
     #[allow(dead_code)] // 预留给精确配额读取逻辑
     fn get_model_quota_from_json(account_path: &PathBuf, model_name: &str) -> Option<i32> {
-        let content = std::fs::read_to_string(account_path).ok()?;
+        let content = crate::modules::account_store::read_account_file(account_path).ok()?;
         let account: serde_json::Value = serde_json::from_str(&content).ok()?;
         let models = account.get("quota")?.get("models")?.as_array()?;
 
@@ -736,7 +1375,7 @@ impl TokenManager {
     }
 
     fn get_available_models_from_json(account_path: &PathBuf) -> Option<HashSet<String>> {
-        let content = std::fs::read_to_string(account_path).ok()?;
+        let content = crate::modules::account_store::read_account_file(account_path).ok()?;
         let account: serde_json::Value = serde_json::from_str(&content).ok()?;
         let models = account.get("quota")?.get("models")?.as_array()?;
         let mut result = HashSet::new();
@@ -870,7 +1509,7 @@ impl TokenManager {
             );
 
             // 3. 写入磁盘
-            std::fs::write(account_path, serde_json::to_string_pretty(account_json).unwrap())
+            crate::modules::account_store::write_account_file(account_path, &serde_json::to_string_pretty(account_json).unwrap())
                 .map_err(|e| format!("写入文件失败: {}", e))?;
 
             // [FIX] 触发 TokenManager 的账号重新加载信号，确保内存中的 protected_models 同步
@@ -921,7 +1560,7 @@ impl TokenManager {
 
         account_json["protected_models"] = serde_json::Value::Array(protected_list);
 
-        let _ = std::fs::write(account_path, serde_json::to_string_pretty(account_json).unwrap());
+        let _ = crate::modules::account_store::write_account_file(account_path, &serde_json::to_string_pretty(account_json).unwrap());
 
         false // 返回 false 表示现在已可以尝试加载该账号（模型级过滤会在 get_token 时发生）
     }
@@ -948,9 +1587,9 @@ impl TokenManager {
                     account_id,
                     model_name
                 );
-                std::fs::write(
+                crate::modules::account_store::write_account_file(
                     account_path,
-                    serde_json::to_string_pretty(account_json).unwrap(),
+                    &serde_json::to_string_pretty(account_json).unwrap(),
                 )
                 .map_err(|e| format!("写入文件失败: {}", e))?;
                 return Ok(true);
@@ -960,6 +1599,38 @@ impl TokenManager {
         Ok(false)
     }
 
+    /// P2C 在前 N 个候选中随机挑选，排在末尾的保留账号仍可能被选中：
+    /// 还有其他未尝试的候选时，直接将保留账号移出候选
+    fn hold_in_reserve(candidates: &mut Vec<ProxyToken>, reserved: Option<&str>, attempted: &HashSet<String>) {
+        let Some(reserved) = reserved else {
+            return;
+        };
+        let has_alternative = candidates
+            .iter()
+            .any(|t| t.account_id != reserved && !attempted.contains(&t.account_id));
+        if has_alternative {
+            candidates.retain(|t| t.account_id != reserved);
+        }
+    }
+
+    /// 模型固定账号 (已由 `promote_pinned` 确认可服务) 未限流、未尝试且未被配额保护时直接选中
+    fn pinned_choice(
+        candidates: &[ProxyToken],
+        pinned: Option<&str>,
+        attempted: &HashSet<String>,
+        normalized_target: &str,
+        quota_protection_enabled: bool,
+    ) -> Option<ProxyToken> {
+        let pinned = pinned?;
+        let token = candidates.iter().find(|t| {
+            t.account_id == pinned
+                && !attempted.contains(&t.account_id)
+                && (!quota_protection_enabled || !t.protected_models.contains(normalized_target))
+        })?;
+        tracing::debug!("📌 [Pinned] Using pinned account {} for {}", token.email, normalized_target);
+        Some(token.clone())
+    }
+
     /// P2C 算法的候选池大小 - 从前 N 个最优候选中随机选择
     const P2C_POOL_SIZE: usize = 5;
 
@@ -1023,6 +1694,143 @@ impl TokenManager {
         Some(selected)
     }
 
+    /// 将进行中预留占用的配额单位从快照的目标模型配额中扣除 (仅影响排序用的快照)
+    fn apply_in_flight_reservations(&self, tokens: &mut [ProxyToken], normalized_target: &str) {
+        for token in tokens.iter_mut() {
+            let in_flight = self.reservations.in_flight(&token.account_id, normalized_target);
+            if in_flight > 0 {
+                if let Some(quota) = token.model_quotas.get_mut(normalized_target) {
+                    *quota -= in_flight as i32;
+                }
+            }
+        }
+    }
+
+    /// 按排序结果依次产出可服务目标模型的账号快照 (失败重试时逐个尝试)
+    pub fn select_iter(&self, model: &str) -> impl Iterator<Item = ProxyToken> {
+        crate::proxy::selection::select_iter(&self.snapshot(), model)
+    }
+
+    /// 按模型成本扣减指定账号在该模型上的内存配额，返回扣减后的剩余配额
+    pub fn consume_quota(&self, account_id: &str, model: &str) -> Option<i32> {
+        self.consume_quota_with(account_id, model, &crate::proxy::selection::get_selection_config())
+    }
+
+    fn consume_quota_with(
+        &self,
+        account_id: &str,
+        model: &str,
+        config: &crate::proxy::selection::SelectionConfig,
+    ) -> Option<i32> {
+        let normalized_target = config.normalize_target(model);
+        // 成本按请求的原始模型名计算 (归一化后 Claude 系列共享同一配额组)
+        let cost_units = config.cost_units(model);
+        let mut entry = self.tokens.get_mut(account_id)?;
+        let quota = entry.model_quotas.get_mut(&normalized_target)?;
+        *quota = (*quota - cost_units).max(0);
+        Some(*quota)
+    }
+
+    /// 为 `get_token` 选中的账号占用预留并挂到当前请求上 (由 monitor 中间件在请求结束时结算)
+    ///
+    /// 账号已选定，不再检查容量；之后的并发请求排序时会扣除这部分进行中预留。
+    fn reserve_for_request(&self, token: &ProxyToken, requested_model: &str, normalized_target: &str) {
+        let cost_units = crate::proxy::selection::get_selection_config().cost_units(requested_model);
+        self.reservations
+            .acquire(&token.account_id, normalized_target, cost_units as u32);
+        crate::proxy::reservation::attach_to_request(TokenReservation::new(
+            token.clone(),
+            normalized_target.to_string(),
+            cost_units,
+            self.reservations.clone(),
+            self.tokens.clone(),
+        ));
+    }
+
+    /// 原子地预留一个可用于目标模型的账号 (供自行管理请求生命周期的批量任务使用)
+    ///
+    /// 与 `get_token` 相同，排除验证封禁中的账号，以及开启配额保护时该模型受保护的账号。
+    /// 排序时会扣除已有的进行中预留；每个账号在该模型上预留占用的配额单位 (模型成本，
+    /// 即该模型配额的百分点) 不会超过其剩余配额百分比。
+    /// 调用方在请求完成后 `commit()` / `reconcile()` (扣减配额) 或 `release()` (放弃)。
+    #[tracing::instrument(level = "debug", name = "reserve_token", skip(self))]
+    pub fn reserve_token(&self, model: &str) -> Option<TokenReservation> {
+        let selection_config = crate::proxy::selection::get_selection_config();
+        let normalized_target = selection_config.normalize_target(model);
+        let quota_protection_enabled = crate::proxy::config::is_quota_protection_enabled();
+        let now = crate::utils::clock::now();
+
+        let mut candidates: Vec<ProxyToken> = self
+            .tokens
+            .iter()
+            .filter(|e| selection_config.can_serve(e.value(), model, &normalized_target))
+            .filter(|e| !e.value().is_blocked(now))
+            .filter(|e| {
+                !(quota_protection_enabled && e.value().protected_models.contains(&normalized_target))
+            })
+            .map(|e| e.value().clone())
+            .collect();
+        // 非阻塞读取熔断配置 (reserve_token 可能在异步上下文中调用)
+        let circuit_breaker_enabled = self
+            .circuit_breaker_config
+            .try_read()
+            .map(|c| c.enabled)
+            .unwrap_or(true);
+        if circuit_breaker_enabled {
+            candidates.retain(|t| {
+                !self
+                    .rate_limit_tracker
+                    .is_rate_limited(&t.account_id, Some(normalized_target.as_str()))
+            });
+        }
+
+        let cost_units = selection_config.cost_units(model);
+        self.apply_in_flight_reservations(&mut candidates, &normalized_target);
+        candidates.retain(|t| t.model_quotas.get(&normalized_target).copied().unwrap_or(0) >= cost_units);
+        let now = crate::utils::clock::now();
+        candidates.sort_by(|a, b| selection_config.compare(a, b, model, &normalized_target, now));
+        selection_config.apply_tier_reserve(&mut candidates, model, &normalized_target);
+
+        let candidate_count = candidates.len();
+        for candidate in candidates {
+            let acquired = self.reservations.try_acquire(
+                &candidate.account_id,
+                &normalized_target,
+                cost_units as u32,
+                || {
+                    // 在预留锁内读取最新配额，防止与 commit 竞争
+                    self.tokens
+                        .get(&candidate.account_id)
+                        .and_then(|t| t.model_quotas.get(&normalized_target).copied())
+                        .unwrap_or(0)
+                        .max(0) as u32
+                },
+            );
+            if acquired {
+                let token = self
+                    .tokens
+                    .get(&candidate.account_id)
+                    .map(|t| t.clone())
+                    .unwrap_or(candidate);
+                crate::proxy::selection::notify_selection(
+                    model,
+                    &token,
+                    candidate_count,
+                    selection_config.policy,
+                );
+                return Some(TokenReservation::new(
+                    token,
+                    normalized_target,
+                    cost_units,
+                    self.reservations.clone(),
+                    self.tokens.clone(),
+                ));
+            }
+        }
+
+        None
+    }
+
     /// 先发送取消信号，再带超时等待任务完成
     ///
     /// # 参数
@@ -1038,33 +1846,12 @@ impl TokenManager {
             Ok(_) => tracing::info!("All background tasks cleaned up gracefully"),
             Err(_) => tracing::warn!("Graceful cleanup timed out after {:?}, tasks were force-aborted", timeout),
         }
+        self.flush_capabilities();
     }
 
-    /// 中止并等待所有后台任务完成
-    /// abort() 仅设置取消标志，必须 await 确认清理完成
+    /// 停止并等待所有维护任务退出
     pub async fn abort_background_tasks(&self) {
-        Self::abort_task(&self.auto_cleanup_handle, "Auto-cleanup task").await;
-    }
-
-    /// 中止单个后台任务并记录结果
-    ///
-    /// # 参数
-    /// * `handle` - 任务句柄的 Mutex 引用
-    /// * `task_name` - 任务名称（用于日志）
-    async fn abort_task(
-        handle: &tokio::sync::Mutex<Option<tokio::task::JoinHandle<()>>>,
-        task_name: &str,
-    ) {
-        let Some(handle) = handle.lock().await.take() else {
-            return;
-        };
-
-        handle.abort();
-        match handle.await {
-            Ok(()) => tracing::debug!("{} completed", task_name),
-            Err(e) if e.is_cancelled() => tracing::info!("{} aborted", task_name),
-            Err(e) => tracing::warn!("{} error: {}", task_name, e),
-        }
+        self.maintenance.stop().await;
     }
 
     /// 获取当前可用的 Token（支持粘性会话与智能调度）
@@ -1072,6 +1859,18 @@ impl TokenManager {
     /// 参数 `force_rotate` 为 true 时将忽略锁定，强制切换账号
     /// 参数 `session_id` 用于跨请求维持会话粘性
     /// 参数 `target_model` 用于检查配额保护 (Issue #621)
+    /// 请求限定了账号分组时 (`X-Account-Group` 请求头，见 `selection::scope_account_group`) 只在组内选号
+    #[tracing::instrument(
+        level = "debug",
+        name = "select_token",
+        skip_all,
+        fields(
+            quota_group = quota_group,
+            force_rotate = force_rotate,
+            model = target_model,
+            has_session = session_id.is_some(),
+        )
+    )]
     pub async fn get_token(
         &self,
         quota_group: &str,
@@ -1096,17 +1895,26 @@ impl TokenManager {
         let pending_delete = crate::proxy::server::take_pending_delete_accounts();
         for account_id in pending_delete {
             self.remove_account(&account_id);
+            self.forget_capabilities(&account_id);
             tracing::info!(
                 "[Proxy] Purged deleted account {} from all caches",
                 account_id
             );
         }
 
+        let account_group = crate::proxy::selection::current_account_group();
+
         // 【优化 Issue #284】添加 5 秒超时，防止死锁
         let timeout_duration = std::time::Duration::from_secs(5);
         match tokio::time::timeout(
             timeout_duration,
-            self.get_token_internal(quota_group, force_rotate, session_id, target_model),
+            self.get_token_internal(
+                quota_group,
+                force_rotate,
+                session_id,
+                target_model,
+                account_group.as_deref(),
+            ),
         )
         .await
         {
@@ -1117,13 +1925,14 @@ impl TokenManager {
         }
     }
 
-    /// 内部实现：获取 Token 的核心逻辑
+    /// 内部实现：获取 Token 的核心逻辑 (`account_group` 为 Some 时只考虑该分组内的账号)
     async fn get_token_internal(
         &self,
         quota_group: &str,
         force_rotate: bool,
         session_id: Option<&str>,
         target_model: &str,
+        account_group: Option<&str>,
     ) -> Result<(String, String, String, String, u64), String> {
         let mut tokens_snapshot: Vec<ProxyToken> =
             self.tokens.iter().map(|e| e.value().clone()).collect();
@@ -1132,14 +1941,20 @@ impl TokenManager {
             return Err("Token pool is empty".to_string());
         }
 
-        // [NEW] 1. 动态能力过滤 (Capability Filter)
-        
-        // 定义常量
-        const RESET_TIME_THRESHOLD_SECS: i64 = 600; // 10 分钟阈值
+        // 归一化目标模型名为标准 ID (应用选择配置中的模型别名)
+        let selection_config = crate::proxy::selection::get_selection_config();
+        let normalized_target = selection_config.normalize_target(target_model);
 
-        // 归一化目标模型名为标准 ID
-        let normalized_target = crate::proxy::common::model_mapping::normalize_to_standard_id(target_model)
-            .unwrap_or_else(|| target_model.to_string());
+        // 限定分组时，固定账号、粘性会话与轮询都只在组内进行；组内无可用账号时不回退到全池
+        if let Some(group) = account_group {
+            selection_config.scope_to_group(&mut tokens_snapshot, group)?;
+            total = tokens_snapshot.len();
+            if total == 0 {
+                return Err(format!("Account group {} has no accounts in the pool", group));
+            }
+        }
+
+        // [NEW] 1. 动态能力过滤 (Capability Filter)
 
         // 仅保留明确拥有该模型配额的账号
         // 这一步确保了 "保证有模型才可以进入轮询"，特别是对 Opus 4.6 等高端模型
@@ -1147,7 +1962,25 @@ impl TokenManager {
         
         // 此处假设所有受支持的模型都会出现在 model_quotas 中
         // 如果 API 返回的配额信息不完整，可能会导致误杀，但为了严格性，我们执行此过滤
-        tokens_snapshot.retain(|t| t.model_quotas.contains_key(&normalized_target));
+        // 开启 assume_capable_when_unknown 时，能力未知的账号暂时保留，选中后再确认
+        tokens_snapshot.retain(|t| selection_config.has_capability(t, &normalized_target));
+        // 开启 enforce_min_tier 时，Ultra 专属模型额外排除低等级账号 (即使其上报了该模型配额)
+        tokens_snapshot.retain(|t| selection_config.meets_min_tier(t, target_model));
+        // 目标模型配额耗尽且刷新时间在宽限窗口之外的账号直接排除；窗口内的账号保留，押注即将刷新
+        let now = crate::utils::clock::now();
+        tokens_snapshot.retain(|t| {
+            !t.is_model_exhausted_with(&normalized_target, now, selection_config.reset_grace_secs)
+        });
+
+        // 没有常规账号可用时，按需即时刷新冷账号 (导入时刷新失败的占位账号)
+        if tokens_snapshot.is_empty() && selection_config.refresh_cold_tokens {
+            if let Some(warmed) = self.warm_cold_token(&normalized_target).await {
+                let in_group = account_group.is_none_or(|group| selection_config.in_group(&warmed, group));
+                if in_group && selection_config.meets_min_tier(&warmed, target_model) {
+                    tokens_snapshot.push(warmed);
+                }
+            }
+        }
 
         if tokens_snapshot.is_empty() {
             if candidate_count_before > 0 {
@@ -1158,51 +1991,25 @@ impl TokenManager {
             return Err("Token pool is empty".to_string());
         }
 
-        tokens_snapshot.sort_by(|a, b| {
-            // Priority 0: 严格的订阅等级排序 (ULTRA > PRO > FREE)
-            // 用户要求：轮询应当遵循 Ultra -> Pro -> Free
-            // 既然已经过滤掉了不支持该模型的账号，剩下的都是支持的
-            // 此时我们优先使用高级订阅
-            let tier_priority = |tier: &Option<String>| {
-                let t = tier.as_deref().unwrap_or("").to_lowercase();
-                if t.contains("ultra") { 0 }
-                else if t.contains("pro") { 1 }
-                else if t.contains("free") { 2 }
-                else { 3 }
-            };
-
-            let tier_cmp = tier_priority(&a.subscription_tier)
-                .cmp(&tier_priority(&b.subscription_tier));
-            if tier_cmp != std::cmp::Ordering::Equal {
-                return tier_cmp;
-            }
-
-            // Priority 1: 目标模型的 quota (higher is better) -> 保护低配额账号
-            // 经过过滤，key 肯定存在
-            let quota_a = a.model_quotas.get(&normalized_target).copied().unwrap_or(0);
-            let quota_b = b.model_quotas.get(&normalized_target).copied().unwrap_or(0);
-
-            let quota_cmp = quota_b.cmp(&quota_a);
-            if quota_cmp != std::cmp::Ordering::Equal {
-                return quota_cmp;
-            }
-
-            // Priority 2: Health score (higher is better)
-            let health_cmp = b.health_score.partial_cmp(&a.health_score)
-                .unwrap_or(std::cmp::Ordering::Equal);
-            if health_cmp != std::cmp::Ordering::Equal {
-                return health_cmp;
-            }
-
-            // Priority 3: Reset time (earlier is better, but only if diff > 10 min)
-            let reset_a = a.reset_time.unwrap_or(i64::MAX);
-            let reset_b = b.reset_time.unwrap_or(i64::MAX);
-            if (reset_a - reset_b).abs() >= RESET_TIME_THRESHOLD_SECS {
-                reset_a.cmp(&reset_b)
-            } else {
-                std::cmp::Ordering::Equal
-            }
-        });
+        // 扣除进行中的预留，避免并发请求集中到同一账号
+        self.apply_in_flight_reservations(&mut tokens_snapshot, &normalized_target);
+
+        // 排序规则: 订阅等级 (ULTRA > PRO > FREE，节约模式下对非 Ultra 专属模型反转)
+        //           -> 目标模型配额 -> 健康分 -> reset_time
+        // 开启 balance_projects 时同一项目 (共享配额) 的账号轮流排列；
+        // 开启 reserve_top_tier 时，最高等级的最后一个可用账号留作 Ultra 专属模型的后备
+        let reserved_account = crate::proxy::selection::rank_for_request(
+            &mut tokens_snapshot,
+            target_model,
+            &normalized_target,
+            &selection_config,
+        );
+        // 模型固定账号可服务时提到首位，轮询阶段优先于 P2C 直接选中
+        let pinned_account = crate::proxy::selection::promote_pinned(
+            &mut tokens_snapshot,
+            &normalized_target,
+            &selection_config,
+        );
 
         // 【调试日志】打印排序后的账号顺序（显示目标模型的 quota）
         tracing::debug!(
@@ -1213,7 +2020,7 @@ impl TokenManager {
                 t.email,
                 t.model_quotas.get(&normalized_target).copied().unwrap_or(0),
                 t.reset_time.map(|ts| {
-                    let now = chrono::Utc::now().timestamp();
+                    let now = crate::utils::clock::now();
                     let diff_secs = ts - now;
                     if diff_secs > 0 {
                         format!("{}m", diff_secs / 60)
@@ -1278,12 +2085,6 @@ impl TokenManager {
                         }
                     }
                     OnDiskAccountState::Enabled => {
-                        let normalized_target =
-                            crate::proxy::common::model_mapping::normalize_to_standard_id(
-                                target_model,
-                            )
-                            .unwrap_or_else(|| target_model.to_string());
-
                 let is_rate_limited = self
                     .is_rate_limited(&preferred_token.account_id, Some(&normalized_target))
                     .await;
@@ -1302,8 +2103,8 @@ impl TokenManager {
                     let mut token = preferred_token.clone();
 
                     // [NEW] 检查 token 是否过期（调整刷新时机对齐官方：90s 宽限期）
-                    let now = chrono::Utc::now().timestamp();
-                    if now >= token.timestamp - 90 {
+                    let now = crate::utils::clock::now();
+                    if token.is_expiring(now, TOKEN_REFRESH_MARGIN_SECS) {
                         // [NEW] 双重检查锁定逻辑 (Double-Checked Locking)
                         // 1. 获取（或创建）该账号专属的刷新锁
                         let refresh_mu = self.refresh_locks.entry(token.account_id.clone())
@@ -1316,7 +2117,7 @@ impl TokenManager {
                         // 3. 再次检查本账号最新状态（可能已被其他并发请求刷新完毕）
                         let latest_token_opt = self.tokens.get(&token.account_id).map(|r| r.clone());
                         if let Some(latest) = latest_token_opt {
-                            if now < latest.timestamp - 90 {
+                            if !latest.is_expiring(now, TOKEN_REFRESH_MARGIN_SECS) {
                                 // 已经被别人刷过了，同步最新数据并跳过刷新动作
                                 token = latest.clone();
                                 tracing::debug!("账号 {} 已由并发线程刷新，跳过重复刷新", token.email);
@@ -1372,6 +2173,13 @@ impl TokenManager {
                         }
                     };
 
+                    crate::proxy::selection::notify_selection(
+                        target_model,
+                        &token,
+                        total,
+                        selection_config.policy,
+                    );
+                    self.reserve_for_request(&token, target_model, &normalized_target);
                     return Ok((token.access_token, project_id, token.email, token.account_id, 0));
                 } else {
                     if is_rate_limited {
@@ -1407,10 +2215,6 @@ impl TokenManager {
             // ===== 【核心】粘性会话与智能调度逻辑 =====
             let mut target_token: Option<ProxyToken> = None;
 
-            // 归一化目标模型名为标准 ID，用于配额保护检查
-            let normalized_target = crate::proxy::common::model_mapping::normalize_to_standard_id(target_model)
-                .unwrap_or_else(|| target_model.to_string());
-
             // 模式 A: 粘性会话处理 (CacheFirst 或 Balance 且有 session_id)
             if !rotate
                 && session_id.is_some()
@@ -1418,8 +2222,9 @@ impl TokenManager {
             {
                 let sid = session_id.unwrap();
 
-                // 1. 检查会话是否已绑定账号
-                if let Some(bound_id) = self.session_accounts.get(sid).map(|v| v.clone()) {
+                // 1. 检查会话是否已绑定账号 (超过该模型的粘性有效期则自动解绑)
+                let now = crate::utils::clock::now();
+                if let Some(bound_id) = self.select_for_session(sid, target_model, &scheduling, now) {
                     // 【修复】先通过 account_id 找到对应的账号，获取其 email
                     // 2. 转换 email -> account_id 检查绑定的账号是否限流
                     if let Some(bound_token) =
@@ -1437,19 +2242,20 @@ impl TokenManager {
                                 "Sticky Session: Bound account {} is rate-limited ({}s), unbinding and switching.",
                                 bound_token.email, reset_sec
                             );
-                            self.session_accounts.remove(sid);
+                            self.clear_session_binding(sid);
                         } else if !attempted.contains(&bound_id)
                             && !(quota_protection_enabled
                                 && bound_token.protected_models.contains(&normalized_target))
                         {
                             // 3. 账号可用且未被标记为尝试失败，优先复用
                             tracing::debug!("Sticky Session: Successfully reusing bound account {} for session {}", bound_token.email, sid);
+                            self.bind_session(sid, &bound_id, now);
                             target_token = Some(bound_token.clone());
                         } else if quota_protection_enabled
                             && bound_token.protected_models.contains(&normalized_target)
                         {
                             tracing::debug!("Sticky Session: Bound account {} is quota-protected for model {} [{}], unbinding and switching.", bound_token.email, normalized_target, target_model);
-                            self.session_accounts.remove(sid);
+                            self.clear_session_binding(sid);
                         }
                     } else {
                         // 绑定的账号已不存在（可能被删除），解绑
@@ -1457,7 +2263,7 @@ impl TokenManager {
                             "Sticky Session: Bound account not found for session {}, unbinding",
                             sid
                         );
-                        self.session_accounts.remove(sid);
+                        self.clear_session_binding(sid);
                     }
                 }
             }
@@ -1514,18 +2320,25 @@ impl TokenManager {
                             non_limited.push(t.clone());
                         }
                     }
+                    let pinned = Self::pinned_choice(
+                        &non_limited, pinned_account.as_deref(), &attempted, &normalized_target, quota_protection_enabled
+                    );
+                    Self::hold_in_reserve(&mut non_limited, reserved_account.as_deref(), &attempted);
 
-                    if let Some(selected) = self.select_with_p2c(
+                    if let Some(selected) = pinned.as_ref().or_else(|| self.select_with_p2c(
                         &non_limited, &attempted, &normalized_target, quota_protection_enabled
-                    ) {
+                    )) {
                         target_token = Some(selected.clone());
                         need_update_last_used = Some((selected.account_id.clone(), std::time::Instant::now()));
 
                         // 如果是会话首次分配且需要粘性，在此建立绑定
                         if let Some(sid) = session_id {
                             if scheduling.mode != SchedulingMode::PerformanceFirst {
-                                self.session_accounts
-                                    .insert(sid.to_string(), selected.account_id.clone());
+                                self.bind_session(
+                                    sid,
+                                    &selected.account_id,
+                                    crate::utils::clock::now(),
+                                );
                                 tracing::debug!(
                                     "Sticky Session: Bound new account {} to session {}",
                                     selected.email,
@@ -1549,10 +2362,14 @@ impl TokenManager {
                         non_limited.push(t.clone());
                     }
                 }
+                let pinned = Self::pinned_choice(
+                    &non_limited, pinned_account.as_deref(), &attempted, &normalized_target, quota_protection_enabled
+                );
+                Self::hold_in_reserve(&mut non_limited, reserved_account.as_deref(), &attempted);
 
-                if let Some(selected) = self.select_with_p2c(
+                if let Some(selected) = pinned.as_ref().or_else(|| self.select_with_p2c(
                     &non_limited, &attempted, &normalized_target, quota_protection_enabled
-                ) {
+                )) {
                     tracing::debug!("  {} - SELECTED via P2C", selected.email);
                     target_token = Some(selected.clone());
 
@@ -1657,8 +2474,8 @@ impl TokenManager {
             }
 
             // 3. [NEW] 检查 token 是否过期（调整刷新时机对齐官方：90s 宽限期）
-            let now = chrono::Utc::now().timestamp();
-            if now >= token.timestamp - 90 {
+            let now = crate::utils::clock::now();
+            if token.is_expiring(now, TOKEN_REFRESH_MARGIN_SECS) {
                 // [NEW] 双重检查锁定逻辑 (Double-Checked Locking)
                 let refresh_mu = self.refresh_locks.entry(token.account_id.clone())
                     .or_insert_with(|| Arc::new(tokio::sync::Mutex::new(())))
@@ -1669,7 +2486,7 @@ impl TokenManager {
                 // 再次检查最新状态
                 let latest_token_opt = self.tokens.get(&token.account_id).map(|r| r.clone());
                 if let Some(latest) = latest_token_opt {
-                    if now < latest.timestamp - 90 {
+                    if !latest.is_expiring(now, TOKEN_REFRESH_MARGIN_SECS) {
                         token = latest.clone();
                         tracing::debug!("账号 {} 已由并发线程在循环中刷新，跳过", token.email);
                     } else {
@@ -1706,6 +2523,39 @@ impl TokenManager {
                 }
             }
 
+            // 3.5 缓存的等级/配额过旧时先刷新，避免选中实际已耗尽的账号；
+            //     能力未知而被暂时放行的账号在首次使用时刷新确认，确认后仍无该模型配额则跳过
+            let refreshed = if selection_config.assumes_capable(&token) {
+                self.refresh_account_state(&token.email).await.map(Some)
+            } else {
+                self.refresh_if_stale(&token, selection_config.max_state_age_secs)
+                    .await
+            };
+            match refreshed {
+                Ok(Some(fresh)) => {
+                    if !fresh.has_model_quota_with(
+                        &normalized_target,
+                        crate::utils::clock::now(),
+                        selection_config.reset_grace_secs,
+                    ) {
+                        tracing::warn!(
+                            "Account {} has no quota left for {} after state refresh, skipping",
+                            fresh.email,
+                            normalized_target
+                        );
+                        attempted.insert(fresh.account_id.clone());
+                        continue;
+                    }
+                    // 刷新过程中 access_token 也可能已更新，直接使用池中最新快照
+                    token = fresh;
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    // 刷新失败不影响本次请求，沿用缓存状态
+                    tracing::warn!("Failed to refresh stale state for {}: {}", token.email, e);
+                }
+            }
+
             // 4. [ENHANCED] 确保有 project_id (使用锁保护 fetch 动作)
             let project_id = if let Some(pid) = &token.project_id {
                 if pid.is_empty() { None } else { Some(pid.clone()) }
@@ -1805,6 +2655,13 @@ impl TokenManager {
                 }
             }
 
+            crate::proxy::selection::notify_selection(
+                target_model,
+                &token,
+                total,
+                selection_config.policy,
+            );
+            self.reserve_for_request(&token, target_model, &normalized_target);
             return Ok((token.access_token, project_id, token.email, token.account_id, 0));
         }
 
@@ -1821,16 +2678,16 @@ impl TokenManager {
         };
 
         let mut content: serde_json::Value = serde_json::from_str(
-            &std::fs::read_to_string(&path).map_err(|e| format!("读取文件失败: {}", e))?,
+            &crate::modules::account_store::read_account_file(&path).map_err(|e| format!("读取文件失败: {}", e))?,
         )
         .map_err(|e| format!("解析 JSON 失败: {}", e))?;
 
-        let now = chrono::Utc::now().timestamp();
+        let now = crate::utils::clock::now();
         content["disabled"] = serde_json::Value::Bool(true);
         content["disabled_at"] = serde_json::Value::Number(now.into());
         content["disabled_reason"] = serde_json::Value::String(truncate_reason(reason, 800));
 
-        std::fs::write(&path, serde_json::to_string_pretty(&content).unwrap())
+        crate::modules::account_store::write_account_file(&path, &serde_json::to_string_pretty(&content).unwrap())
             .map_err(|e| format!("写入文件失败: {}", e))?;
 
         // 【修复 Issue #3】从内存中移除禁用的账号，防止被60s锁定逻辑继续使用
@@ -1842,18 +2699,19 @@ impl TokenManager {
 
     /// 保存 project_id 到账号文件
     async fn save_project_id(&self, account_id: &str, project_id: &str) -> Result<(), String> {
-        let entry = self.tokens.get(account_id)
+        let path = self
+            .tokens
+            .get(account_id)
+            .map(|entry| entry.account_path.clone())
             .ok_or("账号不存在")?;
 
-        let path = &entry.account_path;
-
         let mut content: serde_json::Value = serde_json::from_str(
-            &std::fs::read_to_string(path).map_err(|e| format!("读取文件失败: {}", e))?
+            &crate::modules::account_store::read_account_file(&path).map_err(|e| format!("读取文件失败: {}", e))?
         ).map_err(|e| format!("解析 JSON 失败: {}", e))?;
 
         content["token"]["project_id"] = serde_json::Value::String(project_id.to_string());
 
-        std::fs::write(path, serde_json::to_string_pretty(&content).unwrap())
+        crate::modules::account_store::write_account_file(&path, &serde_json::to_string_pretty(&content).unwrap())
             .map_err(|e| format!("写入文件失败: {}", e))?;
 
         tracing::debug!("已保存 project_id 到账号 {}", account_id);
@@ -1862,22 +2720,27 @@ impl TokenManager {
 
     /// 保存刷新后的 token 到账号文件
     async fn save_refreshed_token(&self, account_id: &str, token_response: &crate::modules::oauth::TokenResponse) -> Result<(), String> {
-        let entry = self.tokens.get(account_id)
+        let path = self
+            .tokens
+            .get(account_id)
+            .map(|entry| entry.account_path.clone())
             .ok_or("账号不存在")?;
 
-        let path = &entry.account_path;
-
         let mut content: serde_json::Value = serde_json::from_str(
-            &std::fs::read_to_string(path).map_err(|e| format!("读取文件失败: {}", e))?
+            &crate::modules::account_store::read_account_file(&path).map_err(|e| format!("读取文件失败: {}", e))?
         ).map_err(|e| format!("解析 JSON 失败: {}", e))?;
 
-        let now = chrono::Utc::now().timestamp();
+        let now = crate::utils::clock::now();
 
         content["token"]["access_token"] = serde_json::Value::String(token_response.access_token.clone());
         content["token"]["expires_in"] = serde_json::Value::Number(token_response.expires_in.into());
         content["token"]["expiry_timestamp"] = serde_json::Value::Number((now + token_response.expires_in).into());
+        content["last_refreshed"] = serde_json::Value::Number(now.into());
+        if let Some(scopes) = token_response.scopes() {
+            content["token"]["scopes"] = serde_json::json!(scopes);
+        }
 
-        std::fs::write(path, serde_json::to_string_pretty(&content).unwrap())
+        crate::modules::account_store::write_account_file(&path, &serde_json::to_string_pretty(&content).unwrap())
             .map_err(|e| format!("写入文件失败: {}", e))?;
 
         tracing::debug!("已保存刷新后的 token 到账号 {}", account_id);
@@ -1894,19 +2757,33 @@ impl TokenManager {
         &self,
         email: &str,
     ) -> Result<(String, String, String, String, u64), String> {
+        self.get_token_by_email_with(email, |refresh_token, account_id| async move {
+            crate::modules::oauth::refresh_access_token(&refresh_token, Some(&account_id)).await
+        })
+        .await
+    }
+
+    async fn get_token_by_email_with<R, RFut>(
+        &self,
+        email: &str,
+        refresh: R,
+    ) -> Result<(String, String, String, String, u64), String>
+    where
+        R: Fn(String, String) -> RFut,
+        RFut: std::future::Future<Output = Result<crate::modules::oauth::TokenResponse, String>>,
+    {
         // 查找账号信息
         let token_info = {
             let mut found = None;
             for entry in self.tokens.iter() {
                 let token = entry.value();
-                if token.email == email {
+                if crate::utils::emails_match(&token.email, email) {
                     found = Some((
                         token.account_id.clone(),
                         token.access_token.clone(),
                         token.refresh_token.clone(),
                         token.timestamp,
-                        token.expires_in,
-                        chrono::Utc::now().timestamp(),
+                        crate::utils::clock::now(),
                         token.project_id.clone(),
                     ));
                     break;
@@ -1920,7 +2797,6 @@ impl TokenManager {
             current_access_token,
             refresh_token,
             timestamp,
-            expires_in,
             now,
             project_id_opt,
         ) = match token_info {
@@ -1932,24 +2808,24 @@ impl TokenManager {
             .filter(|s| !s.is_empty())
             .unwrap_or_else(|| "bamboo-precept-lgxtn".to_string());
 
-        // 检查是否过期 (提前5分钟)
-        if now < timestamp + expires_in - 300 {
+        // 检查是否过期 (timestamp 为过期时间点，提前5分钟刷新)
+        if now < timestamp - 300 {
             return Ok((current_access_token, project_id, email.to_string(), account_id, 0));
         }
 
         tracing::info!("[Warmup] Token for {} is expiring, refreshing...", email);
 
         // 调用 OAuth 刷新 token
-        match crate::modules::oauth::refresh_access_token(&refresh_token, Some(&account_id)).await {
+        match refresh(refresh_token, account_id.clone()).await {
             Ok(token_response) => {
                 tracing::info!("[Warmup] Token refresh successful for {}", email);
-                let new_now = chrono::Utc::now().timestamp();
+                let new_now = crate::utils::clock::now();
 
                 // 更新缓存
                 if let Some(mut entry) = self.tokens.get_mut(&account_id) {
                     entry.access_token = token_response.access_token.clone();
                     entry.expires_in = token_response.expires_in;
-                    entry.timestamp = new_now;
+                    entry.timestamp = new_now + token_response.expires_in;
                 }
 
                 // 保存到磁盘
@@ -1972,6 +2848,559 @@ impl TokenManager {
         }
     }
 
+    /// 依次即时刷新冷账号，返回首个刷新后可服务目标模型 (标准化 ID) 的账号
+    ///
+    /// 刷新成功时占位 access_token 被替换为真实 token 并写回配额；失败的账号在冷却期内不再重试。
+    pub async fn warm_cold_token(&self, normalized_target: &str) -> Option<ProxyToken> {
+        self.warm_cold_token_with(
+            normalized_target,
+            crate::utils::clock::now(),
+            |refresh_token, account_id| async move {
+                crate::modules::oauth::refresh_access_token(&refresh_token, Some(&account_id)).await
+            },
+            Self::fetch_live_quota,
+        )
+        .await
+    }
+
+    async fn warm_cold_token_with<R, RFut, F, FFut>(
+        &self,
+        normalized_target: &str,
+        now: i64,
+        refresh: R,
+        fetch_quota: F,
+    ) -> Option<ProxyToken>
+    where
+        R: Fn(String, String) -> RFut,
+        RFut: std::future::Future<Output = Result<crate::modules::oauth::TokenResponse, String>>,
+        F: Fn(String, String, String) -> FFut,
+        FFut: std::future::Future<Output = Result<(crate::models::QuotaData, Option<String>), String>>,
+    {
+        let mut cold: Vec<ProxyToken> = self
+            .snapshot()
+            .into_iter()
+            .filter(|t| t.enabled && t.is_cold() && !t.is_blocked(now))
+            .collect();
+        cold.sort_by(|a, b| a.account_id.cmp(&b.account_id));
+
+        for token in cold {
+            let recently_tried = matches!(
+                self.cold_refresh_attempts.get(&token.account_id).map(|t| *t),
+                Some(last) if now - last < COLD_REFRESH_COOLDOWN_SECS
+            );
+            if recently_tried {
+                continue;
+            }
+            self.cold_refresh_attempts.insert(token.account_id.clone(), now);
+
+            let result = async {
+                let (access_token, _, _, account_id, _) =
+                    self.get_token_by_email_with(&token.email, &refresh).await?;
+                let (quota, project_id) =
+                    fetch_quota(access_token, token.email.clone(), account_id.clone()).await?;
+                self.apply_quota_state(&account_id, &quota, project_id)
+            }
+            .await;
+
+            match result {
+                Ok(warmed) => {
+                    self.cold_refresh_attempts.remove(&token.account_id);
+                    tracing::info!("[Proxy] Cold account {} upgraded to a live token", warmed.email);
+                    if warmed.model_quotas.get(normalized_target).copied().unwrap_or(0) > 0 {
+                        return Some(warmed);
+                    }
+                }
+                Err(e) => {
+                    tracing::debug!("[Proxy] Cold account {} still unavailable: {}", token.email, e);
+                }
+            }
+        }
+        None
+    }
+
+    /// 按需刷新单个账号的实时状态（订阅等级 / 模型配额）
+    /// 必要时先刷新 access_token，仅更新该账号在池中的 ProxyToken，不影响其他账号
+    pub async fn refresh_account_state(&self, email: &str) -> Result<ProxyToken, String> {
+        self.refresh_account_state_with(email, Self::fetch_live_quota).await
+    }
+
+    async fn fetch_live_quota(
+        access_token: String,
+        email: String,
+        account_id: String,
+    ) -> Result<(crate::models::QuotaData, Option<String>), String> {
+        crate::modules::quota::fetch_quota(&access_token, &email, Some(&account_id))
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    /// 候选账号的缓存状态过旧时先刷新，返回刷新后的快照；状态足够新时返回 None
+    pub async fn refresh_if_stale(
+        &self,
+        token: &ProxyToken,
+        max_state_age_secs: i64,
+    ) -> Result<Option<ProxyToken>, String> {
+        self.refresh_if_stale_with(
+            token,
+            max_state_age_secs,
+            crate::utils::clock::now(),
+            Self::fetch_live_quota,
+        )
+        .await
+    }
+
+    async fn refresh_if_stale_with<F, Fut>(
+        &self,
+        token: &ProxyToken,
+        max_state_age_secs: i64,
+        now: i64,
+        fetch_quota: F,
+    ) -> Result<Option<ProxyToken>, String>
+    where
+        F: FnOnce(String, String, String) -> Fut,
+        Fut: std::future::Future<Output = Result<(crate::models::QuotaData, Option<String>), String>>,
+    {
+        if !token.is_state_stale(now, max_state_age_secs) {
+            return Ok(None);
+        }
+        tracing::debug!(
+            "[Proxy] Cached state of {} is older than {}s, refreshing before selection",
+            token.email,
+            max_state_age_secs
+        );
+        self.refresh_account_state_with(&token.email, fetch_quota)
+            .await
+            .map(Some)
+    }
+
+    async fn refresh_account_state_with<F, Fut>(
+        &self,
+        email: &str,
+        fetch_quota: F,
+    ) -> Result<ProxyToken, String>
+    where
+        F: FnOnce(String, String, String) -> Fut,
+        Fut: std::future::Future<Output = Result<(crate::models::QuotaData, Option<String>), String>>,
+    {
+        // 复用 get_token_by_email 的过期检测与刷新逻辑
+        let (access_token, _, _, account_id, _) = self.get_token_by_email(email).await?;
+
+        let (quota, project_id) =
+            fetch_quota(access_token, email.to_string(), account_id.clone()).await?;
+
+        self.apply_quota_state(&account_id, &quota, project_id)
+    }
+
+    /// 将一次配额查询结果写回账号文件与内存池
+    fn apply_quota_state(
+        &self,
+        account_id: &str,
+        quota: &crate::models::QuotaData,
+        project_id: Option<String>,
+    ) -> Result<ProxyToken, String> {
+        let quota_json = serde_json::to_value(quota).map_err(|e| e.to_string())?;
+        let (account_path, email) = self
+            .tokens
+            .get(account_id)
+            .map(|t| (t.account_path.clone(), t.email.clone()))
+            .ok_or_else(|| format!("未找到账号: {}", account_id))?;
+
+        // 持久化最新配额，保证下次 reload 时状态一致
+        let mut content: serde_json::Value = serde_json::from_str(
+            &crate::modules::account_store::read_account_file(&account_path).map_err(|e| format!("读取文件失败: {}", e))?,
+        )
+        .map_err(|e| format!("解析 JSON 失败: {}", e))?;
+        content["quota"] = quota_json.clone();
+        let refreshed_at = crate::utils::clock::now();
+        content["last_refreshed"] = serde_json::Value::Number(refreshed_at.into());
+        if let Some(pid) = project_id.as_deref().filter(|s| !s.is_empty()) {
+            content["token"]["project_id"] = serde_json::Value::String(pid.to_string());
+        }
+        crate::modules::account_store::write_account_file(&account_path, &serde_json::to_string_pretty(&content).unwrap())
+            .map_err(|e| format!("写入文件失败: {}", e))?;
+
+        let remaining_quota = self.calculate_quota_stats(&quota_json);
+        let reset_time = self.extract_earliest_reset_time(&serde_json::json!({ "quota": quota_json }));
+
+        let selection_config = crate::proxy::selection::get_selection_config();
+        let mut model_quotas = HashMap::new();
+        let mut quota_max = HashMap::new();
+        let mut model_limits: HashMap<String, u64> = HashMap::new();
+        for model in &quota.models {
+            let standard_id = selection_config.quota_key(&model.name);
+            if let Some(max) = model.quota_max {
+                quota_max.insert(standard_id.clone(), max);
+            }
+            model_quotas.insert(standard_id, model.percentage);
+            if let Some(limit) = model.max_output_tokens.filter(|l| *l > 0) {
+                model_limits.insert(model.name.clone(), limit as u64);
+            }
+        }
+
+        self.record_capabilities(account_id, model_quotas.keys().cloned(), refreshed_at);
+
+        let mut entry = self
+            .tokens
+            .get_mut(account_id)
+            .ok_or_else(|| format!("未找到账号: {}", email))?;
+        entry.subscription_tier = quota.subscription_tier.clone();
+        entry.remaining_quota = remaining_quota;
+        entry.reset_time = reset_time;
+        entry.model_quotas = model_quotas;
+        entry.quota_max = quota_max;
+        entry.model_limits = model_limits;
+        entry.cached_capabilities.clear();
+        entry.last_refreshed = Some(refreshed_at);
+        if let Some(pid) = project_id.filter(|s| !s.is_empty()) {
+            entry.project_id = Some(pid);
+        }
+
+        tracing::info!(
+            "[Proxy] Refreshed live state for {} (tier={:?}, models={})",
+            email,
+            entry.subscription_tier,
+            entry.model_quotas.len()
+        );
+        Ok(entry.clone())
+    }
+
+    /// 并发刷新池中所有账号的配额与订阅等级
+    ///
+    /// 同一 project_id 下的账号共享配额，只查询一次并将结果应用到组内所有账号；
+    /// 同时在途的查询数不超过 `concurrency`
+    pub async fn refresh_all_quotas(&self, concurrency: usize) -> QuotaRefreshReport {
+        self.refresh_all_quotas_with(concurrency, |access_token, email, account_id| async move {
+            crate::modules::quota::fetch_quota(&access_token, &email, Some(&account_id))
+                .await
+                .map_err(|e| e.to_string())
+        })
+        .await
+    }
+
+    async fn refresh_all_quotas_with<F, Fut>(
+        &self,
+        concurrency: usize,
+        fetch_quota: F,
+    ) -> QuotaRefreshReport
+    where
+        F: Fn(String, String, String) -> Fut,
+        Fut: std::future::Future<Output = Result<(crate::models::QuotaData, Option<String>), String>>,
+    {
+        let snapshot: Vec<ProxyToken> = self.tokens.iter().map(|e| e.value().clone()).collect();
+        self.refresh_quotas_for_with(snapshot, concurrency, fetch_quota).await
+    }
+
+    /// 模型能力缓存文件路径
+    fn capability_cache_path(&self) -> PathBuf {
+        self.data_dir.join(CAPABILITY_CACHE_FILE)
+    }
+
+    /// 记录账号的模型能力 (只更新内存，由落盘任务批量写回)
+    fn record_capabilities<I>(&self, account_id: &str, models: I, now: i64)
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let Ok(mut cache) = self.capabilities.write() else {
+            return;
+        };
+        cache.record(account_id, models, now);
+        self.capabilities_dirty.store(true, Ordering::Release);
+    }
+
+    /// 删除已删除账号的能力记录
+    fn forget_capabilities(&self, account_id: &str) {
+        if let Ok(mut cache) = self.capabilities.write() {
+            if cache.remove(account_id) {
+                self.capabilities_dirty.store(true, Ordering::Release);
+            }
+        }
+    }
+
+    /// 立即将待写的能力缓存写回文件
+    pub fn flush_capabilities(&self) {
+        Self::flush_capability_cache(
+            &self.capabilities,
+            &self.capabilities_dirty,
+            &self.capability_cache_path(),
+        );
+    }
+
+    /// 在锁外写文件：先复制一份快照再释放读锁；写入失败时保留待写标记，下次重试
+    fn flush_capability_cache(
+        cache: &std::sync::RwLock<CapabilityCache>,
+        dirty: &AtomicBool,
+        path: &std::path::Path,
+    ) {
+        if !dirty.swap(false, Ordering::AcqRel) {
+            return;
+        }
+        let Ok(snapshot) = cache.read().map(|cache| cache.clone()) else {
+            return;
+        };
+        if let Err(e) = snapshot.save(path) {
+            dirty.store(true, Ordering::Release);
+            tracing::warn!("[Capability] {}", e);
+        }
+    }
+
+    /// 池中没有能力记录或记录早于 `now - ttl_secs` 的账号
+    pub fn stale_capability_accounts(&self, now: i64, ttl_secs: i64) -> Vec<String> {
+        let ids: Vec<String> = self.tokens.iter().map(|e| e.key().clone()).collect();
+        match self.capabilities.read() {
+            Ok(cache) => cache.stale_accounts(ids.iter().map(|id| id.as_str()), now, ttl_secs),
+            Err(_) => ids,
+        }
+    }
+
+    /// 仅为能力记录过期的账号重新拉取配额 (同时刷新能力缓存)
+    pub async fn refresh_stale_capabilities(&self, ttl_secs: i64, concurrency: usize) -> QuotaRefreshReport {
+        let stale: HashSet<String> = self
+            .stale_capability_accounts(crate::utils::clock::now(), ttl_secs)
+            .into_iter()
+            .collect();
+        let snapshot: Vec<ProxyToken> = self
+            .tokens
+            .iter()
+            .filter(|e| stale.contains(e.key()))
+            .map(|e| e.value().clone())
+            .collect();
+        if snapshot.is_empty() {
+            return QuotaRefreshReport::default();
+        }
+        self.refresh_quotas_for_with(snapshot, concurrency, Self::fetch_live_quota).await
+    }
+
+    /// 在维护调度器上注册能力刷新任务：每隔 `interval` 整理账号池并刷新过期的模型能力
+    ///
+    /// 首次执行在注册一个间隔之后 (启动预热已刷新过全部账号)；随 TokenManager 停机一同取消。
+    /// 任务只持有弱引用，不会因调度器持有任务而阻止 TokenManager 释放。
+    pub fn start_capability_refresh(
+        self: &Arc<Self>,
+        ttl_secs: i64,
+        interval: std::time::Duration,
+        concurrency: usize,
+    ) {
+        let manager = Arc::downgrade(self);
+        self.maintenance
+            .register(CAPABILITY_REFRESH_TASK, interval, move || {
+                let manager = manager.clone();
+                async move {
+                    if let Some(manager) = manager.upgrade() {
+                        manager.capability_refresh_tick(ttl_secs, concurrency).await;
+                    }
+                }
+            });
+        self.maintenance.start(&self.cancel_token);
+    }
+
+    /// 能力刷新任务的单次执行
+    async fn capability_refresh_tick(&self, ttl_secs: i64, concurrency: usize) {
+        // 先整理账号池：配额已刷新或重新启用的账号放回并随本轮一起刷新，不可用的账号移出
+        let now = crate::utils::clock::now();
+        self.restore_evicted(now).await;
+        self.evict_exhausted(now).await;
+        let report = self.refresh_stale_capabilities(ttl_secs, concurrency).await;
+        if report.total > 0 {
+            tracing::info!(
+                "[Capability] Refreshed {}/{} stale account(s)",
+                report.updated.len(),
+                report.total
+            );
+        }
+    }
+
+    /// 同一项目下其他账号共享的查询结果：只共享模型配额与刷新时间，
+    /// 订阅等级与 403 状态属于账号自身 (FREE 账号不会因与 ULTRA 账号同项目而被提升)
+    fn shared_project_quota(
+        quota: &crate::models::QuotaData,
+        member: &ProxyToken,
+    ) -> crate::models::QuotaData {
+        crate::models::QuotaData {
+            subscription_tier: member.subscription_tier.clone(),
+            is_forbidden: false,
+            forbidden_reason: None,
+            ..quota.clone()
+        }
+    }
+
+    async fn refresh_quotas_for_with<F, Fut>(
+        &self,
+        mut snapshot: Vec<ProxyToken>,
+        concurrency: usize,
+        fetch_quota: F,
+    ) -> QuotaRefreshReport
+    where
+        F: Fn(String, String, String) -> Fut,
+        Fut: std::future::Future<Output = Result<(crate::models::QuotaData, Option<String>), String>>,
+    {
+        use futures::stream::{self, StreamExt};
+
+        snapshot.sort_by(|a, b| a.account_id.cmp(&b.account_id));
+
+        // 按 project_id 分组，未知 project_id 的账号单独成组
+        let mut groups: Vec<Vec<ProxyToken>> = Vec::new();
+        let mut group_index: HashMap<String, usize> = HashMap::new();
+        for token in snapshot {
+            let key = match token.project_id.as_deref().filter(|p| !p.is_empty()) {
+                Some(pid) => format!("project:{}", pid),
+                None => format!("account:{}", token.account_id),
+            };
+            match group_index.get(&key) {
+                Some(&idx) => groups[idx].push(token),
+                None => {
+                    group_index.insert(key, groups.len());
+                    groups.push(vec![token]);
+                }
+            }
+        }
+
+        let mut report = QuotaRefreshReport {
+            total: groups.iter().map(|g| g.len()).sum(),
+            requests: groups.len(),
+            ..Default::default()
+        };
+
+        let fetch_quota = &fetch_quota;
+        let results = stream::iter(groups)
+            .map(|group| async move {
+                let lead = &group[0];
+                let result = match self.get_token_by_email(&lead.email).await {
+                    Ok((access_token, _, _, account_id, _)) => {
+                        fetch_quota(access_token, lead.email.clone(), account_id).await
+                    }
+                    Err(e) => Err(e),
+                };
+                (group, result)
+            })
+            .buffer_unordered(concurrency.max(1))
+            .collect::<Vec<_>>()
+            .await;
+
+        for (group, result) in results {
+            match result {
+                Ok((quota, project_id)) => {
+                    for (idx, token) in group.iter().enumerate() {
+                        let quota = if idx == 0 {
+                            quota.clone()
+                        } else {
+                            Self::shared_project_quota(&quota, token)
+                        };
+                        match self.apply_quota_state(&token.account_id, &quota, project_id.clone()) {
+                            Ok(_) => report.updated.push(token.account_id.clone()),
+                            Err(e) => report.failed.push((token.account_id.clone(), e)),
+                        }
+                    }
+                }
+                Err(e) => {
+                    tracing::warn!("[Proxy] Quota refresh failed for {}: {}", group[0].email, e);
+                    report
+                        .failed
+                        .extend(group.iter().map(|t| (t.account_id.clone(), e.clone())));
+                }
+            }
+        }
+
+        tracing::info!(
+            "[Proxy] Refreshed quotas: {}/{} accounts updated with {} requests",
+            report.updated.len(),
+            report.total,
+            report.requests
+        );
+        report
+    }
+
+    /// 供调用方取消预热等一次性任务的令牌 (graceful_shutdown 时同样会被取消)
+    pub fn child_cancel_token(&self) -> CancellationToken {
+        self.cancel_token.child_token()
+    }
+
+    /// 启动预热：刷新即将过期的 access_token 并拉取所有账号的配额，
+    /// 使首个真实请求无需在选号路径上等待刷新。
+    ///
+    /// 同时在途的账号数不超过 `concurrency`；刷新与配额查询本身经过全局上游限流。
+    /// `cancel` 触发后不再等待剩余账号，已完成的结果仍会写入报告。
+    pub async fn warmup(&self, concurrency: usize, cancel: CancellationToken) -> WarmupReport {
+        self.warmup_with(
+            concurrency,
+            cancel,
+            |refresh_token, account_id| async move {
+                crate::modules::oauth::refresh_access_token(&refresh_token, Some(&account_id)).await
+            },
+            Self::fetch_live_quota,
+        )
+        .await
+    }
+
+    async fn warmup_with<R, RFut, F, FFut>(
+        &self,
+        concurrency: usize,
+        cancel: CancellationToken,
+        refresh: R,
+        fetch_quota: F,
+    ) -> WarmupReport
+    where
+        R: Fn(String, String) -> RFut,
+        RFut: std::future::Future<Output = Result<crate::modules::oauth::TokenResponse, String>>,
+        F: Fn(String, String, String) -> FFut,
+        FFut: std::future::Future<Output = Result<(crate::models::QuotaData, Option<String>), String>>,
+    {
+        use futures::stream::{self, StreamExt};
+
+        let mut snapshot: Vec<ProxyToken> = self.tokens.iter().map(|e| e.value().clone()).collect();
+        snapshot.sort_by(|a, b| a.account_id.cmp(&b.account_id));
+
+        let mut report = WarmupReport {
+            total: snapshot.len(),
+            ..Default::default()
+        };
+
+        let (refresh, fetch_quota) = (&refresh, &fetch_quota);
+        let mut results = std::pin::pin!(stream::iter(snapshot)
+            .map(|token| async move {
+                let result = async {
+                    let (access_token, _, _, account_id, _) =
+                        self.get_token_by_email_with(&token.email, refresh).await?;
+                    let refreshed = access_token != token.access_token;
+                    let (quota, project_id) =
+                        fetch_quota(access_token, token.email.clone(), account_id.clone()).await?;
+                    self.apply_quota_state(&account_id, &quota, project_id)?;
+                    Ok::<bool, String>(refreshed)
+                }
+                .await;
+                (token.account_id, result)
+            })
+            .buffer_unordered(concurrency.max(1))
+            .take_until(cancel.cancelled()));
+
+        let mut completed = 0;
+        while let Some((account_id, result)) = results.next().await {
+            completed += 1;
+            match result {
+                Ok(refreshed) => {
+                    if refreshed {
+                        report.refreshed_tokens.push(account_id.clone());
+                    }
+                    report.quota_updated.push(account_id);
+                }
+                Err(e) => {
+                    tracing::warn!("[Warmup] Failed to warm up {}: {}", account_id, e);
+                    report.failed.push((account_id, e));
+                }
+            }
+        }
+        report.cancelled = completed < report.total;
+
+        tracing::info!(
+            "[Warmup] {}/{} accounts warmed up ({} tokens refreshed, {} failed{})",
+            report.quota_updated.len(),
+            report.total,
+            report.refreshed_tokens.len(),
+            report.failed.len(),
+            if report.cancelled { ", cancelled" } else { "" }
+        );
+        report
+    }
+
     // ===== 限流管理方法 =====
 
     /// 标记账号限流(从外部调用,通常在 handler 中)
@@ -2039,7 +3468,7 @@ impl TokenManager {
     fn email_to_account_id(&self, email: &str) -> Option<String> {
         self.tokens
             .iter()
-            .find(|entry| entry.value().email == email)
+            .find(|entry| crate::utils::emails_match(&entry.value().email, email))
             .map(|entry| entry.value().account_id.clone())
     }
 
@@ -2059,6 +3488,7 @@ impl TokenManager {
     /// 下次失败时从最短的锁定时间开始（智能限流）。
     pub fn mark_account_success(&self, account_id: &str) {
         self.rate_limit_tracker.mark_success(account_id);
+        self.reset_validation_backoff(account_id);
     }
 
     /// 检查是否有可用的 Google 账号
@@ -2137,7 +3567,7 @@ impl TokenManager {
         // 直接用 account_id 查找账号文件（文件名是 {account_id}.json）
         let account_path = self.data_dir.join("accounts").join(format!("{}.json", account_id));
 
-        let content = std::fs::read_to_string(&account_path).ok()?;
+        let content = crate::modules::account_store::read_account_file(&account_path).ok()?;
         let account: serde_json::Value = serde_json::from_str(&content).ok()?;
 
         // 获取 quota.models 中最早的 reset_time（最保守的锁定策略）
@@ -2196,7 +3626,7 @@ impl TokenManager {
         let (access_token, account_id) = {
             let mut found: Option<(String, String)> = None;
             for entry in self.tokens.iter() {
-                if entry.value().email == email {
+                if crate::utils::emails_match(&entry.value().email, email) {
                     found = Some((
                         entry.value().access_token.clone(),
                         entry.value().account_id.clone(),
@@ -2379,6 +3809,7 @@ impl TokenManager {
 
     /// 更新调度配置
     pub async fn update_sticky_config(&self, new_config: StickySessionConfig) {
+        crate::proxy::selection::update_selection_policy(new_config.selection_policy);
         let mut config = self.sticky_config.write().await;
         *config = new_config;
         tracing::debug!("Scheduling configuration updated: {:?}", *config);
@@ -2396,15 +3827,50 @@ impl TokenManager {
         self.circuit_breaker_config.read().await.clone()
     }
 
+    /// 查询会话当前绑定的账号
+    ///
+    /// 距上次命中已超过该模型的粘性有效期 (`StickySessionConfig::sticky_ttl`) 时解绑并返回 None
+    pub fn select_for_session(
+        &self,
+        session_id: &str,
+        model: &str,
+        config: &StickySessionConfig,
+        now: i64,
+    ) -> Option<String> {
+        let bound_id = self.session_accounts.get(session_id).map(|v| v.clone())?;
+        if let Some(last_seen) = self.session_last_seen.get(session_id).map(|v| *v) {
+            let ttl = config.sticky_ttl(model).as_secs() as i64;
+            if now.saturating_sub(last_seen) >= ttl {
+                tracing::debug!(
+                    "Sticky Session: Binding for session {} expired (ttl {}s, model {}), unbinding",
+                    session_id,
+                    ttl,
+                    model
+                );
+                self.clear_session_binding(session_id);
+                return None;
+            }
+        }
+        Some(bound_id)
+    }
+
+    /// 建立或续期会话的粘性绑定
+    pub fn bind_session(&self, session_id: &str, account_id: &str, now: i64) {
+        self.session_accounts
+            .insert(session_id.to_string(), account_id.to_string());
+        self.session_last_seen.insert(session_id.to_string(), now);
+    }
+
     /// 清除特定会话的粘性映射
-    #[allow(dead_code)]
     pub fn clear_session_binding(&self, session_id: &str) {
         self.session_accounts.remove(session_id);
+        self.session_last_seen.remove(session_id);
     }
 
     /// 清除所有会话的粘性映射
     pub fn clear_all_sessions(&self) {
         self.session_accounts.clear();
+        self.session_last_seen.clear();
     }
 
     // ===== [FIX #820] 固定账号模式相关方法 =====
@@ -2480,7 +3946,8 @@ impl TokenManager {
                 None, // session_id
                 true,
             )
-            .with_oauth_client_key(token_info.oauth_client_key.clone());
+            .with_oauth_client_key(token_info.oauth_client_key.clone())
+            .with_scopes(token_info.scopes());
 
             crate::modules::account::upsert_account(email_clone, None, token_data)
         })
@@ -2492,12 +3959,21 @@ impl TokenManager {
         self.reload_all_accounts().await.map(|_| ())
     }
 
+    /// 当前健康分 (未记录过成功/失败的账号视为 1.0)
+    pub fn get_health_score(&self, account_id: &str) -> f32 {
+        self.health_scores.get(account_id).map(|v| *v).unwrap_or(1.0)
+    }
+
     /// 记录请求成功，增加健康分
     pub fn record_success(&self, account_id: &str) {
         self.health_scores
             .entry(account_id.to_string())
             .and_modify(|s| *s = (*s + 0.05).min(1.0))
             .or_insert(1.0);
+        self.reset_validation_backoff(account_id);
+        if let Some(mut token) = self.tokens.get_mut(account_id) {
+            token.last_failure = None;
+        }
         tracing::debug!("📈 Health score increased for account {}", account_id);
     }
 
@@ -2507,6 +3983,10 @@ impl TokenManager {
             .entry(account_id.to_string())
             .and_modify(|s| *s = (*s - 0.2).max(0.0))
             .or_insert(0.8);
+        // 短时间内降低该账号的选择优先级 (见 SelectionConfig::recent_failure_window_secs)
+        if let Some(mut token) = self.tokens.get_mut(account_id) {
+            token.last_failure = Some(crate::utils::clock::now());
+        }
         tracing::warn!("📉 Health score decreased for account {}", account_id);
     }
 
@@ -2589,7 +4069,7 @@ impl TokenManager {
     /// Helper to find account ID by email
     pub fn get_account_id_by_email(&self, email: &str) -> Option<String> {
         for entry in self.tokens.iter() {
-            if entry.value().email == email {
+            if crate::utils::emails_match(&entry.value().email, email) {
                 return Some(entry.key().clone());
             }
         }
@@ -2599,10 +4079,7 @@ impl TokenManager {
     /// Set validation blocked status for an account (internal)
     pub async fn set_validation_block(&self, account_id: &str, block_until: i64, reason: &str) -> Result<(), String> {
         // 1. Update memory
-        if let Some(mut token) = self.tokens.get_mut(account_id) {
-             token.validation_blocked = true;
-             token.validation_blocked_until = block_until;
-        }
+        self.apply_validation_block(account_id, block_until);
 
         // 2. Persist to disk
         let path = self.data_dir.join("accounts").join(format!("{}.json", account_id));
@@ -2610,7 +4087,7 @@ impl TokenManager {
              return Err(format!("Account file not found: {:?}", path));
         }
 
-        let content = std::fs::read_to_string(&path)
+        let content = crate::modules::account_store::read_account_file(&path)
              .map_err(|e| format!("Failed to read account file: {}", e))?;
 
         let mut account: serde_json::Value = serde_json::from_str(&content)
@@ -2663,7 +4140,7 @@ impl TokenManager {
         let json_str = serde_json::to_string_pretty(&account)
              .map_err(|e| format!("Failed to serialize account JSON: {}", e))?;
 
-        std::fs::write(&path, json_str)
+        crate::modules::account_store::write_account_file(&path, &json_str)
              .map_err(|e| format!("Failed to write account file: {}", e))?;
 
         tracing::info!(
@@ -2676,11 +4153,63 @@ impl TokenManager {
         Ok(())
     }
 
+    /// 仅更新内存中的验证封禁状态，账号不在池中时返回 false
+    pub fn apply_validation_block(&self, account_id: &str, block_until: i64) -> bool {
+        match self.tokens.get_mut(account_id) {
+            Some(mut token) => {
+                token.validation_blocked = true;
+                token.validation_blocked_until = block_until;
+                true
+            }
+            None => false,
+        }
+    }
+
     /// Public method to set validation block (called from handlers)
     pub async fn set_validation_block_public(&self, account_id: &str, block_until: i64, reason: &str) -> Result<(), String> {
         self.set_validation_block(account_id, block_until, reason).await
     }
 
+    /// 按连续封禁次数逐级延长验证封禁 (时长取自 `validation_block_steps`)，返回封禁截止时间
+    ///
+    /// 反复触发 VALIDATION_REQUIRED 的账号冷却时间逐步变长，请求成功后从第一级重新开始
+    pub async fn block_validation(&self, account_id: &str, reason: &str) -> Result<i64, String> {
+        let steps = self
+            .circuit_breaker_config
+            .read()
+            .await
+            .validation_block_steps
+            .clone();
+        let consecutive = {
+            let mut count = self
+                .validation_block_counts
+                .entry(account_id.to_string())
+                .or_insert(0);
+            *count += 1;
+            *count
+        };
+        let block_secs = validation_block_secs(&steps, consecutive);
+        let block_until = crate::utils::clock::now() + block_secs as i64;
+        tracing::warn!(
+            "Account {} validation block #{} for {}s",
+            account_id,
+            consecutive,
+            block_secs
+        );
+        self.set_validation_block(account_id, block_until, reason).await?;
+        Ok(block_until)
+    }
+
+    /// 清零连续验证封禁次数 (参数可为 account_id 或 email)
+    fn reset_validation_backoff(&self, account_id: &str) {
+        let key = self
+            .email_to_account_id(account_id)
+            .unwrap_or_else(|| account_id.to_string());
+        if self.validation_block_counts.remove(&key).is_some() {
+            tracing::debug!("Validation block escalation reset for account {}", key);
+        }
+    }
+
     /// Set is_forbidden status for an account (called when proxy encounters 403)
     pub async fn set_forbidden(&self, account_id: &str, reason: &str) -> Result<(), String> {
         // [FIX] 调用封装好的模块函数，确保线程安全地更新账号文件和索引
@@ -2702,6 +4231,16 @@ impl TokenManager {
     }
 }
 
+/// 第 `consecutive` 次连续验证封禁的时长 (秒)：超出档位后沿用最后一档，未配置时为 10 分钟
+fn validation_block_secs(steps: &[u64], consecutive: u32) -> u64 {
+    let index = consecutive.saturating_sub(1) as usize;
+    steps
+        .get(index)
+        .or_else(|| steps.last())
+        .copied()
+        .unwrap_or(600)
+}
+
 /// 截断过长的原因字符串
 fn truncate_reason(reason: &str, max_len: usize) -> String {
     if reason.len() <= max_len {
@@ -2782,6 +4321,664 @@ mod tests {
         let _ = std::fs::remove_dir_all(&tmp_root);
     }
 
+    #[tokio::test]
+    async fn test_refresh_account_state_updates_only_target_token() {
+        let tmp_root = std::env::temp_dir().join(format!(
+            "antigravity-token-manager-test-refresh-state-{}",
+            uuid::Uuid::new_v4()
+        ));
+        let accounts_dir = tmp_root.join("accounts");
+        std::fs::create_dir_all(&accounts_dir).unwrap();
+
+        let now = chrono::Utc::now().timestamp();
+        for (id, email) in [("acc1", "a@test.com"), ("acc2", "b@test.com")] {
+            let json = serde_json::json!({
+                "id": id,
+                "email": email,
+                "token": {
+                    "access_token": format!("atk-{}", id),
+                    "refresh_token": "rtk",
+                    "expires_in": 3600,
+                    "expiry_timestamp": now + 3600
+                },
+                "quota": {
+                    "models": [{ "name": "gemini-3-flash", "percentage": 10, "reset_time": "" }],
+                    "last_updated": now,
+                    "subscription_tier": "FREE"
+                },
+                "created_at": now,
+                "last_used": now
+            });
+            std::fs::write(
+                accounts_dir.join(format!("{}.json", id)),
+                serde_json::to_string_pretty(&json).unwrap(),
+            )
+            .unwrap();
+        }
+
+        let manager = TokenManager::new(tmp_root.clone());
+        manager.load_accounts().await.unwrap();
+        let untouched_before = manager.get_token_by_id("acc2").unwrap();
+
+        let updated = manager
+            .refresh_account_state_with("a@test.com", |access_token, _email, account_id| async move {
+                assert_eq!(access_token, "atk-acc1");
+                assert_eq!(account_id, "acc1");
+                let quota: crate::models::QuotaData = serde_json::from_value(serde_json::json!({
+                    "models": [
+                        { "name": "gemini-3-flash", "percentage": 80, "reset_time": "" },
+                        { "name": "claude-sonnet-4-6", "percentage": 55, "reset_time": "2030-01-01T00:00:00Z" }
+                    ],
+                    "last_updated": 0,
+                    "subscription_tier": "ULTRA"
+                }))
+                .unwrap();
+                Ok((quota, None))
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(updated.subscription_tier.as_deref(), Some("ULTRA"));
+        assert_eq!(updated.model_quotas.len(), 2);
+        assert_eq!(updated.model_quotas.values().copied().max(), Some(80));
+        assert_eq!(updated.remaining_quota, Some(80));
+        assert!(updated.reset_time.is_some());
+
+        let pooled = manager.get_token_by_id("acc1").unwrap();
+        assert_eq!(pooled.subscription_tier.as_deref(), Some("ULTRA"));
+        assert_eq!(pooled.model_quotas, updated.model_quotas);
+
+        let untouched = manager.get_token_by_id("acc2").unwrap();
+        assert_eq!(untouched.subscription_tier, untouched_before.subscription_tier);
+        assert_eq!(untouched.model_quotas, untouched_before.model_quotas);
+
+        // 配额已持久化到磁盘
+        let on_disk: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string(accounts_dir.join("acc1.json")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(on_disk["quota"]["subscription_tier"], "ULTRA");
+        assert!(on_disk["last_refreshed"].as_i64().is_some());
+        let untouched_disk: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string(accounts_dir.join("acc2.json")).unwrap(),
+        )
+        .unwrap();
+        assert!(untouched_disk.get("last_refreshed").is_none());
+
+        let _ = std::fs::remove_dir_all(&tmp_root);
+    }
+
+    #[tokio::test]
+    async fn test_stale_candidate_triggers_state_refresh() {
+        let tmp_root = std::env::temp_dir().join(format!(
+            "antigravity-token-manager-test-stale-state-{}",
+            uuid::Uuid::new_v4()
+        ));
+        let accounts_dir = tmp_root.join("accounts");
+        std::fs::create_dir_all(&accounts_dir).unwrap();
+
+        let now = chrono::Utc::now().timestamp();
+        for (id, email, last_refreshed) in [
+            ("stale", "stale@test.com", now - 900),
+            ("fresh", "fresh@test.com", now - 60),
+        ] {
+            let json = serde_json::json!({
+                "id": id,
+                "email": email,
+                "token": {
+                    "access_token": format!("atk-{}", id),
+                    "refresh_token": "rtk",
+                    "expires_in": 3600,
+                    "expiry_timestamp": now + 3600
+                },
+                "quota": {
+                    "models": [{ "name": "gemini-3-flash", "percentage": 50, "reset_time": "" }],
+                    "last_updated": last_refreshed,
+                    "subscription_tier": "PRO"
+                },
+                "last_refreshed": last_refreshed,
+                "created_at": now,
+                "last_used": now
+            });
+            std::fs::write(
+                accounts_dir.join(format!("{}.json", id)),
+                serde_json::to_string_pretty(&json).unwrap(),
+            )
+            .unwrap();
+        }
+
+        let manager = TokenManager::new(tmp_root.clone());
+        manager.load_accounts().await.unwrap();
+        let stale = manager.get_token_by_id("stale").unwrap();
+        let fresh = manager.get_token_by_id("fresh").unwrap();
+        assert_eq!(stale.last_refreshed, Some(now - 900));
+
+        use std::sync::atomic::Ordering as AtomicOrdering;
+        let fetches = Arc::new(AtomicUsize::new(0));
+        let mock_fetch = |fetches: Arc<AtomicUsize>| {
+            move |_access_token: String, _email: String, _account_id: String| async move {
+                fetches.fetch_add(1, AtomicOrdering::SeqCst);
+                let quota: crate::models::QuotaData = serde_json::from_value(serde_json::json!({
+                    "models": [{ "name": "gemini-3-flash", "percentage": 0, "reset_time": "" }],
+                    "last_updated": 0,
+                    "subscription_tier": "PRO"
+                }))
+                .unwrap();
+                Ok((quota, None))
+            }
+        };
+
+        // 超过 10 分钟未刷新：先刷新，得到实际已耗尽的配额
+        let refreshed = manager
+            .refresh_if_stale_with(&stale, 600, now, mock_fetch(fetches.clone()))
+            .await
+            .unwrap()
+            .expect("stale candidate should be refreshed");
+        assert_eq!(fetches.load(AtomicOrdering::SeqCst), 1);
+        assert_eq!(refreshed.model_quotas.get("gemini-3-flash"), Some(&0));
+        assert!(refreshed.last_refreshed.unwrap() >= now);
+
+        // 1 分钟前刷新过：不触发
+        let result = manager
+            .refresh_if_stale_with(&fresh, 600, now, mock_fetch(fetches.clone()))
+            .await
+            .unwrap();
+        assert!(result.is_none());
+        assert_eq!(fetches.load(AtomicOrdering::SeqCst), 1);
+
+        // 未配置 max_state_age 时从不刷新
+        let result = manager
+            .refresh_if_stale_with(&stale, 0, now, mock_fetch(fetches.clone()))
+            .await
+            .unwrap();
+        assert!(result.is_none());
+        assert_eq!(fetches.load(AtomicOrdering::SeqCst), 1);
+
+        let _ = std::fs::remove_dir_all(&tmp_root);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_refresh_all_quotas_respects_concurrency_and_dedupes_projects() {
+        let tmp_root = std::env::temp_dir().join(format!(
+            "antigravity-token-manager-test-refresh-all-{}",
+            uuid::Uuid::new_v4()
+        ));
+        let accounts_dir = tmp_root.join("accounts");
+        std::fs::create_dir_all(&accounts_dir).unwrap();
+
+        let now = chrono::Utc::now().timestamp();
+        let accounts = [
+            ("acc1", Some("proj-shared")),
+            ("acc2", Some("proj-shared")),
+            ("acc3", Some("proj-3")),
+            ("acc4", None),
+            ("acc5", None),
+            ("acc6", Some("proj-6")),
+        ];
+        for (id, project_id) in accounts {
+            let mut token = serde_json::json!({
+                "access_token": format!("atk-{}", id),
+                "refresh_token": "rtk",
+                "expires_in": 3600,
+                "expiry_timestamp": now + 3600
+            });
+            if let Some(pid) = project_id {
+                token["project_id"] = serde_json::Value::String(pid.to_string());
+            }
+            let json = serde_json::json!({
+                "id": id,
+                "email": format!("{}@test.com", id),
+                "token": token,
+                "quota": {
+                    "models": [{ "name": "gemini-3-flash", "percentage": 10, "reset_time": "" }],
+                    "last_updated": now,
+                    "subscription_tier": "FREE"
+                },
+                "created_at": now,
+                "last_used": now
+            });
+            std::fs::write(
+                accounts_dir.join(format!("{}.json", id)),
+                serde_json::to_string_pretty(&json).unwrap(),
+            )
+            .unwrap();
+        }
+
+        let manager = TokenManager::new(tmp_root.clone());
+        manager.load_accounts().await.unwrap();
+
+        use std::sync::atomic::Ordering as AtomicOrdering;
+
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let report = manager
+            .refresh_all_quotas_with(2, |_access_token, _email, _account_id| {
+                let (in_flight, peak, calls) = (in_flight.clone(), peak.clone(), calls.clone());
+                async move {
+                    let current = in_flight.fetch_add(1, AtomicOrdering::SeqCst) + 1;
+                    peak.fetch_max(current, AtomicOrdering::SeqCst);
+                    calls.fetch_add(1, AtomicOrdering::SeqCst);
+                    tokio::time::sleep(std::time::Duration::from_millis(30)).await;
+                    in_flight.fetch_sub(1, AtomicOrdering::SeqCst);
+
+                    let quota: crate::models::QuotaData = serde_json::from_value(serde_json::json!({
+                        "models": [{ "name": "gemini-3-flash", "percentage": 70, "reset_time": "" }],
+                        "last_updated": 0,
+                        "subscription_tier": "PRO"
+                    }))
+                    .unwrap();
+                    Ok((quota, None))
+                }
+            })
+            .await;
+
+        assert_eq!(report.total, 6);
+        // acc1/acc2 共享 project，只查询一次
+        assert_eq!(report.requests, 5);
+        assert_eq!(calls.load(AtomicOrdering::SeqCst), 5);
+        assert!(peak.load(AtomicOrdering::SeqCst) <= 2, "concurrency bound exceeded");
+        assert!(report.failed.is_empty());
+        assert_eq!(report.updated.len(), 6);
+
+        for (id, _) in accounts {
+            let token = manager.get_token_by_id(id).unwrap();
+            // 共享项目只共享配额：acc2 沿用 acc1 的查询结果，但保留自己的订阅等级
+            let expected_tier = if id == "acc2" { "FREE" } else { "PRO" };
+            assert_eq!(token.subscription_tier.as_deref(), Some(expected_tier), "{}", id);
+            assert_eq!(token.model_quotas.get("gemini-3-flash"), Some(&70));
+        }
+        let acc2_file: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string(accounts_dir.join("acc2.json")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(acc2_file["quota"]["subscription_tier"], "FREE");
+
+        let _ = std::fs::remove_dir_all(&tmp_root);
+    }
+
+    #[tokio::test]
+    async fn test_warmup_refreshes_expiring_tokens_and_populates_quota() {
+        use std::sync::atomic::Ordering as AtomicOrdering;
+
+        let tmp_root = std::env::temp_dir().join(format!(
+            "antigravity-token-manager-test-warmup-{}",
+            uuid::Uuid::new_v4()
+        ));
+        let accounts_dir = tmp_root.join("accounts");
+        std::fs::create_dir_all(&accounts_dir).unwrap();
+
+        let now = chrono::Utc::now().timestamp();
+        // (account_id, 距过期秒数)：acc1/acc3 即将过期，需要刷新
+        let accounts = [("acc1", 60), ("acc2", 3600), ("acc3", -10), ("acc4", 3600)];
+        for (id, expires_after) in accounts {
+            let json = serde_json::json!({
+                "id": id,
+                "email": format!("{}@test.com", id),
+                "token": {
+                    "access_token": format!("old-{}", id),
+                    "refresh_token": format!("rt-{}", id),
+                    "expires_in": 3600,
+                    "expiry_timestamp": now + expires_after
+                },
+                "created_at": now,
+                "last_used": now
+            });
+            std::fs::write(
+                accounts_dir.join(format!("{}.json", id)),
+                serde_json::to_string_pretty(&json).unwrap(),
+            )
+            .unwrap();
+        }
+
+        let manager = TokenManager::new(tmp_root.clone());
+        manager.load_accounts().await.unwrap();
+
+        let refreshes = Arc::new(AtomicUsize::new(0));
+        let refresh = |refresh_token: String, _account_id: String| {
+            let refreshes = refreshes.clone();
+            async move {
+                refreshes.fetch_add(1, AtomicOrdering::SeqCst);
+                Ok::<_, String>(serde_json::from_value::<crate::modules::oauth::TokenResponse>(
+                    serde_json::json!({
+                        "access_token": format!("new-{}", refresh_token.trim_start_matches("rt-")),
+                        "expires_in": 3600
+                    }),
+                )
+                .unwrap())
+            }
+        };
+        let fetch_quota = |_access_token: String, _email: String, _account_id: String| async {
+            let quota: crate::models::QuotaData = serde_json::from_value(serde_json::json!({
+                "models": [{ "name": "gemini-3-flash", "percentage": 55, "reset_time": "" }],
+                "last_updated": 0,
+                "subscription_tier": "PRO"
+            }))
+            .unwrap();
+            Ok::<_, String>((quota, None))
+        };
+
+        // 已取消：不处理任何账号
+        let cancelled = CancellationToken::new();
+        cancelled.cancel();
+        let report = manager.warmup_with(2, cancelled, refresh, fetch_quota).await;
+        assert!(report.cancelled);
+        assert!(report.quota_updated.is_empty());
+        assert_eq!(refreshes.load(AtomicOrdering::SeqCst), 0);
+
+        let mut report = manager
+            .warmup_with(2, CancellationToken::new(), refresh, fetch_quota)
+            .await;
+        report.refreshed_tokens.sort();
+        assert!(!report.cancelled);
+        assert_eq!(report.total, 4);
+        assert!(report.failed.is_empty());
+        assert_eq!(report.quota_updated.len(), 4);
+        assert_eq!(report.refreshed_tokens, vec!["acc1", "acc3"]);
+        assert_eq!(refreshes.load(AtomicOrdering::SeqCst), 2);
+
+        for (id, expires_after) in accounts {
+            let token = manager.get_token_by_id(id).unwrap();
+            let expected_access = if expires_after < 300 {
+                format!("new-{}", id)
+            } else {
+                format!("old-{}", id)
+            };
+            assert_eq!(token.access_token, expected_access);
+            assert!(token.timestamp > now + 300);
+            assert_eq!(token.subscription_tier.as_deref(), Some("PRO"));
+            assert_eq!(token.model_quotas.get("gemini-3-flash"), Some(&55));
+            assert!(token.last_refreshed.is_some());
+        }
+
+        let _ = std::fs::remove_dir_all(&tmp_root);
+    }
+
+    #[tokio::test]
+    async fn test_cold_placeholder_account_becomes_usable_after_refresh_succeeds() {
+        use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+
+        let tmp_root = std::env::temp_dir().join(format!(
+            "antigravity-token-manager-test-cold-{}",
+            uuid::Uuid::new_v4()
+        ));
+        let accounts_dir = tmp_root.join("accounts");
+        std::fs::create_dir_all(&accounts_dir).unwrap();
+
+        let now = chrono::Utc::now().timestamp();
+        // 导入时刷新失败：占位 access_token、无配额数据
+        let json = serde_json::json!({
+            "id": "cold",
+            "email": "cold@test.com",
+            "token": {
+                "access_token": crate::modules::migration::IMPORTED_ACCESS_TOKEN_PLACEHOLDER,
+                "refresh_token": "rt-cold",
+                "expires_in": 0,
+                "expiry_timestamp": now - 60
+            },
+            "created_at": now,
+            "last_used": now
+        });
+        std::fs::write(
+            accounts_dir.join("cold.json"),
+            serde_json::to_string_pretty(&json).unwrap(),
+        )
+        .unwrap();
+
+        let manager = TokenManager::new(tmp_root.clone());
+        manager.load_accounts().await.unwrap();
+        assert!(manager.get_token_by_id("cold").unwrap().is_cold());
+        assert_eq!(manager.select_iter("gemini-3-flash").count(), 0);
+
+        let network_up = Arc::new(AtomicBool::new(false));
+        let refresh = |_refresh_token: String, _account_id: String| {
+            let network_up = network_up.clone();
+            async move {
+                if !network_up.load(AtomicOrdering::SeqCst) {
+                    return Err("network unreachable".to_string());
+                }
+                Ok(serde_json::from_value::<crate::modules::oauth::TokenResponse>(
+                    serde_json::json!({ "access_token": "live-token", "expires_in": 3600 }),
+                )
+                .unwrap())
+            }
+        };
+        let fetch_quota = |_access_token: String, _email: String, _account_id: String| async {
+            let quota: crate::models::QuotaData = serde_json::from_value(serde_json::json!({
+                "models": [{ "name": "gemini-3-flash", "percentage": 80, "reset_time": "" }],
+                "last_updated": 0,
+                "subscription_tier": "PRO"
+            }))
+            .unwrap();
+            Ok::<_, String>((quota, None))
+        };
+
+        // 网络不可用：仍为冷账号
+        assert!(manager
+            .warm_cold_token_with("gemini-3-flash", now, refresh, fetch_quota)
+            .await
+            .is_none());
+        assert!(manager.get_token_by_id("cold").unwrap().is_cold());
+
+        // 网络恢复，但仍在冷却期内：不重试
+        network_up.store(true, AtomicOrdering::SeqCst);
+        assert!(manager
+            .warm_cold_token_with("gemini-3-flash", now + 10, refresh, fetch_quota)
+            .await
+            .is_none());
+
+        // 冷却期后刷新成功：占位 token 升级为真实 token，账号可被选中
+        let warmed = manager
+            .warm_cold_token_with(
+                "gemini-3-flash",
+                now + COLD_REFRESH_COOLDOWN_SECS,
+                refresh,
+                fetch_quota,
+            )
+            .await
+            .expect("cold account should be upgraded");
+        assert_eq!(warmed.access_token, "live-token");
+        assert!(!warmed.is_cold());
+        assert_eq!(warmed.model_quotas.get("gemini-3-flash"), Some(&80));
+        assert_eq!(
+            manager.select_iter("gemini-3-flash").map(|t| t.account_id).collect::<Vec<_>>(),
+            vec!["cold".to_string()]
+        );
+
+        let _ = std::fs::remove_dir_all(&tmp_root);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_reserve_token_never_overcommits_quota() {
+        let manager = Arc::new(TokenManager::new(std::env::temp_dir()));
+        let model = "gemini-3-flash";
+        let target = crate::proxy::selection::normalize_target(model);
+
+        let initial: HashMap<&str, i32> = [("acc1", 5), ("acc2", 3), ("acc3", 2)].into_iter().collect();
+        for (id, quota) in &initial {
+            let mut token = create_test_token(&format!("{}@test.com", id), Some("PRO"), 1.0, None, Some(*quota));
+            token.account_id = id.to_string();
+            token.model_quotas.insert(target.clone(), *quota);
+            manager.tokens.insert(id.to_string(), token);
+        }
+
+        let committed: Arc<DashMap<String, i32>> = Arc::new(DashMap::new());
+        let mut handles = Vec::new();
+        for i in 0..64 {
+            let manager = manager.clone();
+            let committed = committed.clone();
+            handles.push(tokio::spawn(async move {
+                let Some(reservation) = manager.reserve_token(model) else {
+                    return false;
+                };
+                let account_id = reservation.token().account_id.clone();
+                tokio::task::yield_now().await;
+                if i % 7 == 0 {
+                    // 部分请求放弃预留，名额应当归还
+                    reservation.release();
+                    return false;
+                }
+                reservation.commit();
+                *committed.entry(account_id).or_insert(0) += 1;
+                true
+            }));
+        }
+
+        let mut successes = 0;
+        for handle in handles {
+            if handle.await.unwrap() {
+                successes += 1;
+            }
+        }
+
+        assert!(successes <= 10, "total quota units is 10, got {}", successes);
+        for (id, quota) in &initial {
+            let used = committed.get(*id).map(|v| *v).unwrap_or(0);
+            assert!(used <= *quota, "{} overcommitted: {} > {}", id, used, quota);
+            let remaining = manager.tokens.get(*id).unwrap().model_quotas[&target];
+            assert_eq!(remaining, quota - used);
+        }
+        // 被放弃的名额已归还，可继续预留直至配额耗尽
+        let mut drained = 0;
+        while let Some(reservation) = manager.reserve_token(model) {
+            reservation.commit();
+            drained += 1;
+        }
+        assert_eq!(successes + drained, 10);
+        assert_eq!(manager.reservations.in_flight("acc1", &target), 0);
+    }
+
+    #[test]
+    fn test_reservation_reconciles_actual_usage_on_completion() {
+        let manager = TokenManager::new(std::env::temp_dir());
+        let model = "gemini-3-flash";
+        let target = crate::proxy::selection::normalize_target(model);
+        let mut token = create_test_token("usage@test.com", Some("PRO"), 1.0, None, Some(50));
+        token.model_quotas.insert(target.clone(), 50);
+        let account_id = token.account_id.clone();
+        manager.tokens.insert(account_id.clone(), token);
+
+        // 高估：请求失败未产生用量，预留的估算全部退回
+        let reservation = manager.reserve_token(model).unwrap();
+        let estimate = reservation.cost_units();
+        assert_eq!(manager.reservations.in_flight(&account_id, &target), estimate as u32);
+        assert_eq!(reservation.reconcile(Some(0)), Some(50));
+        assert_eq!(manager.reservations.in_flight(&account_id, &target), 0);
+
+        // 低估：实际消耗超出估算，额外扣减差额
+        let reservation = manager.reserve_token(model).unwrap();
+        assert_eq!(reservation.reconcile(Some(estimate + 2)), Some(50 - estimate - 2));
+        assert_eq!(manager.tokens.get(&account_id).unwrap().model_quotas[&target], 50 - estimate - 2);
+        assert_eq!(manager.reservations.in_flight(&account_id, &target), 0);
+
+        // 未报告用量时按估算扣减
+        let reservation = manager.reserve_token(model).unwrap();
+        assert_eq!(reservation.reconcile(None), Some(50 - 2 * estimate - 2));
+
+        // 报告的 token 用量按换算比例转为配额百分点 (向上取整)，未配置比例时无法换算
+        let config = crate::proxy::selection::SelectionConfig {
+            tokens_per_quota_point: 1000,
+            ..Default::default()
+        };
+        assert_eq!(config.usage_points(2500), Some(3));
+        assert_eq!(config.usage_points(0), Some(0));
+        assert_eq!(crate::proxy::selection::SelectionConfig::default().usage_points(2500), None);
+    }
+
+    #[test]
+    fn test_consume_quota_uses_model_cost() {
+        let manager = TokenManager::new(std::env::temp_dir());
+        let mut config = crate::proxy::selection::SelectionConfig::default();
+        config.model_cost.insert("opus".to_string(), 5.0);
+        config.model_cost.insert("haiku".to_string(), 0.5);
+
+        // Opus / Haiku 归一化后共享同一个 claude 配额组
+        let group = config.normalize_target("claude-opus-4-6");
+        let mut token = create_test_token("cost@test.com", Some("ULTRA"), 1.0, None, Some(100));
+        token.model_quotas.insert(group.clone(), 100);
+        manager.tokens.insert(token.account_id.clone(), token);
+
+        let after_opus = manager
+            .consume_quota_with("cost@test.com", "claude-opus-4-6", &config)
+            .unwrap();
+        let after_haiku = manager
+            .consume_quota_with("cost@test.com", "claude-haiku-4-5", &config)
+            .unwrap();
+
+        let opus_spent = 100 - after_opus;
+        let haiku_spent = after_opus - after_haiku;
+        assert_eq!(opus_spent, 5);
+        assert_eq!(haiku_spent, 1);
+        assert!(opus_spent > haiku_spent);
+        assert_eq!(config.projected_requests(after_haiku, "claude-opus-4-6"), 18);
+
+        // 未配置成本的模型默认消耗 1.0
+        assert_eq!(config.model_cost("gemini-3-flash"), 1.0);
+        assert!(manager.consume_quota_with("missing@test.com", "claude-opus-4-6", &config).is_none());
+    }
+
+    #[test]
+    fn test_concurrent_pool_readers_and_writers_stay_consistent() {
+        const READERS: usize = 4;
+        const WRITERS: usize = 4;
+        const WRITES_PER_WRITER: i32 = 50;
+
+        let manager = Arc::new(TokenManager::new(std::env::temp_dir()));
+        let config = Arc::new(crate::proxy::selection::SelectionConfig::default());
+        let target = config.normalize_target("gemini-3-flash");
+        let mut shared = create_test_token("shared@test.com", Some("PRO"), 1.0, None, Some(100));
+        shared.model_quotas.insert(target.clone(), 1_000);
+        manager.upsert_token(shared);
+
+        let (done_tx, done_rx) = std::sync::mpsc::channel();
+        for _ in 0..READERS {
+            let (manager, target, done_tx) = (manager.clone(), target.clone(), done_tx.clone());
+            std::thread::spawn(move || {
+                for _ in 0..200 {
+                    let snapshot = manager.snapshot();
+                    assert!(snapshot.iter().any(|t| t.account_id == "shared@test.com"));
+                    let _ = manager.select_iter(&target).count();
+                    let _ = manager.model_capacity(&target);
+                }
+                done_tx.send(()).unwrap();
+            });
+        }
+        for writer in 0..WRITERS {
+            let (manager, config, target, done_tx) =
+                (manager.clone(), config.clone(), target.clone(), done_tx.clone());
+            std::thread::spawn(move || {
+                for i in 0..WRITES_PER_WRITER {
+                    manager.consume_quota_with("shared@test.com", "gemini-3-flash", &config);
+                    let email = format!("writer{}-{}@test.com", writer, i % 5);
+                    let mut token = create_test_token(&email, Some("FREE"), 1.0, None, Some(50));
+                    token.model_quotas.insert(target.clone(), 50);
+                    manager.upsert_token(token);
+                    manager.apply_validation_block(&email, 0);
+                }
+                done_tx.send(()).unwrap();
+            });
+        }
+        drop(done_tx);
+
+        // 任一线程死锁 (或 panic) 都会在超时内暴露
+        for _ in 0..READERS + WRITERS {
+            done_rx
+                .recv_timeout(std::time::Duration::from_secs(30))
+                .expect("pool access deadlocked or a worker panicked");
+        }
+
+        // 所有扣减都生效且没有丢失更新
+        let shared = manager.get_token_by_id("shared@test.com").unwrap();
+        assert_eq!(shared.model_quotas[&target], 1_000 - (WRITERS as i32 * WRITES_PER_WRITER));
+        assert_eq!(manager.len(), 1 + WRITERS * 5);
+        assert!(manager
+            .snapshot()
+            .iter()
+            .filter(|t| t.account_id != "shared@test.com")
+            .all(|t| t.validation_blocked));
+    }
+
     #[tokio::test]
     async fn test_fixed_account_mode_skips_preferred_when_disabled_on_disk_without_reload() {
         let tmp_root = std::env::temp_dir().join(format!(
@@ -2916,6 +5113,74 @@ mod tests {
         let _ = std::fs::remove_dir_all(&tmp_root);
     }
 
+    #[tokio::test]
+    async fn test_get_token_reserves_selected_account_for_request() {
+        let tmp_root = std::env::temp_dir().join(format!(
+            "antigravity-token-manager-test-request-reservation-{}",
+            uuid::Uuid::new_v4()
+        ));
+        let accounts_dir = tmp_root.join("accounts");
+        std::fs::create_dir_all(&accounts_dir).unwrap();
+
+        let now = chrono::Utc::now().timestamp();
+        let json = serde_json::json!({
+            "id": "acc1",
+            "email": "a@test.com",
+            "token": {
+                "access_token": "atk-acc1",
+                "refresh_token": "rtk-acc1",
+                "expires_in": 3600,
+                "expiry_timestamp": now + 3600,
+                "project_id": "pid-acc1"
+            },
+            "quota": {
+                "models": [
+                    { "name": "gemini-1.5-flash", "percentage": 50 }
+                ]
+            },
+            "disabled": false,
+            "proxy_disabled": false,
+            "created_at": now,
+            "last_used": now
+        });
+        std::fs::write(
+            accounts_dir.join("acc1.json"),
+            serde_json::to_string_pretty(&json).unwrap(),
+        )
+        .unwrap();
+
+        let manager = TokenManager::new(tmp_root.clone());
+        manager.load_accounts().await.unwrap();
+        let model = "gemini-1.5-flash";
+        let target = crate::proxy::selection::normalize_target(model);
+        let cost = crate::proxy::selection::get_selection_config().cost_units(model);
+
+        // 请求作用域外调用时不留下预留
+        manager.get_token("gemini", false, None, model).await.unwrap();
+        assert_eq!(manager.reservations.in_flight("acc1", &target), 0);
+
+        // 请求失败：预留被退回，不扣减配额
+        let slot = crate::proxy::reservation::RequestReservationSlot::default();
+        crate::proxy::reservation::scope_request(slot.clone(), manager.get_token("gemini", false, None, model))
+            .await
+            .unwrap();
+        assert_eq!(manager.reservations.in_flight("acc1", &target), cost as u32);
+        let reservation = crate::proxy::reservation::take_request_reservation(&slot).unwrap();
+        assert_eq!(reservation.reconcile(Some(0)), Some(50));
+        assert_eq!(manager.reservations.in_flight("acc1", &target), 0);
+
+        // 请求成功：按模型成本扣减
+        let slot = crate::proxy::reservation::RequestReservationSlot::default();
+        crate::proxy::reservation::scope_request(slot.clone(), manager.get_token("gemini", false, None, model))
+            .await
+            .unwrap();
+        crate::proxy::reservation::take_request_reservation(&slot).unwrap().commit();
+        assert_eq!(manager.tokens.get("acc1").unwrap().model_quotas[&target], 50 - cost);
+        assert_eq!(manager.reservations.in_flight("acc1", &target), 0);
+
+        let _ = std::fs::remove_dir_all(&tmp_root);
+    }
+
     /// 创建测试用的 ProxyToken
     fn create_test_token(
         email: &str,
@@ -2924,26 +5189,121 @@ mod tests {
         reset_time: Option<i64>,
         remaining_quota: Option<i32>,
     ) -> ProxyToken {
-        ProxyToken {
-            account_id: email.to_string(),
-            access_token: "test_token".to_string(),
-            refresh_token: "test_refresh".to_string(),
-            expires_in: 3600,
-            timestamp: chrono::Utc::now().timestamp() + 3600,
-            email: email.to_string(),
-            account_path: PathBuf::from("/tmp/test"),
-            project_id: None,
-            subscription_tier: tier.map(|s| s.to_string()),
-            remaining_quota,
-            protected_models: HashSet::new(),
-            health_score,
-            reset_time,
-            validation_blocked: false,
-            validation_blocked_until: 0,
-            validation_url: None,
-            model_quotas: HashMap::new(),
-            model_limits: HashMap::new(),
-        }
+        ProxyToken::builder(email, "test_token", "test_refresh", email)
+            .expires(3600, chrono::Utc::now().timestamp() + 3600)
+            .account_path("/tmp/test")
+            .subscription_tier(tier.map(|s| s.to_string()))
+            .remaining_quota(remaining_quota)
+            .health_score(health_score)
+            .reset_time(reset_time)
+            .build()
+    }
+
+    #[test]
+    fn test_proxy_token_builder_defaults() {
+        let token = ProxyToken::builder("acc1", "atk", "rtk", "a@test.com").build();
+        assert_eq!(token.account_id, "acc1");
+        assert_eq!(token.access_token, "atk");
+        assert_eq!(token.refresh_token, "rtk");
+        assert_eq!(token.email, "a@test.com");
+        assert_eq!((token.expires_in, token.timestamp), (0, 0));
+        assert_eq!(token.account_path, PathBuf::new());
+        assert!(token.project_id.is_none());
+        assert!(token.subscription_tier.is_none() && token.tier_override.is_none());
+        assert!(token.remaining_quota.is_none() && token.reset_time.is_none());
+        assert!(token.protected_models.is_empty());
+        assert_eq!(token.health_score, 1.0);
+        assert!(!token.validation_blocked);
+        assert_eq!(token.validation_blocked_until, 0);
+        assert!(token.validation_url.is_none());
+        assert!(token.model_quotas.is_empty() && token.model_limits.is_empty());
+        assert!(token.quota_max.is_empty());
+        assert!(token.last_refreshed.is_none());
+        assert!(token.enabled);
+
+        let token = ProxyToken::builder("acc2", "atk", "rtk", "b@test.com")
+            .subscription_tier(Some("ULTRA".to_string()))
+            .model_quota("claude", 40)
+            .protected_models(["gemini-3-flash"])
+            .validation_blocked(1_000, None)
+            .enabled(false)
+            .build();
+        assert_eq!(token.subscription_tier.as_deref(), Some("ULTRA"));
+        assert_eq!(token.model_quotas.get("claude"), Some(&40));
+        assert!(token.protected_models.contains("gemini-3-flash"));
+        assert!(token.validation_blocked && token.is_blocked(999));
+        assert!(!token.enabled);
+    }
+
+    #[test]
+    fn test_quota_percentage_with_known_max() {
+        let token = ProxyToken::builder("acc1", "atk", "rtk", "a@test.com")
+            .model_quota("claude", 30)
+            .quota_max("claude", 120)
+            .model_quota("gemini-3-flash", 150)
+            .quota_max("gemini-3-flash", 100)
+            .build();
+
+        assert_eq!(token.quota_percentage("claude"), Some(25.0));
+        // 原始模型名按标准化 ID 查找
+        assert_eq!(token.quota_percentage("claude-sonnet-4-5"), Some(25.0));
+        // 超出满额时截断到 100
+        assert_eq!(token.quota_percentage("gemini-3-flash"), Some(100.0));
+    }
+
+    #[test]
+    fn test_quota_percentage_with_unknown_max() {
+        let token = ProxyToken::builder("acc1", "atk", "rtk", "a@test.com")
+            .model_quota("claude", 30)
+            .model_quota("gemini-3-flash", 50)
+            .quota_max("gemini-3-flash", 0)
+            .build();
+
+        assert_eq!(token.quota_percentage("claude"), None);
+        assert_eq!(token.quota_percentage("gemini-3-flash"), None);
+        assert_eq!(token.quota_percentage("unknown-model"), None);
+    }
+
+    #[test]
+    fn test_clock_skew_offset_shifts_expiry_and_reset_decisions() {
+        use crate::utils::clock;
+
+        let local_now = 1_700_000_000;
+        // 上游 Date 头比本机快 1 小时 (本机时钟落后)
+        let server_date = chrono::DateTime::from_timestamp(local_now + 3600, 0)
+            .unwrap()
+            .to_rfc2822();
+        let offset = clock::skew_from_date(&server_date, local_now).unwrap();
+        assert_eq!(offset, 3600);
+        assert!(clock::skew_from_date("not a date", local_now).is_none());
+
+        let token = ProxyToken::builder("skew", "atk", "rtk", "skew@test.com")
+            .expires(3600, local_now + 600)
+            .remaining_quota(Some(0))
+            .reset_time(Some(local_now + 1800))
+            .build();
+
+        // 未校正：token 仍有效，配额尚未刷新
+        let uncorrected = clock::adjusted(local_now, 0);
+        assert!(!token.is_expiring(uncorrected, TOKEN_REFRESH_MARGIN_SECS));
+        assert_eq!(token.unusable_until_with(uncorrected, 0), Some(local_now + 1800));
+
+        // 按服务端时间校正后：token 实际已过期，reset_time 也已过去
+        let corrected = clock::adjusted(local_now, offset);
+        assert!(token.is_expiring(corrected, TOKEN_REFRESH_MARGIN_SECS));
+        assert_eq!(token.unusable_until_with(corrected, 0), None);
+
+        // 本机时钟超前时反向校正：临近过期的 token 不会被过早判定为过期
+        let early = ProxyToken::builder("early", "atk", "rtk", "early@test.com")
+            .expires(3600, local_now - 30)
+            .build();
+        assert!(early.is_expiring(clock::adjusted(local_now, 0), TOKEN_REFRESH_MARGIN_SECS));
+        assert!(!early.is_expiring(clock::adjusted(local_now, -600), TOKEN_REFRESH_MARGIN_SECS));
+
+        // 限流记录使用的 SystemTime 按同一偏差校正 (与服务端 reset 时间比较)
+        let at = |secs: i64| std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs as u64);
+        assert_eq!(clock::adjusted_system(at(local_now), offset), at(corrected));
+        assert_eq!(clock::adjusted_system(at(local_now), -600), at(local_now - 600));
     }
 
     /// 测试排序比较函数（与 get_token_internal 中的逻辑一致）
@@ -3182,26 +5542,44 @@ mod tests {
         remaining_quota: Option<i32>,
         protected_models: HashSet<String>,
     ) -> ProxyToken {
-        ProxyToken {
-            account_id: email.to_string(),
-            access_token: "test_token".to_string(),
-            refresh_token: "test_refresh".to_string(),
-            expires_in: 3600,
-            timestamp: chrono::Utc::now().timestamp() + 3600,
-            email: email.to_string(),
-            account_path: PathBuf::from("/tmp/test"),
-            project_id: None,
-            subscription_tier: Some("PRO".to_string()),
-            remaining_quota,
-            protected_models,
-            health_score: 1.0,
-            reset_time: None,
-            validation_blocked: false,
-            validation_blocked_until: 0,
-            validation_url: None,
-            model_quotas: HashMap::new(),
-            model_limits: HashMap::new(),
-        }
+        ProxyToken::builder(email, "test_token", "test_refresh", email)
+            .expires(3600, chrono::Utc::now().timestamp() + 3600)
+            .account_path("/tmp/test")
+            .subscription_tier(Some("PRO".to_string()))
+            .remaining_quota(remaining_quota)
+            .protected_models(protected_models)
+            .build()
+    }
+
+    #[test]
+    fn test_pinned_choice_skips_attempted_and_protected() {
+        let pinned = create_test_token("pinned@test.com", Some("FREE"), 1.0, None, Some(10));
+        let other = create_test_token("other@test.com", Some("ULTRA"), 1.0, None, Some(90));
+        let candidates = vec![pinned.clone(), other];
+        let mut attempted: HashSet<String> = HashSet::new();
+
+        // 固定账号可用时无视配额直接选中 (P2C 会选配额更高的账号)
+        let chosen = TokenManager::pinned_choice(
+            &candidates, Some("pinned@test.com"), &attempted, "claude-sonnet", false,
+        );
+        assert_eq!(chosen.unwrap().email, "pinned@test.com");
+        assert!(TokenManager::pinned_choice(&candidates, None, &attempted, "claude-sonnet", false).is_none());
+
+        // 已被配额保护或本次请求已尝试失败时回退
+        let protected = vec![create_test_token_with_protected(
+            "pinned@test.com",
+            Some(10),
+            HashSet::from(["claude-sonnet".to_string()]),
+        )];
+        assert!(TokenManager::pinned_choice(
+            &protected, Some("pinned@test.com"), &attempted, "claude-sonnet", true,
+        )
+        .is_none());
+        attempted.insert(pinned.account_id.clone());
+        assert!(TokenManager::pinned_choice(
+            &candidates, Some("pinned@test.com"), &attempted, "claude-sonnet", false,
+        )
+        .is_none());
     }
 
     #[test]
@@ -3547,4 +5925,499 @@ mod tests {
             "Sonnet should sort by quota first, then by tier as tiebreaker"
         );
     }
+
+    #[tokio::test]
+    async fn test_evict_exhausted_only_prunes_unusable_tokens() {
+        let tmp_root = std::env::temp_dir().join(format!(
+            "antigravity-evict-test-{}",
+            uuid::Uuid::new_v4()
+        ));
+        let accounts_dir = tmp_root.join("accounts");
+        std::fs::create_dir_all(&accounts_dir).unwrap();
+        let manager = TokenManager::new(tmp_root.clone());
+        let now = 1_700_000_000;
+
+        let write_account = |id: &str, disabled: bool| {
+            let path = accounts_dir.join(format!("{}.json", id));
+            let json = serde_json::json!({
+                "id": id,
+                "email": id,
+                "token": {
+                    "access_token": "atk",
+                    "refresh_token": "rtk",
+                    "expires_in": 3600,
+                    "expiry_timestamp": now + 3600
+                },
+                "disabled": disabled,
+                "created_at": now,
+                "last_used": now
+            });
+            std::fs::write(&path, json.to_string()).unwrap();
+            path
+        };
+        let token = |id: &str, reset_time: Option<i64>, quota: i32| {
+            let mut token = create_test_token(id, Some("PRO"), 1.0, reset_time, Some(quota));
+            token.account_path = write_account(id, false);
+            token
+        };
+
+        let healthy = token("healthy@test.com", Some(now + 3600), 60);
+        // 配额耗尽且未到刷新时间：剔除
+        let exhausted = token("exhausted@test.com", Some(now + 3600), 0);
+        // 配额为 0 但刷新时间已到：等待配额刷新，不剔除
+        let due = token("due@test.com", Some(now - 60), 0);
+        // 验证封禁是短期状态：留在池中
+        let mut blocked = token("blocked@test.com", None, 80);
+        blocked.validation_blocked = true;
+        blocked.validation_blocked_until = now + 600;
+        // 磁盘上已因 invalid_grant 被禁用，内存中仍是旧状态
+        let revoked = token("revoked@test.com", None, 90);
+        write_account("revoked@test.com", true);
+
+        for token in [healthy, exhausted, due, blocked, revoked] {
+            manager.tokens.insert(token.account_id.clone(), token);
+        }
+        manager
+            .session_accounts
+            .insert("sid".to_string(), "exhausted@test.com".to_string());
+
+        let mut evicted = manager.evict_exhausted(now).await;
+        evicted.sort();
+        assert_eq!(evicted, vec!["exhausted@test.com", "revoked@test.com"]);
+        assert_eq!(manager.len(), 3);
+        assert!(manager.get_token_by_id("due@test.com").is_some());
+        assert!(manager.get_token_by_id("blocked@test.com").is_some());
+        assert!(manager.session_accounts.get("sid").is_none());
+
+        // 再次调用不会重复剔除；未到刷新时间且仍处于禁用状态时不恢复
+        assert!(manager.evict_exhausted(now).await.is_empty());
+        assert!(manager.restore_evicted(now).await.is_empty());
+
+        // 刷新时间到达后，配额耗尽的账号回到池中；吊销的账号仍留在非活跃集合
+        let after_reset = now + 3600;
+        assert_eq!(manager.restore_evicted(after_reset).await, vec!["exhausted@test.com"]);
+        assert!(manager.get_token_by_id("exhausted@test.com").is_some());
+        assert!(manager.evict_exhausted(after_reset).await.is_empty());
+
+        // 磁盘上重新启用后从文件重新加载
+        write_account("revoked@test.com", false);
+        assert_eq!(manager.restore_evicted(after_reset).await, vec!["revoked@test.com"]);
+        assert!(manager.get_token_by_id("revoked@test.com").is_some());
+        assert!(manager.evicted.is_empty());
+        assert_eq!(manager.len(), 5);
+
+        let _ = std::fs::remove_dir_all(&tmp_root);
+    }
+
+    #[test]
+    fn test_health_class_uses_configured_thresholds() {
+        let now = 1_700_000_000;
+        let config = crate::proxy::selection::SelectionConfig::default();
+
+        let classify = |score: f32| {
+            create_test_token("h@test.com", Some("PRO"), score, None, Some(50))
+                .health_class_with(now, &config)
+        };
+        assert_eq!(classify(1.0), HealthClass::Healthy);
+        assert_eq!(classify(0.8), HealthClass::Healthy);
+        assert_eq!(classify(0.6), HealthClass::Degraded);
+        assert_eq!(classify(0.5), HealthClass::Degraded);
+        assert_eq!(classify(0.2), HealthClass::Unhealthy);
+
+        let strict = crate::proxy::selection::SelectionConfig {
+            healthy_threshold: 0.95,
+            degraded_threshold: 0.7,
+            ..Default::default()
+        };
+        let token = create_test_token("h@test.com", Some("PRO"), 0.9, None, Some(50));
+        assert_eq!(token.health_class_with(now, &strict), HealthClass::Degraded);
+    }
+
+    #[test]
+    fn test_blocked_overrides_health_score() {
+        let now = 1_700_000_000;
+        let config = crate::proxy::selection::SelectionConfig::default();
+
+        let mut token = create_test_token("b@test.com", Some("PRO"), 1.0, None, Some(50));
+        token.validation_blocked = true;
+        token.validation_blocked_until = now + 600;
+        assert!(token.is_blocked(now));
+        assert_eq!(token.health_class_with(now, &config), HealthClass::Blocked);
+
+        // 封禁到期后恢复按健康分分级
+        assert_eq!(token.health_class_with(now + 600, &config), HealthClass::Healthy);
+    }
+
+    #[test]
+    fn test_reset_grace_keeps_exhausted_account_near_reset() {
+        let now = 1_700_000_000;
+        let grace = 60;
+        let exhausted_with_reset = |reset: i64| {
+            create_test_token("ultra@test.com", Some("ULTRA"), 1.0, Some(reset), Some(0))
+        };
+
+        // 窗口内 (含边界)：仍为候选
+        assert_eq!(exhausted_with_reset(now + 59).unusable_until_with(now, grace), None);
+        assert_eq!(exhausted_with_reset(now + 60).unusable_until_with(now, grace), None);
+        // 窗口外：排除至刷新时间
+        assert_eq!(
+            exhausted_with_reset(now + 61).unusable_until_with(now, grace),
+            Some(now + 61)
+        );
+        // 宽限期为 0 时恢复严格排除
+        assert_eq!(
+            exhausted_with_reset(now + 59).unusable_until_with(now, 0),
+            Some(now + 59)
+        );
+        // 还有配额的账号不受影响
+        let healthy = create_test_token("pro@test.com", Some("PRO"), 1.0, Some(now + 3600), Some(40));
+        assert!(!healthy.is_quota_exhausted(now, grace));
+    }
+
+    #[tokio::test]
+    async fn test_get_token_keeps_exhausted_account_within_reset_grace() {
+        let tmp_root = std::env::temp_dir().join(format!(
+            "antigravity-token-manager-test-grace-{}",
+            uuid::Uuid::new_v4()
+        ));
+        std::fs::create_dir_all(tmp_root.join("accounts")).unwrap();
+        let manager = TokenManager::new(tmp_root.clone());
+        let model = "gemini-3-flash";
+        let target = crate::proxy::selection::normalize_target(model);
+        let grace = crate::proxy::selection::get_selection_config().reset_grace_secs;
+        let now = chrono::Utc::now().timestamp();
+
+        let exhausted = |reset: i64| {
+            ProxyToken::builder("ultra@test.com", "atk-ultra", "rtk-ultra", "ultra@test.com")
+                .expires(3600, now + 3600)
+                .account_path(tmp_root.join("accounts").join("ultra@test.com.json"))
+                .project_id(Some("pid-ultra".to_string()))
+                .subscription_tier(Some("ULTRA".to_string()))
+                .remaining_quota(Some(0))
+                .reset_time(Some(reset))
+                .model_quota(target.clone(), 0)
+                .build()
+        };
+
+        // 配额为 0，但刷新时间在宽限窗口内：仍被选中
+        manager.upsert_token(exhausted(now + grace / 2));
+        let (access_token, _, email, _, _) = manager
+            .get_token("gemini", false, None, model)
+            .await
+            .unwrap();
+        assert_eq!((access_token.as_str(), email.as_str()), ("atk-ultra", "ultra@test.com"));
+
+        // 刷新时间在窗口之外：排除
+        manager.upsert_token(exhausted(now + grace + 600));
+        let err = manager.get_token("gemini", false, None, model).await.unwrap_err();
+        assert!(err.contains("No accounts available with quota"), "{}", err);
+
+        let _ = std::fs::remove_dir_all(&tmp_root);
+    }
+
+    #[tokio::test]
+    async fn test_get_token_respects_request_account_group() {
+        let tmp_root = std::env::temp_dir().join(format!(
+            "antigravity-token-manager-test-group-{}",
+            uuid::Uuid::new_v4()
+        ));
+        std::fs::create_dir_all(tmp_root.join("accounts")).unwrap();
+        let manager = TokenManager::new(tmp_root.clone());
+        let model = "gemini-3-flash";
+        let target = crate::proxy::selection::normalize_target(model);
+        let now = chrono::Utc::now().timestamp();
+
+        let token = |name: &str, tier: &str| {
+            let email = format!("{}@test.com", name);
+            ProxyToken::builder(name, format!("atk-{}", name), format!("rtk-{}", name), email.clone())
+                .expires(3600, now + 3600)
+                .account_path(tmp_root.join("accounts").join(format!("{}.json", name)))
+                .project_id(Some(format!("pid-{}", name)))
+                .subscription_tier(Some(tier.to_string()))
+                .model_quota(target.clone(), 80)
+                .build()
+        };
+        manager.upsert_token(token("ultra", "ULTRA"));
+        manager.upsert_token(token("pro", "PRO"));
+
+        // 分组名唯一，避免影响共享全局选择配置的其他测试
+        let group = format!("group-{}", uuid::Uuid::new_v4());
+        crate::proxy::selection::modify_selection_config(|config| {
+            config.groups.insert(group.clone(), ["Pro@Test.com".to_string()].into_iter().collect());
+        });
+        // 整体替换选择配置 (如导入) 不会清除来自反代配置的分组
+        crate::proxy::selection::update_selection_config(crate::proxy::selection::SelectionConfig {
+            groups: HashMap::new(),
+            ..crate::proxy::selection::get_selection_config()
+        });
+
+        // 限定分组后多次选号都只落在组内账号
+        for _ in 0..5 {
+            let scoped = crate::proxy::selection::scope_account_group(
+                Some(group.clone()),
+                manager.get_token("gemini", false, None, model),
+            );
+            let (_, _, email, _, _) = scoped.await.unwrap();
+            assert_eq!(email, "pro@test.com");
+        }
+
+        // 作用域外不限定分组
+        assert!(crate::proxy::selection::current_account_group().is_none());
+        manager.get_token("gemini", false, None, model).await.unwrap();
+
+        // 未知分组直接报错，不回退到全池
+        let err = crate::proxy::selection::scope_account_group(
+            Some("no-such-group".to_string()),
+            manager.get_token("gemini", false, None, model),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.contains("Unknown account group"), "{}", err);
+
+        crate::proxy::selection::modify_selection_config(|config| config.groups.remove(&group));
+        let _ = std::fs::remove_dir_all(&tmp_root);
+    }
+
+    #[test]
+    fn test_token_view_serializes_without_secrets() {
+        let mut token = create_test_token("view@test.com", Some("PRO"), 0.6, None, Some(42));
+        token.access_token = "ya29.secret-access".to_string();
+        token.refresh_token = "1//secret-refresh".to_string();
+        token.model_quotas.insert("gemini-3-flash".to_string(), 42);
+
+        let view = token.view();
+        assert_eq!(view.email, "view@test.com");
+        assert_eq!(view.subscription_tier.as_deref(), Some("PRO"));
+        assert_eq!(view.remaining_quota, Some(42));
+        assert_eq!(view.validation_blocked_until, None);
+
+        let json = serde_json::to_value(&view).unwrap();
+        assert_eq!(json["model_quotas"]["gemini-3-flash"], 42);
+        assert!(json.get("access_token").is_none());
+        assert!(json.get("refresh_token").is_none());
+
+        let text = serde_json::to_string(&view).unwrap();
+        assert!(!text.contains("secret-access"));
+        assert!(!text.contains("secret-refresh"));
+    }
+
+    #[test]
+    fn test_sticky_ttl_differs_per_model() {
+        let manager = TokenManager::new(std::env::temp_dir().join("antigravity-sticky-ttl-test"));
+        let config = StickySessionConfig {
+            sticky_ttl_seconds: 600,
+            model_sticky_ttl_seconds: HashMap::from([
+                ("opus".to_string(), 3600),
+                ("haiku".to_string(), 60),
+            ]),
+            ..Default::default()
+        };
+
+        let t0 = 1_700_000_000;
+        manager.bind_session("opus-chat", "acc1", t0);
+        manager.bind_session("haiku-call", "acc2", t0);
+        manager.bind_session("flash-chat", "acc3", t0);
+
+        // 59s：全部仍然有效
+        assert_eq!(
+            manager.select_for_session("haiku-call", "claude-haiku-4-5", &config, t0 + 59),
+            Some("acc2".to_string())
+        );
+
+        // 2 分钟后 (距上次命中 61s)：Haiku 过期，Opus 与默认 TTL 的模型仍然粘性
+        let t1 = t0 + 120;
+        assert_eq!(
+            manager.select_for_session("haiku-call", "claude-haiku-4-5", &config, t1),
+            None
+        );
+        assert!(manager.session_accounts.get("haiku-call").is_none());
+        assert_eq!(
+            manager.select_for_session("opus-chat", "claude-opus-4-6", &config, t1),
+            Some("acc1".to_string())
+        );
+        assert_eq!(
+            manager.select_for_session("flash-chat", "gemini-3-flash", &config, t1),
+            Some("acc3".to_string())
+        );
+
+        // 默认 TTL (600s) 到期，Opus 仍有效；续期后从新的命中时间起算
+        assert_eq!(
+            manager.select_for_session("flash-chat", "gemini-3-flash", &config, t0 + 600),
+            None
+        );
+        manager.bind_session("opus-chat", "acc1", t0 + 3000);
+        assert_eq!(
+            manager.select_for_session("opus-chat", "claude-opus-4-6", &config, t0 + 3700),
+            Some("acc1".to_string())
+        );
+        assert_eq!(
+            manager.select_for_session("opus-chat", "claude-opus-4-6", &config, t0 + 3000 + 3600),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_validation_block_escalates_and_resets_on_success() {
+        let tmp_root = std::env::temp_dir().join(format!(
+            "antigravity-token-manager-test-validation-backoff-{}",
+            uuid::Uuid::new_v4()
+        ));
+        let accounts_dir = tmp_root.join("accounts");
+        std::fs::create_dir_all(&accounts_dir).unwrap();
+        let now = chrono::Utc::now().timestamp();
+        let json = serde_json::json!({
+            "id": "acc1",
+            "email": "a@test.com",
+            "token": {
+                "access_token": "atk-acc1",
+                "refresh_token": "rtk-acc1",
+                "expires_in": 3600,
+                "expiry_timestamp": now + 3600,
+                "project_id": "pid-acc1"
+            },
+            "created_at": now,
+            "last_used": now
+        });
+        std::fs::write(
+            accounts_dir.join("acc1.json"),
+            serde_json::to_string_pretty(&json).unwrap(),
+        )
+        .unwrap();
+
+        let manager = TokenManager::new(tmp_root.clone());
+        manager.load_accounts().await.unwrap();
+        let mut config = crate::models::CircuitBreakerConfig::default();
+        config.validation_block_steps = vec![60, 300, 1800];
+        manager.update_circuit_breaker_config(config).await;
+
+        // 返回值为截止时间，允许调用期间跨过一秒
+        let block = |expected: i64| {
+            let manager = &manager;
+            async move {
+                let start = chrono::Utc::now().timestamp();
+                let until = manager
+                    .block_validation("acc1", "VALIDATION_REQUIRED")
+                    .await
+                    .unwrap();
+                assert!(
+                    (expected..=expected + 1).contains(&(until - start)),
+                    "expected {}s block, got {}s",
+                    expected,
+                    until - start
+                );
+            }
+        };
+
+        // 连续封禁逐级延长，超出档位沿用最后一档
+        for expected in [60, 300, 1800, 1800] {
+            block(expected).await;
+        }
+        assert!(manager.tokens.get("acc1").unwrap().validation_blocked);
+
+        // 请求成功 (处理器以 email 调用) 后从第一档重新开始
+        manager.mark_account_success("a@test.com");
+        block(60).await;
+
+        let _ = std::fs::remove_dir_all(&tmp_root);
+    }
+
+    #[test]
+    fn test_validation_block_secs_clamps_to_schedule() {
+        assert_eq!(validation_block_secs(&[60, 300], 1), 60);
+        assert_eq!(validation_block_secs(&[60, 300], 2), 300);
+        assert_eq!(validation_block_secs(&[60, 300], 9), 300);
+        assert_eq!(validation_block_secs(&[], 1), 600);
+    }
+
+    #[tokio::test]
+    async fn test_background_tasks_share_the_maintenance_scheduler() {
+        let manager = Arc::new(TokenManager::new(PathBuf::from("/tmp/test")));
+        manager.start_auto_cleanup().await;
+        manager.start_capability_refresh(3600, std::time::Duration::from_secs(1800), 2);
+        // 重复注册替换同名任务
+        manager.start_capability_refresh(3600, std::time::Duration::from_secs(900), 2);
+
+        assert!(manager.maintenance.is_running());
+        assert_eq!(
+            manager.maintenance.task_names(),
+            vec![AUTO_CLEANUP_TASK, CAPABILITY_FLUSH_TASK, CAPABILITY_REFRESH_TASK]
+        );
+
+        manager.graceful_shutdown(std::time::Duration::from_secs(5)).await;
+        assert!(!manager.maintenance.is_running());
+    }
+
+    #[tokio::test]
+    async fn test_cached_capabilities_seed_pool_before_first_refresh() {
+        let tmp_root = std::env::temp_dir().join(format!(
+            "antigravity-token-manager-test-capabilities-{}",
+            uuid::Uuid::new_v4()
+        ));
+        let accounts_dir = tmp_root.join("accounts");
+        std::fs::create_dir_all(&accounts_dir).unwrap();
+        let now = chrono::Utc::now().timestamp();
+        for id in ["acc1", "acc2"] {
+            // 尚未拉取过配额 (无 quota 字段)
+            let json = serde_json::json!({
+                "id": id,
+                "email": format!("{}@test.com", id),
+                "token": {
+                    "access_token": format!("atk-{}", id),
+                    "refresh_token": format!("rtk-{}", id),
+                    "expires_in": 3600,
+                    "expiry_timestamp": now + 3600
+                },
+                "created_at": now,
+                "last_used": now
+            });
+            std::fs::write(
+                accounts_dir.join(format!("{}.json", id)),
+                serde_json::to_string_pretty(&json).unwrap(),
+            )
+            .unwrap();
+        }
+        let mut cache = CapabilityCache::default();
+        cache.record("acc1", vec!["gemini-3-flash".to_string()], now - 7200);
+        cache.record("deleted", vec!["gemini-3-flash".to_string()], now);
+        cache.save(&tmp_root.join(CAPABILITY_CACHE_FILE)).unwrap();
+
+        let manager = TokenManager::new(tmp_root.clone());
+        manager.load_accounts().await.unwrap();
+
+        // 重启后未刷新前即可按缓存能力选号，但不伪造配额值
+        let config = crate::proxy::selection::SelectionConfig::default();
+        let seeded = manager.get_token_by_id("acc1").unwrap();
+        assert!(seeded.model_quotas.is_empty());
+        assert!(config.has_capability(&seeded, "gemini-3-flash"));
+        assert!(!config.has_capability(&seeded, "claude-sonnet-4-6"));
+        assert!(!config.has_capability(&manager.get_token_by_id("acc2").unwrap(), "gemini-3-flash"));
+
+        // 超过 TTL 或没有记录的账号需要后台刷新
+        let mut stale = manager.stale_capability_accounts(now, 3600);
+        stale.sort();
+        assert_eq!(stale, vec!["acc1", "acc2"]);
+        assert_eq!(manager.stale_capability_accounts(now, 3 * 3600), vec!["acc2"]);
+
+        // 实时配额写回后能力缓存随之更新并持久化
+        let quota: crate::models::QuotaData = serde_json::from_value(serde_json::json!({
+            "models": [{ "name": "claude-sonnet-4-6", "percentage": 60, "reset_time": "" }],
+            "last_updated": now
+        }))
+        .unwrap();
+        manager.apply_quota_state("acc2", &quota, None).unwrap();
+        assert_eq!(manager.stale_capability_accounts(now, 3600), vec!["acc1"]);
+
+        // 记录先留在内存中，由落盘任务批量写回；已删除账号的记录在加载时被清理
+        let cache_path = tmp_root.join(CAPABILITY_CACHE_FILE);
+        assert!(CapabilityCache::load(&cache_path).get("acc2").is_none());
+        manager.flush_capabilities();
+        let reloaded = CapabilityCache::load(&cache_path);
+        assert!(reloaded.get("deleted").is_none());
+        let entry = reloaded.get("acc2").unwrap();
+        assert!(entry.updated_at >= now);
+        assert_eq!(entry.models.len(), 1);
+        assert!(reloaded.get("acc1").is_some());
+
+        let _ = std::fs::remove_dir_all(&tmp_root);
+    }
 }
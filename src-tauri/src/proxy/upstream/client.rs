@@ -4,7 +4,8 @@
 use dashmap::DashMap;
 use rquest::{header, Client, Response, StatusCode};
 use serde_json::Value;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
 use tokio::sync::RwLock;
 use tokio::time::Duration;
 
@@ -27,6 +28,34 @@ pub struct UpstreamCallResult {
     pub fallback_attempts: Vec<FallbackAttemptLog>,
 }
 
+/// 账号级额外请求头 (account_id -> headers)，由 TokenManager 在加载账号时同步
+static ACCOUNT_EXTRA_HEADERS: OnceLock<std::sync::RwLock<HashMap<String, HashMap<String, String>>>> =
+    OnceLock::new();
+
+fn account_headers_registry() -> &'static std::sync::RwLock<HashMap<String, HashMap<String, String>>> {
+    ACCOUNT_EXTRA_HEADERS.get_or_init(|| std::sync::RwLock::new(HashMap::new()))
+}
+
+/// 设置账号的额外请求头 (为空时移除)
+pub fn set_account_extra_headers(account_id: &str, headers: HashMap<String, String>) {
+    if let Ok(mut registry) = account_headers_registry().write() {
+        if headers.is_empty() {
+            registry.remove(account_id);
+        } else {
+            registry.insert(account_id.to_string(), headers);
+        }
+    }
+}
+
+/// 获取账号的额外请求头
+pub fn account_extra_headers(account_id: &str) -> HashMap<String, String> {
+    account_headers_registry()
+        .read()
+        .ok()
+        .and_then(|registry| registry.get(account_id).cloned())
+        .unwrap_or_default()
+}
+
 /// 邮箱脱敏：只显示前3位 + *** + @域名前2位 + ***
 /// 例: "userexample@gmail.com" → "use***@gm***"
 pub fn mask_email(email: &str) -> String {
@@ -269,27 +298,20 @@ impl UpstreamClient {
             access_token,
             body,
             query_string,
-            std::collections::HashMap::new(),
+            HashMap::new(),
             account_id,
         )
         .await
     }
 
-    /// [FIX #765] 调用 v1internal API，支持透传额外的 Headers
-    /// [ENHANCED] 返回 UpstreamCallResult，包含降级尝试记录，用于 debug 日志
-    pub async fn call_v1_internal_with_headers(
-        &self,
-        method: &str,
+    /// 构建上游请求 Headers：默认客户端特征 -> 账号级 extra_headers -> 单次请求的 extra_headers
+    fn build_headers(
         access_token: &str,
-        body: Value,
-        query_string: Option<&str>,
-        extra_headers: std::collections::HashMap<String, String>,
-        account_id: Option<&str>, // [NEW] Account ID
-    ) -> Result<UpstreamCallResult, String> {
-        // [NEW] Get client based on account (cached in proxy pool manager)
-        let client = self.get_client(account_id).await;
-
-        // 构建 Headers (所有端点复用)
+        user_agent: &str,
+        body: &Value,
+        account_headers: &HashMap<String, String>,
+        extra_headers: HashMap<String, String>,
+    ) -> Result<header::HeaderMap, String> {
         let mut headers = header::HeaderMap::new();
         headers.insert(
             header::CONTENT_TYPE,
@@ -303,7 +325,7 @@ impl UpstreamClient {
 
         headers.insert(
             header::USER_AGENT,
-            header::HeaderValue::from_str(&self.get_user_agent().await).unwrap_or_else(|e| {
+            header::HeaderValue::from_str(user_agent).unwrap_or_else(|e| {
                 tracing::warn!("Invalid User-Agent header value, using fallback: {}", e);
                 header::HeaderValue::from_static("antigravity")
             }),
@@ -345,14 +367,53 @@ impl UpstreamClient {
             }
         }
 
-        // 注入额外的 Headers (如 anthropic-beta)
-        for (k, v) in extra_headers {
-            if let Ok(hk) = header::HeaderName::from_bytes(k.as_bytes()) {
-                if let Ok(hv) = header::HeaderValue::from_str(&v) {
-                    headers.insert(hk, hv);
-                }
+        // 账号级请求头覆盖默认值 (鉴权头除外)，单次请求的额外 Headers (如 anthropic-beta) 最后注入
+        for (k, v) in account_headers {
+            if k.eq_ignore_ascii_case("authorization") {
+                tracing::warn!("Ignoring account extra header that would override authorization");
+                continue;
+            }
+            Self::insert_header(&mut headers, k, v);
+        }
+        for (k, v) in &extra_headers {
+            Self::insert_header(&mut headers, k, v);
+        }
+
+        Ok(headers)
+    }
+
+    /// 插入单个 Header，非法的名称或值被忽略
+    fn insert_header(headers: &mut header::HeaderMap, name: &str, value: &str) {
+        if let Ok(hk) = header::HeaderName::from_bytes(name.as_bytes()) {
+            if let Ok(hv) = header::HeaderValue::from_str(value) {
+                headers.insert(hk, hv);
             }
         }
+    }
+
+    /// [FIX #765] 调用 v1internal API，支持透传额外的 Headers
+    /// [ENHANCED] 返回 UpstreamCallResult，包含降级尝试记录，用于 debug 日志
+    pub async fn call_v1_internal_with_headers(
+        &self,
+        method: &str,
+        access_token: &str,
+        body: Value,
+        query_string: Option<&str>,
+        extra_headers: HashMap<String, String>,
+        account_id: Option<&str>, // [NEW] Account ID
+    ) -> Result<UpstreamCallResult, String> {
+        // [NEW] Get client based on account (cached in proxy pool manager)
+        let client = self.get_client(account_id).await;
+
+        // 构建 Headers (所有端点复用)
+        let account_headers = account_id.map(account_extra_headers).unwrap_or_default();
+        let headers = Self::build_headers(
+            access_token,
+            &self.get_user_agent().await,
+            &body,
+            &account_headers,
+            extra_headers,
+        )?;
 
         // [DEBUG] Log headers for verification
         tracing::debug!(?headers, "Final Upstream Request Headers");
@@ -368,6 +429,9 @@ impl UpstreamClient {
 
             let body_bytes = serde_json::to_vec(&body).map_err(|e| e.to_string())?;
 
+            // 全局上游限流 (每个端点尝试都计入)
+            super::throttle::acquire_upstream_permit().await;
+
             let response = client
                 .post(&url)
                 .headers(headers.clone())
@@ -382,6 +446,10 @@ impl UpstreamClient {
             match response {
                 Ok(resp) => {
                     let status = resp.status();
+                    // 从 Date 头学习本机时钟偏差 (过期 / reset_time 判断统一使用校正后的时间)
+                    if let Some(date) = resp.headers().get(header::DATE).and_then(|v| v.to_str().ok()) {
+                        crate::utils::clock::observe_date_header(date);
+                    }
                     if status.is_success() {
                         if idx > 0 {
                             tracing::info!(
@@ -518,4 +586,37 @@ mod tests {
             "https://cloudcode-pa.googleapis.com/v1internal:streamGenerateContent?alt=sse"
         );
     }
+
+    #[test]
+    fn test_build_headers_includes_account_extra_headers() {
+        set_account_extra_headers(
+            "acc-extra-headers",
+            HashMap::from([
+                ("x-request-identity".to_string(), "team-a".to_string()),
+                ("x-client-name".to_string(), "custom-client".to_string()),
+                ("authorization".to_string(), "Bearer hijacked".to_string()),
+                ("anthropic-beta".to_string(), "account-default".to_string()),
+            ]),
+        );
+        let headers = UpstreamClient::build_headers(
+            "token-123",
+            "antigravity/test",
+            &serde_json::json!({}),
+            &account_extra_headers("acc-extra-headers"),
+            HashMap::from([("anthropic-beta".to_string(), "per-request".to_string())]),
+        )
+        .unwrap();
+
+        assert_eq!(headers["x-request-identity"], "team-a");
+        // 账号级请求头覆盖默认值，但不能覆盖鉴权
+        assert_eq!(headers["x-client-name"], "custom-client");
+        assert_eq!(headers[header::AUTHORIZATION], "Bearer token-123");
+        // 单次请求的额外 Headers 优先级最高
+        assert_eq!(headers["anthropic-beta"], "per-request");
+        assert_eq!(headers[header::USER_AGENT], "antigravity/test");
+
+        // 清空后不再注入
+        set_account_extra_headers("acc-extra-headers", HashMap::new());
+        assert!(account_extra_headers("acc-extra-headers").is_empty());
+    }
 }
@@ -0,0 +1,165 @@
+// 全局上游限流：所有发往上游的请求 (反代、Token 刷新、配额查询) 共享同一个令牌桶，
+// 避免多账号并发时整体请求速率超过上游容忍范围
+
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::proxy::config::UpstreamRateLimitConfig;
+
+/// 令牌桶：以 `rate` 个/秒的速度补充令牌，最多累积 `capacity` 个
+#[derive(Debug)]
+pub struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    state: Mutex<BucketState>,
+}
+
+#[derive(Debug)]
+struct BucketState {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    /// 创建满桶；`now` 为时钟起点 (测试中可传入受控时间)
+    pub fn new(requests_per_second: f64, burst: u32, now: Instant) -> Self {
+        let capacity = burst.max(1) as f64;
+        Self {
+            rate: requests_per_second,
+            capacity,
+            state: Mutex::new(BucketState {
+                tokens: capacity,
+                last_refill: now,
+            }),
+        }
+    }
+
+    /// 在 `now` 时刻尝试取一个令牌，不足时返回需要等待的时长
+    pub fn try_acquire_at(&self, now: Instant) -> Result<(), Duration> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+
+        let elapsed = now.saturating_duration_since(state.last_refill).as_secs_f64();
+        state.tokens = (state.tokens + elapsed * self.rate).min(self.capacity);
+        if now > state.last_refill {
+            state.last_refill = now;
+        }
+
+        if state.tokens >= 1.0 {
+            state.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - state.tokens) / self.rate))
+        }
+    }
+
+    /// 等待直到取得一个令牌
+    pub async fn acquire(&self) {
+        while let Err(wait) = self.try_acquire_at(Instant::now()) {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+// ============================================================================
+// 全局限流器存储 (None 表示未启用)
+// ============================================================================
+static GLOBAL_UPSTREAM_THROTTLE: OnceLock<RwLock<Option<Arc<TokenBucket>>>> = OnceLock::new();
+
+/// 更新全局上游限流配置 (重建令牌桶)
+pub fn update_upstream_rate_limit(config: &UpstreamRateLimitConfig) {
+    let bucket = if !config.enabled {
+        None
+    } else if !config.requests_per_second.is_finite() || config.requests_per_second <= 0.0 {
+        tracing::warn!(
+            "[Upstream-Throttle] Invalid requests_per_second {}, throttle disabled",
+            config.requests_per_second
+        );
+        None
+    } else {
+        Some(Arc::new(TokenBucket::new(
+            config.requests_per_second,
+            config.burst,
+            Instant::now(),
+        )))
+    };
+
+    tracing::info!(
+        "[Upstream-Throttle] Global config updated: enabled={}, rps={}, burst={}",
+        bucket.is_some(),
+        config.requests_per_second,
+        config.burst
+    );
+
+    let lock = GLOBAL_UPSTREAM_THROTTLE.get_or_init(|| RwLock::new(None));
+    if let Ok(mut current) = lock.write() {
+        *current = bucket;
+    }
+}
+
+/// 发送上游请求前调用；未启用限流时立即返回
+pub async fn acquire_upstream_permit() {
+    let bucket = GLOBAL_UPSTREAM_THROTTLE
+        .get()
+        .and_then(|lock| lock.read().ok())
+        .and_then(|current| current.clone());
+
+    if let Some(bucket) = bucket {
+        bucket.acquire().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_burst_never_exceeds_configured_rate() {
+        let rate = 5.0;
+        let burst = 3;
+        let start = Instant::now();
+        let bucket = TokenBucket::new(rate, burst, start);
+
+        // 受控时钟：每 10ms 一拍，每拍都有大量请求同时争抢
+        let mut granted: Vec<Duration> = Vec::new();
+        for tick in 0..1000u64 {
+            let offset = Duration::from_millis(tick * 10);
+            for _ in 0..20 {
+                if bucket.try_acquire_at(start + offset).is_ok() {
+                    granted.push(offset);
+                }
+            }
+        }
+
+        // 任意 1 秒窗口内放行数不超过 rate + burst
+        let window = Duration::from_secs(1);
+        for (i, begin) in granted.iter().enumerate() {
+            let in_window = granted[i..]
+                .iter()
+                .take_while(|t| **t < *begin + window)
+                .count();
+            assert!(
+                in_window as f64 <= rate + burst as f64,
+                "{} requests granted in window starting at {:?}",
+                in_window,
+                begin
+            );
+        }
+
+        // 10 秒总量符合 rate * 时长 + 初始突发
+        let total_secs = 10.0;
+        assert!(granted.len() as f64 <= rate * total_secs + burst as f64);
+        assert!(granted.len() as f64 >= rate * (total_secs - 1.0));
+    }
+
+    #[test]
+    fn test_empty_bucket_reports_wait_time() {
+        let start = Instant::now();
+        let bucket = TokenBucket::new(2.0, 1, start);
+
+        assert!(bucket.try_acquire_at(start).is_ok());
+        let wait = bucket.try_acquire_at(start).unwrap_err();
+        assert_eq!(wait, Duration::from_millis(500));
+
+        assert!(bucket.try_acquire_at(start + wait).is_ok());
+    }
+}
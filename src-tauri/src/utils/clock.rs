@@ -0,0 +1,67 @@
+// 时钟偏差校正
+//
+// 本机时钟不准 (常见于虚拟机) 时，access_token 过期与 reset_time 判断都会出错。
+// 从上游响应的 `Date` 头学习本机与服务端的时间差，过期 / 刷新相关逻辑统一通过 `now()` 取校正后的时间。
+
+use std::sync::atomic::{AtomicI64, Ordering};
+
+/// 偏差小于该值 (秒) 时视为本机时钟准确 (`Date` 头精度为 1 秒，另含网络延迟)
+pub const MIN_SKEW_SECS: i64 = 5;
+
+/// 已学习的时钟偏差 (服务端时间 - 本机时间，秒)
+static OFFSET_SECS: AtomicI64 = AtomicI64::new(0);
+
+/// 校正后的当前 Unix 时间 (秒)
+pub fn now() -> i64 {
+    adjusted(chrono::Utc::now().timestamp(), offset())
+}
+
+/// 校正后的当前时间 (`SystemTime`，保留亚秒精度)，用于与服务端下发的 reset 时间比较
+pub fn system_now() -> std::time::SystemTime {
+    adjusted_system(std::time::SystemTime::now(), offset())
+}
+
+/// 将 `SystemTime` 按偏差 (秒) 校正
+pub fn adjusted_system(local_now: std::time::SystemTime, offset_secs: i64) -> std::time::SystemTime {
+    let shift = std::time::Duration::from_secs(offset_secs.unsigned_abs());
+    if offset_secs >= 0 {
+        local_now + shift
+    } else {
+        local_now - shift
+    }
+}
+
+/// 将本机时间按偏差校正
+pub fn adjusted(local_now: i64, offset_secs: i64) -> i64 {
+    local_now + offset_secs
+}
+
+pub fn offset() -> i64 {
+    OFFSET_SECS.load(Ordering::Relaxed)
+}
+
+pub fn set_offset(offset_secs: i64) {
+    OFFSET_SECS.store(offset_secs, Ordering::Relaxed);
+}
+
+/// 由 HTTP `Date` 头 (RFC 2822 / IMF-fixdate) 计算服务端相对本机的偏差，无法解析时返回 None
+pub fn skew_from_date(date: &str, local_now: i64) -> Option<i64> {
+    chrono::DateTime::parse_from_rfc2822(date.trim())
+        .ok()
+        .map(|server| server.timestamp() - local_now)
+}
+
+/// 根据上游响应的 `Date` 头更新偏差；偏差低于 `MIN_SKEW_SECS` 时视为无偏差
+pub fn observe_date_header(date: &str) {
+    let Some(skew) = skew_from_date(date, chrono::Utc::now().timestamp()) else {
+        return;
+    };
+    let learned = if skew.abs() < MIN_SKEW_SECS { 0 } else { skew };
+    let previous = OFFSET_SECS.swap(learned, Ordering::Relaxed);
+    if (learned - previous).abs() >= MIN_SKEW_SECS {
+        tracing::warn!(
+            "[Clock] Local clock differs from upstream by {}s, applying offset to expiry checks",
+            learned
+        );
+    }
+}
@@ -1,18 +1,220 @@
 use aes_gcm::{
-    aead::{Aead, KeyInit},
+    aead::{Aead, KeyInit, Payload},
     Aes256Gcm, Nonce,
 };
 use base64::{engine::general_purpose, Engine as _};
-use serde::{Deserialize, Deserializer, Serializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use sha2::Digest;
+use std::path::Path;
+use std::sync::OnceLock;
 
+use super::key_provider::{FileKeyProvider, KeyProvider};
+
+/// 旧版固定 nonce，仅用于解密历史数据
 const FIXED_NONCE: &[u8; 12] = b"antigravsalt";
-const ENCRYPTED_PREFIX: &str = "ag_enc_";
+/// 密文前缀 (所有 `encrypt_string*` 输出均以此开头)
+pub const ENCRYPTED_PREFIX: &str = "ag_enc_";
+/// 随机 nonce 格式标记：`ag_enc_v2:` + Base64(nonce || ciphertext)
+/// (':' 不在 Base64 字符集中，不会与旧版密文混淆)
+const RANDOM_NONCE_MARKER: &str = "v2:";
+const NONCE_LEN: usize = 12;
+
+/// 调试构建下记录的最近 nonce 数量上限
+#[cfg(debug_assertions)]
+const NONCE_HISTORY_LIMIT: usize = 4096;
+
+/// 安装级随机密钥文件名 (位于应用数据目录)
+pub const INSTALL_SECRET_FILE: &str = ".install_secret";
+
+/// 已加载的安装级随机密钥 (machine_uid 不可用时的回退)，`forget_keys` 后重新加载
+static INSTALL_SECRET: std::sync::RwLock<Option<String>> = std::sync::RwLock::new(None);
+
+/// 读取或生成安装级随机密钥，首次生成后持久化到指定路径并复用
+fn load_or_create_install_secret(path: &Path) -> Result<String, String> {
+    FileKeyProvider::new(path)
+        .with_repair_permissions(true)
+        .load_or_create_secret()
+}
+
+/// machine_uid 失败时的回退密钥来源
+///
+/// 安装级密钥无法读取或创建 (如数据目录只读) 时返回错误，不回退到任何固定常量：
+/// 调用方据此拒绝加密 / 解密，而不是以众所周知的密钥 "加密"
+fn fallback_device_id() -> Result<String, String> {
+    if let Some(secret) = INSTALL_SECRET.read().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        return Ok(secret.clone());
+    }
+
+    // 持有写锁加载，避免并发首次生成出不同的密钥
+    let mut cached = INSTALL_SECRET.write().unwrap_or_else(|e| e.into_inner());
+    if let Some(secret) = cached.as_ref() {
+        return Ok(secret.clone());
+    }
+    let dir = crate::modules::account::get_data_dir()
+        .map_err(|e| format!("Encryption key unavailable: {}", e))?;
+    let secret = fallback_device_id_in(&dir)?;
+    *cached = Some(secret.clone());
+    Ok(secret)
+}
+
+/// 同 `fallback_device_id`，使用指定数据目录 (不缓存)
+fn fallback_device_id_in(data_dir: &Path) -> Result<String, String> {
+    load_or_create_install_secret(&data_dir.join(INSTALL_SECRET_FILE)).map_err(|e| {
+        tracing::error!("Failed to load per-install encryption secret: {}", e);
+        format!(
+            "Encryption key unavailable: machine_uid failed and the per-install secret could not be loaded ({})",
+            e
+        )
+    })
+}
+
+#[cfg(test)]
+thread_local! {
+    /// 测试专用的密钥覆盖 (按线程隔离，测试互不影响，也不依赖 machine_uid)
+    static TEST_KEY_OVERRIDE: std::cell::Cell<Option<[u8; 32]>> = const { std::cell::Cell::new(None) };
+}
+
+/// 在当前线程内以指定 "设备 ID" 派生的密钥替代真实密钥，guard 释放时恢复
+#[cfg(test)]
+pub(crate) fn override_key_for_test(device_id: &str) -> TestKeyGuard {
+    let previous = TEST_KEY_OVERRIDE.with(|k| k.replace(Some(key_from_device_id(device_id))));
+    TestKeyGuard { previous }
+}
+
+#[cfg(test)]
+pub(crate) struct TestKeyGuard {
+    previous: Option<[u8; 32]>,
+}
+
+#[cfg(test)]
+impl Drop for TestKeyGuard {
+    fn drop(&mut self) {
+        TEST_KEY_OVERRIDE.with(|k| k.set(self.previous));
+    }
+}
+
+/// 运行期切换的密钥：当前生效的密钥，以及切换前用过的旧密钥 (仅用于解密与迁移)
+#[derive(Debug, Default)]
+struct KeyRing {
+    active: Option<[u8; 32]>,
+    retired: Vec<[u8; 32]>,
+}
+
+static KEY_RING: std::sync::RwLock<KeyRing> = std::sync::RwLock::new(KeyRing {
+    active: None,
+    retired: Vec::new(),
+});
+
+fn with_key_ring<R>(f: impl FnOnce(&mut KeyRing) -> R) -> R {
+    f(&mut KEY_RING.write().unwrap_or_else(|e| e.into_inner()))
+}
+
+/// 切换密钥来源 (如 machine_uid -> 密钥文件)
+///
+/// 之后的加密一律使用新密钥；切换前的密钥保留为旧密钥，旧密文仍可解密，
+/// 并在下次写入时 (`serialize_password` / 账号目录迁移) 以新密钥重新加密。
+/// 密钥环不落盘：调用方须先持久化所选来源 (见 `account_store::apply_key_source`)，
+/// 否则重启后回退到 machine_uid，已按新密钥重写的密文将无法解密。
+pub fn set_active_key_provider(provider: &dyn KeyProvider) -> Result<(), String> {
+    let key = provider.encryption_key()?;
+    // 之前的密钥不可用时没有可保留的旧密钥
+    let previous = get_encryption_key().ok();
+    with_key_ring(|ring| {
+        if let Some(previous) = previous.filter(|previous| *previous != key) {
+            if !ring.retired.contains(&previous) {
+                ring.retired.push(previous);
+            }
+        }
+        ring.retired.retain(|retired| *retired != key);
+        ring.active = Some(key);
+    });
+    tracing::info!("Encryption key provider switched, secrets will be re-encrypted on next write");
+    Ok(())
+}
+
+/// 将某个来源的密钥登记为旧密钥 (仅用于解密与迁移)，已是当前密钥时忽略
+///
+/// 重启后密钥环为空，需重新登记之前可能用过的来源，否则切换前写入的密文无法解密
+pub fn retire_key_provider(provider: &dyn KeyProvider) -> Result<(), String> {
+    let key = provider.encryption_key()?;
+    let active = get_encryption_key().ok();
+    with_key_ring(|ring| {
+        if active != Some(key) && !ring.retired.contains(&key) {
+            ring.retired.push(key);
+        }
+    });
+    Ok(())
+}
+
+/// 丢弃内存中的全部密钥材料 (密钥环与已加载的安装级密钥)
+///
+/// 用于清除全部账号之后：对应的密钥文件已被删除，之后的加密重新按设备 ID 派生
+pub fn forget_keys() {
+    with_key_ring(|ring| *ring = KeyRing::default());
+    *INSTALL_SECRET.write().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// 清空密钥环，模拟进程重启
+#[cfg(test)]
+pub(crate) fn reset_key_ring_for_test() {
+    with_key_ring(|ring| *ring = KeyRing::default());
+}
+
+/// 密钥环为进程级全局状态：切换/重置密钥环的测试以及依赖默认密钥的测试需持有此锁
+#[cfg(test)]
+pub(crate) fn lock_key_ring_for_test() -> std::sync::MutexGuard<'static, ()> {
+    static LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// 基于设备 ID (machine_uid，不可用时为安装级随机密钥) 派生的默认密钥来源
+pub struct MachineKeyProvider;
+
+impl KeyProvider for MachineKeyProvider {
+    fn encryption_key(&self) -> Result<[u8; 32], String> {
+        machine_key()
+    }
+}
+
+fn retired_keys() -> Vec<[u8; 32]> {
+    with_key_ring(|ring| ring.retired.clone())
+}
 
-/// 生成加密密钥 (基于设备 ID)
-fn get_encryption_key() -> [u8; 32] {
+/// 当前生效的加密密钥：`set_active_key_provider` 设置的密钥，否则基于设备 ID 生成
+///
+/// 设备 ID 与安装级密钥都不可用时返回错误
+fn get_encryption_key() -> Result<[u8; 32], String> {
+    #[cfg(test)]
+    if let Some(key) = TEST_KEY_OVERRIDE.with(|k| k.get()) {
+        return Ok(key);
+    }
+
+    match with_key_ring(|ring| ring.active) {
+        Some(key) => Ok(key),
+        None => machine_key(),
+    }
+}
+
+fn machine_key() -> Result<[u8; 32], String> {
     // 使用设备唯一标识生成密钥
-    let device_id = machine_uid::get().unwrap_or_else(|_| "default".to_string());
+    let device_id = match machine_uid::get() {
+        Ok(device_id) => device_id,
+        Err(e) => {
+            // 每次派生密钥都会走到这里 (读取每个账号时)，只警告一次
+            static FALLBACK_WARNED: std::sync::Once = std::sync::Once::new();
+            FALLBACK_WARNED.call_once(|| {
+                tracing::warn!(
+                    "machine_uid unavailable ({}), falling back to per-install encryption secret",
+                    e
+                );
+            });
+            fallback_device_id()?
+        }
+    };
+    Ok(key_from_device_id(&device_id))
+}
+
+fn key_from_device_id(device_id: &str) -> [u8; 32] {
     let mut key = [0u8; 32];
     let hash = sha2::Sha256::digest(device_id.as_bytes());
     key.copy_from_slice(&hash);
@@ -25,7 +227,11 @@ where
 {
     // [FIX #1738] 防止双重加密：检查是否已包含魔术前缀
     if password.starts_with(ENCRYPTED_PREFIX) {
-        return serializer.serialize_str(password);
+        // 仍由旧密钥加密的值顺带迁移到当前密钥
+        return match reencrypt_if_retired(password, &[]) {
+            Ok(Some(reencrypted)) => serializer.serialize_str(&reencrypted),
+            _ => serializer.serialize_str(password),
+        };
     }
 
     let encrypted = encrypt_string(password).map_err(serde::ser::Error::custom)?;
@@ -37,70 +243,200 @@ where
     D: Deserializer<'de>,
 {
     let raw = String::deserialize(deserializer)?;
+    match try_deserialize_password(&raw) {
+        Ok(plaintext) => Ok(plaintext),
+        Err(DecryptError::KeyMismatch(original)) => {
+            // 解密失败（如密钥变更），返回原始密文以防止数据丢失；
+            // 需要区分该情况的调用方应使用 try_deserialize_password
+            tracing::warn!("Stored secret cannot be decrypted with the current key (key changed?)");
+            Ok(original)
+        }
+        Err(DecryptError::KeyUnavailable(e)) => {
+            // 无法取得密钥：不解密，原样保留存储值
+            tracing::error!("Stored secret cannot be decrypted: {}", e);
+            Ok(raw)
+        }
+        Err(DecryptError::Malformed(_)) => Ok(raw),
+    }
+}
+
+/// 密码字段解密失败的原因
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecryptError {
+    /// 带前缀的密文无法用当前密钥解密（通常是设备/密钥变更），携带原始存储值以便恢复
+    KeyMismatch(String),
+    /// 带前缀但密文格式损坏 (Base64 / 长度 / UTF-8)
+    Malformed(String),
+    /// 无法取得加密密钥 (machine_uid 与安装级密钥均不可用)，拒绝解密
+    KeyUnavailable(String),
+}
+
+impl std::fmt::Display for DecryptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecryptError::KeyMismatch(_) => write!(f, "Decryption failed: key mismatch"),
+            DecryptError::Malformed(msg) => write!(f, "{}", msg),
+            DecryptError::KeyUnavailable(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+/// 解析存储的密码字段，区分 "明文 / 可解密" 与 "密钥不匹配"
+///
+/// 与 `deserialize_password` 不同，密钥变更时返回 `DecryptError::KeyMismatch`
+/// 而不是把密文当作密码返回
+pub fn try_deserialize_password(raw: &str) -> Result<String, DecryptError> {
     if raw.is_empty() {
-        return Ok(raw);
+        return Ok(String::new());
     }
 
     // [FIX #1738] 检查魔术前缀
-    if raw.starts_with(ENCRYPTED_PREFIX) {
+    if let Some(ciphertext) = raw.strip_prefix(ENCRYPTED_PREFIX) {
         // 新版格式：去前缀后解密
-        let ciphertext = &raw[ENCRYPTED_PREFIX.len()..];
-        match decrypt_string_internal(ciphertext) {
+        decrypt_with_known_keys(ciphertext, &[]).map_err(|e| match e {
+            DecryptError::KeyMismatch(_) => DecryptError::KeyMismatch(raw.to_string()),
+            other => other,
+        })
+    } else {
+        // 兼容旧版：尝试直接解密；失败则认为是普通明文（用户输入的无前缀密码）。
+        // 用户输入的明文通常会因 Base64 错误或 Tag 校验失败而解密失败。
+        match decrypt_with_known_keys(raw, &[]) {
             Ok(plaintext) => Ok(plaintext),
-            Err(_) => {
-                // 解密失败（如密钥变更），返回原始密文以防止数据丢失
-                Ok(raw)
-            }
+            Err(e @ DecryptError::KeyUnavailable(_)) => Err(e),
+            Err(_) => Ok(raw.to_string()),
         }
-    } else {
-        // 兼容旧版：尝试直接解密
-        match decrypt_string_internal(&raw) {
-            Ok(plaintext) => {
-                // 只有当解密出有效的 UTF-8 且看起来像合理个字符串时才认为是旧版密文
-                // 这里 decrypt_string_internal 已经保证了 UTF-8，
-                // 如果是用户输入的明文，通常解密会失败（Base64 错误或 Tag 校验错误）。
-                Ok(plaintext)
-            }
-            Err(_) => {
-                // 解密失败，认为是普通明文（用户输入的无前缀密码）
-                Ok(raw)
-            }
+    }
+}
+
+#[cfg(debug_assertions)]
+#[derive(Default)]
+struct NonceHistory {
+    seen: std::collections::HashSet<[u8; NONCE_LEN]>,
+    order: std::collections::VecDeque<[u8; NONCE_LEN]>,
+}
+
+/// 记录本进程最近使用过的 nonce，返回 false 表示发生了重用 (仅调试构建)
+#[cfg(debug_assertions)]
+fn record_nonce(nonce: &[u8; NONCE_LEN]) -> bool {
+    static RECENT_NONCES: OnceLock<std::sync::Mutex<NonceHistory>> = OnceLock::new();
+
+    let mut history = RECENT_NONCES
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+
+    if !history.seen.insert(*nonce) {
+        return false;
+    }
+    history.order.push_back(*nonce);
+    if history.order.len() > NONCE_HISTORY_LIMIT {
+        if let Some(oldest) = history.order.pop_front() {
+            history.seen.remove(&oldest);
         }
     }
+    true
 }
 
 pub fn encrypt_string(password: &str) -> Result<String, String> {
-    let key = get_encryption_key();
-    let cipher = Aes256Gcm::new(&key.into());
-    // In production, we should use a random nonce and prepend it to the ciphertext
-    // For simplicity in this demo, we use a fixed nonce (NOT SECURE for repeats)
-    // improving security: use random nonce
-    let nonce = Nonce::from_slice(FIXED_NONCE);
+    encrypt_with_key(&get_encryption_key()?, password, &[])
+}
+
+/// 加密并绑定关联数据 (AAD，例如账号 email)
+///
+/// 密文被挪到其他账号上下文时，用不同的 AAD 解密会因认证标签校验失败而报错。
+/// 空 AAD 与 `encrypt_string` 等价。
+pub fn encrypt_string_with_aad(plaintext: &str, aad: &[u8]) -> Result<String, String> {
+    encrypt_with_key(&get_encryption_key()?, plaintext, aad)
+}
+
+fn encrypt_with_key(key: &[u8; 32], password: &str, aad: &[u8]) -> Result<String, String> {
+    let cipher = Aes256Gcm::new(key.into());
+    // 每次加密使用随机 nonce，并将其拼接在密文之前
+    let nonce_bytes: [u8; NONCE_LEN] = rand::random();
+
+    #[cfg(debug_assertions)]
+    if !record_nonce(&nonce_bytes) {
+        panic!("AES-GCM nonce reuse detected in encrypt_string");
+    }
 
     let ciphertext = cipher
-        .encrypt(nonce, password.as_bytes())
+        .encrypt(
+            Nonce::from_slice(&nonce_bytes),
+            Payload {
+                msg: password.as_bytes(),
+                aad,
+            },
+        )
         .map_err(|e| format!("Encryption failed: {}", e))?;
 
-    let base64_ciphertext = general_purpose::STANDARD.encode(ciphertext);
+    let mut payload = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+    payload.extend_from_slice(&nonce_bytes);
+    payload.extend_from_slice(&ciphertext);
+
+    let base64_payload = general_purpose::STANDARD.encode(payload);
     // [FIX #1738] 添加魔术前缀
-    Ok(format!("{}{}", ENCRYPTED_PREFIX, base64_ciphertext))
+    Ok(format!("{}{}{}", ENCRYPTED_PREFIX, RANDOM_NONCE_MARKER, base64_payload))
 }
 
-/// 内部解密函数 (输入必须是纯 Base64 密文，不含前缀)
+/// 内部解密函数 (输入为去掉前缀后的密文，支持随机 nonce 与旧版固定 nonce 两种格式)
 fn decrypt_string_internal(encrypted_base64: &str) -> Result<String, String> {
-    let key = get_encryption_key();
-    let cipher = Aes256Gcm::new(&key.into());
-    let nonce = Nonce::from_slice(FIXED_NONCE);
+    decrypt_with_known_keys(encrypted_base64, &[]).map_err(|e| e.to_string())
+}
 
-    let ciphertext = general_purpose::STANDARD
-        .decode(encrypted_base64)
-        .map_err(|e| format!("Base64 decode failed: {}", e))?;
+/// 先用当前密钥解密，密钥不匹配时依次尝试切换前的旧密钥
+fn decrypt_with_known_keys(encrypted_base64: &str, aad: &[u8]) -> Result<String, DecryptError> {
+    decrypt_with_key_ring(encrypted_base64, aad).map(|(plaintext, _)| plaintext)
+}
 
-    let plaintext = cipher
-        .decrypt(nonce, ciphertext.as_ref())
-        .map_err(|e| format!("Decryption failed: {}", e))?;
+/// 同 `decrypt_with_known_keys`，并返回是否由旧密钥解密 (需要重新加密)
+fn decrypt_with_key_ring(encrypted_base64: &str, aad: &[u8]) -> Result<(String, bool), DecryptError> {
+    let key = get_encryption_key().map_err(DecryptError::KeyUnavailable)?;
+    match decrypt_with_key(&key, encrypted_base64, aad) {
+        Err(DecryptError::KeyMismatch(original)) => retired_keys()
+            .iter()
+            .find_map(|key| decrypt_with_key(key, encrypted_base64, aad).ok())
+            .map(|plaintext| (plaintext, true))
+            .ok_or(DecryptError::KeyMismatch(original)),
+        other => other.map(|plaintext| (plaintext, false)),
+    }
+}
 
-    String::from_utf8(plaintext).map_err(|e| format!("UTF-8 conversion failed: {}", e))
+fn decrypt_with_key(
+    key: &[u8; 32],
+    encrypted_base64: &str,
+    aad: &[u8],
+) -> Result<String, DecryptError> {
+    let cipher = Aes256Gcm::new(key.into());
+    let key_mismatch = |_| DecryptError::KeyMismatch(encrypted_base64.to_string());
+
+    let plaintext = if let Some(payload_b64) = encrypted_base64.strip_prefix(RANDOM_NONCE_MARKER) {
+        let payload = general_purpose::STANDARD
+            .decode(payload_b64)
+            .map_err(|e| DecryptError::Malformed(format!("Base64 decode failed: {}", e)))?;
+        if payload.len() < NONCE_LEN {
+            return Err(DecryptError::Malformed("Ciphertext too short".to_string()));
+        }
+        let (nonce, ciphertext) = payload.split_at(NONCE_LEN);
+        cipher
+            .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad })
+            .map_err(key_mismatch)?
+    } else {
+        let ciphertext = general_purpose::STANDARD
+            .decode(encrypted_base64)
+            .map_err(|e| DecryptError::Malformed(format!("Base64 decode failed: {}", e)))?;
+        cipher
+            .decrypt(
+                Nonce::from_slice(FIXED_NONCE),
+                Payload {
+                    msg: ciphertext.as_ref(),
+                    aad,
+                },
+            )
+            .map_err(key_mismatch)?
+    };
+
+    String::from_utf8(plaintext)
+        .map_err(|e| DecryptError::Malformed(format!("UTF-8 conversion failed: {}", e)))
 }
 
 pub fn decrypt_string(encrypted: &str) -> Result<String, String> {
@@ -111,12 +447,159 @@ pub fn decrypt_string(encrypted: &str) -> Result<String, String> {
     }
 }
 
+/// 解密 `encrypt_string_with_aad` 生成的密文，AAD 不一致时返回错误
+pub fn decrypt_string_with_aad(encrypted: &str, aad: &[u8]) -> Result<String, String> {
+    let ciphertext = encrypted.strip_prefix(ENCRYPTED_PREFIX).unwrap_or(encrypted);
+    decrypt_with_known_keys(ciphertext, aad).map_err(|e| e.to_string())
+}
+
+/// 带前缀的密文只能用切换前的旧密钥解密时返回 true
+pub fn needs_reencryption(encrypted: &str, aad: &[u8]) -> bool {
+    encrypted
+        .strip_prefix(ENCRYPTED_PREFIX)
+        .is_some_and(|ciphertext| matches!(decrypt_with_key_ring(ciphertext, aad), Ok((_, true))))
+}
+
+/// 迁移单个密文：由旧密钥加密时以当前密钥重新加密，已是当前密钥 (或明文) 时返回 None
+pub fn reencrypt_if_retired(encrypted: &str, aad: &[u8]) -> Result<Option<String>, String> {
+    let Some(ciphertext) = encrypted.strip_prefix(ENCRYPTED_PREFIX) else {
+        return Ok(None);
+    };
+    match decrypt_with_key_ring(ciphertext, aad) {
+        Ok((plaintext, true)) => encrypt_with_key(&get_encryption_key()?, &plaintext, aad).map(Some),
+        Ok((_, false)) => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// `repair_double_encrypted` 最多剥离的加密层数
+pub const MAX_ENCRYPTION_LAYERS: usize = 8;
+
+/// 修复 [FIX #1738] 之前被重复加密的存储值
+///
+/// 逐层解密 (带前缀的密文，以及嵌套在内层的无前缀旧版密文)，直到得到明文，
+/// 再以当前密钥重新加密一次。明文输入直接加密；超过 `MAX_ENCRYPTION_LAYERS` 层
+/// 或某一层无法解密时返回错误，不猜测结果。
+pub fn repair_double_encrypted(stored: &str) -> Result<String, String> {
+    if stored.is_empty() {
+        return Ok(String::new());
+    }
+
+    let mut current = stored.to_string();
+    let mut layers = 0;
+    loop {
+        let inner = if let Some(ciphertext) = current.strip_prefix(ENCRYPTED_PREFIX) {
+            let plaintext = decrypt_with_known_keys(ciphertext, &[])
+                .map_err(|e| format!("Failed to decrypt layer {}: {}", layers + 1, e))?;
+            Some(plaintext)
+        } else if !current.is_empty() {
+            // 无前缀的旧版密文；解密失败说明已是明文
+            decrypt_string_internal(&current).ok()
+        } else {
+            None
+        };
+        let Some(inner) = inner else {
+            break;
+        };
+
+        layers += 1;
+        if layers > MAX_ENCRYPTION_LAYERS {
+            return Err(format!(
+                "Stored value is encrypted more than {} times, refusing to repair",
+                MAX_ENCRYPTION_LAYERS
+            ));
+        }
+        current = inner;
+    }
+
+    if layers > 1 {
+        tracing::info!("Repaired secret encrypted {} times", layers);
+    }
+    encrypt_string(&current)
+}
+
+/// 单个存储值的加密状态分类
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecretKind {
+    /// 带 `ag_enc_` 前缀且可用当前密钥解密
+    Prefixed,
+    /// 无前缀的旧版密文，可用当前密钥解密
+    Legacy,
+    /// 带前缀但无法用当前密钥解密（密钥已变更或数据损坏）
+    Undecryptable,
+    /// 普通明文（无前缀且无法解密）
+    Plaintext,
+}
+
+/// 批量审计结果，用于密钥迁移前的预览
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SecretAudit {
+    pub total: usize,
+    pub decryptable: usize,
+    pub undecryptable: usize,
+    pub plaintext: usize,
+    pub prefixed: usize,
+    pub legacy: usize,
+    /// 与输入顺序一一对应的分类结果
+    pub entries: Vec<SecretKind>,
+}
+
+/// 对单个存储值进行分类（只读，不修改任何数据）
+pub fn classify_secret(value: &str) -> SecretKind {
+    if let Some(ciphertext) = value.strip_prefix(ENCRYPTED_PREFIX) {
+        if decrypt_string_internal(ciphertext).is_ok() {
+            SecretKind::Prefixed
+        } else {
+            SecretKind::Undecryptable
+        }
+    } else if !value.is_empty() && decrypt_string_internal(value).is_ok() {
+        SecretKind::Legacy
+    } else {
+        SecretKind::Plaintext
+    }
+}
+
+/// 审计一批已存储的值，统计当前密钥下可解密/不可解密/明文的数量。
+///
+/// 用于密钥提供方迁移 (machine_uid -> 系统密钥库) 前的预览，不会修改任何数据。
+pub fn audit_secrets(values: &[String]) -> SecretAudit {
+    let mut audit = SecretAudit {
+        total: values.len(),
+        ..Default::default()
+    };
+
+    for value in values {
+        let kind = classify_secret(value);
+        match kind {
+            SecretKind::Prefixed => {
+                audit.prefixed += 1;
+                audit.decryptable += 1;
+            }
+            SecretKind::Legacy => {
+                audit.legacy += 1;
+                audit.decryptable += 1;
+            }
+            SecretKind::Undecryptable => {
+                audit.prefixed += 1;
+                audit.undecryptable += 1;
+            }
+            SecretKind::Plaintext => audit.plaintext += 1,
+        }
+        audit.entries.push(kind);
+    }
+
+    audit
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::fs;
 
     #[test]
     fn test_encrypt_decrypt_cycle() {
+        let _ring = lock_key_ring_for_test();
         let password = "my_secret_password";
         let encrypted = encrypt_string(password).unwrap();
         
@@ -127,11 +610,32 @@ mod tests {
         assert_eq!(password, decrypted);
     }
 
+    #[test]
+    fn test_aad_binds_ciphertext_to_account() {
+        let _ring = lock_key_ring_for_test();
+        let encrypted = encrypt_string_with_aad("refresh-token", b"alice@example.com").unwrap();
+        assert!(encrypted.starts_with(ENCRYPTED_PREFIX));
+
+        assert_eq!(
+            decrypt_string_with_aad(&encrypted, b"alice@example.com").unwrap(),
+            "refresh-token"
+        );
+        // 挪到其他账号或丢失 AAD 时解密失败
+        assert!(decrypt_string_with_aad(&encrypted, b"bob@example.com").is_err());
+        assert!(decrypt_string(&encrypted).is_err());
+
+        // 无 AAD 的旧密文等价于空 AAD
+        let plain = encrypt_string("refresh-token").unwrap();
+        assert_eq!(decrypt_string_with_aad(&plain, b"").unwrap(), "refresh-token");
+    }
+
     #[test]
     fn test_legacy_compatibility() {
+        let _key = override_key_for_test("legacy-test-machine");
+
         // 模拟旧版加密（手动调用内部逻辑生成无前缀密文）
         let password = "legacy_password";
-        let key = get_encryption_key();
+        let key = key_from_device_id("legacy-test-machine");
         let cipher = Aes256Gcm::new(&key.into());
         let nonce = Nonce::from_slice(FIXED_NONCE);
         let ciphertext = cipher.encrypt(nonce, password.as_bytes()).unwrap();
@@ -142,5 +646,286 @@ mod tests {
         // 使用新版解密逻辑
         let decrypted = decrypt_string(&legacy_encrypted).unwrap();
         assert_eq!(password, decrypted);
+
+        // 带前缀的固定 nonce 密文 (随机 nonce 之前的版本) 同样可解密
+        let prefixed_fixed_nonce = format!("{}{}", ENCRYPTED_PREFIX, legacy_encrypted);
+        assert_eq!(decrypt_string(&prefixed_fixed_nonce).unwrap(), password);
+    }
+
+    #[test]
+    fn test_encrypt_uses_unique_nonces() {
+        let _ring = lock_key_ring_for_test();
+        let mut outputs = std::collections::HashSet::new();
+        for i in 0..2000 {
+            // 相同明文也必须得到不同密文；调试构建下 nonce 重用会直接 panic
+            let encrypted = encrypt_string("same_password").unwrap();
+            assert!(outputs.insert(encrypted.clone()), "duplicate ciphertext at {}", i);
+            assert_eq!(decrypt_string(&encrypted).unwrap(), "same_password");
+        }
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn test_record_nonce_detects_reuse() {
+        let nonce: [u8; NONCE_LEN] = rand::random();
+        assert!(record_nonce(&nonce));
+        assert!(!record_nonce(&nonce));
+    }
+
+    #[test]
+    fn test_audit_secrets_mixed_inputs() {
+        let _ring = lock_key_ring_for_test();
+        let prefixed = encrypt_string("prefixed_secret").unwrap();
+
+        let key = get_encryption_key().unwrap();
+        let cipher = Aes256Gcm::new(&key.into());
+        let nonce = Nonce::from_slice(FIXED_NONCE);
+        let legacy = general_purpose::STANDARD
+            .encode(cipher.encrypt(nonce, b"legacy_secret".as_ref()).unwrap());
+
+        let values = vec![
+            prefixed.clone(),
+            legacy,
+            "plain_password".to_string(),
+            format!("{}not-base64!!", ENCRYPTED_PREFIX),
+            format!("{}{}", ENCRYPTED_PREFIX, general_purpose::STANDARD.encode(b"garbage bytes")),
+            String::new(),
+        ];
+        let snapshot = values.clone();
+
+        let audit = audit_secrets(&values);
+
+        assert_eq!(values, snapshot, "audit must not mutate inputs");
+        assert_eq!(audit.total, 6);
+        assert_eq!(audit.prefixed, 3);
+        assert_eq!(audit.legacy, 1);
+        assert_eq!(audit.decryptable, 2);
+        assert_eq!(audit.undecryptable, 2);
+        assert_eq!(audit.plaintext, 2);
+        assert_eq!(
+            audit.entries,
+            vec![
+                SecretKind::Prefixed,
+                SecretKind::Legacy,
+                SecretKind::Plaintext,
+                SecretKind::Undecryptable,
+                SecretKind::Undecryptable,
+                SecretKind::Plaintext,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_key_override_cross_machine_decryption() {
+        let (from_a, from_a_with_aad) = {
+            let _machine_a = override_key_for_test("machine-a");
+            assert_eq!(get_encryption_key().unwrap(), key_from_device_id("machine-a"));
+            (
+                encrypt_string("shared-secret").unwrap(),
+                encrypt_string_with_aad("refresh-token", b"alice@example.com").unwrap(),
+            )
+        };
+
+        {
+            // 另一台机器上无法解密，但能识别为密钥不匹配
+            let _machine_b = override_key_for_test("machine-b");
+            assert!(decrypt_string(&from_a).is_err());
+            assert!(decrypt_string_with_aad(&from_a_with_aad, b"alice@example.com").is_err());
+            assert_eq!(
+                try_deserialize_password(&from_a),
+                Err(DecryptError::KeyMismatch(from_a.clone()))
+            );
+            assert_eq!(classify_secret(&from_a), SecretKind::Undecryptable);
+
+            // 覆盖可嵌套，内层释放后恢复外层密钥
+            {
+                let _back_on_a = override_key_for_test("machine-a");
+                assert_eq!(decrypt_string(&from_a).unwrap(), "shared-secret");
+            }
+            assert_eq!(get_encryption_key().unwrap(), key_from_device_id("machine-b"));
+        }
+
+        // 回到原机器后可正常解密
+        let _machine_a = override_key_for_test("machine-a");
+        assert_eq!(decrypt_string(&from_a).unwrap(), "shared-secret");
+        assert_eq!(
+            decrypt_string_with_aad(&from_a_with_aad, b"alice@example.com").unwrap(),
+            "refresh-token"
+        );
+        assert_eq!(classify_secret(&from_a), SecretKind::Prefixed);
+    }
+
+    #[test]
+    fn test_try_deserialize_password_reports_key_mismatch() {
+        let _ring = lock_key_ring_for_test();
+        // 模拟密钥变更：用另一台设备的密钥加密
+        let other_key = key_from_device_id("another-device-id");
+        let foreign = encrypt_with_key(&other_key, "secret", &[]).unwrap();
+
+        assert_eq!(
+            try_deserialize_password(&foreign),
+            Err(DecryptError::KeyMismatch(foreign.clone()))
+        );
+        // 兼容路径保持不变：仍返回原始值而不丢失数据
+        let value: String =
+            deserialize_password(serde_json::Value::String(foreign.clone())).unwrap();
+        assert_eq!(value, foreign);
+
+        // 当前密钥加密的值与普通明文均正常返回
+        let local = encrypt_string("secret").unwrap();
+        assert_eq!(try_deserialize_password(&local).unwrap(), "secret");
+        assert_eq!(try_deserialize_password("plain-password").unwrap(), "plain-password");
+        assert_eq!(try_deserialize_password("").unwrap(), "");
+
+        // 前缀后的内容损坏不视为密钥不匹配
+        assert!(matches!(
+            try_deserialize_password("ag_enc_v2:!!!"),
+            Err(DecryptError::Malformed(_))
+        ));
+    }
+
+    struct DeviceIdKeyProvider(&'static str);
+
+    impl KeyProvider for DeviceIdKeyProvider {
+        fn encryption_key(&self) -> Result<[u8; 32], String> {
+            Ok(key_from_device_id(self.0))
+        }
+    }
+
+    #[test]
+    fn test_switching_key_provider_reencrypts_on_next_write() {
+        let _ring = lock_key_ring_for_test();
+        reset_key_ring_for_test();
+
+        set_active_key_provider(&DeviceIdKeyProvider("machine-uid")).unwrap();
+        let old_key = key_from_device_id("machine-uid");
+        let stored = encrypt_string("proxy-password").unwrap();
+        let stored_with_aad = encrypt_string_with_aad("refresh-token", b"alice@example.com").unwrap();
+        assert!(!needs_reencryption(&stored, &[]));
+
+        set_active_key_provider(&DeviceIdKeyProvider("os-keystore")).unwrap();
+        let new_key = key_from_device_id("os-keystore");
+
+        // 新的加密使用新密钥
+        let fresh = encrypt_string("proxy-password").unwrap();
+        let fresh_payload = fresh.strip_prefix(ENCRYPTED_PREFIX).unwrap();
+        assert_eq!(decrypt_with_key(&new_key, fresh_payload, &[]).unwrap(), "proxy-password");
+        assert!(decrypt_with_key(&old_key, fresh_payload, &[]).is_err());
+
+        // 旧密文仍可解密，并被识别为需要迁移
+        assert_eq!(decrypt_string(&stored).unwrap(), "proxy-password");
+        assert_eq!(try_deserialize_password(&stored).unwrap(), "proxy-password");
+        assert_eq!(
+            decrypt_string_with_aad(&stored_with_aad, b"alice@example.com").unwrap(),
+            "refresh-token"
+        );
+        assert!(needs_reencryption(&stored, &[]));
+        assert!(!needs_reencryption(&fresh, &[]));
+        assert_eq!(reencrypt_if_retired(&fresh, &[]).unwrap(), None);
+
+        // 迁移：序列化时旧密文被改写为新密钥下的密文
+        let written = serialize_password(&stored, serde_json::value::Serializer).unwrap();
+        let written = written.as_str().unwrap();
+        assert_ne!(written, stored);
+        let payload = written.strip_prefix(ENCRYPTED_PREFIX).unwrap();
+        assert_eq!(decrypt_with_key(&new_key, payload, &[]).unwrap(), "proxy-password");
+        let migrated = reencrypt_if_retired(&stored_with_aad, b"alice@example.com")
+            .unwrap()
+            .unwrap();
+        assert!(!needs_reencryption(&migrated, b"alice@example.com"));
+
+        // 再次切换 (如改用密码)，最早的密文依旧可解密
+        set_active_key_provider(&DeviceIdKeyProvider("user-password")).unwrap();
+        assert_eq!(decrypt_string(&stored).unwrap(), "proxy-password");
+        assert_eq!(decrypt_string(&fresh).unwrap(), "proxy-password");
+
+        // 重启后密钥环为空：需重新登记旧来源，切换前的密文才能解密
+        reset_key_ring_for_test();
+        set_active_key_provider(&DeviceIdKeyProvider("user-password")).unwrap();
+        assert!(decrypt_string(&stored).is_err());
+        retire_key_provider(&DeviceIdKeyProvider("machine-uid")).unwrap();
+        retire_key_provider(&DeviceIdKeyProvider("user-password")).unwrap();
+        assert_eq!(decrypt_string(&stored).unwrap(), "proxy-password");
+        assert!(!needs_reencryption(&encrypt_string("x").unwrap(), &[]));
+        reset_key_ring_for_test();
+    }
+
+    #[test]
+    fn test_repair_double_encrypted_peels_nested_layers() {
+        let _key = override_key_for_test("repair-test-machine");
+
+        let once = encrypt_string("proxy-password").unwrap();
+        // 旧版 bug：已加密的值再次被加密
+        let twice = encrypt_with_key(&get_encryption_key().unwrap(), &once, &[]).unwrap();
+        assert_eq!(decrypt_string(&twice).unwrap(), once);
+
+        let repaired = repair_double_encrypted(&twice).unwrap();
+        assert!(repaired.starts_with(ENCRYPTED_PREFIX));
+        assert_eq!(decrypt_string(&repaired).unwrap(), "proxy-password");
+        assert_eq!(try_deserialize_password(&repaired).unwrap(), "proxy-password");
+
+        // 内层为无前缀旧版密文、单层密文、明文也都归一为单层密文
+        let legacy_inner = general_purpose::STANDARD.encode(
+            Aes256Gcm::new(&get_encryption_key().unwrap().into())
+                .encrypt(Nonce::from_slice(FIXED_NONCE), b"legacy".as_ref())
+                .unwrap(),
+        );
+        let wrapped_legacy = encrypt_string(&legacy_inner).unwrap();
+        for (stored, expected) in [
+            (wrapped_legacy.as_str(), "legacy"),
+            (once.as_str(), "proxy-password"),
+            ("plain-password", "plain-password"),
+        ] {
+            let repaired = repair_double_encrypted(stored).unwrap();
+            assert_eq!(decrypt_string(&repaired).unwrap(), expected);
+        }
+        assert_eq!(repair_double_encrypted("").unwrap(), "");
+
+        // 超过层数上限或无法解密时报错
+        let mut deep = "x".to_string();
+        for _ in 0..=MAX_ENCRYPTION_LAYERS {
+            deep = encrypt_string(&deep).unwrap();
+        }
+        assert!(repair_double_encrypted(&deep).is_err());
+        let foreign = encrypt_with_key(&key_from_device_id("other-machine"), &once, &[]).unwrap();
+        assert!(repair_double_encrypted(&foreign).is_err());
+    }
+
+    #[test]
+    fn test_install_secret_persisted_across_processes() {
+        let dir = std::env::temp_dir().join(format!("ag_install_secret_{}", uuid::Uuid::new_v4()));
+        let path = dir.join(INSTALL_SECRET_FILE);
+
+        // 两次独立加载（模拟两个进程）得到同一个持久化密钥
+        let first = load_or_create_install_secret(&path).unwrap();
+        let second = load_or_create_install_secret(&path).unwrap();
+
+        assert_eq!(first, second);
+        assert_ne!(first, "default");
+        assert_eq!(first.len(), 64);
+        assert_ne!(key_from_device_id(&first), key_from_device_id("default"));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_unavailable_install_secret_is_an_error_not_a_constant() {
+        // 数据目录 "路径" 实际是文件：无法创建安装级密钥
+        let dir = std::env::temp_dir().join(format!("ag_install_secret_{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let not_a_dir = dir.join("data");
+        fs::write(&not_a_dir, "file").unwrap();
+
+        let err = fallback_device_id_in(&not_a_dir).unwrap_err();
+        assert!(err.contains("Encryption key unavailable"), "{}", err);
+
+        let _ = fs::remove_dir_all(&dir);
     }
 }
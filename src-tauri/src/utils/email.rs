@@ -0,0 +1,36 @@
+// 邮箱归一化：邮箱被用作 upsert / 去重 / 查找的键，统一在比较前归一化，
+// 避免 `User@Example.com ` 与 `user@example.com` 被视为两个账号
+
+/// 归一化邮箱：去除首尾空白并转为小写
+///
+/// 不折叠本地部分的 `.` 或 `+tag`：这些规则仅对部分邮件服务商成立，
+/// 折叠后可能把两个不同的账号合并为一个。
+pub fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+/// 两个邮箱归一化后是否相同
+pub fn emails_match(a: &str, b: &str) -> bool {
+    normalize_email(a) == normalize_email(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_email_trims_and_lowercases() {
+        assert_eq!(normalize_email("  User@Example.COM \n"), "user@example.com");
+        assert_eq!(normalize_email("first.last+tag@gmail.com"), "first.last+tag@gmail.com");
+        assert!(emails_match("User@Example.com ", "user@example.com"));
+        assert!(!emails_match("a@example.com", "b@example.com"));
+    }
+
+    #[test]
+    fn test_normalize_email_is_idempotent() {
+        for email in ["user@example.com", " MiXeD@Case.Org", "ÄBC@Example.de", ""] {
+            let once = normalize_email(email);
+            assert_eq!(normalize_email(&once), once);
+        }
+    }
+}
@@ -0,0 +1,78 @@
+// JWT 载荷解析 (不校验签名)
+//
+// 仅在 userinfo 请求失败时从令牌声明中读取 email 作为回退；
+// 未经签名校验，结果只能作为展示 / 标识的提示，不能当作身份凭证。
+
+use base64::{engine::general_purpose, Engine as _};
+use serde_json::Value;
+
+/// 解码 JWT 中间段 (base64url 载荷)，不是三段式 JWT 或载荷不是 JSON 对象时返回 None
+pub fn decode_payload_unverified(token: &str) -> Option<Value> {
+    let mut segments = token.trim().split('.');
+    let (_header, payload, _signature) = (segments.next()?, segments.next()?, segments.next()?);
+    if segments.next().is_some() {
+        return None;
+    }
+
+    let bytes = general_purpose::URL_SAFE_NO_PAD
+        .decode(payload.trim_end_matches('='))
+        .ok()?;
+    serde_json::from_slice::<Value>(&bytes)
+        .ok()
+        .filter(Value::is_object)
+}
+
+/// 载荷中的 `email` 声明 (未验证)
+pub fn unverified_email(token: &str) -> Option<String> {
+    decode_payload_unverified(token)?
+        .get("email")?
+        .as_str()
+        .map(str::trim)
+        .filter(|email| email.contains('@'))
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode_segment(json: &str) -> String {
+        general_purpose::URL_SAFE_NO_PAD.encode(json)
+    }
+
+    #[test]
+    fn test_email_claim_is_read_from_payload() {
+        let token = format!(
+            "{}.{}.signature-not-checked",
+            encode_segment(r#"{"alg":"RS256","typ":"JWT"}"#),
+            encode_segment(r#"{"sub":"1234567890","email":"alice@example.com","email_verified":true,"exp":1700000000}"#),
+        );
+
+        let payload = decode_payload_unverified(&token).unwrap();
+        assert_eq!(payload["sub"], "1234567890");
+        assert_eq!(unverified_email(&token).as_deref(), Some("alice@example.com"));
+
+        // 带 padding 的载荷同样可解码
+        let padded = format!(
+            "h.{}.s",
+            general_purpose::URL_SAFE.encode(r#"{"email":"bob@example.com"}"#)
+        );
+        assert_eq!(unverified_email(&padded).as_deref(), Some("bob@example.com"));
+    }
+
+    #[test]
+    fn test_non_jwt_tokens_yield_no_email() {
+        // Google 的 access_token 通常是不透明字符串
+        assert_eq!(unverified_email("ya29.a0AfB_byC-opaque-token"), None);
+        assert_eq!(unverified_email("a.b.c.d"), None);
+        assert_eq!(unverified_email(&format!("h.{}.s", encode_segment("[1,2]"))), None);
+        assert_eq!(
+            unverified_email(&format!("h.{}.s", encode_segment(r#"{"sub":"42"}"#))),
+            None
+        );
+        assert_eq!(
+            unverified_email(&format!("h.{}.s", encode_segment(r#"{"email":"not-an-email"}"#))),
+            None
+        );
+    }
+}
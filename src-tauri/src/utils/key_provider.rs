@@ -0,0 +1,200 @@
+// 加密密钥来源：系统密钥库不可用时，回退为应用数据目录下的密钥文件
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use sha2::Digest;
+
+/// 默认密钥文件名 (位于应用数据目录)
+pub const KEY_FILE_NAME: &str = "encryption.key";
+
+/// 提供 32 字节的 AES-256 密钥
+pub trait KeyProvider {
+    fn encryption_key(&self) -> Result<[u8; 32], String>;
+}
+
+/// 基于文件的密钥存储
+///
+/// 文件内容为随机生成的 64 位十六进制字符串；Unix 下以 `0600` 创建，
+/// 读取时若发现对组/其他用户可读写则拒绝使用 (或在允许时修正权限)
+#[derive(Debug, Clone)]
+pub struct FileKeyProvider {
+    path: PathBuf,
+    repair_permissions: bool,
+}
+
+impl FileKeyProvider {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            repair_permissions: false,
+        }
+    }
+
+    /// 使用应用数据目录下的默认密钥文件
+    pub fn in_data_dir() -> Result<Self, String> {
+        let dir = crate::modules::account::get_data_dir()?;
+        Ok(Self::new(dir.join(KEY_FILE_NAME)))
+    }
+
+    /// 权限过宽时自动收紧为 `0600` 而不是拒绝使用
+    pub fn with_repair_permissions(mut self, repair: bool) -> Self {
+        self.repair_permissions = repair;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 读取已有密钥，不存在时生成并持久化
+    pub fn load_or_create_secret(&self) -> Result<String, String> {
+        if self.path.exists() {
+            if let Some(secret) = self.read_secret()? {
+                return Ok(secret);
+            }
+        }
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create key directory: {}", e))?;
+        }
+
+        let bytes: [u8; 32] = rand::random();
+        let secret: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+
+        match options.open(&self.path) {
+            Ok(mut file) => {
+                file.write_all(secret.as_bytes())
+                    .map_err(|e| format!("Failed to write key file: {}", e))?;
+                Ok(secret)
+            }
+            // 并发进程抢先创建：以磁盘上的值为准
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => self
+                .read_secret()?
+                .ok_or_else(|| "Key file exists but is empty".to_string()),
+            Err(e) => Err(format!("Failed to create key file: {}", e)),
+        }
+    }
+
+    /// 校验权限后读取密钥，空文件返回 None
+    fn read_secret(&self) -> Result<Option<String>, String> {
+        self.check_permissions()?;
+        let content =
+            fs::read_to_string(&self.path).map_err(|e| format!("Failed to read key file: {}", e))?;
+        let secret = content.trim();
+        Ok((!secret.is_empty()).then(|| secret.to_string()))
+    }
+
+    #[cfg(unix)]
+    fn check_permissions(&self) -> Result<(), String> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mode = fs::metadata(&self.path)
+            .map_err(|e| format!("Failed to stat key file: {}", e))?
+            .permissions()
+            .mode();
+        if mode & 0o077 == 0 {
+            return Ok(());
+        }
+
+        tracing::warn!(
+            "Key file {:?} has insecure permissions {:o}",
+            self.path,
+            mode & 0o777
+        );
+        if !self.repair_permissions {
+            return Err(format!(
+                "Key file {:?} is accessible by other users (mode {:o}), refusing to use it",
+                self.path,
+                mode & 0o777
+            ));
+        }
+
+        fs::set_permissions(&self.path, fs::Permissions::from_mode(0o600))
+            .map_err(|e| format!("Failed to restrict key file permissions: {}", e))?;
+        tracing::info!("Restricted key file {:?} permissions to 600", self.path);
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    fn check_permissions(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+impl KeyProvider for FileKeyProvider {
+    fn encryption_key(&self) -> Result<[u8; 32], String> {
+        let secret = self.load_or_create_secret()?;
+        let mut key = [0u8; 32];
+        key.copy_from_slice(&sha2::Sha256::digest(secret.as_bytes()));
+        Ok(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_key_path() -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("ag_key_provider_{}", uuid::Uuid::new_v4()))
+            .join(KEY_FILE_NAME)
+    }
+
+    #[test]
+    fn test_key_is_stable_across_loads() {
+        let path = temp_key_path();
+        let first = FileKeyProvider::new(&path).encryption_key().unwrap();
+        let second = FileKeyProvider::new(&path).encryption_key().unwrap();
+        assert_eq!(first, second);
+
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_created_key_file_has_0600_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = temp_key_path();
+        FileKeyProvider::new(&path).load_or_create_secret().unwrap();
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_too_open_key_file_is_refused_or_repaired() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = temp_key_path();
+        let provider = FileKeyProvider::new(&path);
+        let secret = provider.load_or_create_secret().unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        assert!(provider.load_or_create_secret().is_err());
+
+        let repaired = provider
+            .clone()
+            .with_repair_permissions(true)
+            .load_or_create_secret()
+            .unwrap();
+        assert_eq!(repaired, secret);
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
@@ -1,4 +1,11 @@
 pub mod http;
 pub mod protobuf;
 pub mod crypto;
+pub mod key_provider;
 pub mod command;
+pub mod email;
+pub mod jwt;
+pub mod clock;
+pub mod scheduler;
+
+pub use email::{emails_match, normalize_email};
@@ -1,3 +1,5 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
 /// Protobuf Varint Encoding
 pub fn encode_varint(mut value: u64) -> Vec<u8> {
     let mut buf = Vec::new();
@@ -26,6 +28,9 @@ pub fn read_varint(data: &[u8], offset: usize) -> Result<(u64, usize), String> {
             break;
         }
         shift += 7;
+        if shift >= 64 {
+            return Err("varint_overflow".to_string());
+        }
     }
 
     Ok((result, pos))
@@ -33,27 +38,99 @@ pub fn read_varint(data: &[u8], offset: usize) -> Result<(u64, usize), String> {
 
 /// Skip Protobuf Field
 pub fn skip_field(data: &[u8], offset: usize, wire_type: u8) -> Result<usize, String> {
-    match wire_type {
+    let end = match wire_type {
         0 => {
             // Varint
             let (_, new_offset) = read_varint(data, offset)?;
-            Ok(new_offset)
+            new_offset
         }
         1 => {
             // 64-bit
-            Ok(offset + 8)
+            offset.saturating_add(8)
         }
         2 => {
             // Length-delimited
             let (length, content_offset) = read_varint(data, offset)?;
-            Ok(content_offset + length as usize)
+            content_offset.saturating_add(length as usize)
         }
         5 => {
             // 32-bit
-            Ok(offset + 4)
+            offset.saturating_add(4)
+        }
+        _ => return Err(format!("unknown_wire_type: {}", wire_type)),
+    };
+    // 截断的数据不能越界切片
+    if end > data.len() {
+        return Err("incomplete_data".to_string());
+    }
+    Ok(end)
+}
+
+/// Protobuf 线格式类型 (不含已废弃的 group 类型 3/4)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WireType {
+    Varint,
+    Fixed64,
+    LengthDelimited,
+    Fixed32,
+}
+
+impl WireType {
+    pub fn from_u8(wire_type: u8) -> Option<Self> {
+        match wire_type {
+            0 => Some(Self::Varint),
+            1 => Some(Self::Fixed64),
+            2 => Some(Self::LengthDelimited),
+            5 => Some(Self::Fixed32),
+            _ => None,
+        }
+    }
+}
+
+/// 列出顶层出现的所有字段：(字段号, 线格式类型, 值的字节长度)
+///
+/// 用于诊断：IDE 调整 protobuf 结构后 `find_field` 只会返回 `Ok(None)`，
+/// 该结果可以附在问题反馈中，直接看出新格式里实际有哪些字段。
+pub fn scan_fields(data: &[u8]) -> Result<Vec<(u32, WireType, usize)>, String> {
+    let mut fields = Vec::new();
+    let mut offset = 0;
+
+    while offset < data.len() {
+        let (tag, value_offset) = read_varint(data, offset)
+            .map_err(|e| format!("{} (tag at offset {})", e, offset))?;
+        let field_num = (tag >> 3) as u32;
+        let wire_type = WireType::from_u8((tag & 7) as u8).ok_or_else(|| {
+            format!("unknown_wire_type: {} (field {} at offset {})", tag & 7, field_num, offset)
+        })?;
+
+        let (length, next_offset) = match wire_type {
+            WireType::Varint => {
+                let (_, end) = read_varint(data, value_offset)?;
+                (end - value_offset, end)
+            }
+            WireType::Fixed64 => (8, value_offset + 8),
+            WireType::Fixed32 => (4, value_offset + 4),
+            WireType::LengthDelimited => {
+                let (length, content_offset) = read_varint(data, value_offset)?;
+                (length as usize, content_offset.saturating_add(length as usize))
+            }
+        };
+        if next_offset > data.len() {
+            return Err(format!(
+                "truncated_field: {} at offset {} needs {} bytes, {} available",
+                field_num,
+                offset,
+                length,
+                data.len().saturating_sub(next_offset - length)
+            ));
         }
-        _ => Err(format!("unknown_wire_type: {}", wire_type)),
+
+        fields.push((field_num, wire_type, length));
+        offset = next_offset;
     }
+
+    Ok(fields)
 }
 
 /// Remove specified Protobuf field
@@ -95,10 +172,9 @@ pub fn find_field(data: &[u8], target_field: u32) -> Result<Option<Vec<u8>>, Str
         let field_num = (tag >> 3) as u32;
 
         if field_num == target_field && wire_type == 2 {
-            let (length, content_offset) = read_varint(data, new_offset)?;
-            return Ok(Some(
-                data[content_offset..content_offset + length as usize].to_vec(),
-            ));
+            let (_, content_offset) = read_varint(data, new_offset)?;
+            let end = skip_field(data, new_offset, wire_type)?;
+            return Ok(Some(data[content_offset..end].to_vec()));
         }
 
         // Skip field
@@ -108,6 +184,82 @@ pub fn find_field(data: &[u8], target_field: u32) -> Result<Option<Vec<u8>>, Str
     Ok(None)
 }
 
+/// 按出现顺序返回指定字段的全部内容 (Length-Delimited only，用于 repeated 字段)
+pub fn find_all_fields(data: &[u8], target_field: u32) -> Result<Vec<Vec<u8>>, String> {
+    let mut values = Vec::new();
+    let mut offset = 0;
+
+    while offset < data.len() {
+        let (tag, new_offset) = match read_varint(data, offset) {
+            Ok(v) => v,
+            Err(_) => break, // Incomplete data, stop
+        };
+
+        let wire_type = (tag & 7) as u8;
+        let field_num = (tag >> 3) as u32;
+        let next_offset = skip_field(data, new_offset, wire_type)?;
+
+        if field_num == target_field && wire_type == 2 {
+            let (_, content_offset) = read_varint(data, new_offset)?;
+            values.push(data[content_offset..next_offset].to_vec());
+        }
+        offset = next_offset;
+    }
+
+    Ok(values)
+}
+
+/// 嵌套字段路径的默认最大深度
+pub const DEFAULT_MAX_NESTING_DEPTH: usize = 32;
+
+/// 当前生效的最大嵌套深度 (可通过配置 `token_field_paths.max_nesting_depth` 调整)
+static MAX_NESTING_DEPTH: AtomicUsize = AtomicUsize::new(DEFAULT_MAX_NESTING_DEPTH);
+
+pub fn max_nesting_depth() -> usize {
+    MAX_NESTING_DEPTH.load(Ordering::Relaxed)
+}
+
+pub fn set_max_nesting_depth(depth: usize) {
+    MAX_NESTING_DEPTH.store(depth, Ordering::Relaxed);
+}
+
+/// 沿字段号路径逐层查找嵌套的 length-delimited 字段
+///
+/// 例如 `find_field_path(outer, &[1, 2, 1])` 等价于连续三次 `find_field`；
+/// 路径中任意一层缺失时返回 `Ok(None)`。路径深度超过 `max_nesting_depth()` 时返回错误。
+pub fn find_field_path(data: &[u8], path: &[u32]) -> Result<Option<Vec<u8>>, String> {
+    find_field_path_with_depth(data, path, max_nesting_depth())
+}
+
+/// 同 `find_field_path`，使用指定的最大嵌套深度
+///
+/// 每下降一层都会复制并重新扫描内层数据：构造的深层嵌套 blob 配合超长路径
+/// 会放大解析开销，因此在开始解析前即拒绝超出深度的路径。
+pub fn find_field_path_with_depth(
+    data: &[u8],
+    path: &[u32],
+    max_depth: usize,
+) -> Result<Option<Vec<u8>>, String> {
+    if path.len() > max_depth {
+        return Err(format!(
+            "Protobuf field path depth {} exceeds limit {}",
+            path.len(),
+            max_depth
+        ));
+    }
+
+    let mut current = data.to_vec();
+
+    for &field_num in path {
+        match find_field(&current, field_num)? {
+            Some(inner) => current = inner,
+            None => return Ok(None),
+        }
+    }
+
+    Ok(Some(current))
+}
+
 /// Create OAuthTokenInfo (Field 6)
 /// 
 /// Structure:
@@ -360,3 +512,306 @@ pub fn create_string_value_payload(value: &str) -> Vec<u8> {
 pub fn create_minimal_user_status_payload(email: &str) -> Vec<u8> {
     [encode_string_field(3, email), encode_string_field(7, email)].concat()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_field_path_multi_level() {
+        let innermost = encode_string_field(1, "token-value");
+        let middle = [encode_varint_field(1, 7), encode_len_delim_field(2, &innermost)].concat();
+        let outer = [encode_string_field(3, "noise"), encode_len_delim_field(1, &middle)].concat();
+
+        assert_eq!(
+            find_field_path(&outer, &[1, 2, 1]).unwrap(),
+            Some(b"token-value".to_vec())
+        );
+        assert_eq!(find_field_path(&outer, &[1, 2]).unwrap(), Some(innermost));
+        assert_eq!(find_field_path(&outer, &[]).unwrap(), Some(outer.clone()));
+    }
+
+    #[test]
+    fn test_find_field_path_breaks_midway() {
+        let middle = encode_string_field(5, "other");
+        let outer = encode_len_delim_field(1, &middle);
+
+        assert_eq!(find_field_path(&outer, &[1, 2, 1]).unwrap(), None);
+        assert_eq!(find_field_path(&outer, &[9]).unwrap(), None);
+    }
+
+    #[test]
+    fn test_find_field_path_rejects_nesting_beyond_limit() {
+        let limit = DEFAULT_MAX_NESTING_DEPTH;
+        let mut blob = encode_string_field(1, "deep");
+        for _ in 0..limit + 8 {
+            blob = encode_len_delim_field(1, &blob);
+        }
+
+        let too_deep = vec![1; limit + 9];
+        let err = find_field_path_with_depth(&blob, &too_deep, limit).unwrap_err();
+        assert!(err.contains("exceeds limit"), "unexpected error: {}", err);
+
+        // 限制以内的路径照常解析
+        let within = vec![1; limit];
+        assert!(find_field_path_with_depth(&blob, &within, limit).unwrap().is_some());
+        assert_eq!(
+            find_field_path_with_depth(&blob, &too_deep, limit + 9).unwrap(),
+            Some(b"deep".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_scan_fields_reports_mixed_types() {
+        let data = [
+            encode_varint_field(1, 300),
+            encode_string_field(2, "user@test.com"),
+            {
+                let mut f = encode_varint((3 << 3) | 1);
+                f.extend(42u64.to_le_bytes());
+                f
+            },
+            {
+                let mut f = encode_varint((4 << 3) | 5);
+                f.extend(7u32.to_le_bytes());
+                f
+            },
+            encode_len_delim_field(6, &create_oauth_info("atk", "rtk", 1_700_000_000, true)),
+            encode_varint_field(2, 1),
+        ]
+        .concat();
+
+        let fields = scan_fields(&data).unwrap();
+        assert_eq!(
+            fields,
+            vec![
+                (1, WireType::Varint, 2),
+                (2, WireType::LengthDelimited, 13),
+                (3, WireType::Fixed64, 8),
+                (4, WireType::Fixed32, 4),
+                (6, WireType::LengthDelimited, create_oauth_info("atk", "rtk", 1_700_000_000, true).len()),
+                (2, WireType::Varint, 1),
+            ]
+        );
+        assert!(scan_fields(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_scan_fields_rejects_truncated_and_unknown_wire_types() {
+        let mut truncated = encode_string_field(2, "user@test.com");
+        truncated.truncate(truncated.len() - 3);
+        assert!(scan_fields(&truncated).unwrap_err().contains("truncated_field: 2"));
+
+        // wire type 3 (start group) 不受支持
+        let group = encode_varint((5 << 3) | 3);
+        assert!(scan_fields(&group).unwrap_err().contains("unknown_wire_type: 3"));
+    }
+}
+
+/// 随机化往返测试：生成合法的 protobuf 缓冲区，断言查找函数取回的正是写入的值；
+/// 并以随机垃圾数据 / 截断 / 翻转字节的缓冲区喂给解析函数，断言不会 panic。
+///
+/// 使用固定种子，失败时可通过打印的种子复现。
+#[cfg(test)]
+mod fuzz_tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    const ITERATIONS: u64 = 500;
+    /// protobuf 允许的最大字段号 (2^29 - 1)
+    const MAX_FIELD_NUM: u32 = (1 << 29) - 1;
+
+    #[derive(Debug, Clone)]
+    enum Value {
+        Varint(u64),
+        Fixed64(u64),
+        Fixed32(u32),
+        Bytes(Vec<u8>),
+    }
+
+    impl Value {
+        fn wire_type(&self) -> WireType {
+            match self {
+                Value::Varint(_) => WireType::Varint,
+                Value::Fixed64(_) => WireType::Fixed64,
+                Value::Fixed32(_) => WireType::Fixed32,
+                Value::Bytes(_) => WireType::LengthDelimited,
+            }
+        }
+
+        fn encode(&self, field_num: u32) -> Vec<u8> {
+            let tag = |wire_type: u64| encode_varint(((field_num as u64) << 3) | wire_type);
+            match self {
+                Value::Varint(v) => encode_varint_field(field_num, *v),
+                Value::Fixed64(v) => [tag(1), v.to_le_bytes().to_vec()].concat(),
+                Value::Fixed32(v) => [tag(5), v.to_le_bytes().to_vec()].concat(),
+                Value::Bytes(b) => encode_len_delim_field(field_num, b),
+            }
+        }
+
+        fn encoded_len(&self) -> usize {
+            match self {
+                Value::Varint(v) => encode_varint(*v).len(),
+                Value::Fixed64(_) => 8,
+                Value::Fixed32(_) => 4,
+                Value::Bytes(b) => b.len(),
+            }
+        }
+    }
+
+    fn random_field_num(rng: &mut StdRng) -> u32 {
+        // 偏向小字段号，使同一字段重复出现 (repeated) 的情况足够常见
+        if rng.gen_bool(0.8) {
+            rng.gen_range(1..=8)
+        } else {
+            rng.gen_range(1..=MAX_FIELD_NUM)
+        }
+    }
+
+    fn random_bytes(rng: &mut StdRng, max_len: usize) -> Vec<u8> {
+        let len = rng.gen_range(0..=max_len);
+        (0..len).map(|_| rng.gen()).collect()
+    }
+
+    fn random_value(rng: &mut StdRng) -> Value {
+        match rng.gen_range(0..4) {
+            0 => Value::Varint(match rng.gen_range(0..3) {
+                0 => rng.gen_range(0..128),
+                1 => rng.gen_range(0..1 << 32),
+                _ => rng.gen(),
+            }),
+            1 => Value::Fixed64(rng.gen()),
+            2 => Value::Fixed32(rng.gen()),
+            _ => Value::Bytes(random_bytes(rng, 64)),
+        }
+    }
+
+    fn random_message(rng: &mut StdRng) -> Vec<(u32, Value)> {
+        let count = rng.gen_range(0..16);
+        (0..count)
+            .map(|_| (random_field_num(rng), random_value(rng)))
+            .collect()
+    }
+
+    fn encode_message(fields: &[(u32, Value)]) -> Vec<u8> {
+        fields.iter().flat_map(|(num, value)| value.encode(*num)).collect()
+    }
+
+    fn bytes_of(fields: &[(u32, Value)], target: u32) -> Vec<Vec<u8>> {
+        fields
+            .iter()
+            .filter(|(num, _)| *num == target)
+            .filter_map(|(_, value)| match value {
+                Value::Bytes(b) => Some(b.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// 调用所有解析函数，只关心是否 panic
+    fn parse_everything(data: &[u8], field_num: u32) {
+        let _ = read_varint(data, 0);
+        let _ = scan_fields(data);
+        let _ = find_field(data, field_num);
+        let _ = find_all_fields(data, field_num);
+        let _ = find_field_path(data, &[field_num, 1, 2]);
+        let _ = find_varint_field(data, field_num);
+        let _ = remove_field(data, field_num);
+        for wire_type in 0..8 {
+            let _ = skip_field(data, 0, wire_type);
+        }
+    }
+
+    #[test]
+    fn fuzz_find_field_round_trip() {
+        for seed in 0..ITERATIONS {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let fields = random_message(&mut rng);
+            let data = encode_message(&fields);
+
+            let scanned = scan_fields(&data).unwrap_or_else(|e| panic!("seed {}: {}", seed, e));
+            let expected: Vec<(u32, WireType, usize)> = fields
+                .iter()
+                .map(|(num, value)| (*num, value.wire_type(), value.encoded_len()))
+                .collect();
+            assert_eq!(scanned, expected, "seed {}", seed);
+
+            let mut targets: Vec<u32> = fields.iter().map(|(num, _)| *num).collect();
+            targets.push(random_field_num(&mut rng));
+            for target in targets {
+                let all = bytes_of(&fields, target);
+                let context = format!("seed {} field {}", seed, target);
+                assert_eq!(find_all_fields(&data, target).unwrap(), all, "{}", context);
+                assert_eq!(find_field(&data, target).unwrap(), all.first().cloned(), "{}", context);
+
+                let first_varint = fields.iter().find_map(|(num, value)| match value {
+                    Value::Varint(v) if *num == target => Some(*v),
+                    _ => None,
+                });
+                assert_eq!(find_varint_field(&data, target).unwrap(), first_varint, "{}", context);
+
+                let remaining: Vec<(u32, Value)> =
+                    fields.iter().filter(|(num, _)| *num != target).cloned().collect();
+                assert_eq!(remove_field(&data, target).unwrap(), encode_message(&remaining), "{}", context);
+            }
+        }
+    }
+
+    #[test]
+    fn fuzz_find_field_path_nested_round_trip() {
+        for seed in 0..ITERATIONS {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let depth = rng.gen_range(1..=4);
+            let path: Vec<u32> = (0..depth).map(|_| random_field_num(&mut rng)).collect();
+            let leaf = random_bytes(&mut rng, 32);
+
+            // 由内向外逐层包装，每层前后混入与路径字段号不同的随机字段
+            let mut data = leaf.clone();
+            for &field_num in path.iter().rev() {
+                let noise = |rng: &mut StdRng| -> Vec<u8> {
+                    let fields: Vec<(u32, Value)> = random_message(rng)
+                        .into_iter()
+                        .filter(|(num, _)| *num != field_num)
+                        .collect();
+                    encode_message(&fields)
+                };
+                let (before, after) = (noise(&mut rng), noise(&mut rng));
+                data = [before, encode_len_delim_field(field_num, &data), after].concat();
+            }
+
+            assert_eq!(find_field_path(&data, &path).unwrap(), Some(leaf), "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn fuzz_garbage_input_never_panics() {
+        for seed in 0..ITERATIONS {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let field_num = random_field_num(&mut rng);
+
+            // 纯随机字节
+            parse_everything(&random_bytes(&mut rng, 64), field_num);
+
+            // 合法消息被截断或翻转若干字节
+            let valid = encode_message(&random_message(&mut rng));
+            if !valid.is_empty() {
+                let cut = rng.gen_range(0..valid.len());
+                parse_everything(&valid[..cut], field_num);
+
+                let mut corrupted = valid.clone();
+                for _ in 0..rng.gen_range(1..=4) {
+                    let idx = rng.gen_range(0..corrupted.len());
+                    corrupted[idx] = rng.gen();
+                }
+                parse_everything(&corrupted, field_num);
+            }
+
+            // 超长 varint 与超大长度前缀
+            parse_everything(&[0xFF; 12], field_num);
+            let mut huge_len = encode_varint(((field_num as u64) << 3) | 2);
+            huge_len.extend(encode_varint(u64::MAX));
+            parse_everything(&huge_len, field_num);
+        }
+    }
+}
@@ -0,0 +1,229 @@
+// 后台维护任务调度器
+//
+// 统一管理按固定间隔运行的维护任务 (清理过期限流记录、刷新能力缓存等)，替代各处手写的 interval 循环：
+// - 每个任务独立运行，间隔附加随机抖动，避免多个任务 / 多个实例同时触发
+// - 停止或父级取消令牌触发时，等待中与执行中的任务都会在下一个 await 点退出
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rand::Rng;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+/// 默认抖动比例：实际间隔落在 [interval, interval * 1.1) 之间
+pub const DEFAULT_JITTER_RATIO: f64 = 0.1;
+
+type TaskFn = Arc<dyn Fn() -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
+
+#[derive(Clone)]
+struct ScheduledTask {
+    name: String,
+    interval: Duration,
+    run: TaskFn,
+}
+
+struct RunningTask {
+    name: String,
+    cancel: CancellationToken,
+    handle: JoinHandle<()>,
+}
+
+struct Running {
+    cancel: CancellationToken,
+    tasks: Vec<RunningTask>,
+}
+
+/// 周期任务调度器；任务在 `start` 后于当前 tokio 运行时中运行
+pub struct TaskScheduler {
+    jitter_ratio: f64,
+    tasks: Mutex<Vec<ScheduledTask>>,
+    running: Mutex<Option<Running>>,
+}
+
+impl Default for TaskScheduler {
+    fn default() -> Self {
+        Self::new(DEFAULT_JITTER_RATIO)
+    }
+}
+
+impl TaskScheduler {
+    /// `jitter_ratio` 截断到 [0, 1]：每次等待额外增加 `interval * jitter_ratio` 以内的随机时长
+    pub fn new(jitter_ratio: f64) -> Self {
+        Self {
+            jitter_ratio: jitter_ratio.clamp(0.0, 1.0),
+            tasks: Mutex::new(Vec::new()),
+            running: Mutex::new(None),
+        }
+    }
+
+    /// 注册任务；同名任务被替换。调度器运行中时立即按新配置启动 (旧任务被取消)
+    pub fn register<F, Fut>(&self, name: impl Into<String>, interval: Duration, task: F)
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let task = ScheduledTask {
+            name: name.into(),
+            interval,
+            run: Arc::new(move || Box::pin(task())),
+        };
+        {
+            let mut tasks = self.tasks.lock().unwrap_or_else(|e| e.into_inner());
+            tasks.retain(|t| t.name != task.name);
+            tasks.push(task.clone());
+        }
+
+        let mut running = self.running.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(running) = running.as_mut() {
+            if let Some(pos) = running.tasks.iter().position(|t| t.name == task.name) {
+                let old = running.tasks.swap_remove(pos);
+                old.cancel.cancel();
+                old.handle.abort();
+            }
+            running.tasks.push(self.spawn_task(task, &running.cancel));
+        }
+    }
+
+    /// 启动所有已注册任务；`parent` 取消时任务随之停止。已在运行时返回 false
+    pub fn start(&self, parent: &CancellationToken) -> bool {
+        let mut running = self.running.lock().unwrap_or_else(|e| e.into_inner());
+        if running.is_some() {
+            return false;
+        }
+        let cancel = parent.child_token();
+        let tasks = self.tasks.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        let tasks = tasks
+            .into_iter()
+            .map(|task| self.spawn_task(task, &cancel))
+            .collect();
+        *running = Some(Running { cancel, tasks });
+        true
+    }
+
+    /// 取消所有任务并等待其退出；之后可再次 `start`
+    pub async fn stop(&self) {
+        let Some(running) = self.running.lock().unwrap_or_else(|e| e.into_inner()).take() else {
+            return;
+        };
+        running.cancel.cancel();
+        for task in running.tasks {
+            match task.handle.await {
+                Ok(()) => tracing::debug!("[Scheduler] Task '{}' stopped", task.name),
+                Err(e) if e.is_cancelled() => tracing::debug!("[Scheduler] Task '{}' aborted", task.name),
+                Err(e) => tracing::warn!("[Scheduler] Task '{}' failed: {}", task.name, e),
+            }
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_some()
+    }
+
+    /// 已注册的任务名 (按注册顺序)
+    pub fn task_names(&self) -> Vec<String> {
+        self.tasks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|t| t.name.clone())
+            .collect()
+    }
+
+    fn spawn_task(&self, task: ScheduledTask, parent: &CancellationToken) -> RunningTask {
+        let cancel = parent.child_token();
+        let task_cancel = cancel.clone();
+        let jitter_ratio = self.jitter_ratio;
+        let name = task.name.clone();
+        let handle = tokio::spawn(async move {
+            tracing::debug!(
+                "[Scheduler] Task '{}' scheduled every {:?}",
+                task.name,
+                task.interval
+            );
+            loop {
+                let delay = jittered(task.interval, jitter_ratio, rand::thread_rng().gen::<f64>());
+                tokio::select! {
+                    _ = task_cancel.cancelled() => break,
+                    _ = tokio::time::sleep(delay) => {}
+                }
+                tokio::select! {
+                    _ = task_cancel.cancelled() => break,
+                    _ = (task.run)() => {}
+                }
+            }
+        });
+        RunningTask {
+            name,
+            cancel,
+            handle,
+        }
+    }
+}
+
+/// `interval` 加上 `interval * jitter_ratio * sample` 的抖动 (`sample` 取值 [0, 1))
+fn jittered(interval: Duration, jitter_ratio: f64, sample: f64) -> Duration {
+    interval + interval.mul_f64(jitter_ratio * sample.clamp(0.0, 1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn test_registered_task_fires_repeatedly_until_stopped() {
+        let scheduler = TaskScheduler::new(0.5);
+        let runs = Arc::new(AtomicUsize::new(0));
+        let counter = runs.clone();
+        scheduler.register("tick", Duration::from_millis(5), move || {
+            let counter = counter.clone();
+            async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+        assert_eq!(scheduler.task_names(), vec!["tick".to_string()]);
+
+        let root = CancellationToken::new();
+        assert!(scheduler.start(&root));
+        assert!(!scheduler.start(&root), "second start is a no-op");
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while runs.load(Ordering::SeqCst) < 5 {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .expect("task should fire at least 5 times");
+
+        scheduler.stop().await;
+        assert!(!scheduler.is_running());
+        let after_stop = runs.load(Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), after_stop);
+
+        // 父级令牌取消 (如停机) 同样终止任务
+        assert!(scheduler.start(&root));
+        root.cancel();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let after_cancel = runs.load(Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), after_cancel);
+        scheduler.stop().await;
+    }
+
+    #[test]
+    fn test_jitter_stays_within_ratio() {
+        let interval = Duration::from_secs(10);
+        assert_eq!(jittered(interval, 0.2, 0.0), interval);
+        assert_eq!(jittered(interval, 0.2, 0.5), Duration::from_secs(11));
+        assert!(jittered(interval, 0.2, 0.999) < Duration::from_secs(12));
+        assert_eq!(jittered(interval, 0.0, 0.9), interval);
+        assert_eq!(TaskScheduler::new(5.0).jitter_ratio, 1.0);
+    }
+}
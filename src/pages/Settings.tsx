@@ -75,7 +75,8 @@ function Settings() {
         },
         circuit_breaker: {
             enabled: false,
-            backoff_steps: [30, 60, 120, 300, 600]
+            backoff_steps: [30, 60, 120, 300, 600],
+            validation_block_steps: [600, 1800, 7200]
         },
         hidden_menu_items: [],  // 菜单显示设置：默认不隐藏任何菜单项
 
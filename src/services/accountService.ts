@@ -1,5 +1,5 @@
 import i18n from '../i18n';
-import { Account, DeviceProfile, DeviceProfileVersion, QuotaData } from '../types/account';
+import { Account, AccountSource, DeviceProfile, DeviceProfileVersion, ModelCapacity, ProxyTokenView, ProbeResult, PurgeReport, QuotaData, QuotaDiff, QuotaSnapshot, SelectionTrace } from '../types/account';
 import { request as invoke } from '../utils/request';
 
 // 检查环境 (可选)
@@ -20,6 +20,10 @@ export async function listAccounts(): Promise<Account[]> {
     return response || [];
 }
 
+export async function listAccountsBySource(source: AccountSource): Promise<Account[]> {
+    return await invoke('list_accounts_by_source', { source });
+}
+
 export async function getCurrentAccount(): Promise<Account | null> {
     return await invoke('get_current_account');
 }
@@ -36,8 +40,13 @@ export async function deleteAccounts(accountIds: string[]): Promise<void> {
     return await invoke('delete_accounts', { accountIds });
 }
 
-export async function switchAccount(accountId: string): Promise<void> {
-    return await invoke('switch_account', { accountId });
+// 清除全部账号及凭据 (不可恢复，confirm 必须为 true)
+export async function purgeAllAccounts(confirm: boolean): Promise<PurgeReport> {
+    return await invoke('purge_all_accounts', { confirm });
+}
+
+export async function switchAccount(accountId: string, force?: boolean): Promise<void> {
+    return await invoke('switch_account', { accountId, force });
 }
 
 export async function fetchAccountQuota(accountId: string): Promise<QuotaData> {
@@ -120,18 +129,105 @@ export async function setActiveOAuthClient(clientKey: string): Promise<void> {
 }
 
 // 导入
-export async function importV1Accounts(): Promise<Account[]> {
-    return await invoke('import_v1_accounts');
+export type EmailFilter =
+    | { type: 'domain'; value: string }
+    | { type: 'contains'; value: string }
+    | { type: 'regex'; value: string };
+
+export interface SkippedAccount {
+    email: string;
+    reason: 'FilteredOut' | 'CapReached';
+}
+
+export interface V1ImportReport {
+    imported: Account[];
+    skipped: SkippedAccount[];  // 不匹配过滤条件或超出数量上限而未导入的条目
+}
+
+export async function importV1Accounts(filter?: EmailFilter, maxAccounts?: number): Promise<V1ImportReport> {
+    return await invoke('import_v1_accounts', { filter, maxAccounts });
+}
+
+export type IndexProblem =
+    | { kind: 'not_an_object' }
+    | { kind: 'missing_field' }
+    | { kind: 'wrong_type'; expected: string }
+    | { kind: 'empty' };
+
+export interface IndexIssue {
+    account_id: string | null;
+    field: string | null;
+    problem: IndexProblem;
+}
+
+export interface V1IndexValidation {
+    index_file: string;
+    issues: IndexIssue[];
+}
+
+// 检查 V1 索引文件而不导入 (dry run)
+export async function validateV1Index(): Promise<V1IndexValidation[]> {
+    return await invoke('validate_v1_index');
+}
+
+export interface AccountVerification {
+    account_id: string;
+    email: string;
+    verified: boolean;
+    reason: string | null;
+}
+
+export interface VerifyReport {
+    total: number;
+    verified: number;
+    accounts: AccountVerification[];
+    cancelled: boolean;
+}
+
+// 导入后验证账号可用 (刷新 token + 获取用户信息)
+export async function verifyImportedAccounts(accountIds: string[], concurrency?: number): Promise<VerifyReport> {
+    return await invoke('verify_imported_accounts', { accountIds, concurrency });
+}
+
+// 取消进行中的导入验证 (未验证的账号在报告中记为未验证)
+export async function cancelImportVerification(): Promise<void> {
+    return await invoke('cancel_import_verification');
+}
+
+// 仅导入 V1 索引中记录的当前账号 (current_account_id)
+export async function importCurrentV1Account(): Promise<Account> {
+    return await invoke('import_current_v1_account');
+}
+
+export interface IdeInstallation {
+    label: 'Stable' | 'Insiders' | 'Unknown';
+    user_data_dir: string;
+    db_path: string;
+}
+
+export async function listIdeInstallations(): Promise<IdeInstallation[]> {
+    return await invoke('list_ide_installations');
 }
 
 export async function importFromDb(): Promise<Account> {
     return await invoke('import_from_db');
 }
 
+// 通过运行中 IDE 的本地 API 导入 (未指定端口时读取 ABV_IDE_API_PORT，端点不可用时回退到数据库)
+export async function importFromLocalIdeApi(port?: number): Promise<Account> {
+    return await invoke('import_from_local_ide_api', { port: port ?? null });
+}
+
 export async function importFromCustomDb(path: string): Promise<Account> {
     return await invoke('import_custom_db', { path });
 }
 
+export type DbFormat = 'new_unified' | 'legacy_jetski' | 'v1_backup_json' | 'unknown';
+
+export async function detectImportFormat(path: string): Promise<DbFormat> {
+    return await invoke('detect_import_format', { path });
+}
+
 export async function syncAccountFromDb(): Promise<Account | null> {
     return await invoke('sync_account_from_db');
 }
@@ -214,6 +310,67 @@ export async function exportAccounts(accountIds: string[]): Promise<ExportAccoun
     return await invoke('export_accounts', { accountIds });
 }
 
+export type SummaryExportFormat = 'csv' | 'jsonl';
+
+export async function exportAccountSummary(format: SummaryExportFormat): Promise<string> {
+    return await invoke('export_account_summary', { format });
+}
+
+// 手动指定订阅等级 (null 表示恢复使用上游返回的等级)
+export async function setAccountTierOverride(accountId: string, tier: string | null): Promise<Account> {
+    return await invoke('set_account_tier_override', { accountId, tier });
+}
+
+// 启用 / 停用账号 (软禁用，保留账号数据)
+export async function setAccountEnabled(email: string, enabled: boolean): Promise<Account> {
+    return await invoke('set_account_enabled', { email, enabled });
+}
+
+// 反代账号池运行时状态 (等级 / 配额 / 健康度)，不包含令牌
+export async function getProxyTokenViews(): Promise<ProxyTokenView[]> {
+    return await invoke('get_proxy_token_views');
+}
+
+// 反代账号池对指定模型的总可用容量 (未封禁且可服务该模型的账号)
+export async function getProxyModelCapacity(model: string): Promise<ModelCapacity> {
+    return await invoke('get_proxy_model_capacity', { model });
+}
+
+// 为模型固定账号 (该账号可服务时优先选中)，持久化保存
+export async function pinProxyAccount(model: string, email: string): Promise<void> {
+    return await invoke('pin_proxy_account', { model, email });
+}
+
+// 取消模型的固定账号，返回之前是否存在固定
+export async function unpinProxyAccount(model: string): Promise<boolean> {
+    return await invoke('unpin_proxy_account', { model });
+}
+
+// 所有模型固定账号 (标准化模型 ID -> 账号邮箱)
+export async function getProxyPinnedAccounts(): Promise<Record<string, string>> {
+    return await invoke('get_proxy_pinned_accounts');
+}
+
+// 反代账号池当前的配额快照
+export async function getProxyQuotaSnapshot(): Promise<QuotaSnapshot> {
+    return await invoke('get_proxy_quota_snapshot');
+}
+
+// 比较两次配额快照，得到各账号各模型的消耗
+export async function diffProxyQuotaSnapshots(old: QuotaSnapshot, next: QuotaSnapshot): Promise<QuotaDiff> {
+    return await invoke('diff_proxy_quota_snapshots', { old, new: next });
+}
+
+// 使用指定账号真实发送一次最小请求，验证模型是否可用 (失败会更新该账号的健康状态)
+export async function probeProxyModel(email: string, model: string): Promise<ProbeResult> {
+    return await invoke('probe_proxy_model', { email, model });
+}
+
+// 目标模型的选择诊断 (排序位置、针对该模型的健康状态与跳过原因)
+export async function getProxySelectionTrace(model: string): Promise<SelectionTrace> {
+    return await invoke('get_proxy_selection_trace', { model });
+}
+
 // 自定义标签相关
 export async function updateAccountLabel(accountId: string, label: string): Promise<void> {
     return await invoke('update_account_label', { accountId, label });
@@ -1,6 +1,7 @@
 export interface Account {
     id: string;
     email: string;
+    subject?: string;
     name?: string;
     token: TokenData;
     device_profile?: DeviceProfile;
@@ -9,19 +10,26 @@ export interface Account {
     disabled?: boolean;
     disabled_reason?: string;
     disabled_at?: number;
+    enabled?: boolean;  // 用户软禁用开关，false 时不参与反代选择 (缺省为 true)
     proxy_disabled?: boolean;
     proxy_disabled_reason?: string;
     proxy_disabled_at?: number;
     protected_models?: string[];
     custom_label?: string;  // 用户自定义标签
+    tier_override?: string;  // 手动指定的订阅等级，优先于上游返回值
+    extra_headers?: Record<string, string>;  // 该账号上游请求附加的请求头
     validation_blocked?: boolean;
     validation_blocked_until?: number;
     validation_blocked_reason?: string;
     validation_url?: string;
     created_at: number;
     last_used: number;
+    last_refreshed?: number;  // 最近一次 Token 刷新/配额查询成功的时间 (秒)
+    source?: AccountSource;  // 首次导入时的来源
 }
 
+export type AccountSource = 'v1_migration' | 'live_db' | 'manual_token' | 'jsonl' | 'env';
+
 export interface TokenData {
     access_token: string;
     refresh_token: string;
@@ -29,6 +37,8 @@ export interface TokenData {
     expiry_timestamp: number;
     token_type: string;
     email?: string;
+    scopes?: string[];
+    email_unverified?: boolean;  // email 仅来自令牌 JWT 声明，未经 userinfo 确认
 }
 
 export interface QuotaData {
@@ -44,6 +54,7 @@ export interface ModelQuota {
     name: string;
     percentage: number;
     reset_time: string;
+    quota_max?: number;
     display_name?: string;
     supports_images?: boolean;
     supports_thinking?: boolean;
@@ -69,3 +80,106 @@ export interface DeviceProfileVersion {
     is_current?: boolean;
 }
 
+// 反代账号池中的运行时状态 (不含任何令牌)
+export interface ProxyTokenView {
+    account_id: string;
+    email: string;
+    project_id: string | null;
+    subscription_tier: string | null;
+    tier_override: string | null;
+    remaining_quota: number | null;
+    health_score: number;
+    health_class: 'Healthy' | 'Degraded' | 'Unhealthy' | 'Blocked';
+    reset_time: number | null;
+    validation_blocked_until: number | null;
+    model_quotas: Record<string, number>;
+    last_refreshed: number | null;
+    last_failure: number | null;
+}
+
+export interface TierCapacity {
+    total_quota: number;
+    eligible_accounts: number;
+    projected_requests: number;
+}
+
+// 账号池对某个模型的总可用容量
+export interface ModelCapacity {
+    total_quota: number;
+    eligible_accounts: number;
+    projected_requests: number;  // 按模型成本估算还能支撑的请求数
+    by_tier: Record<string, TierCapacity>;  // 按订阅等级 (大写，无等级为 UNKNOWN) 分组
+}
+
+// 单次模型探测结果
+export interface ProbeResult {
+    email: string;
+    model: string;
+    success: boolean;
+    status?: number;  // 上游 HTTP 状态码 (网络错误时缺省)
+    latency_ms: number;
+    error?: string;
+}
+
+// 账号池配额快照
+export interface AccountQuotaSnapshot {
+    email: string;
+    reset_time: number | null;
+    model_quotas: Record<string, number>;
+    quota_max: Record<string, number>;
+}
+
+export interface QuotaSnapshot {
+    taken_at: number;
+    accounts: Record<string, AccountQuotaSnapshot>;  // 按 account_id
+}
+
+export interface ModelConsumption {
+    model: string;
+    before: number;
+    after: number;
+    consumed: number;  // 始终非负
+    reset: boolean;    // 两次快照之间发生了配额刷新
+}
+
+export interface AccountQuotaDiff {
+    account_id: string;
+    email: string;
+    models: ModelConsumption[];
+}
+
+// 两次配额快照之间的消耗
+export interface QuotaDiff {
+    elapsed_secs: number;
+    accounts: AccountQuotaDiff[];
+    total_consumed: Record<string, number>;
+}
+
+// 清除全部账号的结果
+export interface PurgeReport {
+    accounts_removed: number;
+    files_removed: number;
+    bytes_overwritten: number;
+}
+
+// 选择诊断：账号未进入候选排序的原因
+export type TraceSkipReason = 'disabled' | 'no_capability' | 'below_min_tier' | 'model_breaker_open';
+
+export interface CandidateTrace {
+    account_id: string;
+    email: string;
+    tier: string | null;
+    model_quota: number | null;
+    health_class: ProxyTokenView['health_class'];
+    model_health_class: ProxyTokenView['health_class'];  // 针对请求模型 (该模型熔断中时为 Blocked)
+    model_breaker_wait_secs: number;
+    rank: number | null;
+    skipped: TraceSkipReason | null;
+}
+
+// 目标模型的选择诊断
+export interface SelectionTrace {
+    model: string;
+    normalized_target: string;
+    candidates: CandidateTrace[];
+}
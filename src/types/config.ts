@@ -25,6 +25,15 @@ export interface ProxyConfig {
     global_system_prompt?: GlobalSystemPromptConfig;
     image_thinking_mode?: 'enabled' | 'disabled'; // [NEW] 图像思维模式开关
     proxy_pool?: ProxyPoolConfig;
+    upstream_rate_limit?: UpstreamRateLimitConfig;
+    account_groups?: Record<string, string[]>; // 账号分组: 组名 -> 账号邮箱 (请求头 X-Account-Group 指定组名时只在组内选号)
+}
+
+/** 全局上游限流配置 (所有账号共享) */
+export interface UpstreamRateLimitConfig {
+    enabled: boolean;
+    requests_per_second: number;
+    burst: number;
 }
 
 // ============================================================================
@@ -66,9 +75,14 @@ export interface DebugLoggingConfig {
 
 export type SchedulingMode = 'CacheFirst' | 'Balance' | 'PerformanceFirst';
 
+export type SelectionPolicy = 'TierFirst' | 'ConserveHighTier';
+
 export interface StickySessionConfig {
     mode: SchedulingMode;
     max_wait_seconds: number;
+    selection_policy?: SelectionPolicy;
+    sticky_ttl_seconds?: number;
+    model_sticky_ttl_seconds?: Record<string, number>;
 }
 
 export type ZaiDispatchMode = 'off' | 'exclusive' | 'pooled' | 'fallback';
@@ -121,6 +135,7 @@ export interface ExperimentalConfig {
 export interface CircuitBreakerConfig {
     enabled: boolean;
     backoff_steps: number[];
+    validation_block_steps: number[];
 }
 
 export interface AppConfig {
@@ -144,6 +159,24 @@ export interface AppConfig {
     circuit_breaker: CircuitBreakerConfig; // [NEW] 熔断器配置
     proxy: ProxyConfig;
     cloudflared: CloudflaredConfig; // [NEW] Cloudflared 配置
+    encrypt_account_store?: boolean; // 账号文件静态加密
+    encryption_key_source?: 'machine_id' | 'key_file'; // 加密密钥来源：设备 ID 或数据目录下的密钥文件
+    token_field_paths?: TokenFieldPathsConfig; // 覆盖 IDE 数据库中 Token 的 Protobuf 字段路径
+    auto_repair_accounts?: boolean; // 启动时尝试从 IDE 数据库修复空 Refresh Token
+}
+
+// IDE 数据库中 OAuthInfo 的 Protobuf 字段路径
+export interface OAuthFieldPaths {
+    oauth_info?: number[];
+    refresh_token: number[];
+    access_token?: number[];
+    expiry_seconds?: number[];
+}
+
+export interface TokenFieldPathsConfig {
+    new_unified?: OAuthFieldPaths;
+    legacy_jetski?: OAuthFieldPaths;
+    max_nesting_depth?: number; // 字段路径最大嵌套深度，默认 32
 }
 
 // ============================================================================